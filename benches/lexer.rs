@@ -0,0 +1,78 @@
+//! Lexer throughput on generated sources of increasing size.
+//!
+//! Run with `cargo bench --bench lexer`. Time per byte should stay flat as the
+//! input doubles; a quadratic lexer shows up as the ratio doubling with it.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use lune::frontend::lexer::lexer::Lexer;
+
+/// A chunk of source touching every kind of token, with some non-ASCII text
+const CHUNK: &str = r#"# Compute a few things
+proc add(a: int, b: int): int {
+    return a + b * 2
+}
+
+proc main() {
+    var x: int = add(0x1F, 0b101)
+    var s: str = "héllo wörld ✓"
+    if x >= 3 {
+        print(s)
+    } else if x != 0 {
+        x += 1
+    }
+    while x < 10 {
+        x -= -1
+    }
+}
+"#;
+
+const SIZES_MB: [usize; 4] = [1, 2, 4, 8];
+const RUNS: u32 = 5;
+
+fn source(bytes: usize) -> String {
+    let mut source = String::with_capacity(bytes + CHUNK.len());
+    while source.len() < bytes {
+        source += CHUNK;
+    }
+    source
+}
+
+/// Best of `RUNS` scans of `source`
+fn time_scan(source: &str) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut tokens = 0;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let scanned = Lexer::new(black_box(source))
+            .scan_all()
+            .expect("benchmark source should lex");
+        best = best.min(start.elapsed());
+        tokens = scanned.len();
+    }
+
+    (best, tokens)
+}
+
+fn main() {
+    let mut baseline = None;
+
+    println!("{:>6} {:>10} {:>12} {:>10} {:>8}", "size", "tokens", "time", "ns/byte", "ratio");
+    for mb in SIZES_MB {
+        let source = source(mb * 1024 * 1024);
+        let (time, tokens) = time_scan(&source);
+
+        let per_byte = time.as_nanos() as f64 / source.len() as f64;
+        let ratio = per_byte / *baseline.get_or_insert(per_byte);
+        println!(
+            "{:>4}MB {:>10} {:>10.2}ms {:>10.2} {:>8.2}",
+            mb,
+            tokens,
+            time.as_secs_f64() * 1000.0,
+            per_byte,
+            ratio
+        );
+    }
+}
@@ -1,4 +1,32 @@
 pub mod ast;
 
+// Visitors, mutable visitors and folders over the AST
+pub mod visit;
+
 // AST pretty printer
 pub mod ast_dumper;
+
+// S-expression printer and reader
+pub mod ast_printer;
+pub mod ast_reader;
+
+// Constant folding and algebraic simplification
+pub mod fold;
+
+// Intermediate representation in SSA form
+pub mod ir;
+
+// C code generation
+pub mod cgen;
+
+// x86-64 assembly generation from the IR
+pub mod asm;
+
+// LLVM IR generation from the IR
+pub mod llvm;
+
+// WebAssembly text generation from the IR
+pub mod wat;
+
+// Bytecode compilation and the virtual machine
+pub mod bytecode;
@@ -0,0 +1,428 @@
+//! x86-64 assembly generation.
+//!
+//! Emits GNU assembler text for Linux from an IR module, to be assembled
+//! with `as` and linked with `ld`. Nothing is linked in: a small runtime
+//! written in assembly prints with the `write` system call, and `_start`
+//! calls the entry proc and exits.
+//!
+//! Procs follow the System V calling convention. Registers aren't
+//! allocated, every IR register gets its own 8 byte slot in the stack frame
+//! and instructions load their operands into `%rax` and `%rcx`. Ints use 32
+//! bit instructions, so they wrap like in C, and division traps with
+//! `SIGFPE` on zero and on `-2147483648 / -1`. Bools are 0 or 1 and strings
+//! are pointers to NUL terminated bytes.
+//!
+//! The slots of `alloca`s come after the registers' in the frame, globals
+//! holding memory are zeroed bytes in `.bss`. `memcopy` is a `rep movsb`
+//! and `trap` is `ud2`, which stops the program with `SIGILL`.
+//!
+//! Phis are copied on the edges leading to their block, through the stack so
+//! they all read the values from before any of them is written.
+//!
+//! Every symbol is encoded with `cgen::encode`, so none clash with the
+//! runtime's `lune_` names or `_start`.
+
+use std::collections::HashMap;
+
+use super::ast::{BinOpKind, UnaryOpKind};
+use super::cgen::encode;
+use super::ir::*;
+
+/// Argument registers of the System V calling convention
+const ARGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+const RUNTIME: &str = r#"    .text
+
+    .globl _start
+_start:
+    call {entry}
+    movl $60, %eax              # exit(0)
+    xorl %edi, %edi
+    syscall
+
+# Print the int in %edi and a newline
+lune_print_int:
+    subq $40, %rsp
+    leaq 31(%rsp), %rsi         # digits are written backwards from here
+    movb $10, 32(%rsp)
+    movslq %edi, %rax
+    movq %rax, %r8
+    testq %rax, %rax
+    jns 1f
+    negq %rax                   # in 64 bits, so -2147483648 works
+1:  movl $10, %ecx
+2:  xorl %edx, %edx
+    divq %rcx
+    addb $48, %dl               # '0'
+    movb %dl, (%rsi)
+    decq %rsi
+    testq %rax, %rax
+    jnz 2b
+    testq %r8, %r8
+    jns 3f
+    movb $45, (%rsi)            # '-'
+    decq %rsi
+3:  incq %rsi
+    leaq 33(%rsp), %rdx
+    subq %rsi, %rdx
+    movl $1, %edi
+    movl $1, %eax               # write(1, %rsi, %rdx)
+    syscall
+    addq $40, %rsp
+    ret
+
+# Print the string in %rdi and a newline
+lune_print_str:
+    movq %rdi, %rsi
+    xorl %edx, %edx
+1:  cmpb $0, (%rsi,%rdx)
+    je 2f
+    incq %rdx
+    jmp 1b
+2:  movl $1, %edi
+    movl $1, %eax
+    syscall
+    leaq lune_newline(%rip), %rsi
+    movl $1, %edx
+    movl $1, %edi
+    movl $1, %eax
+    syscall
+    ret
+
+# Print the bool in %edi as true or false
+lune_print_bool:
+    movl %edi, %eax
+    leaq lune_false(%rip), %rdi
+    leaq lune_true(%rip), %rsi
+    testl %eax, %eax
+    cmovnzq %rsi, %rdi
+    jmp lune_print_str
+
+# Whether the strings in %rdi and %rsi are equal, in %eax
+lune_str_eq:
+1:  movb (%rdi), %al
+    cmpb (%rsi), %al
+    jne 2f
+    testb %al, %al
+    je 3f
+    incq %rdi
+    incq %rsi
+    jmp 1b
+2:  xorl %eax, %eax
+    ret
+3:  movl $1, %eax
+    ret
+
+    .section .rodata
+lune_true:
+    .asciz "true"
+lune_false:
+    .asciz "false"
+lune_newline:
+    .byte 10
+"#;
+
+/// String for the `.asciz` directive, escaping everything outside printable
+/// ASCII as octal
+fn asm_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'"' => out += "\\\"",
+            b'\\' => out += "\\\\",
+            b' '..=b'~' => out.push(byte as char),
+            _ => out += &format!("\\{:03o}", byte),
+        }
+    }
+    out + "\""
+}
+
+/// Generates assembly from a verified IR module
+pub struct AsmGen {
+    /// String constants, emitted in `.rodata` as `.Lstr<n>`
+    strings: Vec<String>,
+    /// Frame offset of every register of the proc being generated
+    slots: HashMap<Reg, i64>,
+    /// Frame offset of the memory of every `alloca` of the proc being generated
+    allocas: HashMap<Reg, i64>,
+    /// Symbol of the proc being generated, which its labels start with
+    proc: String,
+    /// Number of labels made for conditional branches in the current proc
+    labels: usize,
+    out: String,
+}
+
+impl AsmGen {
+    pub fn new() -> AsmGen {
+        AsmGen {
+            strings: vec![],
+            slots: HashMap::new(),
+            allocas: HashMap::new(),
+            proc: String::new(),
+            labels: 0,
+            out: String::new(),
+        }
+    }
+
+    /// Generate the assembly for `module`
+    pub fn generate(&mut self, module: &Module) -> String {
+        self.out = RUNTIME.replace("{entry}", &encode(&module.entry));
+        self.out += "\n    .text\n";
+        for proc in &module.procs {
+            self.proc(proc);
+        }
+
+        if !module.globals.is_empty() {
+            self.out += "\n    .bss\n    .p2align 3\n";
+            for global in &module.globals {
+                let size = match global.ty {
+                    Ty::Ptr => global.size.div_ceil(8) * 8,
+                    _ => 8,
+                };
+                self.out += &format!("{}:\n    .zero {}\n", encode(&global.name), size);
+            }
+        }
+        if !self.strings.is_empty() {
+            self.out += "\n    .section .rodata\n";
+            for (i, value) in self.strings.iter().enumerate() {
+                self.out += &format!(".Lstr{}:\n    .asciz {}\n", i, asm_string(value));
+            }
+        }
+        self.out += "\n    .section .note.GNU-stack,\"\",@progbits\n";
+        std::mem::take(&mut self.out)
+    }
+
+    fn line(&mut self, line: &str) {
+        self.out += "    ";
+        self.out += line;
+        self.out += "\n";
+    }
+
+    fn slot(&self, reg: Reg) -> String {
+        format!("{}(%rbp)", self.slots[&reg])
+    }
+
+    fn label(&self, block: BlockId) -> String {
+        format!(".L{}_{}", self.proc, block)
+    }
+
+    fn proc(&mut self, proc: &Proc) {
+        self.proc = encode(&proc.name);
+        self.labels = 0;
+        self.slots = (0..proc.next_reg())
+            .map(|i| (Reg(i), -8 * (i as i64 + 1)))
+            .collect();
+        let mut frame = 8 * proc.next_reg() as i64;
+        self.allocas.clear();
+        for inst in proc.blocks.iter().flat_map(|block| &block.insts) {
+            if let (Some(dest), Op::Alloca(size)) = (inst.dest, &inst.op) {
+                frame += (*size as i64 + 7) / 8 * 8;
+                self.allocas.insert(dest, -frame);
+            }
+        }
+        // Keep %rsp 16 byte aligned for calls
+        let frame = (frame + 15) / 16 * 16;
+
+        self.out += &format!("\n{}:\n", self.proc);
+        self.line("pushq %rbp");
+        self.line("movq %rsp, %rbp");
+        if frame > 0 {
+            self.line(&format!("subq ${}, %rsp", frame));
+        }
+        for (i, &(reg, _)) in proc.params.iter().enumerate() {
+            match ARGS.get(i) {
+                Some(arg) => self.line(&format!("movq {}, {}", arg, self.slot(reg))),
+                None => {
+                    // Above the saved %rbp and the return address
+                    self.line(&format!("movq {}(%rbp), %rax", 16 + 8 * (i - ARGS.len())));
+                    self.line(&format!("movq %rax, {}", self.slot(reg)));
+                }
+            }
+        }
+
+        for block in &proc.blocks {
+            self.out += &format!("{}:\n", self.label(block.id));
+            for inst in &block.insts {
+                self.inst(inst);
+            }
+            self.terminator(proc, block);
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match &inst.op {
+            Op::Const(Const::Int(n)) => self.line(&format!("movl ${}, %eax", n)),
+            Op::Const(Const::Bool(b)) => self.line(&format!("movl ${}, %eax", *b as i32)),
+            Op::Const(Const::Str(s)) => {
+                let i = match self.strings.iter().position(|other| other == s) {
+                    Some(i) => i,
+                    None => {
+                        self.strings.push(s.clone());
+                        self.strings.len() - 1
+                    }
+                };
+                self.line(&format!("leaq .Lstr{}(%rip), %rax", i));
+            }
+            Op::Unary(op, rhs) => {
+                self.line(&format!("movq {}, %rax", self.slot(*rhs)));
+                match op {
+                    UnaryOpKind::Neg => self.line("negl %eax"),
+                    UnaryOpKind::Not => self.line("xorl $1, %eax"),
+                }
+            }
+            Op::Binary(op, lhs, rhs) => self.binary(inst.ty, *op, *lhs, *rhs),
+            Op::Call(name, args) => self.call(&encode(name), args),
+            Op::Load(name) => self.line(&format!("movq {}(%rip), %rax", encode(name))),
+            Op::Store(name, value) => {
+                self.line(&format!("movq {}, %rax", self.slot(*value)));
+                self.line(&format!("movq %rax, {}(%rip)", encode(name)));
+            }
+            Op::Print(value) => {
+                let helper = match inst.ty {
+                    Ty::Int => "lune_print_int",
+                    Ty::Bool => "lune_print_bool",
+                    Ty::Str => "lune_print_str",
+                    Ty::Ptr | Ty::Void => unreachable!("only ints, bools and strings are printed"),
+                };
+                self.line(&format!("movq {}, %rdi", self.slot(*value)));
+                self.line(&format!("call {}", helper));
+            }
+            Op::Copy(value) => self.line(&format!("movq {}, %rax", self.slot(*value))),
+            Op::Alloca(_) => {
+                let at = self.allocas[&inst.dest.unwrap()];
+                self.line(&format!("leaq {}(%rbp), %rax", at));
+            }
+            Op::Addr(name) => self.line(&format!("leaq {}(%rip), %rax", encode(name))),
+            Op::Offset(address, offset) => {
+                self.line(&format!("movq {}, %rax", self.slot(*address)));
+                self.line(&format!("addq ${}, %rax", offset));
+            }
+            Op::Index(address, index, size) => {
+                self.line(&format!("movq {}, %rax", self.slot(*address)));
+                self.line(&format!("movslq {}, %rcx", self.slot(*index)));
+                self.line(&format!("imulq ${}, %rcx", size));
+                self.line("addq %rcx, %rax");
+            }
+            Op::Read(address) => {
+                self.line(&format!("movq {}, %rax", self.slot(*address)));
+                self.line("movq (%rax), %rax");
+            }
+            Op::Write(value, address) => {
+                self.line(&format!("movq {}, %rcx", self.slot(*address)));
+                self.line(&format!("movq {}, %rax", self.slot(*value)));
+                self.line("movq %rax, (%rcx)");
+            }
+            Op::MemCopy(to, from, size) => {
+                self.line(&format!("movq {}, %rdi", self.slot(*to)));
+                self.line(&format!("movq {}, %rsi", self.slot(*from)));
+                self.line(&format!("movl ${}, %ecx", size));
+                self.line("rep movsb");
+            }
+            // Written on the edges leading here
+            Op::Phi(_) => return,
+        }
+        if let Some(dest) = inst.dest {
+            self.line(&format!("movq %rax, {}", self.slot(dest)));
+        }
+    }
+
+    fn binary(&mut self, ty: Ty, op: BinOpKind, lhs: Reg, rhs: Reg) {
+        if ty == Ty::Str {
+            self.line(&format!("movq {}, %rdi", self.slot(lhs)));
+            self.line(&format!("movq {}, %rsi", self.slot(rhs)));
+            self.line("call lune_str_eq");
+            if op == BinOpKind::Ne {
+                self.line("xorl $1, %eax");
+            }
+            return;
+        }
+
+        self.line(&format!("movq {}, %rax", self.slot(lhs)));
+        self.line(&format!("movq {}, %rcx", self.slot(rhs)));
+        let set = match op {
+            BinOpKind::Add => return self.line("addl %ecx, %eax"),
+            BinOpKind::Sub => return self.line("subl %ecx, %eax"),
+            BinOpKind::Mul => return self.line("imull %ecx, %eax"),
+            BinOpKind::Div => {
+                self.line("cltd");
+                return self.line("idivl %ecx");
+            }
+            BinOpKind::Eq => "sete",
+            BinOpKind::Ne => "setne",
+            BinOpKind::Lt => "setl",
+            BinOpKind::Le => "setle",
+            BinOpKind::Gt => "setg",
+            BinOpKind::Ge => "setge",
+        };
+        self.line("cmpl %ecx, %eax");
+        self.line(&format!("{} %al", set));
+        self.line("movzbl %al, %eax");
+    }
+
+    fn call(&mut self, symbol: &str, args: &[Reg]) {
+        // Arguments past the sixth go on the stack, the last one first, with
+        // padding to keep %rsp aligned
+        let stack = args.len().saturating_sub(ARGS.len());
+        let pad = stack % 2 * 8;
+        if pad > 0 {
+            self.line(&format!("subq ${}, %rsp", pad));
+        }
+        for &arg in args.iter().skip(ARGS.len()).rev() {
+            self.line(&format!("pushq {}", self.slot(arg)));
+        }
+        for (&arg, reg) in args.iter().zip(ARGS) {
+            self.line(&format!("movq {}, {}", self.slot(arg), reg));
+        }
+        self.line(&format!("call {}", symbol));
+        if stack > 0 {
+            self.line(&format!("addq ${}, %rsp", stack * 8 + pad));
+        }
+    }
+
+    /// Write the phis of `target` for the edge from `from`, taking the
+    /// `nth` value from `from` where it branches there more than once
+    fn edge(&mut self, proc: &Proc, from: BlockId, target: BlockId, nth: usize) {
+        let phis = proc.phi_moves(from, target, nth);
+        for &(_, value) in &phis {
+            self.line(&format!("pushq {}", self.slot(value)));
+        }
+        for &(dest, _) in phis.iter().rev() {
+            self.line(&format!("popq {}", self.slot(dest)));
+        }
+    }
+
+    fn terminator(&mut self, proc: &Proc, block: &Block) {
+        match block.term {
+            Terminator::Br(target) => {
+                self.edge(proc, block.id, target, 0);
+                self.line(&format!("jmp {}", self.label(target)));
+            }
+            Terminator::CondBr(cond, then, otherwise) => {
+                self.line(&format!("movq {}, %rax", self.slot(cond)));
+                self.line("testl %eax, %eax");
+                let other = format!(".L{}_else{}", self.proc, self.labels);
+                self.labels += 1;
+                self.line(&format!("jz {}", other));
+                self.edge(proc, block.id, then, 0);
+                self.line(&format!("jmp {}", self.label(then)));
+                self.out += &format!("{}:\n", other);
+                self.edge(proc, block.id, otherwise, (then == otherwise) as usize);
+                self.line(&format!("jmp {}", self.label(otherwise)));
+            }
+            Terminator::Ret(value) => {
+                if let Some(value) = value {
+                    self.line(&format!("movq {}, %rax", self.slot(value)));
+                }
+                self.line("leave");
+                self.line("ret");
+            }
+            Terminator::Unreachable | Terminator::Trap => self.line("ud2"),
+        }
+    }
+}
+
+impl Default for AsmGen {
+    fn default() -> AsmGen {
+        AsmGen::new()
+    }
+}
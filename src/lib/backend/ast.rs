@@ -1,60 +1,530 @@
 /// AST nodes
+///
+/// Nodes live in arenas owned by `AST` and refer to each other through
+/// `ExprId`/`StmtId` indices. Every node also carries a `NodeId` that stays
+/// unique across the whole tree, so passes can key side tables on it.
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::ops::{Index, IndexMut};
 
-use crate::frontend::lexer::token::Token;
+use crate::frontend::lexer::token::{Span, TokenKind};
+use crate::json::Json;
 use crate::types::Type;
 
-/// Type alias for AST
-pub type AST = Vec<Stmt>;
+/// Identity of a node, unique across expressions, statements and names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+/// Index of an expression in `AST::exprs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(pub u32);
+
+/// Index of a statement in `AST::stmts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StmtId(pub u32);
+
+/// List of statements in a block, in order
+pub type Block = Vec<StmtId>;
 
-#[derive(Debug, PartialEq)]
-pub enum Expr {
-    IntLit(i32),
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub id: NodeId,
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// Integer literal, unsized until type checking
+    IntLit(u128),
     StringLit(String),
+    BoolLit(bool),
+
+    /// Reference to a variable or parameter
+    Variable(Name),
+
+    /// Procedure call eg say_hello("world")
+    Call(Name, Vec<ExprId>),
 
     /// Unary operator eg -1
-    UnaryOp(Token, Box<Expr>),
+    UnaryOp(UnaryOpKind, ExprId),
 
     /// Binary operator eg 2 + 4
-    BinOp(Box<Expr>, Token, Box<Expr>)
+    BinOp(ExprId, BinOpKind, ExprId),
+
+    /// Struct literal eg Point { x: 1, y: 2 }
+    StructLit(Name, Vec<(Name, ExprId)>),
+
+    /// Field access eg p.x
+    FieldAccess(ExprId, Name),
+
+    /// Case expression, every arm produces a value
+    Case(ExprId, Vec<Arm<ExprId>>),
+
+    /// Array literal eg [1, 2, 3]
+    ArrayLit(Vec<ExprId>),
+
+    /// Indexing eg a[i]
+    Index(ExprId, ExprId),
+
+    /// Slicing with optional bounds eg a[1..3] or a[..]
+    Slice(ExprId, Option<ExprId>, Option<ExprId>),
+}
+
+impl ExprKind {
+    /// Whether the expression names a storage location that can be assigned to
+    pub fn is_place(&self, ast: &AST) -> bool {
+        match self {
+            ExprKind::Variable(_) => true,
+            ExprKind::FieldAccess(object, _) => ast[*object].kind.is_place(ast),
+            ExprKind::Index(object, _) => ast[*object].kind.is_place(ast),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOpKind {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOpKind {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl UnaryOpKind {
+    pub fn from_token(kind: &TokenKind) -> Option<UnaryOpKind> {
+        match kind {
+            TokenKind::Minus => Some(UnaryOpKind::Neg),
+            TokenKind::Bang => Some(UnaryOpKind::Not),
+            _ => None,
+        }
+    }
+}
+
+impl BinOpKind {
+    pub fn from_token(kind: &TokenKind) -> Option<BinOpKind> {
+        let op = match kind {
+            TokenKind::Plus => BinOpKind::Add,
+            TokenKind::Minus => BinOpKind::Sub,
+            TokenKind::Star => BinOpKind::Mul,
+            TokenKind::Slash => BinOpKind::Div,
+            TokenKind::EqualEqual => BinOpKind::Eq,
+            TokenKind::BangEqual => BinOpKind::Ne,
+            TokenKind::Less => BinOpKind::Lt,
+            TokenKind::LessEqual => BinOpKind::Le,
+            TokenKind::Greater => BinOpKind::Gt,
+            TokenKind::GreaterEqual => BinOpKind::Ge,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Whether the operator compares its operands and produces a bool
+    pub fn is_comparison(&self) -> bool {
+        !matches!(
+            self,
+            BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul | BinOpKind::Div
+        )
+    }
 }
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for UnaryOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOpKind::Neg => write!(f, "-"),
+            UnaryOpKind::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl fmt::Display for BinOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str_val = match self {
+            BinOpKind::Add => "+",
+            BinOpKind::Sub => "-",
+            BinOpKind::Mul => "*",
+            BinOpKind::Div => "/",
+            BinOpKind::Eq => "==",
+            BinOpKind::Ne => "!=",
+            BinOpKind::Lt => "<",
+            BinOpKind::Le => "<=",
+            BinOpKind::Gt => ">",
+            BinOpKind::Ge => ">=",
+        };
+
+        write!(f, "{}", str_val)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Name {
-    pub value: String
+    pub id: NodeId,
+    pub value: String,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Stmt {
-    Expr(Expr),
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: Name,
+    pub type_: Type,
+}
 
-    /// Variable declaration
-    VarDeclaration(Name, Type, Expr),
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proc {
+    pub name: Name,
+    pub params: Vec<Param>,
+    pub return_type: Type,
+    pub body: Block,
+}
 
-    //IfStatement(Comparison, )
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: Name,
+    pub type_: Type,
+}
 
-    /// Assignment
-    Assignment(Name, Expr),
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: Name,
+    pub fields: Vec<Field>,
 }
 
-///
-/// Visitor trait that structs can inherit from to walk the AST
-///
-pub trait Visitor<T> {
-    fn visit_expr(&mut self, expr: &Expr) -> T;
-    fn visit_stmt(&mut self, stmt: &Stmt) -> T;
-    fn visit_name(&mut self, name: &Name) -> T;
+/// A variant of an enum, with an optional payload and explicit value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: Name,
+    pub fields: Vec<Field>,
+    pub value: Option<i128>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: Name,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// `_`, matches anything
+    Wildcard,
+    Int(i128),
+    /// Inclusive range of integers eg 1..5
+    Range(i128, i128),
+    Str(String),
+    /// Enum variant and the names its payload is bound to, eg Circle(r).
+    /// Without bindings the payload is ignored.
+    Variant(Name, Vec<Name>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
 }
 
-impl fmt::Display for Expr {
+impl fmt::Display for Pattern {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let result = match &*self {
-            Expr::IntLit(n) => write!(f, "{}", n),
-            Expr::StringLit(s) => write!(f, "\"{}\"", s),
-            Expr::BinOp(lhs, op, rhs) => write!(f, "{}{}{}", lhs, op.kind, rhs),
-            Expr::UnaryOp(op, rhs) => write!(f, "{}{}", op.kind, rhs),
+        match &self.kind {
+            PatternKind::Wildcard => write!(f, "_"),
+            PatternKind::Int(n) => write!(f, "{}", n),
+            PatternKind::Range(start, end) => write!(f, "{}..{}", start, end),
+            PatternKind::Str(s) => write!(f, "{}", Json::String(s.clone())),
+            PatternKind::Variant(name, bindings) if bindings.is_empty() => {
+                write!(f, "{}", name.value)
+            }
+            PatternKind::Variant(name, bindings) => {
+                let bindings: Vec<&str> = bindings.iter().map(|b| b.value.as_str()).collect();
+                write!(f, "{}({})", name.value, bindings.join(", "))
+            }
+        }
+    }
+}
+
+/// Arm of a case, the body is a block in statements and an expression in expressions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm<T> {
+    pub pattern: Pattern,
+    pub body: T,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub id: NodeId,
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Expr(ExprId),
+
+    /// Variable declaration
+    VarDeclaration(Name, Type, ExprId),
+
+    /// Constant declaration, the value is evaluated at compile time
+    ConstDeclaration(Name, Type, ExprId),
+
+    /// Assignment to a variable or a field, the target is always a place
+    Assignment(ExprId, ExprId),
+
+    /// If statement with an optional else block
+    If(ExprId, Block, Option<Block>),
+
+    /// While loop
+    While(ExprId, Block),
+
+    /// Return from the enclosing proc
+    Return(Option<ExprId>),
+
+    /// Procedure declaration
+    ProcDeclaration(Proc),
+
+    /// Struct declaration
+    StructDeclaration(Struct),
+
+    /// Enum declaration
+    EnumDeclaration(Enum),
+
+    /// Case statement, runs the block of the first matching arm
+    Case(ExprId, Vec<Arm<Block>>),
+}
+
+/// `import "path/to/mod"` or `import path.to.mod` at the top of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    /// Path of the module relative to the importing file or the search path,
+    /// with `/` separators and without the extension
+    pub path: String,
+    /// Name the module's items are qualified with, the last part of the path
+    pub name: String,
+    pub span: Span,
+}
+
+/// Arena holding every node of a program
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AST {
+    pub exprs: Vec<Expr>,
+    pub stmts: Vec<Stmt>,
+    /// Top level statements, in order
+    pub root: Block,
+    /// Doc comments of declarations, keyed by the statement's node id
+    pub docs: HashMap<NodeId, String>,
+    /// Modules the file imports, in order
+    pub imports: Vec<Import>,
+    /// Top level declarations marked `pub`, by the statement's node id
+    pub public: HashSet<NodeId>,
+    next_id: u32,
+}
+
+impl AST {
+    pub fn new() -> AST {
+        AST::default()
+    }
+
+    /// Empty arena handing out node ids from `next_id`, so trees parsed
+    /// separately can be merged with `append`
+    pub fn with_ids_from(next_id: usize) -> AST {
+        AST {
+            next_id: next_id as u32,
+            ..AST::default()
+        }
+    }
+
+    /// Number of node ids handed out, ids are below this
+    pub fn node_count(&self) -> usize {
+        self.next_id as usize
+    }
+
+    fn node_id(&mut self) -> NodeId {
+        self.next_id += 1;
+        NodeId(self.next_id - 1)
+    }
+
+    pub fn add_expr(&mut self, kind: ExprKind, span: Span) -> ExprId {
+        let id = self.node_id();
+        self.exprs.push(Expr { id, kind, span });
+        ExprId(self.exprs.len() as u32 - 1)
+    }
+
+    pub fn add_stmt(&mut self, kind: StmtKind, span: Span) -> StmtId {
+        let id = self.node_id();
+        self.stmts.push(Stmt { id, kind, span });
+        StmtId(self.stmts.len() as u32 - 1)
+    }
+
+    pub fn name(&mut self, value: String, span: Span) -> Name {
+        Name {
+            id: self.node_id(),
+            value,
+            span,
+        }
+    }
+
+    /// Rebuild the arenas with only the nodes reachable from the root, in
+    /// tree order. Node ids are kept, arena indices are not.
+    pub fn compact(self) -> AST {
+        let mut old = self;
+        let mut new = AST {
+            docs: std::mem::take(&mut old.docs),
+            imports: std::mem::take(&mut old.imports),
+            public: std::mem::take(&mut old.public),
+            next_id: old.next_id,
+            ..AST::default()
         };
 
-        result
+        let root = std::mem::take(&mut old.root);
+        new.root = root.into_iter().map(|id| old.move_stmt(&mut new, id)).collect();
+        new
+    }
+
+    /// Move the top level statements of `other` into this arena and return
+    /// their new ids, without adding them to the root. The node ids of both
+    /// trees have to be distinct, see `with_ids_from`.
+    pub fn append(&mut self, other: AST) -> Block {
+        let mut other = other;
+        self.docs.extend(std::mem::take(&mut other.docs));
+        self.public.extend(std::mem::take(&mut other.public));
+        self.next_id = u32::max(self.next_id, other.next_id);
+
+        let root = std::mem::take(&mut other.root);
+        root.into_iter().map(|id| other.move_stmt(self, id)).collect()
     }
-}
\ No newline at end of file
+
+    fn move_block(&mut self, new: &mut AST, block: Block) -> Block {
+        block.into_iter().map(|id| self.move_stmt(new, id)).collect()
+    }
+
+    fn move_stmt(&mut self, new: &mut AST, id: StmtId) -> StmtId {
+        let stmt = &mut self[id];
+        let (node, span) = (stmt.id, stmt.span);
+        let kind = match std::mem::replace(&mut stmt.kind, StmtKind::Return(None)) {
+            StmtKind::Expr(e) => StmtKind::Expr(self.move_expr(new, e)),
+            StmtKind::VarDeclaration(name, type_, e) => {
+                StmtKind::VarDeclaration(name, type_, self.move_expr(new, e))
+            }
+            StmtKind::ConstDeclaration(name, type_, e) => {
+                StmtKind::ConstDeclaration(name, type_, self.move_expr(new, e))
+            }
+            StmtKind::Assignment(target, e) => {
+                let target = self.move_expr(new, target);
+                StmtKind::Assignment(target, self.move_expr(new, e))
+            }
+            StmtKind::If(cond, then, otherwise) => StmtKind::If(
+                self.move_expr(new, cond),
+                self.move_block(new, then),
+                otherwise.map(|b| self.move_block(new, b)),
+            ),
+            StmtKind::While(cond, body) => {
+                StmtKind::While(self.move_expr(new, cond), self.move_block(new, body))
+            }
+            StmtKind::Return(e) => StmtKind::Return(e.map(|e| self.move_expr(new, e))),
+            StmtKind::ProcDeclaration(proc) => StmtKind::ProcDeclaration(Proc {
+                body: self.move_block(new, proc.body),
+                ..proc
+            }),
+            kind @ (StmtKind::StructDeclaration(_) | StmtKind::EnumDeclaration(_)) => kind,
+            StmtKind::Case(scrutinee, arms) => StmtKind::Case(
+                self.move_expr(new, scrutinee),
+                arms.into_iter()
+                    .map(|arm| Arm {
+                        pattern: arm.pattern,
+                        body: self.move_block(new, arm.body),
+                    })
+                    .collect(),
+            ),
+        };
+
+        new.stmts.push(Stmt { id: node, kind, span });
+        StmtId(new.stmts.len() as u32 - 1)
+    }
+
+    fn move_expr(&mut self, new: &mut AST, id: ExprId) -> ExprId {
+        let expr = &mut self[id];
+        let (node, span) = (expr.id, expr.span);
+        let kind = match std::mem::replace(&mut expr.kind, ExprKind::BoolLit(false)) {
+            ExprKind::Call(name, args) => {
+                ExprKind::Call(name, args.into_iter().map(|a| self.move_expr(new, a)).collect())
+            }
+            ExprKind::UnaryOp(op, rhs) => ExprKind::UnaryOp(op, self.move_expr(new, rhs)),
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let lhs = self.move_expr(new, lhs);
+                ExprKind::BinOp(lhs, op, self.move_expr(new, rhs))
+            }
+            ExprKind::StructLit(name, fields) => ExprKind::StructLit(
+                name,
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field, self.move_expr(new, value)))
+                    .collect(),
+            ),
+            ExprKind::FieldAccess(object, field) => {
+                ExprKind::FieldAccess(self.move_expr(new, object), field)
+            }
+            ExprKind::ArrayLit(elements) => ExprKind::ArrayLit(
+                elements.into_iter().map(|e| self.move_expr(new, e)).collect(),
+            ),
+            ExprKind::Index(object, index) => {
+                let object = self.move_expr(new, object);
+                ExprKind::Index(object, self.move_expr(new, index))
+            }
+            ExprKind::Slice(object, start, end) => {
+                let object = self.move_expr(new, object);
+                let start = start.map(|start| self.move_expr(new, start));
+                ExprKind::Slice(object, start, end.map(|end| self.move_expr(new, end)))
+            }
+            ExprKind::Case(scrutinee, arms) => ExprKind::Case(
+                self.move_expr(new, scrutinee),
+                arms.into_iter()
+                    .map(|arm| Arm {
+                        pattern: arm.pattern,
+                        body: self.move_expr(new, arm.body),
+                    })
+                    .collect(),
+            ),
+            kind => kind,
+        };
+
+        new.exprs.push(Expr { id: node, kind, span });
+        ExprId(new.exprs.len() as u32 - 1)
+    }
+}
+
+impl Index<ExprId> for AST {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for AST {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.0 as usize]
+    }
+}
+
+impl Index<StmtId> for AST {
+    type Output = Stmt;
+
+    fn index(&self, id: StmtId) -> &Stmt {
+        &self.stmts[id.0 as usize]
+    }
+}
+
+impl IndexMut<StmtId> for AST {
+    fn index_mut(&mut self, id: StmtId) -> &mut Stmt {
+        &mut self.stmts[id.0 as usize]
+    }
+}
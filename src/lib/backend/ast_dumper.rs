@@ -1,23 +1,633 @@
+//! Stable, documented AST dumps for debugging and snapshot tests.
+//!
+//! Three formats are supported, none of them depend on Rust's `Debug` layout:
+//!
+//! * `tree`: one node per line, children indented by two spaces. Each line is
+//!   the node kind, its details and the span as `@line:start..end`, where the
+//!   line is 1-based and start/end are byte offsets into the source.
+//!
+//!   ```text
+//!   VarDeclaration x: int @1:0..14
+//!     IntLit 1 @1:13..14
+//!   ```
+//!
+//!   Doc comments are shown as a `Doc "text"` line under their declaration,
+//!   which starts with `pub` when it is public. Imports come first, as
+//!   `Import path as name` lines. Case arms are an `Arm` line with the
+//!   pattern in source syntax, the bounds of a slice are under `Start` and
+//!   `End` lines.
+//!
+//! * `json`: an array of statement objects. Every node has a `"node"` kind and a
+//!   `"span"` object with `start`, `end` and 1-based `line`, plus one key per child.
+//!   Declarations have a `"doc"` string, or null without a doc comment, and
+//!   a `"pub"` bool. Imports come first, as `Import` nodes with a `path` and
+//!   a `name`. Patterns are nodes too, eg `RangePattern` with `start` and `end`.
+//!
+//! * `sexpr`: S-expressions without spans, eg `(var x int (+ 1 2))`, as written
+//!   by `ASTPrinter` and read back by `ASTReader`.
+//!
+//! A program's AST holds the modules it imports too, with spans offset past
+//! the files before them. `dump_program` dumps them relative to their own
+//! file, like the importing file's.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
 use super::ast::*;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use super::ast_printer::ASTPrinter;
+use super::visit::{self, VisitorMut};
+use crate::frontend::lexer::token::Span;
+use crate::frontend::modules::{file_at, SourceFile};
+use crate::json::Json;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AstFormat {
+    Tree,
+    Json,
+    SExpr,
+}
+
+impl FromStr for AstFormat {
+    type Err = String;
 
-pub struct ASTDumper;
+    fn from_str(s: &str) -> Result<AstFormat, String> {
+        match s {
+            "tree" => Ok(AstFormat::Tree),
+            "json" => Ok(AstFormat::Json),
+            "sexpr" => Ok(AstFormat::SExpr),
+            _ => Err(format!("unknown AST format `{}`, expected tree, json or sexpr", s)),
+        }
+    }
+}
+
+impl fmt::Display for AstFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AstFormat::Tree => "tree",
+            AstFormat::Json => "json",
+            AstFormat::SExpr => "sexpr",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+pub struct ASTDumper {
+    pub format: AstFormat,
+}
 
 impl ASTDumper {
-    /// Dump the unvisited AST to a file
-    pub fn dump_ast(&self, ast: AST) {
-        let header = "// This file was auto-generated by ast_dumper.rs\n\n";
+    pub fn new(format: AstFormat) -> ASTDumper {
+        ASTDumper { format }
+    }
+
+    /// Dump the AST of a program read from `files`, with the spans of each
+    /// module relative to its own file
+    pub fn dump_program<W: Write>(
+        &self,
+        ast: &AST,
+        files: &[SourceFile],
+        out: &mut W,
+    ) -> io::Result<()> {
+        let mut ast = ast.clone();
+        visit::visit_ast_mut(&mut Relative { files }, &mut ast);
+        for import in &mut ast.imports {
+            import.span = relative(files, import.span);
+        }
+        self.dump_ast(&ast, out)
+    }
 
-        let dump = format!("{:#?}", ast);
+    /// Dump the unvisited AST to `out` in the selected format
+    pub fn dump_ast<W: Write>(&self, ast: &AST, out: &mut W) -> io::Result<()> {
+        match self.format {
+            AstFormat::Tree => {
+                let mut lines = vec![];
+                for import in &ast.imports {
+                    let text = format!("Import {} as {}", import.path, import.name);
+                    tree_line(&mut lines, 0, text, import.span);
+                }
+                for &stmt in &ast.root {
+                    tree_stmt(ast, stmt, 0, &mut lines);
+                }
+                for line in lines {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+            AstFormat::Json => {
+                let imports = ast.imports.iter().map(json_import);
+                let stmts = ast.root.iter().map(|&stmt| json_stmt(ast, stmt));
+                let nodes: Vec<Json> = imports.chain(stmts).collect();
+                writeln!(out, "{}", Json::from(nodes).pretty())?;
+            }
+            AstFormat::SExpr => {
+                let mut printer = ASTPrinter {};
+                for line in printer.lines(ast) {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+        }
 
-        let file = File::create("ast_dump.rs").expect("unable to create file");
-        let mut writer = BufWriter::new(file);
+        out.flush()
+    }
+}
 
-        // Write the header comment
-        writer.write_all(header.as_bytes()).expect("failed to write");
+/// Makes the spans of every module relative to the file it was read from
+struct Relative<'a> {
+    files: &'a [SourceFile],
+}
+
+fn relative(files: &[SourceFile], span: Span) -> Span {
+    let base = file_at(files, span.start).map_or(0, |file| file.base);
+    Span::new(span.start - base, span.end - base, span.line)
+}
+
+impl VisitorMut for Relative<'_> {
+    fn visit_stmt_mut(&mut self, ast: &mut AST, stmt: StmtId) {
+        ast[stmt].span = relative(self.files, ast[stmt].span);
+        visit::walk_stmt_mut(self, ast, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, ast: &mut AST, expr: ExprId) {
+        ast[expr].span = relative(self.files, ast[expr].span);
+        visit::walk_expr_mut(self, ast, expr);
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        pattern.span = relative(self.files, pattern.span);
+        visit::walk_pattern_mut(self, pattern);
+    }
+
+    fn visit_name_mut(&mut self, name: &mut Name) {
+        name.span = relative(self.files, name.span);
+    }
+}
+
+// Indented tree
+
+fn tree_span(span: Span) -> String {
+    format!("@{}:{}..{}", span.line + 1, span.start, span.end)
+}
+
+fn tree_line(lines: &mut Vec<String>, depth: usize, text: String, span: Span) {
+    lines.push(format!("{}{} {}", "  ".repeat(depth), text, tree_span(span)));
+}
+
+fn tree_block(ast: &AST, label: &str, stmts: &[StmtId], depth: usize, lines: &mut Vec<String>) {
+    lines.push(format!("{}{}", "  ".repeat(depth), label));
+    for &stmt in stmts {
+        tree_stmt(ast, stmt, depth + 1, lines);
+    }
+}
+
+fn tree_fields(fields: &[Field], depth: usize, lines: &mut Vec<String>) {
+    for field in fields {
+        let text = format!("Field {}: {}", field.name.value, field.type_);
+        tree_line(lines, depth, text, field.name.span);
+    }
+}
 
-        // .. and write the AST
-        writer.write_all(&dump.as_bytes()).expect("unable to write");
+/// `pub ` before the name of a public declaration
+fn tree_pub(ast: &AST, id: StmtId) -> &'static str {
+    if ast.public.contains(&ast[id].id) {
+        "pub "
+    } else {
+        ""
     }
 }
+
+fn tree_doc(ast: &AST, id: StmtId, depth: usize, lines: &mut Vec<String>) {
+    if let Some(doc) = ast.docs.get(&ast[id].id) {
+        lines.push(format!("{}Doc {}", "  ".repeat(depth), Json::String(doc.clone())));
+    }
+}
+
+fn tree_stmt(ast: &AST, id: StmtId, depth: usize, lines: &mut Vec<String>) {
+    let stmt = &ast[id];
+    match &stmt.kind {
+        StmtKind::Expr(expr) => {
+            tree_line(lines, depth, "Expr".into(), stmt.span);
+            tree_expr(ast, *expr, depth + 1, lines);
+        }
+        StmtKind::VarDeclaration(name, type_, value) => {
+            let text = format!("VarDeclaration {}{}: {}", tree_pub(ast, id), name.value, type_);
+            tree_line(lines, depth, text, stmt.span);
+            tree_doc(ast, id, depth + 1, lines);
+            tree_expr(ast, *value, depth + 1, lines);
+        }
+        StmtKind::ConstDeclaration(name, type_, value) => {
+            let text = format!("ConstDeclaration {}{}: {}", tree_pub(ast, id), name.value, type_);
+            tree_line(lines, depth, text, stmt.span);
+            tree_doc(ast, id, depth + 1, lines);
+            tree_expr(ast, *value, depth + 1, lines);
+        }
+        StmtKind::Assignment(target, value) => {
+            tree_line(lines, depth, "Assignment".into(), stmt.span);
+            tree_expr(ast, *target, depth + 1, lines);
+            tree_expr(ast, *value, depth + 1, lines);
+        }
+        StmtKind::If(cond, then, otherwise) => {
+            tree_line(lines, depth, "If".into(), stmt.span);
+            tree_expr(ast, *cond, depth + 1, lines);
+            tree_block(ast, "Then", then, depth + 1, lines);
+            if let Some(otherwise) = otherwise {
+                tree_block(ast, "Else", otherwise, depth + 1, lines);
+            }
+        }
+        StmtKind::While(cond, body) => {
+            tree_line(lines, depth, "While".into(), stmt.span);
+            tree_expr(ast, *cond, depth + 1, lines);
+            tree_block(ast, "Body", body, depth + 1, lines);
+        }
+        StmtKind::Return(value) => {
+            tree_line(lines, depth, "Return".into(), stmt.span);
+            if let Some(value) = value {
+                tree_expr(ast, *value, depth + 1, lines);
+            }
+        }
+        StmtKind::ProcDeclaration(proc) => {
+            let text = format!(
+                "ProcDeclaration {}{}: {}",
+                tree_pub(ast, id),
+                proc.name.value,
+                proc.return_type
+            );
+            tree_line(lines, depth, text, stmt.span);
+            tree_doc(ast, id, depth + 1, lines);
+            for param in &proc.params {
+                let text = format!("Param {}: {}", param.name.value, param.type_);
+                tree_line(lines, depth + 1, text, param.name.span);
+            }
+            tree_block(ast, "Body", &proc.body, depth + 1, lines);
+        }
+        StmtKind::StructDeclaration(struct_) => {
+            let text = format!("StructDeclaration {}{}", tree_pub(ast, id), struct_.name.value);
+            tree_line(lines, depth, text, stmt.span);
+            tree_doc(ast, id, depth + 1, lines);
+            tree_fields(&struct_.fields, depth + 1, lines);
+        }
+        StmtKind::EnumDeclaration(enum_) => {
+            let text = format!("EnumDeclaration {}{}", tree_pub(ast, id), enum_.name.value);
+            tree_line(lines, depth, text, stmt.span);
+            tree_doc(ast, id, depth + 1, lines);
+            for variant in &enum_.variants {
+                let text = match variant.value {
+                    Some(value) => format!("Variant {} = {}", variant.name.value, value),
+                    None => format!("Variant {}", variant.name.value),
+                };
+                tree_line(lines, depth + 1, text, variant.name.span);
+                tree_fields(&variant.fields, depth + 2, lines);
+            }
+        }
+        StmtKind::Case(scrutinee, arms) => {
+            tree_line(lines, depth, "Case".into(), stmt.span);
+            tree_expr(ast, *scrutinee, depth + 1, lines);
+            for arm in arms {
+                tree_line(lines, depth + 1, format!("Arm {}", arm.pattern), arm.pattern.span);
+                for &stmt in &arm.body {
+                    tree_stmt(ast, stmt, depth + 2, lines);
+                }
+            }
+        }
+    }
+}
+
+fn tree_expr(ast: &AST, id: ExprId, depth: usize, lines: &mut Vec<String>) {
+    let expr = &ast[id];
+    let text = match &expr.kind {
+        ExprKind::IntLit(n) => format!("IntLit {}", n),
+        ExprKind::StringLit(s) => format!("StringLit {}", Json::String(s.clone())),
+        ExprKind::BoolLit(b) => format!("BoolLit {}", b),
+        ExprKind::Variable(name) => format!("Variable {}", name.value),
+        ExprKind::Call(name, _) => format!("Call {}", name.value),
+        ExprKind::UnaryOp(op, _) => format!("UnaryOp {}", op),
+        ExprKind::BinOp(_, op, _) => format!("BinOp {}", op),
+        ExprKind::StructLit(name, _) => format!("StructLit {}", name.value),
+        ExprKind::FieldAccess(_, field) => format!("FieldAccess {}", field.value),
+        ExprKind::Case(_, _) => "Case".into(),
+        ExprKind::ArrayLit(elements) => format!("ArrayLit {}", elements.len()),
+        ExprKind::Index(_, _) => "Index".into(),
+        ExprKind::Slice(_, _, _) => "Slice".into(),
+    };
+    tree_line(lines, depth, text, expr.span);
+
+    match &expr.kind {
+        ExprKind::Call(_, args) => {
+            for &arg in args {
+                tree_expr(ast, arg, depth + 1, lines);
+            }
+        }
+        ExprKind::UnaryOp(_, rhs) => tree_expr(ast, *rhs, depth + 1, lines),
+        ExprKind::BinOp(lhs, _, rhs) => {
+            tree_expr(ast, *lhs, depth + 1, lines);
+            tree_expr(ast, *rhs, depth + 1, lines);
+        }
+        ExprKind::StructLit(_, fields) => {
+            for (field, value) in fields {
+                tree_line(lines, depth + 1, format!("FieldInit {}", field.value), field.span);
+                tree_expr(ast, *value, depth + 2, lines);
+            }
+        }
+        ExprKind::FieldAccess(object, _) => tree_expr(ast, *object, depth + 1, lines),
+        ExprKind::Case(scrutinee, arms) => {
+            tree_expr(ast, *scrutinee, depth + 1, lines);
+            for arm in arms {
+                tree_line(lines, depth + 1, format!("Arm {}", arm.pattern), arm.pattern.span);
+                tree_expr(ast, arm.body, depth + 2, lines);
+            }
+        }
+        ExprKind::ArrayLit(elements) => {
+            for &element in elements {
+                tree_expr(ast, element, depth + 1, lines);
+            }
+        }
+        ExprKind::Index(object, index) => {
+            tree_expr(ast, *object, depth + 1, lines);
+            tree_expr(ast, *index, depth + 1, lines);
+        }
+        ExprKind::Slice(object, start, end) => {
+            tree_expr(ast, *object, depth + 1, lines);
+            for (label, bound) in [("Start", start), ("End", end)] {
+                if let Some(bound) = bound {
+                    lines.push(format!("{}{}", "  ".repeat(depth + 1), label));
+                    tree_expr(ast, *bound, depth + 2, lines);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// JSON
+
+fn json_span(span: Span) -> Json {
+    Json::object(vec![
+        ("start", span.start.into()),
+        ("end", span.end.into()),
+        ("line", (span.line + 1).into()),
+    ])
+}
+
+fn json_name(name: &Name) -> Json {
+    Json::object(vec![
+        ("value", name.value.clone().into()),
+        ("span", json_span(name.span)),
+    ])
+}
+
+fn json_node(node: &str, span: Span, fields: Vec<(&str, Json)>) -> Json {
+    let mut node = vec![("node", node.into()), ("span", json_span(span))];
+    node.extend(fields);
+    Json::object(node)
+}
+
+fn json_fields(fields: &[Field]) -> Json {
+    fields
+        .iter()
+        .map(|f| {
+            Json::object(vec![
+                ("name", json_name(&f.name)),
+                ("type", f.type_.to_string().into()),
+            ])
+        })
+        .collect::<Vec<Json>>()
+        .into()
+}
+
+fn json_pattern(pattern: &Pattern) -> Json {
+    let (node, fields) = match &pattern.kind {
+        PatternKind::Wildcard => ("WildcardPattern", vec![]),
+        PatternKind::Int(n) => ("IntPattern", vec![("value", Json::Number(*n as f64))]),
+        PatternKind::Range(start, end) => (
+            "RangePattern",
+            vec![("start", Json::Number(*start as f64)), ("end", Json::Number(*end as f64))],
+        ),
+        PatternKind::Str(s) => ("StrPattern", vec![("value", s.clone().into())]),
+        PatternKind::Variant(name, bindings) => (
+            "VariantPattern",
+            vec![
+                ("name", json_name(name)),
+                ("bindings", bindings.iter().map(json_name).collect::<Vec<Json>>().into()),
+            ],
+        ),
+    };
+
+    json_node(node, pattern.span, fields)
+}
+
+/// Case arms as `{"pattern", "body"}` objects, `body` converts each arm's body
+fn json_arms<T>(arms: &[Arm<T>], body: impl Fn(&T) -> Json) -> Json {
+    arms.iter()
+        .map(|arm| {
+            Json::object(vec![("pattern", json_pattern(&arm.pattern)), ("body", body(&arm.body))])
+        })
+        .collect::<Vec<Json>>()
+        .into()
+}
+
+fn json_block(ast: &AST, stmts: &[StmtId]) -> Json {
+    stmts.iter().map(|&stmt| json_stmt(ast, stmt)).collect::<Vec<Json>>().into()
+}
+
+fn json_import(import: &Import) -> Json {
+    json_node(
+        "Import",
+        import.span,
+        vec![("path", import.path.clone().into()), ("name", import.name.clone().into())],
+    )
+}
+
+fn json_pub(ast: &AST, id: StmtId) -> Json {
+    Json::Bool(ast.public.contains(&ast[id].id))
+}
+
+fn json_doc(ast: &AST, id: StmtId) -> Json {
+    ast.docs
+        .get(&ast[id].id)
+        .map_or(Json::Null, |doc| doc.clone().into())
+}
+
+fn json_stmt(ast: &AST, id: StmtId) -> Json {
+    let stmt = &ast[id];
+    let (node, fields) = match &stmt.kind {
+        StmtKind::Expr(expr) => ("Expr", vec![("expr", json_expr(ast, *expr))]),
+        StmtKind::VarDeclaration(name, type_, value) => (
+            "VarDeclaration",
+            vec![
+                ("name", json_name(name)),
+                ("type", type_.to_string().into()),
+                ("value", json_expr(ast, *value)),
+                ("doc", json_doc(ast, id)),
+                ("pub", json_pub(ast, id)),
+            ],
+        ),
+        StmtKind::ConstDeclaration(name, type_, value) => (
+            "ConstDeclaration",
+            vec![
+                ("name", json_name(name)),
+                ("type", type_.to_string().into()),
+                ("value", json_expr(ast, *value)),
+                ("doc", json_doc(ast, id)),
+                ("pub", json_pub(ast, id)),
+            ],
+        ),
+        StmtKind::Assignment(target, value) => (
+            "Assignment",
+            vec![("target", json_expr(ast, *target)), ("value", json_expr(ast, *value))],
+        ),
+        StmtKind::If(cond, then, otherwise) => (
+            "If",
+            vec![
+                ("cond", json_expr(ast, *cond)),
+                ("then", json_block(ast, then)),
+                ("else", otherwise.as_deref().map_or(Json::Null, |b| json_block(ast, b))),
+            ],
+        ),
+        StmtKind::While(cond, body) => (
+            "While",
+            vec![("cond", json_expr(ast, *cond)), ("body", json_block(ast, body))],
+        ),
+        StmtKind::Return(value) => (
+            "Return",
+            vec![("value", value.map_or(Json::Null, |v| json_expr(ast, v)))],
+        ),
+        StmtKind::ProcDeclaration(proc) => {
+            let params: Vec<Json> = proc
+                .params
+                .iter()
+                .map(|p| {
+                    Json::object(vec![
+                        ("name", json_name(&p.name)),
+                        ("type", p.type_.to_string().into()),
+                    ])
+                })
+                .collect();
+
+            (
+                "ProcDeclaration",
+                vec![
+                    ("name", json_name(&proc.name)),
+                    ("params", params.into()),
+                    ("return_type", proc.return_type.to_string().into()),
+                    ("body", json_block(ast, &proc.body)),
+                    ("doc", json_doc(ast, id)),
+                    ("pub", json_pub(ast, id)),
+                ],
+            )
+        }
+        StmtKind::StructDeclaration(struct_) => (
+            "StructDeclaration",
+            vec![
+                ("name", json_name(&struct_.name)),
+                ("fields", json_fields(&struct_.fields)),
+                ("doc", json_doc(ast, id)),
+                ("pub", json_pub(ast, id)),
+            ],
+        ),
+        StmtKind::EnumDeclaration(enum_) => {
+            let variants: Vec<Json> = enum_
+                .variants
+                .iter()
+                .map(|v| {
+                    Json::object(vec![
+                        ("name", json_name(&v.name)),
+                        ("fields", json_fields(&v.fields)),
+                        ("value", v.value.map_or(Json::Null, |n| Json::Number(n as f64))),
+                    ])
+                })
+                .collect();
+
+            (
+                "EnumDeclaration",
+                vec![
+                    ("name", json_name(&enum_.name)),
+                    ("variants", variants.into()),
+                    ("doc", json_doc(ast, id)),
+                    ("pub", json_pub(ast, id)),
+                ],
+            )
+        }
+        StmtKind::Case(scrutinee, arms) => (
+            "Case",
+            vec![
+                ("scrutinee", json_expr(ast, *scrutinee)),
+                ("arms", json_arms(arms, |body| json_block(ast, body))),
+            ],
+        ),
+    };
+
+    json_node(node, stmt.span, fields)
+}
+
+fn json_expr(ast: &AST, id: ExprId) -> Json {
+    let expr = &ast[id];
+    let (node, fields) = match &expr.kind {
+        ExprKind::IntLit(n) => ("IntLit", vec![("value", Json::Number(*n as f64))]),
+        ExprKind::StringLit(s) => ("StringLit", vec![("value", s.clone().into())]),
+        ExprKind::BoolLit(b) => ("BoolLit", vec![("value", (*b).into())]),
+        ExprKind::Variable(name) => ("Variable", vec![("name", json_name(name))]),
+        ExprKind::Call(name, args) => (
+            "Call",
+            vec![
+                ("name", json_name(name)),
+                ("args", args.iter().map(|&arg| json_expr(ast, arg)).collect::<Vec<Json>>().into()),
+            ],
+        ),
+        ExprKind::UnaryOp(op, rhs) => (
+            "UnaryOp",
+            vec![("op", op.to_string().into()), ("operand", json_expr(ast, *rhs))],
+        ),
+        ExprKind::BinOp(lhs, op, rhs) => (
+            "BinOp",
+            vec![
+                ("op", op.to_string().into()),
+                ("lhs", json_expr(ast, *lhs)),
+                ("rhs", json_expr(ast, *rhs)),
+            ],
+        ),
+        ExprKind::StructLit(name, fields) => {
+            let fields: Vec<Json> = fields
+                .iter()
+                .map(|(field, value)| {
+                    Json::object(vec![
+                        ("name", json_name(field)),
+                        ("value", json_expr(ast, *value)),
+                    ])
+                })
+                .collect();
+            ("StructLit", vec![("name", json_name(name)), ("fields", fields.into())])
+        }
+        ExprKind::FieldAccess(object, field) => (
+            "FieldAccess",
+            vec![("object", json_expr(ast, *object)), ("field", json_name(field))],
+        ),
+        ExprKind::Case(scrutinee, arms) => (
+            "Case",
+            vec![
+                ("scrutinee", json_expr(ast, *scrutinee)),
+                ("arms", json_arms(arms, |&body| json_expr(ast, body))),
+            ],
+        ),
+        ExprKind::ArrayLit(elements) => (
+            "ArrayLit",
+            vec![(
+                "elements",
+                elements.iter().map(|&e| json_expr(ast, e)).collect::<Vec<Json>>().into(),
+            )],
+        ),
+        ExprKind::Index(object, index) => (
+            "Index",
+            vec![("object", json_expr(ast, *object)), ("index", json_expr(ast, *index))],
+        ),
+        ExprKind::Slice(object, start, end) => (
+            "Slice",
+            vec![
+                ("object", json_expr(ast, *object)),
+                ("start", start.map_or(Json::Null, |start| json_expr(ast, start))),
+                ("end", end.map_or(Json::Null, |end| json_expr(ast, end))),
+            ],
+        ),
+    };
+
+    json_node(node, expr.span, fields)
+}
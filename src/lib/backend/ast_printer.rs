@@ -1,21 +1,52 @@
 use super::ast::*;
+use super::visit::Visitor;
+use crate::json::Json;
 
-use serde_lexpr::{from_str, to_string};
-
-
-pub struct ASTPrinter {
-
-}
+/// Prints the AST as S-expressions, one statement per line.
+/// `ASTReader` parses the output back into an AST.
+///
+/// Imports come first, as `(import "path" name)`, and public declarations
+/// are wrapped in `(pub ...)`.
+pub struct ASTPrinter {}
 
 impl ASTPrinter {
     /// Print out the AST joined together with newlines
-    pub fn print(&mut self, stmts: Vec<Stmt>) {
-        println!("{}\n", "AST print (S expressions):");
-        println!("[");
-        for (pos, stmt) in stmts.iter().enumerate() {
-            println!("{}", self.visit_stmt(&stmt));
+    pub fn print(&mut self, ast: &AST) -> String {
+        self.lines(ast).join("\n")
+    }
+
+    /// The imports and top level statements of the AST, one per line
+    pub fn lines(&mut self, ast: &AST) -> Vec<String> {
+        let imports = ast.imports.iter().map(|import| {
+            format!("(import {} {})", Json::String(import.path.clone()), import.name)
+        });
+        let mut lines: Vec<String> = imports.collect();
+        for &stmt in &ast.root {
+            let line = self.visit_stmt(ast, stmt);
+            if ast.public.contains(&ast[stmt].id) {
+                lines.push(format!("(pub {})", line));
+            } else {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    fn visit_block(&mut self, ast: &AST, stmts: &[StmtId]) -> String {
+        let mut out = String::from("(block");
+        for &stmt in stmts {
+            out += " ";
+            out += &self.visit_stmt(ast, stmt);
         }
-        println!("]");
+        out + ")"
+    }
+
+    fn visit_fields(&mut self, fields: &[Field]) -> String {
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|f| format!("({} {})", self.visit_name(&f.name), f.type_))
+            .collect();
+        format!("({})", fields.join(" "))
     }
 }
 
@@ -24,20 +55,173 @@ impl Visitor<String> for ASTPrinter {
         name.value.clone()
     }
 
-    fn visit_stmt(&mut self, stmt: &Stmt) -> String {
-        match &*stmt {
-            Stmt::VarDeclaration(name, _, value) => format!("(var {} {})", name.value.clone(), self.visit_expr(value)),
-            _ => "undefined".into()
+    fn visit_pattern(&mut self, pattern: &Pattern) -> String {
+        match &pattern.kind {
+            PatternKind::Range(start, end) => format!("(.. {} {})", start, end),
+            PatternKind::Variant(name, bindings) if !bindings.is_empty() => {
+                let mut out = format!("({}", self.visit_name(name));
+                for binding in bindings {
+                    out += " ";
+                    out += &self.visit_name(binding);
+                }
+                out + ")"
+            }
+            _ => pattern.to_string(),
         }
     }
 
-    fn visit_expr(&mut self, expr: &Expr) -> String {
-        match &*expr {
-            Expr::IntLit(n) => n.to_string(),
-            Expr::StringLit(s) => format!("\"{}\"", s),
+    fn visit_stmt(&mut self, ast: &AST, stmt: StmtId) -> String {
+        match &ast[stmt].kind {
+            StmtKind::Expr(expr) => self.visit_expr(ast, *expr),
+            StmtKind::VarDeclaration(name, type_, value) => format!(
+                "(var {} {} {})",
+                self.visit_name(name),
+                type_,
+                self.visit_expr(ast, *value)
+            ),
+            StmtKind::ConstDeclaration(name, type_, value) => format!(
+                "(const {} {} {})",
+                self.visit_name(name),
+                type_,
+                self.visit_expr(ast, *value)
+            ),
+            StmtKind::Assignment(target, value) => format!(
+                "(assign {} {})",
+                self.visit_expr(ast, *target),
+                self.visit_expr(ast, *value)
+            ),
+            StmtKind::If(cond, then, otherwise) => {
+                let mut out = format!(
+                    "(if {} {}",
+                    self.visit_expr(ast, *cond),
+                    self.visit_block(ast, then)
+                );
+                if let Some(otherwise) = otherwise {
+                    out += " ";
+                    out += &self.visit_block(ast, otherwise);
+                }
+                out + ")"
+            }
+            StmtKind::While(cond, body) => format!(
+                "(while {} {})",
+                self.visit_expr(ast, *cond),
+                self.visit_block(ast, body)
+            ),
+            StmtKind::Return(Some(value)) => format!("(return {})", self.visit_expr(ast, *value)),
+            StmtKind::Return(None) => "(return)".into(),
+            StmtKind::ProcDeclaration(proc) => {
+                let params: Vec<String> = proc
+                    .params
+                    .iter()
+                    .map(|p| format!("({} {})", self.visit_name(&p.name), p.type_))
+                    .collect();
+                format!(
+                    "(proc {} ({}) {} {})",
+                    self.visit_name(&proc.name),
+                    params.join(" "),
+                    proc.return_type,
+                    self.visit_block(ast, &proc.body)
+                )
+            }
+            StmtKind::StructDeclaration(struct_) => format!(
+                "(struct {} {})",
+                self.visit_name(&struct_.name),
+                self.visit_fields(&struct_.fields)
+            ),
+            StmtKind::EnumDeclaration(enum_) => {
+                let mut out = format!("(enum {}", self.visit_name(&enum_.name));
+                for variant in &enum_.variants {
+                    out += &format!(
+                        " ({} {}",
+                        self.visit_name(&variant.name),
+                        self.visit_fields(&variant.fields)
+                    );
+                    if let Some(value) = variant.value {
+                        out += &format!(" {}", value);
+                    }
+                    out += ")";
+                }
+                out + ")"
+            }
+            StmtKind::Case(scrutinee, arms) => {
+                let mut out = format!("(case {}", self.visit_expr(ast, *scrutinee));
+                for arm in arms {
+                    out += &format!(
+                        " ({} {})",
+                        self.visit_pattern(&arm.pattern),
+                        self.visit_block(ast, &arm.body)
+                    );
+                }
+                out + ")"
+            }
+        }
+    }
 
-            Expr::BinOp(lhs, op, rhs) => format!("({} {} {})", op.kind, lhs.clone(), rhs.clone()),
-            Expr::UnaryOp(op, rhs) => format!("({} {})", op.kind, rhs.clone())
+    fn visit_expr(&mut self, ast: &AST, expr: ExprId) -> String {
+        match &ast[expr].kind {
+            ExprKind::IntLit(n) => n.to_string(),
+            // Strings use JSON escapes so they survive a round trip
+            ExprKind::StringLit(s) => Json::String(s.clone()).to_string(),
+            ExprKind::BoolLit(b) => b.to_string(),
+            ExprKind::Variable(name) => self.visit_name(name),
+            ExprKind::Call(name, args) => {
+                let mut out = format!("(call {}", self.visit_name(name));
+                for &arg in args {
+                    out += " ";
+                    out += &self.visit_expr(ast, arg);
+                }
+                out + ")"
+            }
+            ExprKind::BinOp(lhs, op, rhs) => format!(
+                "({} {} {})",
+                op,
+                self.visit_expr(ast, *lhs),
+                self.visit_expr(ast, *rhs)
+            ),
+            ExprKind::UnaryOp(op, rhs) => format!("({} {})", op, self.visit_expr(ast, *rhs)),
+            ExprKind::StructLit(name, fields) => {
+                let mut out = format!("(new {}", self.visit_name(name));
+                for (field, value) in fields {
+                    out += &format!(" ({} {})", self.visit_name(field), self.visit_expr(ast, *value));
+                }
+                out + ")"
+            }
+            ExprKind::FieldAccess(object, field) => {
+                format!("(. {} {})", self.visit_expr(ast, *object), self.visit_name(field))
+            }
+            ExprKind::Case(scrutinee, arms) => {
+                let mut out = format!("(case {}", self.visit_expr(ast, *scrutinee));
+                for arm in arms {
+                    out += &format!(
+                        " ({} {})",
+                        self.visit_pattern(&arm.pattern),
+                        self.visit_expr(ast, arm.body)
+                    );
+                }
+                out + ")"
+            }
+            ExprKind::ArrayLit(elements) => {
+                let mut out = String::from("(array");
+                for &element in elements {
+                    out += " ";
+                    out += &self.visit_expr(ast, element);
+                }
+                out + ")"
+            }
+            ExprKind::Index(object, index) => format!(
+                "(index {} {})",
+                self.visit_expr(ast, *object),
+                self.visit_expr(ast, *index)
+            ),
+            // A missing bound is written as ()
+            ExprKind::Slice(object, start, end) => {
+                let mut out = format!("(slice {}", self.visit_expr(ast, *object));
+                for bound in [start, end] {
+                    out += " ";
+                    out += &bound.map_or("()".into(), |bound| self.visit_expr(ast, bound));
+                }
+                out + ")"
+            }
         }
     }
-}
\ No newline at end of file
+}
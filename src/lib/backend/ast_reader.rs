@@ -0,0 +1,481 @@
+use super::ast::*;
+use crate::errors::ReadError;
+use crate::frontend::lexer::token::Span;
+use crate::json::Json;
+use crate::types::Type;
+
+/// A parsed S-expression, with the offset it started at for errors
+#[derive(Debug, Clone, PartialEq)]
+enum Datum {
+    Int(i128, usize),
+    Str(String, usize),
+    Symbol(String, usize),
+    List(Vec<Datum>, usize),
+}
+
+impl Datum {
+    fn position(&self) -> usize {
+        match self {
+            Datum::Int(_, p) | Datum::Str(_, p) | Datum::Symbol(_, p) | Datum::List(_, p) => *p,
+        }
+    }
+}
+
+/// Reads the S-expressions written by `ASTPrinter` back into an AST.
+/// Spans aren't part of the format, so every node gets an empty span.
+pub struct ASTReader<'src> {
+    source: &'src str,
+    position: usize,
+    ast: AST,
+}
+
+impl<'src> ASTReader<'src> {
+    pub fn new(source: &'src str) -> ASTReader<'src> {
+        ASTReader {
+            source,
+            position: 0,
+            ast: AST::new(),
+        }
+    }
+
+    pub fn read(&mut self) -> Result<AST, ReadError> {
+        let mut stmts = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.position >= self.source.len() {
+                break;
+            }
+            let datum = self.datum()?;
+            match &datum {
+                Datum::List(items, _) => match items.as_slice() {
+                    [Datum::Symbol(head, _), Datum::Str(path, _), Datum::Symbol(name, _)]
+                        if head == "import" =>
+                    {
+                        self.ast.imports.push(Import {
+                            path: path.clone(),
+                            name: name.clone(),
+                            span: Span::default(),
+                        });
+                    }
+                    [Datum::Symbol(head, _), declaration] if head == "pub" => {
+                        let stmt = self.stmt(declaration)?;
+                        let id = self.ast[stmt].id;
+                        self.ast.public.insert(id);
+                        stmts.push(stmt);
+                    }
+                    _ => stmts.push(self.stmt(&datum)?),
+                },
+                _ => stmts.push(self.stmt(&datum)?),
+            }
+        }
+
+        let mut ast = std::mem::take(&mut self.ast);
+        ast.root = stmts;
+        Ok(ast)
+    }
+
+    fn error(position: usize, message: &str) -> ReadError {
+        ReadError {
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn datum(&mut self) -> Result<Datum, ReadError> {
+        self.skip_whitespace();
+        let start = self.position;
+
+        match self.peek() {
+            None => Err(Self::error(start, "unexpected end of input")),
+            Some('(') => {
+                self.position += 1;
+                let mut items = vec![];
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(')') => {
+                            self.position += 1;
+                            return Ok(Datum::List(items, start));
+                        }
+                        None => return Err(Self::error(start, "unclosed list")),
+                        _ => items.push(self.datum()?),
+                    }
+                }
+            }
+            Some(')') => Err(Self::error(start, "unexpected ')'")),
+            Some('"') => {
+                // Find the closing quote, skipping escapes
+                let bytes = self.source.as_bytes();
+                let mut end = start + 1;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                if end >= bytes.len() {
+                    return Err(Self::error(start, "unterminated string"));
+                }
+
+                self.position = end + 1;
+                match Json::parse(&self.source[start..self.position]) {
+                    Ok(Json::String(s)) => Ok(Datum::Str(s, start)),
+                    _ => Err(Self::error(start, "invalid string")),
+                }
+            }
+            Some(_) => {
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    self.position += c.len_utf8();
+                }
+
+                let atom = &self.source[start..self.position];
+                let is_number = atom
+                    .strip_prefix('-')
+                    .unwrap_or(atom)
+                    .starts_with(|c: char| c.is_ascii_digit());
+                if is_number {
+                    let n = atom
+                        .parse()
+                        .map_err(|_| Self::error(start, "invalid integer"))?;
+                    return Ok(Datum::Int(n, start));
+                }
+                Ok(Datum::Symbol(atom.into(), start))
+            }
+        }
+    }
+
+    fn name(&mut self, datum: &Datum) -> Result<Name, ReadError> {
+        match datum {
+            Datum::Symbol(value, _) => Ok(self.ast.name(value.clone(), Span::default())),
+            _ => Err(Self::error(datum.position(), "expected a name")),
+        }
+    }
+
+    fn type_(datum: &Datum) -> Result<Type, ReadError> {
+        match datum {
+            Datum::Symbol(s, position) => {
+                Self::parse_type(s).ok_or_else(|| Self::error(*position, "invalid type"))
+            }
+            _ => Err(Self::error(datum.position(), "expected a type")),
+        }
+    }
+
+    /// Parse a type as displayed, eg `int` or `[3][]Point`
+    fn parse_type(s: &str) -> Option<Type> {
+        let Some(rest) = s.strip_prefix('[') else {
+            return Some(match s {
+                "int" => Type::Int,
+                "str" => Type::String,
+                "bool" => Type::Bool,
+                "void" => Type::Void,
+                _ => Type::Named(s.into()),
+            });
+        };
+
+        let (len, element) = rest.split_once(']')?;
+        let element = Box::new(Self::parse_type(element)?);
+        if len.is_empty() {
+            Some(Type::Slice(element))
+        } else if let Ok(len) = len.parse() {
+            Some(Type::Array(element, len))
+        } else {
+            Some(Type::ConstArray(element, len.into()))
+        }
+    }
+
+    fn fields(&mut self, datum: &Datum) -> Result<Vec<Field>, ReadError> {
+        let Datum::List(fields, position) = datum else {
+            return Err(Self::error(datum.position(), "expected a field list"));
+        };
+
+        fields
+            .iter()
+            .map(|field| match field {
+                Datum::List(pair, _) if pair.len() == 2 => Ok(Field {
+                    name: self.name(&pair[0])?,
+                    type_: Self::type_(&pair[1])?,
+                }),
+                _ => Err(Self::error(*position, "expected a (name type) field")),
+            })
+            .collect()
+    }
+
+    fn variant(&mut self, datum: &Datum) -> Result<Variant, ReadError> {
+        match datum {
+            Datum::List(items, _) if items.len() == 2 || items.len() == 3 => {
+                let value = match items.get(2) {
+                    Some(Datum::Int(n, _)) => Some(*n),
+                    Some(other) => return Err(Self::error(other.position(), "expected a value")),
+                    None => None,
+                };
+                Ok(Variant {
+                    name: self.name(&items[0])?,
+                    fields: self.fields(&items[1])?,
+                    value,
+                })
+            }
+            _ => Err(Self::error(datum.position(), "expected a (name fields value?) variant")),
+        }
+    }
+
+    fn pattern(&mut self, datum: &Datum) -> Result<Pattern, ReadError> {
+        let kind = match datum {
+            Datum::Int(n, _) => PatternKind::Int(*n),
+            Datum::Str(s, _) => PatternKind::Str(s.clone()),
+            Datum::Symbol(s, _) if s == "_" => PatternKind::Wildcard,
+            Datum::Symbol(_, _) => PatternKind::Variant(self.name(datum)?, vec![]),
+            Datum::List(items, position) => match items.as_slice() {
+                [Datum::Symbol(head, _), Datum::Int(start, _), Datum::Int(end, _)] if head == ".." => {
+                    PatternKind::Range(*start, *end)
+                }
+                [name, bindings @ ..] if !bindings.is_empty() => PatternKind::Variant(
+                    self.name(name)?,
+                    bindings.iter().map(|b| self.name(b)).collect::<Result<_, _>>()?,
+                ),
+                _ => return Err(Self::error(*position, "expected a pattern")),
+            },
+        };
+
+        Ok(Pattern {
+            kind,
+            span: Span::default(),
+        })
+    }
+
+    /// Read the `(pattern body)` arms of a case, `body` reads each arm's body
+    fn arms<T>(
+        &mut self,
+        arms: &[Datum],
+        mut body: impl FnMut(&mut Self, &Datum) -> Result<T, ReadError>,
+    ) -> Result<Vec<Arm<T>>, ReadError> {
+        arms.iter()
+            .map(|arm| match arm {
+                Datum::List(pair, _) if pair.len() == 2 => Ok(Arm {
+                    pattern: self.pattern(&pair[0])?,
+                    body: body(self, &pair[1])?,
+                }),
+                _ => Err(Self::error(arm.position(), "expected a (pattern body) arm")),
+            })
+            .collect()
+    }
+
+    /// Whether a `(case ...)` has blocks for arms, which makes it a statement
+    fn is_case_stmt(items: &[Datum]) -> bool {
+        items[2..].iter().all(|arm| match arm {
+            Datum::List(pair, _) => matches!(
+                pair.get(1),
+                Some(Datum::List(body, _)) if matches!(body.first(), Some(Datum::Symbol(s, _)) if s == "block")
+            ),
+            _ => false,
+        })
+    }
+
+    fn block(&mut self, datum: &Datum) -> Result<Block, ReadError> {
+        match datum {
+            Datum::List(items, _) if matches!(items.first(), Some(Datum::Symbol(s, _)) if s == "block") => {
+                items[1..].iter().map(|item| self.stmt(item)).collect()
+            }
+            _ => Err(Self::error(datum.position(), "expected a (block ...)")),
+        }
+    }
+
+    fn stmt(&mut self, datum: &Datum) -> Result<StmtId, ReadError> {
+        let (head, items) = match datum {
+            Datum::List(items, _) => match items.first() {
+                Some(Datum::Symbol(head, _)) => (head.as_str(), items.as_slice()),
+                _ => ("", items.as_slice()),
+            },
+            _ => ("", &[][..]),
+        };
+
+        let kind = match (head, items.len()) {
+            ("var", 4) => StmtKind::VarDeclaration(
+                self.name(&items[1])?,
+                Self::type_(&items[2])?,
+                self.expr(&items[3])?,
+            ),
+            ("const", 4) => StmtKind::ConstDeclaration(
+                self.name(&items[1])?,
+                Self::type_(&items[2])?,
+                self.expr(&items[3])?,
+            ),
+            ("assign", 3) => StmtKind::Assignment(self.expr(&items[1])?, self.expr(&items[2])?),
+            ("if", 3) => StmtKind::If(self.expr(&items[1])?, self.block(&items[2])?, None),
+            ("if", 4) => StmtKind::If(
+                self.expr(&items[1])?,
+                self.block(&items[2])?,
+                Some(self.block(&items[3])?),
+            ),
+            ("while", 3) => StmtKind::While(self.expr(&items[1])?, self.block(&items[2])?),
+            ("return", 1) => StmtKind::Return(None),
+            ("return", 2) => StmtKind::Return(Some(self.expr(&items[1])?)),
+            ("proc", 5) => {
+                let Datum::List(params, position) = &items[2] else {
+                    return Err(Self::error(items[2].position(), "expected a parameter list"));
+                };
+
+                let name = self.name(&items[1])?;
+                let params = params
+                    .iter()
+                    .map(|param| match param {
+                        Datum::List(pair, _) if pair.len() == 2 => Ok(Param {
+                            name: self.name(&pair[0])?,
+                            type_: Self::type_(&pair[1])?,
+                        }),
+                        _ => Err(Self::error(*position, "expected a (name type) parameter")),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                StmtKind::ProcDeclaration(Proc {
+                    name,
+                    params,
+                    return_type: Self::type_(&items[3])?,
+                    body: self.block(&items[4])?,
+                })
+            }
+            ("struct", 3) => {
+                let name = self.name(&items[1])?;
+                StmtKind::StructDeclaration(Struct {
+                    name,
+                    fields: self.fields(&items[2])?,
+                })
+            }
+            ("enum", n) if n >= 2 => {
+                let name = self.name(&items[1])?;
+                let variants = items[2..]
+                    .iter()
+                    .map(|variant| self.variant(variant))
+                    .collect::<Result<_, _>>()?;
+                StmtKind::EnumDeclaration(Enum { name, variants })
+            }
+            ("case", n) if n >= 2 && Self::is_case_stmt(items) => {
+                let scrutinee = self.expr(&items[1])?;
+                StmtKind::Case(scrutinee, self.arms(&items[2..], Self::block)?)
+            }
+            ("var" | "const" | "assign" | "if" | "while" | "return" | "proc" | "struct" | "enum", _) => {
+                return Err(Self::error(datum.position(), &format!("malformed ({} ...)", head)))
+            }
+            _ => StmtKind::Expr(self.expr(datum)?),
+        };
+
+        Ok(self.ast.add_stmt(kind, Span::default()))
+    }
+
+    /// A bound of a slice, () when it is left out
+    fn bound(&mut self, datum: &Datum) -> Result<Option<ExprId>, ReadError> {
+        match datum {
+            Datum::List(items, _) if items.is_empty() => Ok(None),
+            _ => Ok(Some(self.expr(datum)?)),
+        }
+    }
+
+    fn unary_operator(datum: &Datum) -> Option<UnaryOpKind> {
+        match datum {
+            Datum::Symbol(op, _) if op == "-" => Some(UnaryOpKind::Neg),
+            Datum::Symbol(op, _) if op == "!" => Some(UnaryOpKind::Not),
+            _ => None,
+        }
+    }
+
+    fn binary_operator(datum: &Datum) -> Option<BinOpKind> {
+        let Datum::Symbol(op, _) = datum else {
+            return None;
+        };
+
+        let op = match op.as_str() {
+            "+" => BinOpKind::Add,
+            "-" => BinOpKind::Sub,
+            "*" => BinOpKind::Mul,
+            "/" => BinOpKind::Div,
+            "==" => BinOpKind::Eq,
+            "!=" => BinOpKind::Ne,
+            "<" => BinOpKind::Lt,
+            "<=" => BinOpKind::Le,
+            ">" => BinOpKind::Gt,
+            ">=" => BinOpKind::Ge,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn expr(&mut self, datum: &Datum) -> Result<ExprId, ReadError> {
+        let kind = match datum {
+            Datum::Int(n, position) => {
+                let n = u128::try_from(*n).map_err(|_| Self::error(*position, "integer out of range"))?;
+                ExprKind::IntLit(n)
+            }
+            Datum::Str(s, _) => ExprKind::StringLit(s.clone()),
+            Datum::Symbol(s, _) if s == "true" => ExprKind::BoolLit(true),
+            Datum::Symbol(s, _) if s == "false" => ExprKind::BoolLit(false),
+            Datum::Symbol(_, _) => ExprKind::Variable(self.name(datum)?),
+            Datum::List(items, position) => match items.as_slice() {
+                [Datum::Symbol(head, _), name, args @ ..] if head == "call" => ExprKind::Call(
+                    self.name(name)?,
+                    args.iter().map(|arg| self.expr(arg)).collect::<Result<_, _>>()?,
+                ),
+                [Datum::Symbol(head, _), name, fields @ ..] if head == "new" => {
+                    let name = self.name(name)?;
+                    let fields = fields
+                        .iter()
+                        .map(|field| match field {
+                            Datum::List(pair, _) if pair.len() == 2 => {
+                                Ok((self.name(&pair[0])?, self.expr(&pair[1])?))
+                            }
+                            _ => Err(Self::error(field.position(), "expected a (name value) field")),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    ExprKind::StructLit(name, fields)
+                }
+                [Datum::Symbol(head, _), elements @ ..] if head == "array" => ExprKind::ArrayLit(
+                    elements.iter().map(|element| self.expr(element)).collect::<Result<_, _>>()?,
+                ),
+                [Datum::Symbol(head, _), object, index] if head == "index" => {
+                    let object = self.expr(object)?;
+                    ExprKind::Index(object, self.expr(index)?)
+                }
+                [Datum::Symbol(head, _), object, start, end] if head == "slice" => {
+                    let object = self.expr(object)?;
+                    let start = self.bound(start)?;
+                    ExprKind::Slice(object, start, self.bound(end)?)
+                }
+                [Datum::Symbol(head, _), scrutinee, arms @ ..] if head == "case" => {
+                    let scrutinee = self.expr(scrutinee)?;
+                    ExprKind::Case(scrutinee, self.arms(arms, Self::expr)?)
+                }
+                [Datum::Symbol(head, _), object, field] if head == "." => {
+                    let object = self.expr(object)?;
+                    ExprKind::FieldAccess(object, self.name(field)?)
+                }
+                [op, rhs] => {
+                    let op = Self::unary_operator(op)
+                        .ok_or_else(|| Self::error(*position, "unknown unary operator"))?;
+                    ExprKind::UnaryOp(op, self.expr(rhs)?)
+                }
+                [op, lhs, rhs] => {
+                    let op = Self::binary_operator(op)
+                        .ok_or_else(|| Self::error(*position, "unknown binary operator"))?;
+                    {
+                        let lhs = self.expr(lhs)?;
+                        ExprKind::BinOp(lhs, op, self.expr(rhs)?)
+                    }
+                }
+                _ => return Err(Self::error(*position, "expected an expression")),
+            },
+        };
+
+        Ok(self.ast.add_expr(kind, Span::default()))
+    }
+}
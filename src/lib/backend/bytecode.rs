@@ -0,0 +1,354 @@
+//! Bytecode for the virtual machine.
+//!
+//! A program is a constant pool, a list of globals and a list of functions,
+//! one per proc plus the entry function running the top level statements.
+//! Each function is a sequence of instructions for a stack machine: operands
+//! are pushed onto the value stack and instructions pop their operands and
+//! push their result. A call's arguments become the first locals of the
+//! callee, which live on the value stack below its operands.
+//!
+//! Structs, arrays and enums with a payload are all records, a list of
+//! values. An enum's record starts with its tag, enums without payloads are
+//! just their tag. Records are values like in Lune: reading one out of a
+//! variable, field or element is followed by `copy`, and storing one
+//! overwrites the stored record in place, so slices into it see the change.
+//!
+//! `Display` prints the disassembly `--emit=bytecode` shows:
+//!
+//! ```text
+//! entry main
+//!
+//! const #0 = int 1
+//! const #1 = int 41
+//!
+//! proc inc(1) locals 1 {
+//!     0  load_local 0
+//!     1  const #0          ; 1
+//!     2  add
+//!     3  return_value
+//! }
+//!
+//! proc main(0) locals 0 {
+//!     0  const #1          ; 41
+//!     1  call 0            ; inc
+//!     2  print
+//!     3  return
+//! }
+//! ```
+
+use std::fmt;
+
+use super::ast::{BinOpKind, UnaryOpKind};
+use crate::json::Json;
+
+// Compilation from the checked AST
+pub mod compile;
+
+// Reading and writing `.lbc` files
+pub mod file;
+
+// The virtual machine
+pub mod vm;
+
+/// An entry of the constant pool
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i32),
+    Str(String),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Int(n) => write!(f, "int {}", n),
+            Constant::Str(s) => write!(f, "str {}", Json::String(s.clone())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    /// Push an entry of the constant pool
+    Const(u32),
+    True,
+    False,
+    Pop,
+    /// Replace the record on top with a copy of it, anything else is kept
+    Copy,
+    LoadLocal(u32),
+    StoreLocal(u32),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    Unary(UnaryOpKind),
+    Binary(BinOpKind),
+    Jump(u32),
+    /// Pop a bool and jump if it is false
+    JumpIfFalse(u32),
+    /// Call a function, popping its arguments
+    Call(u32),
+    /// Return from a function without a result
+    Return,
+    /// Return the value on top
+    ReturnValue,
+    Print,
+    /// Length of the array or slice on top
+    Len,
+    /// Pop that many values into a record, the first pushed first
+    Record(u32),
+    /// Replace the record on top with one of its fields
+    Field(u32),
+    /// Pop a value and a record and store the value into a field of it
+    SetField(u32),
+    /// Pop an index and an array or slice, push the element
+    Index,
+    /// Pop a value, an index and an array or slice, store the element
+    SetIndex,
+    /// Pop the bounds that are given and an array or slice, push a slice of it
+    Slice {
+        start: bool,
+        end: bool,
+    },
+    /// Replace the enum record on top with its tag
+    Tag,
+}
+
+impl Instr {
+    /// Number of the instruction in `.lbc` files and its operand, if it has one
+    pub fn encode(&self) -> (u8, Option<u32>) {
+        match *self {
+            Instr::Const(n) => (0, Some(n)),
+            Instr::True => (1, None),
+            Instr::False => (2, None),
+            Instr::Pop => (3, None),
+            Instr::Copy => (4, None),
+            Instr::LoadLocal(n) => (5, Some(n)),
+            Instr::StoreLocal(n) => (6, Some(n)),
+            Instr::LoadGlobal(n) => (7, Some(n)),
+            Instr::StoreGlobal(n) => (8, Some(n)),
+            Instr::Unary(UnaryOpKind::Neg) => (9, None),
+            Instr::Unary(UnaryOpKind::Not) => (10, None),
+            Instr::Binary(BinOpKind::Add) => (11, None),
+            Instr::Binary(BinOpKind::Sub) => (12, None),
+            Instr::Binary(BinOpKind::Mul) => (13, None),
+            Instr::Binary(BinOpKind::Div) => (14, None),
+            Instr::Binary(BinOpKind::Eq) => (15, None),
+            Instr::Binary(BinOpKind::Ne) => (16, None),
+            Instr::Binary(BinOpKind::Lt) => (17, None),
+            Instr::Binary(BinOpKind::Le) => (18, None),
+            Instr::Binary(BinOpKind::Gt) => (19, None),
+            Instr::Binary(BinOpKind::Ge) => (20, None),
+            Instr::Jump(n) => (21, Some(n)),
+            Instr::JumpIfFalse(n) => (22, Some(n)),
+            Instr::Call(n) => (23, Some(n)),
+            Instr::Return => (24, None),
+            Instr::ReturnValue => (25, None),
+            Instr::Print => (26, None),
+            Instr::Len => (27, None),
+            Instr::Record(n) => (28, Some(n)),
+            Instr::Field(n) => (29, Some(n)),
+            Instr::SetField(n) => (30, Some(n)),
+            Instr::Index => (31, None),
+            Instr::SetIndex => (32, None),
+            Instr::Slice { start, end } => (33, Some(start as u32 | (end as u32) << 1)),
+            Instr::Tag => (34, None),
+        }
+    }
+
+    /// Whether the instruction numbered `op` takes an operand
+    pub fn has_operand(op: u8) -> bool {
+        matches!(op, 0 | 5..=8 | 21..=23 | 28..=30 | 33)
+    }
+
+    /// The instruction numbered `op` with `operand`, None if there is no such instruction
+    pub fn decode(op: u8, operand: u32) -> Option<Instr> {
+        let instr = match op {
+            0 => Instr::Const(operand),
+            1 => Instr::True,
+            2 => Instr::False,
+            3 => Instr::Pop,
+            4 => Instr::Copy,
+            5 => Instr::LoadLocal(operand),
+            6 => Instr::StoreLocal(operand),
+            7 => Instr::LoadGlobal(operand),
+            8 => Instr::StoreGlobal(operand),
+            9 => Instr::Unary(UnaryOpKind::Neg),
+            10 => Instr::Unary(UnaryOpKind::Not),
+            11 => Instr::Binary(BinOpKind::Add),
+            12 => Instr::Binary(BinOpKind::Sub),
+            13 => Instr::Binary(BinOpKind::Mul),
+            14 => Instr::Binary(BinOpKind::Div),
+            15 => Instr::Binary(BinOpKind::Eq),
+            16 => Instr::Binary(BinOpKind::Ne),
+            17 => Instr::Binary(BinOpKind::Lt),
+            18 => Instr::Binary(BinOpKind::Le),
+            19 => Instr::Binary(BinOpKind::Gt),
+            20 => Instr::Binary(BinOpKind::Ge),
+            21 => Instr::Jump(operand),
+            22 => Instr::JumpIfFalse(operand),
+            23 => Instr::Call(operand),
+            24 => Instr::Return,
+            25 => Instr::ReturnValue,
+            26 => Instr::Print,
+            27 => Instr::Len,
+            28 => Instr::Record(operand),
+            29 => Instr::Field(operand),
+            30 => Instr::SetField(operand),
+            31 => Instr::Index,
+            32 => Instr::SetIndex,
+            33 if operand < 4 => Instr::Slice {
+                start: operand & 1 != 0,
+                end: operand & 2 != 0,
+            },
+            34 => Instr::Tag,
+            _ => return None,
+        };
+        Some(instr)
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Instr::Const(n) => return write!(f, "const #{}", n),
+            Instr::True => "true",
+            Instr::False => "false",
+            Instr::Pop => "pop",
+            Instr::Copy => "copy",
+            Instr::LoadLocal(n) => return write!(f, "load_local {}", n),
+            Instr::StoreLocal(n) => return write!(f, "store_local {}", n),
+            Instr::LoadGlobal(n) => return write!(f, "load_global @{}", n),
+            Instr::StoreGlobal(n) => return write!(f, "store_global @{}", n),
+            Instr::Unary(UnaryOpKind::Neg) => "neg",
+            Instr::Unary(UnaryOpKind::Not) => "not",
+            Instr::Binary(op) => match op {
+                BinOpKind::Add => "add",
+                BinOpKind::Sub => "sub",
+                BinOpKind::Mul => "mul",
+                BinOpKind::Div => "div",
+                BinOpKind::Eq => "eq",
+                BinOpKind::Ne => "ne",
+                BinOpKind::Lt => "lt",
+                BinOpKind::Le => "le",
+                BinOpKind::Gt => "gt",
+                BinOpKind::Ge => "ge",
+            },
+            Instr::Jump(n) => return write!(f, "jump {}", n),
+            Instr::JumpIfFalse(n) => return write!(f, "jump_if_false {}", n),
+            Instr::Call(n) => return write!(f, "call {}", n),
+            Instr::Return => "return",
+            Instr::ReturnValue => "return_value",
+            Instr::Print => "print",
+            Instr::Len => "len",
+            Instr::Record(n) => return write!(f, "record {}", n),
+            Instr::Field(n) => return write!(f, "field {}", n),
+            Instr::SetField(n) => return write!(f, "set_field {}", n),
+            Instr::Index => "index",
+            Instr::SetIndex => "set_index",
+            Instr::Slice { start, end } => {
+                let start = if *start { "start" } else { "" };
+                let end = if *end { "end" } else { "" };
+                return write!(f, "slice {}..{}", start, end);
+            }
+            Instr::Tag => "tag",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Source position of an instruction that can fail at run time, 1 based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub pc: u32,
+    /// Index of the file in `Program::files`
+    pub file: u32,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    /// Number of parameters, the first locals
+    pub params: u32,
+    /// Number of locals, parameters included
+    pub locals: u32,
+    pub code: Vec<Instr>,
+    /// Locations of the instructions that can fail, by increasing `pc`
+    pub locations: Vec<Location>,
+}
+
+impl Function {
+    /// Source location of the instruction at `pc`, if it has one
+    pub fn location(&self, pc: usize) -> Option<Location> {
+        let i = self
+            .locations
+            .binary_search_by_key(&(pc as u32), |location| location.pc)
+            .ok()?;
+        Some(self.locations[i])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// Paths of the source files, which runtime errors are reported in
+    pub files: Vec<String>,
+    pub constants: Vec<Constant>,
+    /// Names of the globals
+    pub globals: Vec<String>,
+    pub functions: Vec<Function>,
+    /// Function running the top level statements
+    pub entry: u32,
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "entry {}", self.functions[self.entry as usize].name)?;
+        if !self.constants.is_empty() {
+            writeln!(f)?;
+        }
+        for (i, constant) in self.constants.iter().enumerate() {
+            writeln!(f, "const #{} = {}", i, constant)?;
+        }
+        if !self.globals.is_empty() {
+            writeln!(f)?;
+        }
+        for (i, global) in self.globals.iter().enumerate() {
+            writeln!(f, "global @{} {}", i, global)?;
+        }
+
+        for function in &self.functions {
+            writeln!(
+                f,
+                "\nproc {}({}) locals {} {{",
+                function.name, function.params, function.locals
+            )?;
+            let width = function.code.len().saturating_sub(1).to_string().len();
+            for (pc, instr) in function.code.iter().enumerate() {
+                // What operands refer to, where it isn't obvious from the number
+                let comment = match instr {
+                    Instr::Const(n) => self.constants.get(*n as usize).map(|c| match c {
+                        Constant::Int(n) => n.to_string(),
+                        Constant::Str(s) => Json::String(s.clone()).to_string(),
+                    }),
+                    Instr::LoadGlobal(n) | Instr::StoreGlobal(n) => {
+                        self.globals.get(*n as usize).cloned()
+                    }
+                    Instr::Call(n) => self
+                        .functions
+                        .get(*n as usize)
+                        .map(|callee| callee.name.clone()),
+                    _ => None,
+                };
+                match comment {
+                    Some(comment) => {
+                        let instr = instr.to_string();
+                        writeln!(f, "    {:>width$}  {:<16}  ; {}", pc, instr, comment)?;
+                    }
+                    None => writeln!(f, "    {:>width$}  {}", pc, instr)?,
+                }
+            }
+            writeln!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
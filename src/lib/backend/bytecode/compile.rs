@@ -0,0 +1,736 @@
+//! Compilation from the checked, constant folded AST into bytecode.
+//!
+//! Top level procs become functions of the same name and top level
+//! statements become the entry function, which comes last. Top level
+//! variables become globals, every other variable, parameter and binding of
+//! a case arm gets its own local in its function.
+//!
+//! Expressions naming a place, a variable, field or element, are compiled
+//! without copying when they are the object of a field access, index or
+//! slice, so that slices point into the record itself. Used as values they
+//! are copied, unless they are ints, bools, strings or slices.
+//!
+//! Globals start out zeroed in C, which a proc can see if it's called before
+//! a global's declaration runs. The entry function starts by storing the
+//! zero value into the globals procs refer to.
+//!
+//! `case` stores its scrutinee in a local and tests the arms in order, the
+//! last one needs no test as cases are exhaustive. Code after a `return` is
+//! left out.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::backend::ast::{
+    Arm, ExprId, ExprKind, Name, NodeId, Pattern, PatternKind, StmtId, StmtKind, AST,
+};
+use crate::backend::visit::{walk_stmt, Visitor};
+use crate::frontend::consteval::Value;
+use crate::frontend::lexer::token::Span;
+use crate::frontend::modules::SourceFile;
+use crate::frontend::resolver::{DefKind, Resolution};
+use crate::types::Type;
+
+/// Where a definition lives at run time
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    Local(u32),
+    Global(u32),
+    Function(u32),
+    /// A constant, by the instruction pushing its value
+    Const(Instr),
+    /// A variant of an enum, by its tag
+    Variant(i32),
+}
+
+/// Collects the definitions named in procs
+struct Uses<'a> {
+    resolution: &'a Resolution,
+    defs: HashSet<usize>,
+    /// Whether the statements being visited are in a proc
+    in_proc: bool,
+}
+
+impl Visitor<()> for Uses<'_> {
+    fn visit_stmt(&mut self, ast: &AST, stmt: StmtId) {
+        match &ast[stmt].kind {
+            StmtKind::ProcDeclaration(_) => {
+                self.in_proc = true;
+                walk_stmt(self, ast, stmt);
+                self.in_proc = false;
+            }
+            _ if self.in_proc => walk_stmt(self, ast, stmt),
+            _ => {}
+        }
+    }
+
+    fn visit_name(&mut self, name: &Name) {
+        if let Some(&def) = self.resolution.names.get(&name.id) {
+            self.defs.insert(def);
+        }
+    }
+}
+
+/// Compiles an AST that resolved and type checked without errors
+pub struct Compiler<'a> {
+    resolution: &'a Resolution,
+    types: &'a HashMap<NodeId, Type>,
+    /// Values of the constants, by the id of their declaration's name
+    consts: &'a HashMap<NodeId, Value>,
+    /// Files of the program, for the locations of runtime errors
+    files: &'a [SourceFile],
+    places: HashMap<usize, Place>,
+    /// Variant whose tag is 0 of each enum, by the enum's name
+    zero_variants: HashMap<String, usize>,
+    constants: Vec<Constant>,
+    /// The function being compiled
+    code: Vec<Instr>,
+    locations: Vec<Location>,
+    locals: u32,
+    /// Whether control can reach the end of `code`, nothing is emitted when it can't
+    live: bool,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(
+        resolution: &'a Resolution,
+        types: &'a HashMap<NodeId, Type>,
+        consts: &'a HashMap<NodeId, Value>,
+    ) -> Compiler<'a> {
+        Compiler {
+            resolution,
+            types,
+            consts,
+            files: &[],
+            places: HashMap::new(),
+            zero_variants: HashMap::new(),
+            constants: vec![],
+            code: vec![],
+            locations: vec![],
+            locals: 0,
+            live: true,
+        }
+    }
+
+    /// Report runtime errors in the files the program was loaded from
+    pub fn with_files(mut self, files: &'a [SourceFile]) -> Compiler<'a> {
+        self.files = files;
+        self
+    }
+
+    /// Compile the program `ast`
+    pub fn compile(&mut self, ast: &AST) -> Program {
+        let mut procs = vec![];
+        let mut globals = vec![];
+        for &id in &ast.root {
+            match &ast[id].kind {
+                StmtKind::ProcDeclaration(proc) => {
+                    let place = Place::Function(procs.len() as u32);
+                    self.places.insert(self.def(&proc.name), place);
+                    procs.push(proc);
+                }
+                StmtKind::VarDeclaration(name, _, _) => {
+                    let place = Place::Global(globals.len() as u32);
+                    self.places.insert(self.def(name), place);
+                    globals.push(name.value.clone());
+                }
+                StmtKind::ConstDeclaration(name, _, _) => {
+                    // Constants are only missing when their declaration had errors
+                    if let Some(value) = self.consts.get(&name.id) {
+                        let instr = match value {
+                            Value::Int(n) => Instr::Const(self.constant(Constant::Int(*n))),
+                            Value::Str(s) => Instr::Const(self.constant(Constant::Str(s.clone()))),
+                            Value::Bool(true) => Instr::True,
+                            Value::Bool(false) => Instr::False,
+                        };
+                        self.places.insert(self.def(name), Place::Const(instr));
+                    }
+                }
+                StmtKind::EnumDeclaration(enum_) => {
+                    // Like C, variants without a value count up from the previous one
+                    let mut next = 0;
+                    for variant in &enum_.variants {
+                        let tag = variant.value.unwrap_or(next) as i32;
+                        let def = self.def(&variant.name);
+                        self.places.insert(def, Place::Variant(tag));
+                        if tag == 0 {
+                            self.zero_variants.insert(enum_.name.value.clone(), def);
+                        }
+                        next = tag as i128 + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut functions = vec![];
+        for proc in &procs {
+            self.begin();
+            for param in &proc.params {
+                self.local(&param.name);
+            }
+            self.block(ast, &proc.body);
+            functions.push(self.finish(&proc.name.value, proc.params.len() as u32));
+        }
+
+        // The entry function takes a name no proc has
+        let mut entry = "main".to_string();
+        let mut n = 0;
+        while procs.iter().any(|proc| proc.name.value == entry) {
+            n += 1;
+            entry = format!("main_{}", n);
+        }
+        self.begin();
+        let mut uses = Uses {
+            resolution: self.resolution,
+            defs: HashSet::new(),
+            in_proc: false,
+        };
+        for &id in &ast.root {
+            uses.visit_stmt(ast, id);
+        }
+        for &id in &ast.root {
+            if let StmtKind::VarDeclaration(name, _, value) = &ast[id].kind {
+                if uses.defs.contains(&self.def(name)) {
+                    self.zero(self.type_of(ast, *value));
+                    self.store(name);
+                }
+            }
+        }
+        for &id in &ast.root {
+            match &ast[id].kind {
+                StmtKind::VarDeclaration(name, _, value) => {
+                    self.expr(ast, *value);
+                    self.store(name);
+                }
+                _ => self.stmt(ast, id),
+            }
+        }
+        functions.push(self.finish(&entry, 0));
+
+        Program {
+            files: match self.files {
+                [] => vec!["<input>".into()],
+                files => files
+                    .iter()
+                    .map(|file| file.path.display().to_string())
+                    .collect(),
+            },
+            constants: std::mem::take(&mut self.constants),
+            globals,
+            entry: functions.len() as u32 - 1,
+            functions,
+        }
+    }
+
+    fn def(&self, name: &Name) -> usize {
+        self.resolution.names[&name.id]
+    }
+
+    fn type_of(&self, ast: &AST, id: ExprId) -> &'a Type {
+        self.types
+            .get(&ast[id].id)
+            .expect("expression should be type checked")
+    }
+
+    /// Whether values of `type_` are records, which are copied when read
+    fn is_record(&self, type_: &Type) -> bool {
+        match type_ {
+            Type::Array(_, _) => true,
+            Type::Named(_) => {
+                self.resolution.struct_def(type_).is_some()
+                    || self.resolution.is_tagged_union(type_)
+            }
+            _ => false,
+        }
+    }
+
+    /// Index of `constant` in the pool, adding it if it isn't there yet
+    fn constant(&mut self, constant: Constant) -> u32 {
+        let i = match self.constants.iter().position(|other| *other == constant) {
+            Some(i) => i,
+            None => {
+                self.constants.push(constant);
+                self.constants.len() - 1
+            }
+        };
+        i as u32
+    }
+
+    fn int(&mut self, n: i32) {
+        let constant = self.constant(Constant::Int(n));
+        self.emit(Instr::Const(constant));
+    }
+
+    /// Push the value a C global of `type_` starts out with
+    fn zero(&mut self, type_: &Type) {
+        match type_ {
+            Type::Int => self.int(0),
+            Type::Bool => self.emit(Instr::False),
+            Type::String => {
+                let constant = self.constant(Constant::Str(String::new()));
+                self.emit(Instr::Const(constant));
+            }
+            Type::Array(element, len) => {
+                for _ in 0..*len {
+                    self.zero(element);
+                }
+                self.emit(Instr::Record(*len as u32));
+            }
+            Type::Slice(_) => {
+                self.emit(Instr::Record(0));
+                self.emit(Instr::Slice {
+                    start: false,
+                    end: false,
+                });
+            }
+            Type::Named(_) => {
+                let resolution = self.resolution;
+                if let Some(def) = resolution.struct_def(type_) {
+                    for (_, type_) in &def.fields {
+                        self.zero(type_);
+                    }
+                    self.emit(Instr::Record(def.fields.len() as u32));
+                    return;
+                }
+                // An enum, the payload is that of the variant whose tag is 0 if there is one
+                self.int(0);
+                if !resolution.is_tagged_union(type_) {
+                    return;
+                }
+                let Type::Named(name) = type_ else {
+                    unreachable!("enums are named types");
+                };
+                let fields = match self.zero_variants.get(name) {
+                    Some(&variant) => &resolution.defs[variant].fields[..],
+                    None => &[],
+                };
+                for (_, type_) in fields {
+                    self.zero(type_);
+                }
+                self.emit(Instr::Record(fields.len() as u32 + 1));
+            }
+            Type::Void | Type::ConstArray(_, _) => unreachable!("variables have a known type"),
+        }
+    }
+
+    /// Start compiling a function
+    fn begin(&mut self) {
+        self.code.clear();
+        self.locations.clear();
+        self.locals = 0;
+        self.live = true;
+    }
+
+    /// Finish the function being compiled, returning at its end if control gets there
+    fn finish(&mut self, name: &str, params: u32) -> Function {
+        self.emit(Instr::Return);
+        Function {
+            name: name.into(),
+            params,
+            locals: self.locals,
+            code: std::mem::take(&mut self.code),
+            locations: std::mem::take(&mut self.locations),
+        }
+    }
+
+    /// Give the definition `name` declares a new local
+    fn local(&mut self, name: &Name) -> u32 {
+        let local = self.temp();
+        self.places.insert(self.def(name), Place::Local(local));
+        local
+    }
+
+    /// A new local no definition refers to
+    fn temp(&mut self) -> u32 {
+        self.locals += 1;
+        self.locals - 1
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        if !self.live {
+            return;
+        }
+        self.code.push(instr);
+        if matches!(instr, Instr::Jump(_) | Instr::Return | Instr::ReturnValue) {
+            self.live = false;
+        }
+    }
+
+    /// Emit an instruction that can fail, recording the location of `span` for its error
+    fn emit_at(&mut self, instr: Instr, span: Span) {
+        if !self.live {
+            return;
+        }
+        let file = self.files.iter().rposition(|file| file.base <= span.start);
+        let (line, column) = match file {
+            Some(file) => self.files[file].position(span.start),
+            None => (span.line, 0),
+        };
+        self.locations.push(Location {
+            pc: self.code.len() as u32,
+            file: file.unwrap_or(0) as u32,
+            line: line as u32 + 1,
+            column: column as u32 + 1,
+        });
+        self.emit(instr);
+    }
+
+    /// Emit a jump to be pointed at a label later, None if it can't be reached
+    fn jump(&mut self, instr: Instr) -> Option<usize> {
+        if !self.live {
+            return None;
+        }
+        self.emit(instr);
+        Some(self.code.len() - 1)
+    }
+
+    /// Point `jumps` here, control reaches here if any of them can be taken
+    fn label(&mut self, jumps: impl IntoIterator<Item = Option<usize>>) {
+        let target = self.code.len() as u32;
+        for jump in jumps.into_iter().flatten() {
+            match &mut self.code[jump] {
+                Instr::Jump(to) | Instr::JumpIfFalse(to) => *to = target,
+                _ => unreachable!("only jumps are patched"),
+            }
+            self.live = true;
+        }
+    }
+
+    /// Store the value on top into the variable `name` declares or refers to
+    fn store(&mut self, name: &Name) {
+        let def = self.def(name);
+        let place = match self.places.get(&def) {
+            Some(&place) => place,
+            None => Place::Local(self.local(name)),
+        };
+        match place {
+            Place::Local(local) => self.emit(Instr::StoreLocal(local)),
+            Place::Global(global) => self.emit(Instr::StoreGlobal(global)),
+            _ => unreachable!("only variables are assigned to"),
+        }
+    }
+
+    fn block(&mut self, ast: &AST, stmts: &[StmtId]) {
+        for &stmt in stmts {
+            self.stmt(ast, stmt);
+        }
+    }
+
+    fn stmt(&mut self, ast: &AST, id: StmtId) {
+        match &ast[id].kind {
+            StmtKind::Expr(expr) => {
+                self.expr(ast, *expr);
+                if *self.type_of(ast, *expr) != Type::Void {
+                    self.emit(Instr::Pop);
+                }
+            }
+            StmtKind::VarDeclaration(name, _, value) => {
+                // The value can refer to an outer variable of the same name,
+                // so it is compiled before the new one gets its local
+                self.expr(ast, *value);
+                let local = self.local(name);
+                self.emit(Instr::StoreLocal(local));
+            }
+            StmtKind::Assignment(target, value) => match &ast[*target].kind {
+                ExprKind::Variable(name) => {
+                    self.expr(ast, *value);
+                    self.store(name);
+                }
+                ExprKind::FieldAccess(object, field) => {
+                    self.place(ast, *object);
+                    self.expr(ast, *value);
+                    let field = self.field(ast, *object, &field.value);
+                    self.emit(Instr::SetField(field));
+                }
+                ExprKind::Index(object, index) => {
+                    self.place(ast, *object);
+                    self.expr(ast, *index);
+                    self.expr(ast, *value);
+                    self.emit_at(Instr::SetIndex, ast[*target].span);
+                }
+                _ => unreachable!("assignments are to places"),
+            },
+            StmtKind::If(cond, then, otherwise) => {
+                self.expr(ast, *cond);
+                let skip = self.jump(Instr::JumpIfFalse(0));
+                self.block(ast, then);
+                match otherwise {
+                    Some(otherwise) => {
+                        let end = self.jump(Instr::Jump(0));
+                        self.label([skip]);
+                        self.block(ast, otherwise);
+                        self.label([end]);
+                    }
+                    None => self.label([skip]),
+                }
+            }
+            StmtKind::While(cond, body) => {
+                let start = self.code.len() as u32;
+                self.expr(ast, *cond);
+                let exit = self.jump(Instr::JumpIfFalse(0));
+                self.block(ast, body);
+                self.emit(Instr::Jump(start));
+                self.label([exit]);
+            }
+            StmtKind::Return(Some(value)) => {
+                self.expr(ast, *value);
+                self.emit(Instr::ReturnValue);
+            }
+            StmtKind::Return(None) => self.emit(Instr::Return),
+            StmtKind::Case(scrutinee, arms) => {
+                self.case(ast, *scrutinee, arms, |compiler, body| {
+                    compiler.block(ast, body)
+                });
+            }
+            // Procs, types and constants only appear at the top level, which
+            // `compile` handles
+            StmtKind::ProcDeclaration(_)
+            | StmtKind::StructDeclaration(_)
+            | StmtKind::EnumDeclaration(_)
+            | StmtKind::ConstDeclaration(_, _, _) => {}
+        }
+    }
+
+    /// Index of the field `name` of the struct `object` is
+    fn field(&self, ast: &AST, object: ExprId, name: &str) -> u32 {
+        let def = self
+            .resolution
+            .struct_def(self.type_of(ast, object))
+            .expect("fields are accessed on structs");
+        def.fields
+            .iter()
+            .position(|(field, _)| field == name)
+            .unwrap() as u32
+    }
+
+    /// Compile `expr` as a value, copying records read out of a place
+    fn expr(&mut self, ast: &AST, id: ExprId) {
+        self.place(ast, id);
+        let is_place = match &ast[id].kind {
+            ExprKind::Variable(name) => {
+                matches!(
+                    self.places[&self.def(name)],
+                    Place::Local(_) | Place::Global(_)
+                )
+            }
+            ExprKind::FieldAccess(_, _) | ExprKind::Index(_, _) => true,
+            _ => false,
+        };
+        if is_place && self.is_record(self.type_of(ast, id)) {
+            self.emit(Instr::Copy);
+        }
+    }
+
+    /// Compile `expr` without copying what it reads, for accessing part of it
+    fn place(&mut self, ast: &AST, id: ExprId) {
+        match &ast[id].kind {
+            // Literals too large for an int are negated, which wraps back around
+            ExprKind::IntLit(n) => self.int(*n as i32),
+            ExprKind::StringLit(s) => {
+                let constant = self.constant(Constant::Str(s.clone()));
+                self.emit(Instr::Const(constant));
+            }
+            ExprKind::BoolLit(true) => self.emit(Instr::True),
+            ExprKind::BoolLit(false) => self.emit(Instr::False),
+            ExprKind::Variable(name) => match self.places[&self.def(name)] {
+                Place::Local(local) => self.emit(Instr::LoadLocal(local)),
+                Place::Global(global) => self.emit(Instr::LoadGlobal(global)),
+                Place::Const(instr) => self.emit(instr),
+                Place::Variant(tag) => self.variant(ast, name, tag, &[]),
+                Place::Function(_) => unreachable!("procs are only called"),
+            },
+            ExprKind::Call(name, args) => {
+                let def = self.def(name);
+                match self.resolution.defs[def].kind {
+                    DefKind::Builtin if name.value == "print" => {
+                        self.expr(ast, args[0]);
+                        self.emit(Instr::Print);
+                    }
+                    DefKind::Builtin => {
+                        self.place(ast, args[0]);
+                        self.emit(Instr::Len);
+                    }
+                    _ => match self.places[&def] {
+                        Place::Variant(tag) => self.variant(ast, name, tag, args),
+                        Place::Function(function) => {
+                            for &arg in args {
+                                self.expr(ast, arg);
+                            }
+                            self.emit_at(Instr::Call(function), ast[id].span);
+                        }
+                        _ => unreachable!("only procs and variants are called"),
+                    },
+                }
+            }
+            ExprKind::UnaryOp(op, rhs) => {
+                self.expr(ast, *rhs);
+                self.emit(Instr::Unary(*op));
+            }
+            ExprKind::BinOp(lhs, op, rhs) => {
+                self.expr(ast, *lhs);
+                self.expr(ast, *rhs);
+                self.emit_at(Instr::Binary(*op), ast[id].span);
+            }
+            // Fields are stored in declaration order, whatever order the literal has
+            ExprKind::StructLit(_, fields) => {
+                let def = self
+                    .resolution
+                    .struct_def(self.type_of(ast, id))
+                    .expect("struct literals have a struct type");
+                for (field, _) in &def.fields {
+                    let (_, value) = fields
+                        .iter()
+                        .find(|(name, _)| name.value == *field)
+                        .unwrap();
+                    self.expr(ast, *value);
+                }
+                self.emit(Instr::Record(def.fields.len() as u32));
+            }
+            ExprKind::FieldAccess(object, field) => {
+                self.place(ast, *object);
+                let field = self.field(ast, *object, &field.value);
+                self.emit(Instr::Field(field));
+            }
+            ExprKind::Case(scrutinee, arms) => {
+                self.case(ast, *scrutinee, arms, |compiler, body| {
+                    compiler.expr(ast, *body)
+                });
+            }
+            ExprKind::ArrayLit(elements) => {
+                for &element in elements {
+                    self.expr(ast, element);
+                }
+                self.emit(Instr::Record(elements.len() as u32));
+            }
+            ExprKind::Index(object, index) => {
+                self.place(ast, *object);
+                self.expr(ast, *index);
+                self.emit_at(Instr::Index, ast[id].span);
+            }
+            ExprKind::Slice(object, start, end) => {
+                self.place(ast, *object);
+                for &bound in start.iter().chain(end) {
+                    self.expr(ast, bound);
+                }
+                let slice = Instr::Slice {
+                    start: start.is_some(),
+                    end: end.is_some(),
+                };
+                self.emit_at(slice, ast[id].span);
+            }
+        }
+    }
+
+    /// Construct the value of the variant `name` with the payload `args`
+    fn variant(&mut self, ast: &AST, name: &Name, tag: i32, args: &[ExprId]) {
+        self.int(tag);
+        let def = &self.resolution.defs[self.def(name)];
+        if !self.resolution.is_tagged_union(&def.type_) {
+            return;
+        }
+        for &arg in args {
+            self.expr(ast, arg);
+        }
+        self.emit(Instr::Record(args.len() as u32 + 1));
+    }
+
+    /// Compile a case, `body` compiles the body of an arm
+    fn case<T>(
+        &mut self,
+        ast: &AST,
+        scrutinee: ExprId,
+        arms: &[Arm<T>],
+        mut body: impl FnMut(&mut Self, &T),
+    ) {
+        let type_ = self.type_of(ast, scrutinee);
+        self.expr(ast, scrutinee);
+        let temp = self.temp();
+        self.emit(Instr::StoreLocal(temp));
+
+        let mut ends = vec![];
+        for (i, arm) in arms.iter().enumerate() {
+            let last = i + 1 == arms.len();
+            let next = if last {
+                vec![]
+            } else {
+                self.pattern(temp, type_, &arm.pattern)
+            };
+            self.bind(temp, &arm.pattern);
+            body(self, &arm.body);
+            if !last {
+                ends.push(self.jump(Instr::Jump(0)));
+                self.label(next);
+            }
+        }
+        self.label(ends);
+    }
+
+    /// Test whether the value in `temp` matches `pattern`, returning the
+    /// jumps taken when it doesn't
+    fn pattern(&mut self, temp: u32, type_: &Type, pattern: &Pattern) -> Vec<Option<usize>> {
+        let tagged = self.resolution.is_tagged_union(type_);
+        let test = |compiler: &mut Self, instr: Instr, op: BinOpKind| {
+            compiler.emit(Instr::LoadLocal(temp));
+            if tagged {
+                compiler.emit(Instr::Tag);
+            }
+            compiler.emit(instr);
+            compiler.emit(Instr::Binary(op));
+            compiler.jump(Instr::JumpIfFalse(0))
+        };
+        match &pattern.kind {
+            PatternKind::Wildcard => vec![],
+            PatternKind::Int(n) => {
+                let constant = self.constant(Constant::Int(*n as i32));
+                vec![test(self, Instr::Const(constant), BinOpKind::Eq)]
+            }
+            // Bounds at the ends of the range of ints are always true
+            PatternKind::Range(start, end) => {
+                let mut jumps = vec![];
+                if *start > i32::MIN as i128 {
+                    let constant = self.constant(Constant::Int(*start as i32));
+                    jumps.push(test(self, Instr::Const(constant), BinOpKind::Ge));
+                }
+                if *end < i32::MAX as i128 {
+                    let constant = self.constant(Constant::Int(*end as i32));
+                    jumps.push(test(self, Instr::Const(constant), BinOpKind::Le));
+                }
+                jumps
+            }
+            PatternKind::Str(s) => {
+                let constant = self.constant(Constant::Str(s.clone()));
+                vec![test(self, Instr::Const(constant), BinOpKind::Eq)]
+            }
+            PatternKind::Variant(name, _) => {
+                let Place::Variant(tag) = self.places[&self.def(name)] else {
+                    unreachable!("variant patterns name variants");
+                };
+                let constant = self.constant(Constant::Int(tag));
+                vec![test(self, Instr::Const(constant), BinOpKind::Eq)]
+            }
+        }
+    }
+
+    /// Copy the payload in `temp` into the locals of the names `pattern` binds
+    fn bind(&mut self, temp: u32, pattern: &Pattern) {
+        let PatternKind::Variant(name, bindings) = &pattern.kind else {
+            return;
+        };
+        let resolution = self.resolution;
+        let variant = &resolution.defs[self.def(name)];
+        for (i, (binding, (_, type_))) in bindings.iter().zip(&variant.fields).enumerate() {
+            // `_` binds nothing
+            if !resolution.names.contains_key(&binding.id) {
+                continue;
+            }
+            self.emit(Instr::LoadLocal(temp));
+            self.emit(Instr::Field(i as u32 + 1));
+            if self.is_record(type_) {
+                self.emit(Instr::Copy);
+            }
+            let local = self.local(binding);
+            self.emit(Instr::StoreLocal(local));
+        }
+    }
+}
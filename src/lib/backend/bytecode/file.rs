@@ -0,0 +1,363 @@
+//! Reading and writing compiled programs as `.lbc` files.
+//!
+//! A file starts with the magic bytes `LUNEBC` and a version byte, followed
+//! by the program: the source paths, the constant pool, the names of the
+//! globals, the entry function and the functions. Numbers are unsigned
+//! LEB128 except for int constants, which are 4 bytes little endian, and
+//! strings are a length followed by UTF-8. Each instruction is its number
+//! from `Instr::encode`, followed by its operand if it has one.
+//!
+//! A loaded program is checked before the VM gets it: every operand refers
+//! to something that exists, every jump lands on an instruction and the
+//! stack has the same depth whichever way control reaches an instruction,
+//! so a corrupt file is an error instead of a crash.
+
+use super::*;
+use crate::errors::BytecodeError;
+
+const MAGIC: &[u8] = b"LUNEBC";
+const VERSION: u8 = 2;
+
+/// More locals than any function needs, so a corrupt count can't exhaust memory
+const MAX_LOCALS: u32 = 1 << 20;
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+impl Program {
+    /// The program as the contents of a `.lbc` file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        write_u32(&mut out, self.files.len() as u32);
+        for file in &self.files {
+            write_str(&mut out, file);
+        }
+
+        write_u32(&mut out, self.constants.len() as u32);
+        for constant in &self.constants {
+            match constant {
+                Constant::Int(n) => {
+                    out.push(0);
+                    out.extend_from_slice(&n.to_le_bytes());
+                }
+                Constant::Str(s) => {
+                    out.push(1);
+                    write_str(&mut out, s);
+                }
+            }
+        }
+        write_u32(&mut out, self.globals.len() as u32);
+        for global in &self.globals {
+            write_str(&mut out, global);
+        }
+        write_u32(&mut out, self.entry);
+
+        write_u32(&mut out, self.functions.len() as u32);
+        for function in &self.functions {
+            write_str(&mut out, &function.name);
+            write_u32(&mut out, function.params);
+            write_u32(&mut out, function.locals);
+            write_u32(&mut out, function.code.len() as u32);
+            for instr in &function.code {
+                let (op, operand) = instr.encode();
+                out.push(op);
+                if let Some(operand) = operand {
+                    write_u32(&mut out, operand);
+                }
+            }
+            write_u32(&mut out, function.locations.len() as u32);
+            for location in &function.locations {
+                write_u32(&mut out, location.pc);
+                write_u32(&mut out, location.file);
+                write_u32(&mut out, location.line);
+                write_u32(&mut out, location.column);
+            }
+        }
+        out
+    }
+
+    /// Load a program from the contents of a `.lbc` file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, BytecodeError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if !bytes.starts_with(MAGIC) {
+            return Err(reader.error("not a Lune bytecode file"));
+        }
+        reader.position = MAGIC.len();
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(BytecodeError {
+                position: MAGIC.len(),
+                message: format!("unsupported version {}, expected {}", version, VERSION),
+            });
+        }
+        let mut files = vec![];
+        for _ in 0..reader.u32()? {
+            files.push(reader.string()?);
+        }
+
+        let mut constants = vec![];
+        for _ in 0..reader.u32()? {
+            let constant = match reader.byte()? {
+                0 => Constant::Int(reader.i32()?),
+                1 => Constant::Str(reader.string()?),
+                tag => return Err(reader.error(&format!("unknown constant kind {}", tag))),
+            };
+            constants.push(constant);
+        }
+        let mut globals = vec![];
+        for _ in 0..reader.u32()? {
+            globals.push(reader.string()?);
+        }
+        let entry = reader.u32()?;
+
+        let mut functions = vec![];
+        let mut starts = vec![];
+        for _ in 0..reader.u32()? {
+            starts.push(reader.position);
+            let name = reader.string()?;
+            let params = reader.u32()?;
+            let locals = reader.u32()?;
+            let mut code = vec![];
+            for _ in 0..reader.u32()? {
+                let op = reader.byte()?;
+                let operand = if Instr::has_operand(op) {
+                    reader.u32()?
+                } else {
+                    0
+                };
+                let instr = Instr::decode(op, operand)
+                    .ok_or_else(|| reader.error(&format!("unknown instruction {}", op)))?;
+                code.push(instr);
+            }
+            let mut locations: Vec<Location> = vec![];
+            for _ in 0..reader.u32()? {
+                let location = Location {
+                    pc: reader.u32()?,
+                    file: reader.u32()?,
+                    line: reader.u32()?,
+                    column: reader.u32()?,
+                };
+                if locations.last().is_some_and(|last| last.pc >= location.pc) {
+                    return Err(reader.error("locations are out of order"));
+                }
+                if location.file as usize >= files.len() {
+                    return Err(reader.error(&format!("file {} doesn't exist", location.file)));
+                }
+                locations.push(location);
+            }
+            functions.push(Function {
+                name,
+                params,
+                locals,
+                code,
+                locations,
+            });
+        }
+        if reader.position != bytes.len() {
+            return Err(reader.error("unexpected bytes after the program"));
+        }
+
+        let program = Program {
+            files,
+            constants,
+            globals,
+            functions,
+            entry,
+        };
+        if program
+            .functions
+            .get(entry as usize)
+            .is_none_or(|entry| entry.params != 0)
+        {
+            return Err(BytecodeError {
+                position: bytes.len(),
+                message: format!("entry {} isn't a function without parameters", entry),
+            });
+        }
+        for (function, start) in program.functions.iter().zip(starts) {
+            check(&program, function).map_err(|message| BytecodeError {
+                position: start,
+                message: format!("in `{}`: {}", function.name, message),
+            })?;
+        }
+        Ok(program)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: &str) -> BytecodeError {
+        BytecodeError {
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, BytecodeError> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeError> {
+        let mut value: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u32;
+            if shift == 28 && bits > 0xf {
+                return Err(self.error("number too large"));
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(self.error("number too large"))
+    }
+
+    fn i32(&mut self) -> Result<i32, BytecodeError> {
+        let mut bytes = [0; 4];
+        for byte in &mut bytes {
+            *byte = self.byte()?;
+        }
+        Ok(i32::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, BytecodeError> {
+        let len = self.u32()? as usize;
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.saturating_add(len))
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        let value = String::from_utf8(bytes.to_vec()).map_err(|_| self.error("invalid UTF-8"))?;
+        self.position += len;
+        Ok(value)
+    }
+}
+
+/// Whether `function` returns a value, which it does with `return_value`
+fn returns_value(function: &Function) -> bool {
+    function.code.contains(&Instr::ReturnValue)
+}
+
+/// Check that the operands of `function` are in range and that the stack
+/// never underflows and has one depth at each instruction
+fn check(program: &Program, function: &Function) -> Result<(), String> {
+    if function.params > function.locals {
+        return Err("more parameters than locals".into());
+    }
+    if function.locals > MAX_LOCALS {
+        return Err(format!(
+            "{} locals, at most {} are allowed",
+            function.locals, MAX_LOCALS
+        ));
+    }
+    if returns_value(function) && function.code.contains(&Instr::Return) {
+        return Err("returns both with and without a value".into());
+    }
+
+    let len = function.code.len();
+    if len == 0 {
+        return Err("no code".into());
+    }
+    let mut depths: Vec<Option<u32>> = vec![None; len];
+    depths[0] = Some(0);
+    let mut work = vec![0];
+
+    while let Some(pc) = work.pop() {
+        let instr = function.code[pc];
+        let depth = depths[pc].unwrap();
+        let in_range = |n: u32, count: usize| (n as usize) < count;
+        let (pops, pushes) = match instr {
+            Instr::Const(n) if !in_range(n, program.constants.len()) => {
+                return Err(format!("constant #{} doesn't exist at {}", n, pc));
+            }
+            Instr::LoadLocal(n) | Instr::StoreLocal(n)
+                if !in_range(n, function.locals as usize) =>
+            {
+                return Err(format!("local {} doesn't exist at {}", n, pc));
+            }
+            Instr::LoadGlobal(n) | Instr::StoreGlobal(n) if !in_range(n, program.globals.len()) => {
+                return Err(format!("global @{} doesn't exist at {}", n, pc));
+            }
+            Instr::Jump(n) | Instr::JumpIfFalse(n) if !in_range(n, len) => {
+                return Err(format!("jump to {} past the end at {}", n, pc));
+            }
+            Instr::Call(n) if !in_range(n, program.functions.len()) => {
+                return Err(format!("function {} doesn't exist at {}", n, pc));
+            }
+            Instr::Const(_) | Instr::True | Instr::False => (0, 1),
+            Instr::LoadLocal(_) | Instr::LoadGlobal(_) => (0, 1),
+            Instr::Pop | Instr::StoreLocal(_) | Instr::StoreGlobal(_) | Instr::Print => (1, 0),
+            Instr::Copy | Instr::Unary(_) | Instr::Len | Instr::Field(_) | Instr::Tag => (1, 1),
+            Instr::Binary(_) | Instr::Index => (2, 1),
+            Instr::Jump(_) => (0, 0),
+            Instr::JumpIfFalse(_) => (1, 0),
+            Instr::Call(n) => {
+                let callee = &program.functions[n as usize];
+                (callee.params, returns_value(callee) as u32)
+            }
+            Instr::Return => (0, 0),
+            Instr::ReturnValue => (1, 0),
+            Instr::Record(n) => (n, 1),
+            Instr::SetField(_) => (2, 0),
+            Instr::SetIndex => (3, 0),
+            Instr::Slice { start, end } => (1 + start as u32 + end as u32, 1),
+        };
+        if pops > depth {
+            return Err(format!(
+                "`{}` pops more than is on the stack at {}",
+                instr, pc
+            ));
+        }
+        let depth = depth - pops + pushes;
+
+        let successors = match instr {
+            Instr::Jump(target) => vec![target as usize],
+            Instr::JumpIfFalse(target) => vec![pc + 1, target as usize],
+            Instr::Return | Instr::ReturnValue if depth != 0 => {
+                return Err(format!("values left on the stack when returning at {}", pc));
+            }
+            Instr::Return | Instr::ReturnValue => vec![],
+            _ => vec![pc + 1],
+        };
+        for next in successors {
+            if next >= len {
+                return Err(format!("control runs past the end after {}", pc));
+            }
+            match depths[next] {
+                Some(other) if other != depth => {
+                    return Err(format!("stack depth differs between paths at {}", next));
+                }
+                Some(_) => {}
+                None => {
+                    depths[next] = Some(depth);
+                    work.push(next);
+                }
+            }
+        }
+    }
+    Ok(())
+}
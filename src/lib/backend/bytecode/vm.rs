@@ -0,0 +1,402 @@
+//! The virtual machine running bytecode.
+//!
+//! Values live on a single stack shared by every call: a frame's locals
+//! start at its base and its operands come after them. Records are shared
+//! through reference counting so a slice can point into one, `copy` makes a
+//! new one where Lune copies a value.
+//!
+//! Indexes and slice bounds are always checked, as are divisions, which
+//! stop the program with an error at the source location of the
+//! instruction. Ints wrap around on overflow like the other backends.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use super::*;
+use crate::errors::RuntimeError;
+
+/// Calls deeper than this stop the program instead of using up memory
+const MAX_FRAMES: usize = 1 << 16;
+
+/// Fields of a record, shared with the slices pointing into it
+type Fields = Rc<RefCell<Vec<Value>>>;
+
+/// A value at run time
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// What locals hold before they are written
+    Void,
+    Int(i32),
+    Bool(bool),
+    Str(Rc<str>),
+    /// A struct, an array or an enum with a payload
+    Record(Fields),
+    /// Elements of an array, from a start and with a length
+    Slice(Fields, u32, u32),
+}
+
+impl Value {
+    /// The value with every record in it copied, slices still point where they did
+    fn copy(&self) -> Value {
+        match self {
+            Value::Record(fields) => {
+                let fields = fields.borrow().iter().map(Value::copy).collect();
+                Value::Record(Rc::new(RefCell::new(fields)))
+            }
+            value => value.clone(),
+        }
+    }
+}
+
+/// Write `value` into `slot`. A record of the same size is overwritten in
+/// place, so slices pointing into it see the new elements.
+fn store(slot: &mut Value, value: Value) {
+    if let (Value::Record(dst), Value::Record(src)) = (&*slot, &value) {
+        if !Rc::ptr_eq(dst, src) {
+            if let (Ok(mut dst), Ok(src)) = (dst.try_borrow_mut(), src.try_borrow()) {
+                if dst.len() == src.len() {
+                    for (dst, src) in dst.iter_mut().zip(src.iter()) {
+                        store(dst, src.clone());
+                    }
+                    return;
+                }
+            }
+        }
+    }
+    *slot = value;
+}
+
+/// A call being run
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    function: usize,
+    /// Next instruction to run
+    pc: usize,
+    /// Where the locals start on the stack
+    base: usize,
+}
+
+/// Runs a program, printing to `out`
+pub struct Vm<'p, W: Write> {
+    program: &'p Program,
+    /// The constant pool as values, so strings aren't allocated each time they are used
+    constants: Vec<Value>,
+    globals: Vec<Value>,
+    stack: Vec<Value>,
+    /// Callers of the running function
+    frames: Vec<Frame>,
+    out: W,
+}
+
+impl<'p, W: Write> Vm<'p, W> {
+    pub fn new(program: &'p Program, out: W) -> Vm<'p, W> {
+        let constants = program
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Constant::Int(n) => Value::Int(*n),
+                Constant::Str(s) => Value::Str(s.as_str().into()),
+            })
+            .collect();
+        Vm {
+            program,
+            constants,
+            globals: vec![Value::Void; program.globals.len()],
+            stack: vec![],
+            frames: vec![],
+            out,
+        }
+    }
+
+    /// Run the entry function to the end
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let result = self.execute();
+        let flushed = self.out.flush().map_err(|err| RuntimeError {
+            location: None,
+            message: format!("couldn't write output: {}", err),
+        });
+        result.and(flushed)
+    }
+
+    /// An error at the instruction `frame` just ran
+    fn error(&self, frame: &Frame, message: String) -> RuntimeError {
+        let function = &self.program.functions[frame.function];
+        let location = function.location(frame.pc - 1).map(|location| {
+            let path = &self.program.files[location.file as usize];
+            format!("{}:{}:{}", path, location.line, location.column)
+        });
+        RuntimeError { location, message }
+    }
+
+    fn invalid(&self, frame: &Frame) -> RuntimeError {
+        let function = &self.program.functions[frame.function];
+        let message = format!(
+            "invalid bytecode at {} in `{}`",
+            frame.pc - 1,
+            function.name
+        );
+        RuntimeError {
+            location: None,
+            message,
+        }
+    }
+
+    fn pop(&mut self, frame: &Frame) -> Result<Value, RuntimeError> {
+        self.stack.pop().ok_or_else(|| self.invalid(frame))
+    }
+
+    fn pop_int(&mut self, frame: &Frame) -> Result<i32, RuntimeError> {
+        match self.pop(frame)? {
+            Value::Int(n) => Ok(n),
+            _ => Err(self.invalid(frame)),
+        }
+    }
+
+    /// Pop an array or slice, returning its elements, start and length
+    fn pop_elements(&mut self, frame: &Frame) -> Result<(Fields, u32, u32), RuntimeError> {
+        match self.pop(frame)? {
+            Value::Record(elements) => {
+                let len = elements.borrow().len() as u32;
+                Ok((elements, 0, len))
+            }
+            Value::Slice(elements, start, len) => Ok((elements, start, len)),
+            _ => Err(self.invalid(frame)),
+        }
+    }
+
+    /// Pop an index and an array or slice, returning the elements and the
+    /// position of the indexed one in them
+    fn pop_element(&mut self, frame: &Frame) -> Result<(Fields, usize), RuntimeError> {
+        let index = self.pop_int(frame)?;
+        let (elements, start, len) = self.pop_elements(frame)?;
+        if index < 0 || index as u32 >= len {
+            let message = format!(
+                "index out of bounds: the len is {} but the index is {}",
+                len, index
+            );
+            return Err(self.error(frame, message));
+        }
+        let position = (start + index as u32) as usize;
+        if position >= elements.borrow().len() {
+            return Err(self.invalid(frame));
+        }
+        Ok((elements, position))
+    }
+
+    fn binary(
+        &self,
+        frame: &Frame,
+        op: BinOpKind,
+        lhs: Value,
+        rhs: Value,
+    ) -> Result<Value, RuntimeError> {
+        let value = match (lhs, rhs) {
+            (Value::Int(l), Value::Int(r)) => match op {
+                BinOpKind::Add => Value::Int(l.wrapping_add(r)),
+                BinOpKind::Sub => Value::Int(l.wrapping_sub(r)),
+                BinOpKind::Mul => Value::Int(l.wrapping_mul(r)),
+                BinOpKind::Div if r == 0 => {
+                    return Err(self.error(frame, "attempt to divide by zero".into()))
+                }
+                BinOpKind::Div => match l.checked_div(r) {
+                    Some(n) => Value::Int(n),
+                    None => return Err(self.error(frame, "attempt to divide with overflow".into())),
+                },
+                BinOpKind::Eq => Value::Bool(l == r),
+                BinOpKind::Ne => Value::Bool(l != r),
+                BinOpKind::Lt => Value::Bool(l < r),
+                BinOpKind::Le => Value::Bool(l <= r),
+                BinOpKind::Gt => Value::Bool(l > r),
+                BinOpKind::Ge => Value::Bool(l >= r),
+            },
+            (Value::Bool(l), Value::Bool(r)) if op == BinOpKind::Eq => Value::Bool(l == r),
+            (Value::Bool(l), Value::Bool(r)) if op == BinOpKind::Ne => Value::Bool(l != r),
+            (Value::Str(l), Value::Str(r)) if op == BinOpKind::Eq => Value::Bool(l == r),
+            (Value::Str(l), Value::Str(r)) if op == BinOpKind::Ne => Value::Bool(l != r),
+            _ => return Err(self.invalid(frame)),
+        };
+        Ok(value)
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        let program = self.program;
+        let entry = program.entry as usize;
+        let mut frame = Frame {
+            function: entry,
+            pc: 0,
+            base: 0,
+        };
+        self.stack
+            .resize(program.functions[entry].locals as usize, Value::Void);
+        let mut code = &program.functions[entry].code[..];
+
+        loop {
+            let Some(&instr) = code.get(frame.pc) else {
+                frame.pc += 1;
+                return Err(self.invalid(&frame));
+            };
+            frame.pc += 1;
+            match instr {
+                Instr::Const(n) => {
+                    let value = self
+                        .constants
+                        .get(n as usize)
+                        .ok_or_else(|| self.invalid(&frame))?;
+                    self.stack.push(value.clone());
+                }
+                Instr::True => self.stack.push(Value::Bool(true)),
+                Instr::False => self.stack.push(Value::Bool(false)),
+                Instr::Pop => {
+                    self.pop(&frame)?;
+                }
+                Instr::Copy => {
+                    let value = self.pop(&frame)?;
+                    self.stack.push(value.copy());
+                }
+                Instr::LoadLocal(n) => {
+                    let value = self.stack[frame.base + n as usize].clone();
+                    self.stack.push(value);
+                }
+                Instr::StoreLocal(n) => {
+                    let value = self.pop(&frame)?;
+                    store(&mut self.stack[frame.base + n as usize], value);
+                }
+                Instr::LoadGlobal(n) => self.stack.push(self.globals[n as usize].clone()),
+                Instr::StoreGlobal(n) => {
+                    let value = self.pop(&frame)?;
+                    store(&mut self.globals[n as usize], value);
+                }
+                Instr::Unary(op) => {
+                    let value = match (op, self.pop(&frame)?) {
+                        (UnaryOpKind::Neg, Value::Int(n)) => Value::Int(n.wrapping_neg()),
+                        (UnaryOpKind::Not, Value::Bool(b)) => Value::Bool(!b),
+                        _ => return Err(self.invalid(&frame)),
+                    };
+                    self.stack.push(value);
+                }
+                Instr::Binary(op) => {
+                    let rhs = self.pop(&frame)?;
+                    let lhs = self.pop(&frame)?;
+                    let value = self.binary(&frame, op, lhs, rhs)?;
+                    self.stack.push(value);
+                }
+                Instr::Jump(target) => frame.pc = target as usize,
+                Instr::JumpIfFalse(target) => match self.pop(&frame)? {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => frame.pc = target as usize,
+                    _ => return Err(self.invalid(&frame)),
+                },
+                Instr::Call(n) => {
+                    if self.frames.len() >= MAX_FRAMES {
+                        return Err(self.error(&frame, "stack overflow".into()));
+                    }
+                    let callee = &program.functions[n as usize];
+                    let base = self.stack.len() - callee.params as usize;
+                    self.stack
+                        .resize(base + callee.locals as usize, Value::Void);
+                    self.frames.push(frame);
+                    frame = Frame {
+                        function: n as usize,
+                        pc: 0,
+                        base,
+                    };
+                    code = &callee.code;
+                }
+                Instr::Return | Instr::ReturnValue => {
+                    let result = match instr {
+                        Instr::ReturnValue => Some(self.pop(&frame)?),
+                        _ => None,
+                    };
+                    self.stack.truncate(frame.base);
+                    let Some(caller) = self.frames.pop() else {
+                        return Ok(());
+                    };
+                    frame = caller;
+                    code = &program.functions[frame.function].code;
+                    self.stack.extend(result);
+                }
+                Instr::Print => {
+                    let written = match self.pop(&frame)? {
+                        Value::Int(n) => writeln!(self.out, "{}", n),
+                        Value::Bool(b) => writeln!(self.out, "{}", b),
+                        Value::Str(s) => writeln!(self.out, "{}", s),
+                        _ => return Err(self.invalid(&frame)),
+                    };
+                    written.map_err(|err| RuntimeError {
+                        location: None,
+                        message: format!("couldn't write output: {}", err),
+                    })?;
+                }
+                Instr::Len => {
+                    let (_, _, len) = self.pop_elements(&frame)?;
+                    self.stack.push(Value::Int(len as i32));
+                }
+                Instr::Record(n) => {
+                    let start = self
+                        .stack
+                        .len()
+                        .checked_sub(n as usize)
+                        .ok_or_else(|| self.invalid(&frame))?;
+                    let fields = self.stack.split_off(start);
+                    self.stack
+                        .push(Value::Record(Rc::new(RefCell::new(fields))));
+                }
+                Instr::Field(_) | Instr::Tag => {
+                    let n = match instr {
+                        Instr::Field(n) => n as usize,
+                        _ => 0,
+                    };
+                    let Value::Record(fields) = self.pop(&frame)? else {
+                        return Err(self.invalid(&frame));
+                    };
+                    let field = fields.borrow().get(n).cloned();
+                    self.stack.push(field.ok_or_else(|| self.invalid(&frame))?);
+                }
+                Instr::SetField(n) => {
+                    let value = self.pop(&frame)?;
+                    let Value::Record(fields) = self.pop(&frame)? else {
+                        return Err(self.invalid(&frame));
+                    };
+                    let mut fields = fields.borrow_mut();
+                    let field = fields
+                        .get_mut(n as usize)
+                        .ok_or_else(|| self.invalid(&frame))?;
+                    store(field, value);
+                }
+                Instr::Index => {
+                    let (elements, position) = self.pop_element(&frame)?;
+                    let element = elements.borrow()[position].clone();
+                    self.stack.push(element);
+                }
+                Instr::SetIndex => {
+                    let value = self.pop(&frame)?;
+                    let (elements, position) = self.pop_element(&frame)?;
+                    store(&mut elements.borrow_mut()[position], value);
+                }
+                Instr::Slice { start, end } => {
+                    let end = if end {
+                        Some(self.pop_int(&frame)?)
+                    } else {
+                        None
+                    };
+                    let start = if start {
+                        Some(self.pop_int(&frame)?)
+                    } else {
+                        None
+                    };
+                    let (elements, offset, len) = self.pop_elements(&frame)?;
+                    let start = start.unwrap_or(0);
+                    let end = end.unwrap_or(len as i32);
+                    if start < 0 || start > end || end > len as i32 {
+                        let message =
+                            format!("slice out of bounds: {}..{} of len {}", start, end, len);
+                        return Err(self.error(&frame, message));
+                    }
+                    let slice = Value::Slice(elements, offset + start as u32, (end - start) as u32);
+                    self.stack.push(slice);
+                }
+            }
+        }
+    }
+}
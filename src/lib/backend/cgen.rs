@@ -0,0 +1,1138 @@
+//! C code generation.
+//!
+//! Emits a single C99 translation unit from a checked AST. Top level
+//! statements run in order from `main`, top level variables become globals
+//! so procs can use them.
+//!
+//! A program can also be split into a unit per module, see `Unit`, so each
+//! module can be compiled on its own. Every unit declares everything in the
+//! program and defines its module's procs and globals, and a function that
+//! runs its top level statements, which `main` calls in order.
+//!
+//! Structs become `typedef struct` definitions with their fields in
+//! declaration order, so they have the same layout as the equivalent C
+//! struct and can be passed to C functions as is.
+//!
+//! Enums without payloads become C enums with the same values. Enums with a
+//! payload on any variant become a tagged union: a struct with a `tag` enum
+//! and an `as` union holding one struct per variant with fields.
+//!
+//! `case` evaluates its scrutinee once into a temporary declared at the top
+//! of the function. Statements on enums become a `switch`, everything else
+//! becomes an `if`/`else` chain, and case expressions a chain of `?:`.
+//!
+//! Constants become `static const` globals initialized with the value they
+//! were evaluated to, so string concatenation never happens at run time. Ones
+//! that are never used are left out, as C warns about them.
+//!
+//! Arrays become a struct wrapping a C array, so they are copied by value like
+//! Lune structs, and slices a struct of a pointer and a length. Indexing and
+//! slicing check their bounds at run time and abort with the Lune source
+//! location, unless checks are turned off for release builds.
+//!
+//! Ints wrap around on overflow like in the other backends, so arithmetic
+//! goes through helpers doing it on unsigned ints, which C defines. Division
+//! is checked even when other checks are off, as C leaves division by zero
+//! and of `INT32_MIN` by -1 undefined.
+//!
+//! C doesn't order the evaluation of operands and arguments, so when one of
+//! them calls a proc they are stored in temporaries left to right first, in
+//! the order the VM evaluates them.
+//!
+//! Top level names are always encoded, see `encode`, so they can't clash with
+//! anything the included headers declare. Locals and fields are kept as they
+//! are when they are already valid and unreserved in C, see `mangle`.
+
+use std::collections::{HashMap, HashSet};
+
+use super::ast::*;
+use super::visit::{walk_expr, walk_stmt, Visitor};
+use crate::frontend::consteval::Value;
+use crate::frontend::lexer::token::Span;
+use crate::frontend::modules::{file_at, SourceFile};
+use crate::frontend::resolver::{DefKind, Resolution};
+use crate::types::Type;
+
+/// Names C or the included headers reserve, which locals and fields can't
+/// use as is. Functions only matter when generated code calls them.
+const C_RESERVED: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "bool", "true", "false", "strcmp",
+    "size_t", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t",
+    "uint64_t", "stdin", "stdout", "stderr", "linux", "unix", "i386",
+];
+
+const PRELUDE: &str = r#"#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static inline void lune_print_int(int32_t value) { printf("%ld\n", (long)value); }
+static inline void lune_print_str(const char *value) { printf("%s\n", value); }
+static inline void lune_print_bool(bool value) { printf("%s\n", value ? "true" : "false"); }
+
+static inline int32_t lune_check_index(int32_t index, int32_t len, const char *location) {
+    if (index < 0 || index >= len) {
+        fflush(stdout);
+        fprintf(stderr, "%s: index out of bounds: the len is %ld but the index is %ld\n",
+                location, (long)len, (long)index);
+        abort();
+    }
+    return index;
+}
+
+static inline void lune_check_slice(int32_t start, int32_t end, int32_t len, const char *location) {
+    if (start < 0 || start > end || end > len) {
+        fflush(stdout);
+        fprintf(stderr, "%s: slice out of bounds: %ld..%ld of len %ld\n",
+                location, (long)start, (long)end, (long)len);
+        abort();
+    }
+}
+
+/* Unsigned arithmetic wraps, converting back is modulo 2^32 in every compiler we target */
+static inline int32_t lune_add(int32_t a, int32_t b) { return (int32_t)((uint32_t)a + (uint32_t)b); }
+static inline int32_t lune_sub(int32_t a, int32_t b) { return (int32_t)((uint32_t)a - (uint32_t)b); }
+static inline int32_t lune_mul(int32_t a, int32_t b) { return (int32_t)((uint32_t)a * (uint32_t)b); }
+static inline int32_t lune_neg(int32_t a) { return (int32_t)(0u - (uint32_t)a); }
+
+static inline int32_t lune_div(int32_t a, int32_t b, const char *location) {
+    if (b == 0 || (a == INT32_MIN && b == -1)) {
+        fflush(stdout);
+        fprintf(stderr, "%s: attempt to divide %s\n", location, b == 0 ? "by zero" : "with overflow");
+        abort();
+    }
+    return a / b;
+}
+"#;
+
+/// Encode any identifier into `[A-Za-z0-9_]`. `_` becomes `__`, the `.` of
+/// names qualified with their module becomes `_m_` and other characters
+/// become `_u<hex>_`, so a lone `_` followed by anything but `u` or `m`
+/// never appears and suffixes like `_1` can't clash.
+pub fn encode(name: &str) -> String {
+    let mut out = String::from("lune_");
+    for c in name.chars() {
+        match c {
+            '_' => out += "__",
+            '.' => out += "_m_",
+            c if c.is_ascii_alphanumeric() => out.push(c),
+            c => out += &format!("_u{:x}_", c as u32),
+        }
+    }
+    out
+}
+
+/// C identifier for a local or a field. Plain ASCII names are kept, names
+/// that are non-ASCII, reserved in C, all uppercase like the macros of the
+/// headers or start with `_` or `lune_` are encoded.
+pub fn mangle(name: &str) -> String {
+    let plain = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.chars().any(|c| c.is_ascii_lowercase())
+        && !name.starts_with('_')
+        && !name.starts_with("lune_")
+        && !C_RESERVED.contains(&name);
+
+    if plain {
+        name.into()
+    } else {
+        encode(name)
+    }
+}
+
+/// C string literal, escaping everything outside printable ASCII as octal
+fn c_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'"' => out += "\\\"",
+            b'\\' => out += "\\\\",
+            b'\n' => out += "\\n",
+            b'\t' => out += "\\t",
+            b' '..=b'~' => out.push(byte as char),
+            _ => out += &format!("\\{:03o}", byte),
+        }
+    }
+    out + "\""
+}
+
+/// Part of the C name of a type, different for every Lune type
+fn type_key(type_: &Type) -> String {
+    match type_ {
+        Type::Int => "int".into(),
+        Type::String => "str".into(),
+        Type::Bool => "bool".into(),
+        Type::Void => "void".into(),
+        Type::Named(name) => encode(name),
+        Type::Array(element, len) => format!("array_t{}_{}", len, type_key(element)),
+        Type::Slice(element) => format!("slice_t_{}", type_key(element)),
+        Type::ConstArray(_, _) => unreachable!("array lengths are evaluated before code gen"),
+    }
+}
+
+/// Name of the function running the top level statements of `module`
+pub fn init_name(module: &str) -> String {
+    // `_t` never appears in a mangled name, so this can't clash with one
+    match module {
+        "" => "lune_init_t".into(),
+        module => format!("lune_init_t_{}", &encode(module)["lune_".len()..]),
+    }
+}
+
+/// C integer literal for a value in i32 range
+fn c_int(value: i128) -> String {
+    // 2147483648 doesn't fit in an int32_t, so -2147483648 isn't an int32_t in C
+    if value == i32::MIN as i128 {
+        "INT32_MIN".into()
+    } else {
+        value.to_string()
+    }
+}
+
+/// `value` after the assignments in `setup`, which the comma operator orders
+fn sequence(setup: Vec<String>, value: String) -> String {
+    if setup.is_empty() {
+        value
+    } else {
+        format!("({}, {})", setup.join(", "), value)
+    }
+}
+
+/// Collects the definitions generated code refers to
+struct Uses<'a> {
+    resolution: &'a Resolution,
+    defs: HashSet<usize>,
+}
+
+impl Visitor<()> for Uses<'_> {
+    fn visit_stmt(&mut self, ast: &AST, stmt: StmtId) {
+        // Constants are emitted with their value, their initializers aren't generated
+        if !matches!(ast[stmt].kind, StmtKind::ConstDeclaration(_, _, _)) {
+            walk_stmt(self, ast, stmt);
+        }
+    }
+
+    fn visit_name(&mut self, name: &Name) {
+        if let Some(&def) = self.resolution.names.get(&name.id) {
+            self.defs.insert(def);
+        }
+    }
+}
+
+/// Finds calls, which may have side effects
+#[derive(Default)]
+struct Calls {
+    found: bool,
+}
+
+impl Visitor<()> for Calls {
+    fn visit_expr(&mut self, ast: &AST, expr: ExprId) {
+        if matches!(ast[expr].kind, ExprKind::Call(_, _)) {
+            self.found = true;
+        } else {
+            walk_expr(self, ast, expr);
+        }
+    }
+}
+
+/// One module of a program, generated as its own translation unit
+pub struct Unit<'u> {
+    /// Name of the module, empty for the file being compiled
+    pub module: &'u str,
+    /// Top level statements of the module
+    pub root: &'u [StmtId],
+    /// For the unit that defines `main`, the modules in the order their top
+    /// level statements run
+    pub main: Option<&'u [String]>,
+}
+
+/// Generates C from an AST that resolved and type checked without errors
+pub struct CGen<'a> {
+    resolution: &'a Resolution,
+    types: &'a HashMap<NodeId, Type>,
+    /// Values of the constants, by the id of their declaration's name
+    consts: &'a HashMap<NodeId, Value>,
+    /// C name of every definition, by definition index
+    names: HashMap<usize, String>,
+    /// C names of globals and procs
+    globals: HashSet<String>,
+    /// C names of locals in the function being generated
+    locals: HashSet<String>,
+    /// Declarations of the temporaries in the function being generated
+    temps: Vec<String>,
+    /// Whether to emit runtime checks such as bounds checks
+    checks: bool,
+    /// Files of the program, for the locations failed checks report
+    files: &'a [SourceFile],
+    out: String,
+    indent: usize,
+}
+
+impl<'a> CGen<'a> {
+    pub fn new(
+        resolution: &'a Resolution,
+        types: &'a HashMap<NodeId, Type>,
+        consts: &'a HashMap<NodeId, Value>,
+    ) -> CGen<'a> {
+        CGen {
+            resolution,
+            types,
+            consts,
+            names: HashMap::new(),
+            globals: HashSet::new(),
+            locals: HashSet::new(),
+            temps: vec![],
+            checks: true,
+            files: &[],
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    /// Report failed runtime checks in the files the program was loaded from
+    pub fn with_files(mut self, files: &'a [SourceFile]) -> CGen<'a> {
+        self.files = files;
+        self
+    }
+
+    /// Turn runtime checks on or off, they are off for release builds
+    pub fn checks(mut self, checks: bool) -> CGen<'a> {
+        self.checks = checks;
+        self
+    }
+
+    /// Generate the translation unit for `ast`
+    pub fn generate(&mut self, ast: &AST) -> String {
+        self.generate_unit(ast, None)
+    }
+
+    /// Generate the translation unit of one module of `ast`
+    pub fn generate_module(&mut self, ast: &AST, unit: &Unit) -> String {
+        self.generate_unit(ast, Some(unit))
+    }
+
+    fn generate_unit(&mut self, ast: &AST, unit: Option<&Unit>) -> String {
+        self.out = String::from(PRELUDE);
+        let root = unit.map_or(&ast.root[..], |unit| unit.root);
+
+        // Procs and globals are declared up front so they can be used in any order
+        let mut procs = vec![];
+        let mut globals = vec![];
+        let mut consts = vec![];
+        let mut types = vec![];
+        let mut enums = HashMap::new();
+        for &id in &ast.root {
+            match &ast[id].kind {
+                StmtKind::ProcDeclaration(proc) => procs.push((id, proc)),
+                StmtKind::VarDeclaration(name, type_, _) => globals.push((id, name, type_)),
+                StmtKind::ConstDeclaration(name, type_, _) => consts.push((name, type_)),
+                StmtKind::StructDeclaration(struct_) => types.push(&struct_.name),
+                StmtKind::EnumDeclaration(enum_) => {
+                    types.push(&enum_.name);
+                    enums.insert(enum_.name.value.as_str(), enum_);
+                }
+                _ => {}
+            }
+        }
+
+        for name in &types {
+            self.declare(name, true);
+        }
+        // Variants are C enumerators, which share the namespace of globals
+        for enum_ in enums.values() {
+            for variant in &enum_.variants {
+                self.declare(&variant.name, true);
+            }
+        }
+        let mut defined = HashSet::new();
+        for name in &types {
+            self.define_type(&name.value, &enums, &mut defined);
+        }
+
+        // Every array and slice type the program uses gets a struct, the order is
+        // by name so the output doesn't depend on hash map order
+        let resolution = self.resolution;
+        let mut used: Vec<&Type> = self.types.values().collect();
+        for def in &resolution.defs {
+            used.push(&def.type_);
+            used.extend(&def.params);
+            used.extend(def.fields.iter().map(|(_, type_)| type_));
+        }
+        used.sort_by_cached_key(|type_| type_key(type_));
+        used.dedup();
+        for type_ in used {
+            self.define(type_, &enums, &mut defined);
+        }
+
+        self.out += "\n";
+        for (_, proc) in &procs {
+            self.locals.clear();
+            let name = self.declare(&proc.name, true);
+            let signature = self.signature(&name, proc);
+            self.out += &format!("{};\n", signature);
+        }
+        self.locals.clear();
+        for (id, name, type_) in &globals {
+            let name = self.declare(name, true);
+            let mut decl = self.c_decl(type_, &name);
+            // A string read before its initializer runs is empty, as in the VM,
+            // rather than a null pointer
+            if **type_ == Type::String && (unit.is_none() || root.contains(id)) {
+                decl += " = \"\"";
+            }
+            // Globals of other units are defined there
+            match unit {
+                None => self.out += &format!("static {};\n", decl),
+                Some(_) if root.contains(id) => self.out += &format!("{};\n", decl),
+                Some(_) => self.out += &format!("extern {};\n", decl),
+            }
+        }
+        let mut uses = Uses {
+            resolution: self.resolution,
+            defs: HashSet::new(),
+        };
+        for &stmt in root {
+            uses.visit_stmt(ast, stmt);
+        }
+        for (name, type_) in &consts {
+            let used = self.resolution.names.get(&name.id).is_some_and(|def| uses.defs.contains(def));
+            let Some(value) = self.consts.get(&name.id).filter(|_| used) else {
+                continue;
+            };
+            let value = match value {
+                Value::Int(n) => c_int(i128::from(*n)),
+                Value::Str(s) => c_string(s),
+                Value::Bool(b) => b.to_string(),
+            };
+            let name = self.declare(name, true);
+            let decl = self.c_decl(type_, &format!("const {}", name));
+            self.out += &format!("static {} = {};\n", decl, value);
+        }
+
+        for (_, proc) in procs.iter().filter(|(id, _)| root.contains(id)) {
+            self.locals.clear();
+            let name = self.name(&proc.name);
+            let signature = self.signature(&name, proc);
+            self.out += &format!("\n{} {{\n", signature);
+            let start = self.out.len();
+            self.indent += 1;
+            self.block_body(ast, &proc.body);
+            self.declare_temps(start);
+            self.indent -= 1;
+            self.out += "}\n";
+        }
+
+        self.locals.clear();
+        match unit {
+            None => self.out += "\nint main(void) {\n",
+            Some(unit) => self.out += &format!("\nvoid {}(void) {{\n", init_name(unit.module)),
+        }
+        let start = self.out.len();
+        self.indent += 1;
+        for &id in root {
+            match &ast[id].kind {
+                StmtKind::ProcDeclaration(_)
+                | StmtKind::StructDeclaration(_)
+                | StmtKind::EnumDeclaration(_)
+                | StmtKind::ConstDeclaration(_, _, _) => {}
+                // Globals are already declared, only assign them here
+                StmtKind::VarDeclaration(name, _, value) => {
+                    let line = format!("{} = {};", self.name(name), self.expr(ast, *value));
+                    self.line(&line);
+                }
+                _ => self.stmt(ast, id),
+            }
+        }
+        if unit.is_none() {
+            self.line("return 0;");
+        }
+        self.declare_temps(start);
+        self.indent -= 1;
+        self.out += "}\n";
+
+        if let Some((unit, modules)) = unit.and_then(|unit| Some((unit, unit.main?))) {
+            self.out += "\n";
+            for module in modules.iter().filter(|&module| module != unit.module) {
+                self.out += &format!("void {}(void);\n", init_name(module));
+            }
+            self.out += "\nint main(void) {\n";
+            for module in modules {
+                self.out += &format!("    {}();\n", init_name(module));
+            }
+            self.out += "    return 0;\n}\n";
+        }
+
+        std::mem::take(&mut self.out)
+    }
+
+    /// Emit the typedef of `type_` if it needs one, after the types it contains
+    fn define(&mut self, type_: &Type, enums: &HashMap<&str, &Enum>, defined: &mut HashSet<String>) {
+        match type_ {
+            Type::Named(name) => self.define_type(name, enums, defined),
+            Type::Array(element, len) => {
+                if !defined.insert(type_key(type_)) {
+                    return;
+                }
+                // Elements are stored inline, so their type has to be complete first
+                self.define(element, enums, defined);
+                let c_name = self.c_type(type_);
+                let data = self.c_decl(element, &format!("data[{}]", len));
+                self.out += &format!("\ntypedef struct {} {{ {}; }} {};\n", c_name, data, c_name);
+            }
+            Type::Slice(element) => {
+                if !defined.insert(type_key(type_)) {
+                    return;
+                }
+                // A pointer to a struct tag can come before the struct's definition,
+                // which lets a struct hold slices of itself
+                let element = if self.is_struct(element) {
+                    format!("struct {}", self.c_type(element))
+                } else {
+                    self.define(element, enums, defined);
+                    self.c_type(element)
+                };
+                let ptr = if element.ends_with('*') {
+                    format!("{}*ptr", element)
+                } else {
+                    format!("{} *ptr", element)
+                };
+                let c_name = self.c_type(type_);
+                self.out += &format!("\ntypedef struct {} {{ {}; int32_t len; }} {};\n", c_name, ptr, c_name);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `type_` becomes a C struct
+    fn is_struct(&self, type_: &Type) -> bool {
+        match type_ {
+            Type::Array(_, _) | Type::Slice(_) => true,
+            Type::Named(_) => {
+                self.resolution.struct_def(type_).is_some() || self.resolution.is_tagged_union(type_)
+            }
+            _ => false,
+        }
+    }
+
+    /// Emit the typedef of the struct or enum `name`, after the types it contains
+    fn define_type(&mut self, name: &str, enums: &HashMap<&str, &Enum>, defined: &mut HashSet<String>) {
+        let type_ = Type::Named(name.into());
+        if !defined.insert(type_key(&type_)) {
+            return;
+        }
+        let resolution = self.resolution;
+        let variants = resolution.variants(&type_);
+        let fields: Vec<&(String, Type)> = match resolution.struct_def(&type_) {
+            Some(def) => def.fields.iter().collect(),
+            None => variants.iter().flat_map(|variant| &variant.fields).collect(),
+        };
+
+        // Fields hold other types by value, so those have to be complete first
+        for (_, type_) in &fields {
+            self.define(type_, enums, defined);
+        }
+
+        let c_name = self.c_type(&type_);
+        if resolution.struct_def(&type_).is_some() {
+            self.out += &format!("\ntypedef struct {} {{\n", c_name);
+            for (field, type_) in &fields {
+                self.out += &format!("    {};\n", self.c_decl(type_, &mangle(field)));
+            }
+            self.out += &format!("}} {};\n", c_name);
+            return;
+        }
+        let Some(enum_) = enums.get(name) else {
+            return;
+        };
+
+        // Only explicit values are written out, C counts up from the previous one like Lune
+        let enumerators: Vec<String> = enum_
+            .variants
+            .iter()
+            .map(|variant| match variant.value {
+                Some(value) => format!("{} = {}", self.name(&variant.name), c_int(value)),
+                None => self.name(&variant.name),
+            })
+            .collect();
+
+        if !resolution.is_tagged_union(&type_) {
+            self.out += &format!("\ntypedef enum {} {{ {} }} {};\n", c_name, enumerators.join(", "), c_name);
+            return;
+        }
+
+        self.out += &format!("\ntypedef struct {} {{\n", c_name);
+        self.out += &format!("    enum {{ {} }} tag;\n", enumerators.join(", "));
+        self.out += "    union {\n";
+        // C99 has no empty structs, variants without fields only have a tag
+        for variant in enum_.variants.iter().filter(|variant| !variant.fields.is_empty()) {
+            let fields: Vec<String> = variant
+                .fields
+                .iter()
+                .map(|field| format!("{};", self.c_decl(&field.type_, &mangle(&field.name.value))))
+                .collect();
+            self.out += &format!("        struct {{ {} }} {};\n", fields.join(" "), self.name(&variant.name));
+        }
+        self.out += "    } as;\n";
+        self.out += &format!("}} {};\n", c_name);
+    }
+
+    fn c_type(&self, type_: &Type) -> String {
+        match type_ {
+            Type::Int => "int32_t".into(),
+            Type::String => "const char *".into(),
+            Type::Bool => "bool".into(),
+            Type::Void => "void".into(),
+            Type::Named(name) => self
+                .resolution
+                .types
+                .get(name)
+                .and_then(|def| self.names.get(def))
+                .cloned()
+                .unwrap_or_else(|| encode(name)),
+            // `_t` never appears in a mangled name, so these can't clash with one
+            Type::Array(_, _) | Type::Slice(_) | Type::ConstArray(_, _) => {
+                format!("lune_{}", type_key(type_))
+            }
+        }
+    }
+
+    /// Declaration of `name` with `type_`, eg `int32_t x` or `const char *s`
+    fn c_decl(&self, type_: &Type, name: &str) -> String {
+        let type_ = self.c_type(type_);
+        if type_.ends_with('*') {
+            format!("{}{}", type_, name)
+        } else {
+            format!("{} {}", type_, name)
+        }
+    }
+
+    fn signature(&mut self, name: &str, proc: &Proc) -> String {
+        let params: Vec<String> = proc
+            .params
+            .iter()
+            .map(|param| {
+                let name = self.declare(&param.name, false);
+                self.c_decl(&param.type_, &name)
+            })
+            .collect();
+
+        let params = if params.is_empty() {
+            "void".into()
+        } else {
+            params.join(", ")
+        };
+        format!("{}({})", self.c_decl(&proc.return_type, name), params)
+    }
+
+    /// Pick a C name for the definition `name` declares. A name already
+    /// taken gets a numbered suffix, so shadowing in Lune never turns into
+    /// shadowing in C, which has different scoping rules.
+    fn declare(&mut self, name: &Name, global: bool) -> String {
+        let Some(&def) = self.resolution.names.get(&name.id) else {
+            return mangle(&name.value);
+        };
+        if let Some(c_name) = self.names.get(&def).cloned() {
+            // Params are declared again when their proc is generated
+            if !global {
+                self.locals.insert(c_name.clone());
+            }
+            return c_name;
+        }
+
+        let mut c_name = if global {
+            encode(&name.value)
+        } else {
+            mangle(&name.value)
+        };
+        let mut n = 0;
+        while self.globals.contains(&c_name) || self.locals.contains(&c_name) {
+            n += 1;
+            c_name = format!("{}_{}", encode(&name.value), n);
+        }
+
+        if global {
+            self.globals.insert(c_name.clone());
+        } else {
+            self.locals.insert(c_name.clone());
+        }
+        self.names.insert(def, c_name.clone());
+        c_name
+    }
+
+    /// C name of the definition `name` refers to
+    fn name(&self, name: &Name) -> String {
+        self.resolution
+            .names
+            .get(&name.id)
+            .and_then(|def| self.names.get(def))
+            .cloned()
+            .unwrap_or_else(|| mangle(&name.value))
+    }
+
+    /// Declare a temporary of `type_` at the top of the current function
+    fn temp(&mut self, type_: &Type) -> String {
+        // `_t` never appears in a mangled name, so this can't clash with one
+        let name = format!("lune_tmp_t{}", self.temps.len());
+        self.temps.push(self.c_decl(type_, &name));
+        name
+    }
+
+    /// Insert the declarations of the temporaries used since `start` there
+    fn declare_temps(&mut self, start: usize) {
+        let temps: String = self
+            .temps
+            .drain(..)
+            .map(|temp| format!("{}{};\n", "    ".repeat(self.indent), temp))
+            .collect();
+        self.out.insert_str(start, &temps);
+    }
+
+    /// C condition for `pattern` matching the value in `temp`, None if it always does
+    fn pattern_cond(&self, temp: &str, type_: &Type, pattern: &Pattern) -> Option<String> {
+        let cond = match &pattern.kind {
+            PatternKind::Wildcard => return None,
+            PatternKind::Int(n) => format!("{} == {}", temp, c_int(*n)),
+            // Bounds at the ends of the range of i32 are always true, gcc warns about them
+            PatternKind::Range(start, end) => {
+                let mut bounds = vec![];
+                if *start > i32::MIN as i128 {
+                    bounds.push(format!("{} >= {}", temp, c_int(*start)));
+                }
+                if *end < i32::MAX as i128 {
+                    bounds.push(format!("{} <= {}", temp, c_int(*end)));
+                }
+                if bounds.is_empty() {
+                    return None;
+                }
+                bounds.join(" && ")
+            }
+            PatternKind::Str(s) => format!("strcmp({}, {}) == 0", temp, c_string(s)),
+            PatternKind::Variant(name, _) => format!("{} == {}", self.tag(temp, type_), self.name(name)),
+        };
+        Some(format!("({})", cond))
+    }
+
+    /// The tag of the enum value in `temp`, which is the value itself for plain enums
+    fn tag(&self, temp: &str, type_: &Type) -> String {
+        if self.resolution.is_tagged_union(type_) {
+            format!("{}.tag", temp)
+        } else {
+            temp.into()
+        }
+    }
+
+    /// Point the names a variant pattern binds at the payload in `temp`
+    fn bind(&mut self, temp: &str, pattern: &Pattern) {
+        let PatternKind::Variant(name, bindings) = &pattern.kind else {
+            return;
+        };
+        let resolution = self.resolution;
+        let Some(variant) = resolution.lookup(name) else {
+            return;
+        };
+
+        for (binding, (field, _)) in bindings.iter().zip(&variant.fields) {
+            if let Some(&def) = resolution.names.get(&binding.id) {
+                let place = format!("{}.as.{}.{}", temp, self.name(name), mangle(field));
+                self.names.insert(def, place);
+            }
+        }
+    }
+
+    fn case_stmt(&mut self, ast: &AST, scrutinee: ExprId, arms: &[Arm<Block>]) {
+        let type_ = self.type_of(ast, scrutinee);
+        let temp = self.temp(type_);
+        let line = format!("{} = {};", temp, self.expr(ast, scrutinee));
+        self.line(&line);
+
+        if !self.resolution.variants(type_).is_empty() {
+            let line = format!("switch ({}) {{", self.tag(&temp, type_));
+            self.line(&line);
+            for (i, arm) in arms.iter().enumerate() {
+                // Exhaustive without a wildcard, the last arm is the default so
+                // C sees that the switch always runs an arm
+                let label = match &arm.pattern.kind {
+                    PatternKind::Variant(name, _) if i + 1 == arms.len() => {
+                        format!("case {}: default: {{", self.name(name))
+                    }
+                    PatternKind::Variant(name, _) => format!("case {}: {{", self.name(name)),
+                    _ => "default: {".into(),
+                };
+                self.line(&label);
+                self.bind(&temp, &arm.pattern);
+                self.indent += 1;
+                self.block_body(ast, &arm.body);
+                self.line("break;");
+                self.indent -= 1;
+                self.line("}");
+            }
+            self.line("}");
+            return;
+        }
+
+        // Cases are exhaustive, so the last arm needs no condition
+        for (i, arm) in arms.iter().enumerate() {
+            let cond = self.pattern_cond(&temp, type_, &arm.pattern);
+            let line = match (i, cond) {
+                (i, _) if i + 1 == arms.len() && i == 0 => "{".into(),
+                (i, _) if i + 1 == arms.len() => "} else {".into(),
+                (0, Some(cond)) => format!("if {} {{", cond),
+                (_, Some(cond)) => format!("}} else if {} {{", cond),
+                (_, None) => unreachable!("only the last arm can be a wildcard"),
+            };
+            self.line(&line);
+            self.block(ast, &arm.body);
+        }
+        if !arms.is_empty() {
+            self.line("}");
+        }
+    }
+
+    fn case_expr(&mut self, ast: &AST, scrutinee: ExprId, arms: &[Arm<ExprId>]) -> String {
+        let type_ = self.type_of(ast, scrutinee);
+        let temp = self.temp(type_);
+        let mut out = format!("({} = {}, ", temp, self.expr(ast, scrutinee));
+        for (i, arm) in arms.iter().enumerate() {
+            self.bind(&temp, &arm.pattern);
+            let body = self.expr(ast, arm.body);
+            match self.pattern_cond(&temp, type_, &arm.pattern) {
+                Some(cond) if i + 1 < arms.len() => out += &format!("{} ? ({}) : ", cond, body),
+                _ => out += &format!("({})", body),
+            }
+        }
+        out + ")"
+    }
+
+    /// Construct the value of the variant `name` with the payload `args`
+    fn variant(&mut self, ast: &AST, name: &Name, args: &[ExprId]) -> String {
+        let resolution = self.resolution;
+        let c_name = self.name(name);
+        let Some(def) = resolution.lookup(name) else {
+            return c_name;
+        };
+        if !resolution.is_tagged_union(&def.type_) {
+            return c_name;
+        }
+
+        let type_ = self.c_type(&def.type_);
+        if args.is_empty() {
+            return format!("({}){{ .tag = {} }}", type_, c_name);
+        }
+        let mut setup = vec![];
+        let values = self.operands(ast, args, &mut setup);
+        let fields: Vec<String> = def
+            .fields
+            .iter()
+            .zip(values)
+            .map(|((field, _), value)| format!(".{} = {}", mangle(field), value))
+            .collect();
+        let value = format!("({}){{ .tag = {}, .as.{} = {{ {} }} }}", type_, c_name, c_name, fields.join(", "));
+        sequence(setup, value)
+    }
+
+    /// C string literal with the source location of `span`, eg "main.lune:3:9"
+    fn location(&self, span: Span) -> String {
+        let location = match file_at(self.files, span.start) {
+            Some(file) => {
+                let (line, column) = file.position(span.start);
+                format!("{}:{}:{}", file.path.display(), line + 1, column + 1)
+            }
+            None => format!("<input>:{}", span.line + 1),
+        };
+        c_string(&location)
+    }
+
+    /// `index` checked against `len` if checks are on
+    fn check_index(&self, index: String, len: &str, span: Span) -> String {
+        if self.checks {
+            format!("lune_check_index({}, {}, {})", index, len, self.location(span))
+        } else {
+            index
+        }
+    }
+
+    /// Whether evaluating the expression twice is the same as once
+    fn is_pure(ast: &AST, id: ExprId) -> bool {
+        match &ast[id].kind {
+            ExprKind::IntLit(_) | ExprKind::Variable(_) => true,
+            ExprKind::FieldAccess(object, _) => Self::is_pure(ast, *object),
+            _ => false,
+        }
+    }
+
+    /// C for an expression that is used more than once. Unless it's pure, it is
+    /// stored in a temporary by an assignment pushed onto `setup`.
+    fn once(&mut self, ast: &AST, id: ExprId, setup: &mut Vec<String>) -> String {
+        let value = self.expr(ast, id);
+        if Self::is_pure(ast, id) {
+            return value;
+        }
+        let temp = self.temp(self.type_of(ast, id));
+        setup.push(format!("{} = {}", temp, value));
+        temp
+    }
+
+    /// C for `operands` evaluated left to right. If any of them makes a call,
+    /// the ones that aren't literals are stored in temporaries by assignments
+    /// pushed onto `setup`, as C would evaluate them in any order.
+    fn operands(&mut self, ast: &AST, operands: &[ExprId], setup: &mut Vec<String>) -> Vec<String> {
+        let is_literal = |id: ExprId| {
+            matches!(ast[id].kind, ExprKind::IntLit(_) | ExprKind::StringLit(_) | ExprKind::BoolLit(_))
+        };
+        let mut calls = Calls::default();
+        for &id in operands {
+            calls.visit_expr(ast, id);
+        }
+        let ordered = !calls.found || operands.iter().filter(|&&id| !is_literal(id)).count() < 2;
+
+        let mut values = vec![];
+        for &id in operands {
+            let value = self.expr(ast, id);
+            if ordered || is_literal(id) {
+                values.push(value);
+                continue;
+            }
+            let temp = self.temp(self.type_of(ast, id));
+            setup.push(format!("{} = {}", temp, value));
+            values.push(temp);
+        }
+        values
+    }
+
+    fn index(&mut self, ast: &AST, id: ExprId, object: ExprId, index: ExprId) -> String {
+        let span = ast[id].span;
+        let i = self.expr(ast, index);
+        if let Type::Array(_, len) = self.type_of(ast, object) {
+            // Constant indexes into arrays were checked by the type checker
+            let i = match ast[index].kind {
+                ExprKind::IntLit(_) => i,
+                _ => self.check_index(i, &len.to_string(), span),
+            };
+            return format!("{}.data[{}]", self.expr(ast, object), i);
+        }
+
+        let mut setup = vec![];
+        let slice = self.once(ast, object, &mut setup);
+        let i = self.check_index(i, &format!("{}.len", slice), span);
+        if setup.is_empty() {
+            format!("{}.ptr[{}]", slice, i)
+        } else {
+            // The comma operator orders the assignment before the read
+            format!("*({}, {}.ptr + {})", setup.join(", "), slice, i)
+        }
+    }
+
+    fn slice(
+        &mut self,
+        ast: &AST,
+        id: ExprId,
+        object: ExprId,
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+    ) -> String {
+        let mut setup = vec![];
+        let (ptr, len) = match self.type_of(ast, object) {
+            Type::Array(_, len) => (format!("{}.data", self.expr(ast, object)), len.to_string()),
+            _ => {
+                let slice = self.once(ast, object, &mut setup);
+                (format!("{}.ptr", slice), format!("{}.len", slice))
+            }
+        };
+        let start = match start {
+            Some(start) => self.once(ast, start, &mut setup),
+            None => "0".into(),
+        };
+        let end = match end {
+            Some(end) => self.once(ast, end, &mut setup),
+            None => len.clone(),
+        };
+        if self.checks {
+            let location = self.location(ast[id].span);
+            setup.push(format!("lune_check_slice({}, {}, {}, {})", start, end, len, location));
+        }
+
+        let type_ = self.c_type(self.type_of(ast, id));
+        let value = format!("({}){{ {} + {}, {} - {} }}", type_, ptr, start, end, start);
+        sequence(setup, value)
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out += &"    ".repeat(self.indent);
+        self.out += text;
+        self.out += "\n";
+    }
+
+    fn block_body(&mut self, ast: &AST, stmts: &[StmtId]) {
+        for &stmt in stmts {
+            self.stmt(ast, stmt);
+        }
+    }
+
+    fn block(&mut self, ast: &AST, stmts: &[StmtId]) {
+        self.indent += 1;
+        self.block_body(ast, stmts);
+        self.indent -= 1;
+    }
+
+    fn stmt(&mut self, ast: &AST, id: StmtId) {
+        match &ast[id].kind {
+            StmtKind::Expr(expr) => {
+                let line = format!("{};", self.expr(ast, *expr));
+                self.line(&line);
+            }
+            StmtKind::VarDeclaration(name, type_, value) => {
+                // The value can refer to an outer variable of the same name,
+                // so it is generated before the new name is declared
+                let value = self.expr(ast, *value);
+                let name = self.declare(name, false);
+                self.line(&format!("{} = {};", self.c_decl(type_, &name), value));
+            }
+            StmtKind::Assignment(target, value) => {
+                let line = format!("{} = {};", self.expr(ast, *target), self.expr(ast, *value));
+                self.line(&line);
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                let line = format!("if ({}) {{", self.expr(ast, *cond));
+                self.line(&line);
+                self.block(ast, then);
+                if let Some(otherwise) = otherwise {
+                    self.line("} else {");
+                    self.block(ast, otherwise);
+                }
+                self.line("}");
+            }
+            StmtKind::While(cond, body) => {
+                let line = format!("while ({}) {{", self.expr(ast, *cond));
+                self.line(&line);
+                self.block(ast, body);
+                self.line("}");
+            }
+            StmtKind::Return(Some(value)) => {
+                let line = format!("return {};", self.expr(ast, *value));
+                self.line(&line);
+            }
+            StmtKind::Return(None) => self.line("return;"),
+            StmtKind::Case(scrutinee, arms) => self.case_stmt(ast, *scrutinee, arms),
+            // Procs, types and constants only appear at the top level, which
+            // `generate` handles
+            StmtKind::ProcDeclaration(_)
+            | StmtKind::StructDeclaration(_)
+            | StmtKind::EnumDeclaration(_)
+            | StmtKind::ConstDeclaration(_, _, _) => {}
+        }
+    }
+
+    fn type_of(&self, ast: &AST, id: ExprId) -> &'a Type {
+        self.types
+            .get(&ast[id].id)
+            .expect("expression should be type checked")
+    }
+
+    fn expr(&mut self, ast: &AST, id: ExprId) -> String {
+        match &ast[id].kind {
+            ExprKind::IntLit(n) => n.to_string(),
+            ExprKind::StringLit(s) => c_string(s),
+            ExprKind::BoolLit(b) => b.to_string(),
+            ExprKind::Variable(name) if self.is_variant(name) => self.variant(ast, name, &[]),
+            ExprKind::Variable(name) => self.name(name),
+            ExprKind::Call(name, args) if name.value == "print" && self.is_builtin(name) => {
+                let helper = match self.type_of(ast, args[0]) {
+                    Type::Int => "lune_print_int",
+                    Type::String => "lune_print_str",
+                    Type::Bool => "lune_print_bool",
+                    Type::Void
+                    | Type::Named(_)
+                    | Type::Array(_, _)
+                    | Type::Slice(_)
+                    | Type::ConstArray(_, _) => {
+                        unreachable!("only ints, strings and bools can be printed")
+                    }
+                };
+                format!("{}({})", helper, self.expr(ast, args[0]))
+            }
+            ExprKind::Call(name, args) if name.value == "len" && self.is_builtin(name) => {
+                match self.type_of(ast, args[0]) {
+                    Type::Array(_, len) if Self::is_pure(ast, args[0]) => len.to_string(),
+                    // Still evaluated for its side effects
+                    Type::Array(_, len) => format!("((void){}, {})", self.expr(ast, args[0]), len),
+                    _ => format!("{}.len", self.expr(ast, args[0])),
+                }
+            }
+            ExprKind::Call(name, args) if self.is_variant(name) => self.variant(ast, name, args),
+            ExprKind::Call(name, args) => {
+                let mut setup = vec![];
+                let args = self.operands(ast, args, &mut setup);
+                sequence(setup, format!("{}({})", self.name(name), args.join(", ")))
+            }
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs)
+                if ast[*rhs].kind == ExprKind::IntLit(i32::MIN.unsigned_abs() as u128) =>
+            {
+                c_int(i32::MIN as i128)
+            }
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs) if matches!(ast[*rhs].kind, ExprKind::IntLit(_)) => {
+                format!("(-{})", self.expr(ast, *rhs))
+            }
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs) => format!("lune_neg({})", self.expr(ast, *rhs)),
+            ExprKind::UnaryOp(op, rhs) => format!("({}{})", op, self.expr(ast, *rhs)),
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let mut setup = vec![];
+                let [l, r]: [String; 2] = self.operands(ast, &[*lhs, *rhs], &mut setup).try_into().unwrap();
+                let value = match op {
+                    BinOpKind::Eq | BinOpKind::Ne if *self.type_of(ast, *lhs) == Type::String => {
+                        format!("(strcmp({}, {}) {} 0)", l, r, op)
+                    }
+                    BinOpKind::Add => format!("lune_add({}, {})", l, r),
+                    BinOpKind::Sub => format!("lune_sub({}, {})", l, r),
+                    BinOpKind::Mul => format!("lune_mul({}, {})", l, r),
+                    // Dividing by a positive literal can't trap
+                    BinOpKind::Div if matches!(ast[*rhs].kind, ExprKind::IntLit(n) if n != 0) => {
+                        format!("({} / {})", l, r)
+                    }
+                    BinOpKind::Div => format!("lune_div({}, {}, {})", l, r, self.location(ast[id].span)),
+                    _ => format!("({} {} {})", l, op, r),
+                };
+                sequence(setup, value)
+            }
+            // A compound literal, designated so the fields can come in any order
+            ExprKind::StructLit(_, fields) => {
+                let mut setup = vec![];
+                let values: Vec<ExprId> = fields.iter().map(|(_, value)| *value).collect();
+                let values = self.operands(ast, &values, &mut setup);
+                let fields: Vec<String> = fields
+                    .iter()
+                    .zip(values)
+                    .map(|((field, _), value)| format!(".{} = {}", mangle(&field.value), value))
+                    .collect();
+                let type_ = self.c_type(self.type_of(ast, id));
+                sequence(setup, format!("({}){{ {} }}", type_, fields.join(", ")))
+            }
+            ExprKind::FieldAccess(object, field) => {
+                format!("{}.{}", self.expr(ast, *object), mangle(&field.value))
+            }
+            ExprKind::Case(scrutinee, arms) => self.case_expr(ast, *scrutinee, arms),
+            ExprKind::ArrayLit(elements) => {
+                let mut setup = vec![];
+                let elements = self.operands(ast, elements, &mut setup);
+                let type_ = self.c_type(self.type_of(ast, id));
+                sequence(setup, format!("({}){{ {{ {} }} }}", type_, elements.join(", ")))
+            }
+            ExprKind::Index(object, index) => self.index(ast, id, *object, *index),
+            ExprKind::Slice(object, start, end) => self.slice(ast, id, *object, *start, *end),
+        }
+    }
+
+    fn is_variant(&self, name: &Name) -> bool {
+        self.resolution
+            .lookup(name)
+            .is_some_and(|def| def.kind == DefKind::Variant)
+    }
+
+    fn is_builtin(&self, name: &Name) -> bool {
+        self.resolution
+            .lookup(name)
+            .is_some_and(|def| def.kind == DefKind::Builtin)
+    }
+}
@@ -0,0 +1,160 @@
+//! Constant folding and algebraic simplification.
+//!
+//! Runs over the checked AST, folding unary and binary operators whose
+//! operands are known at compile time into literals, and dropping operations
+//! that can't change their operand: `x * 1`, `1 * x`, `x / 1`, `x + 0`,
+//! `0 + x`, `x - 0` and `!!b`. The operand is still evaluated, so side effects
+//! are kept.
+//!
+//! Ints follow the run time semantics of i32: `+`, `-` and `*` wrap around,
+//! and division by zero or of `-2147483648` by `-1` traps. A division that is
+//! known to trap is reported as an error instead of being folded.
+//!
+//! Negative results are written as `-` applied to a literal, the same way the
+//! parser reads them, and new nodes are given a type so code generation can
+//! use the folded AST as it would the original.
+
+use std::collections::HashMap;
+
+use super::ast::*;
+use super::visit::{fold_expr, Folder};
+use crate::errors::FoldError;
+use crate::types::Type;
+
+/// Folds constant expressions, see the module docs
+pub struct ConstFolder<'a> {
+    /// Types from the checker, extended with the nodes the folder adds
+    types: &'a mut HashMap<NodeId, Type>,
+    pub errors: Vec<FoldError>,
+}
+
+impl<'a> ConstFolder<'a> {
+    pub fn new(types: &'a mut HashMap<NodeId, Type>) -> ConstFolder<'a> {
+        ConstFolder {
+            types,
+            errors: vec![],
+        }
+    }
+
+    /// Value of an int literal, or of a negated one
+    fn int(ast: &AST, expr: ExprId) -> Option<i32> {
+        match ast[expr].kind {
+            ExprKind::IntLit(n) => i32::try_from(n).ok(),
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs) => match ast[rhs].kind {
+                ExprKind::IntLit(n) => i32::try_from(-i64::try_from(n).ok()?).ok(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn bool(ast: &AST, expr: ExprId) -> Option<bool> {
+        match ast[expr].kind {
+            ExprKind::BoolLit(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Replace `expr` with a literal of `value`
+    fn set_int(&mut self, ast: &mut AST, expr: ExprId, value: i32) {
+        let literal = ExprKind::IntLit(value.unsigned_abs() as u128);
+        ast[expr].kind = if value < 0 {
+            let rhs = ast.add_expr(literal, ast[expr].span);
+            self.types.insert(ast[rhs].id, Type::Int);
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs)
+        } else {
+            literal
+        };
+    }
+
+    fn unary(&mut self, ast: &mut AST, expr: ExprId, op: UnaryOpKind, rhs: ExprId) -> ExprId {
+        match op {
+            // A negated literal is already as folded as it gets
+            UnaryOpKind::Neg if matches!(ast[rhs].kind, ExprKind::IntLit(_)) => {}
+            UnaryOpKind::Neg => {
+                if let Some(n) = Self::int(ast, rhs) {
+                    self.set_int(ast, expr, n.wrapping_neg());
+                }
+            }
+            UnaryOpKind::Not => {
+                if let Some(b) = Self::bool(ast, rhs) {
+                    ast[expr].kind = ExprKind::BoolLit(!b);
+                } else if let ExprKind::UnaryOp(UnaryOpKind::Not, inner) = ast[rhs].kind {
+                    return inner;
+                }
+            }
+        }
+        expr
+    }
+
+    fn binop(&mut self, ast: &mut AST, expr: ExprId, lhs: ExprId, op: BinOpKind, rhs: ExprId) -> ExprId {
+        let (a, b) = (Self::int(ast, lhs), Self::int(ast, rhs));
+        if op == BinOpKind::Div {
+            let message = match (a, b) {
+                (Some(a), Some(0)) => Some(format!("attempt to divide `{}` by zero", a)),
+                (None, Some(0)) => Some("attempt to divide by zero".to_string()),
+                (Some(i32::MIN), Some(-1)) => Some(format!(
+                    "attempt to compute `{} / -1`, which would overflow",
+                    i32::MIN
+                )),
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.errors.push(FoldError {
+                    span: ast[expr].span,
+                    message,
+                });
+                return expr;
+            }
+        }
+
+        if let (Some(a), Some(b)) = (a, b) {
+            let value = match op {
+                BinOpKind::Add => a.wrapping_add(b),
+                BinOpKind::Sub => a.wrapping_sub(b),
+                BinOpKind::Mul => a.wrapping_mul(b),
+                BinOpKind::Div => a / b,
+                _ => {
+                    ast[expr].kind = ExprKind::BoolLit(match op {
+                        BinOpKind::Eq => a == b,
+                        BinOpKind::Ne => a != b,
+                        BinOpKind::Lt => a < b,
+                        BinOpKind::Le => a <= b,
+                        BinOpKind::Gt => a > b,
+                        _ => a >= b,
+                    });
+                    return expr;
+                }
+            };
+            self.set_int(ast, expr, value);
+            return expr;
+        }
+
+        if let (Some(a), Some(b)) = (Self::bool(ast, lhs), Self::bool(ast, rhs)) {
+            match op {
+                BinOpKind::Eq => ast[expr].kind = ExprKind::BoolLit(a == b),
+                BinOpKind::Ne => ast[expr].kind = ExprKind::BoolLit(a != b),
+                _ => {}
+            }
+            return expr;
+        }
+
+        match (op, a, b) {
+            (BinOpKind::Mul, Some(1), _) | (BinOpKind::Add, Some(0), _) => rhs,
+            (BinOpKind::Mul | BinOpKind::Div, _, Some(1))
+            | (BinOpKind::Add | BinOpKind::Sub, _, Some(0)) => lhs,
+            _ => expr,
+        }
+    }
+}
+
+impl Folder for ConstFolder<'_> {
+    fn fold_expr(&mut self, ast: &mut AST, expr: ExprId) -> ExprId {
+        let expr = fold_expr(self, ast, expr);
+        match ast[expr].kind {
+            ExprKind::UnaryOp(op, rhs) => self.unary(ast, expr, op, rhs),
+            ExprKind::BinOp(lhs, op, rhs) => self.binop(ast, expr, lhs, op, rhs),
+            _ => expr,
+        }
+    }
+}
@@ -0,0 +1,634 @@
+//! Mid-level intermediate representation.
+//!
+//! A module is a list of globals and procs. Each proc is a control flow
+//! graph of basic blocks, the first one being where it starts. Blocks hold
+//! instructions writing to virtual registers and end in a terminator that
+//! branches, returns or marks the end as unreachable.
+//!
+//! Registers are in SSA form: each is written by exactly one instruction or
+//! parameter, and every use is dominated by its definition. Where values
+//! meet, a `phi` at the start of a block picks the value for the predecessor
+//! control came from. Ints, bools and strings are kept in registers. Globals
+//! can be seen by every proc, so they are addressable and read and written
+//! with explicit `load` and `store` instructions.
+//!
+//! Structs, tagged unions, arrays and slices are kept in memory and
+//! registers hold their address, a `ptr`. Memory comes from `alloca`, a slot
+//! of the proc's frame that is the same every time the instruction runs, or
+//! from a global holding bytes rather than a scalar, whose address `addr`
+//! takes. `offset` and `index` compute addresses inside it, `read` and
+//! `write` move scalars in and out and `memcopy` copies whole values. Every
+//! scalar takes 8 bytes of memory, see `lower` for the layout of the rest.
+//! A failed bounds check ends in `trap`, which stops the program.
+//!
+//! The text format is what `Display` prints and `parser` reads back:
+//!
+//! ```text
+//! entry @main
+//!
+//! global @count: int
+//! global @origin: ptr 16
+//!
+//! proc @add(%0: int, %1: int): int {
+//! bb0:
+//!     %2 = add int %0, %1
+//!     ret int %2
+//! }
+//!
+//! proc @y(%0: ptr): int {
+//! bb0:
+//!     %1 = offset ptr %0, 8
+//!     %2 = read int %1
+//!     ret int %2
+//! }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::ast::{BinOpKind, UnaryOpKind};
+use crate::json::Json;
+
+// Lowering from the checked AST
+pub mod lower;
+
+// Reading the text format back
+pub mod parser;
+
+// Checking the invariants above
+pub mod verify;
+
+// Optimization passes and the pass manager
+pub mod opt;
+
+/// Type of a register, global or proc result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Int,
+    Bool,
+    Str,
+    /// Address of memory
+    Ptr,
+    Void,
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Ty::Int => "int",
+            Ty::Bool => "bool",
+            Ty::Str => "str",
+            Ty::Ptr => "ptr",
+            Ty::Void => "void",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Virtual register, printed as `%n`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Reg(pub u32);
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+/// Basic block, printed as `bbn`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Const {
+    Int(i32),
+    Bool(bool),
+    Str(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Op {
+    Const(Const),
+    Unary(UnaryOpKind, Reg),
+    Binary(BinOpKind, Reg, Reg),
+    /// Call a proc of the module by name
+    Call(String, Vec<Reg>),
+    /// Read a global
+    Load(String),
+    /// Write a global
+    Store(String, Reg),
+    Print(Reg),
+    /// The value of another register
+    Copy(Reg),
+    /// Value from the predecessor control came from
+    Phi(Vec<(Reg, BlockId)>),
+    /// Address of a slot of this many bytes in the proc's frame
+    Alloca(u32),
+    /// Address of a global holding memory
+    Addr(String),
+    /// Address this many bytes past an address
+    Offset(Reg, u32),
+    /// Address of the element at an int index, elements taking this many bytes
+    Index(Reg, Reg, u32),
+    /// Read the value at an address
+    Read(Reg),
+    /// Write a value to an address
+    Write(Reg, Reg),
+    /// Copy this many bytes to the first address from the second, which
+    /// are either the same or don't overlap
+    MemCopy(Reg, Reg, u32),
+}
+
+/// An instruction, writing to `dest` unless it produces nothing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inst {
+    pub dest: Option<Reg>,
+    /// Type written after the mnemonic: the operand type of binary
+    /// operators, `store`, `print`, `write` and `memcopy`, the result type
+    /// of everything else
+    pub ty: Ty,
+    pub op: Op,
+}
+
+impl Inst {
+    /// Type of the value written to `dest`
+    pub fn result_type(&self) -> Ty {
+        match &self.op {
+            Op::Binary(op, _, _) if op.is_comparison() => Ty::Bool,
+            Op::Store(_, _) | Op::Print(_) | Op::Write(_, _) | Op::MemCopy(_, _, _) => Ty::Void,
+            _ => self.ty,
+        }
+    }
+
+    /// Registers the instruction reads
+    pub fn uses(&self) -> Vec<Reg> {
+        match &self.op {
+            Op::Const(_) | Op::Load(_) | Op::Alloca(_) | Op::Addr(_) => vec![],
+            Op::Unary(_, reg)
+            | Op::Store(_, reg)
+            | Op::Print(reg)
+            | Op::Copy(reg)
+            | Op::Offset(reg, _)
+            | Op::Read(reg) => vec![*reg],
+            Op::Binary(_, lhs, rhs) | Op::Index(lhs, rhs, _) | Op::MemCopy(lhs, rhs, _) => {
+                vec![*lhs, *rhs]
+            }
+            Op::Write(value, address) => vec![*value, *address],
+            Op::Call(_, args) => args.clone(),
+            Op::Phi(incoming) => incoming.iter().map(|(reg, _)| *reg).collect(),
+        }
+    }
+
+    /// Replace every register the instruction reads with `f(reg)`
+    pub fn map_uses(&mut self, mut f: impl FnMut(Reg) -> Reg) {
+        match &mut self.op {
+            Op::Const(_) | Op::Load(_) | Op::Alloca(_) | Op::Addr(_) => {}
+            Op::Unary(_, reg)
+            | Op::Store(_, reg)
+            | Op::Print(reg)
+            | Op::Copy(reg)
+            | Op::Offset(reg, _)
+            | Op::Read(reg) => *reg = f(*reg),
+            Op::Binary(_, lhs, rhs)
+            | Op::Index(lhs, rhs, _)
+            | Op::MemCopy(lhs, rhs, _)
+            | Op::Write(lhs, rhs) => {
+                *lhs = f(*lhs);
+                *rhs = f(*rhs);
+            }
+            Op::Call(_, args) => args.iter_mut().for_each(|arg| *arg = f(*arg)),
+            Op::Phi(incoming) => incoming.iter_mut().for_each(|(reg, _)| *reg = f(*reg)),
+        }
+    }
+
+    /// Whether removing the instruction when its result is unused changes nothing
+    pub fn is_pure(&self) -> bool {
+        match &self.op {
+            // Division traps on zero
+            Op::Binary(BinOpKind::Div, _, _) => false,
+            Op::Call(_, _)
+            | Op::Store(_, _)
+            | Op::Print(_)
+            | Op::Write(_, _)
+            | Op::MemCopy(_, _, _) => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Br(BlockId),
+    /// Branch to the first block if the condition is true, else the second
+    CondBr(Reg, BlockId, BlockId),
+    Ret(Option<Reg>),
+    Unreachable,
+    /// Stop the program, for failed bounds checks
+    Trap,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Br(target) => vec![*target],
+            Terminator::CondBr(_, then, otherwise) => vec![*then, *otherwise],
+            Terminator::Ret(_) | Terminator::Unreachable | Terminator::Trap => vec![],
+        }
+    }
+
+    pub fn uses(&self) -> Vec<Reg> {
+        match self {
+            Terminator::CondBr(cond, _, _) => vec![*cond],
+            Terminator::Ret(Some(value)) => vec![*value],
+            _ => vec![],
+        }
+    }
+
+    /// Replace every register the terminator reads with `f(reg)`
+    pub fn map_uses(&mut self, mut f: impl FnMut(Reg) -> Reg) {
+        match self {
+            Terminator::CondBr(cond, _, _) => *cond = f(*cond),
+            Terminator::Ret(Some(value)) => *value = f(*value),
+            _ => {}
+        }
+    }
+
+    /// Replace every block the terminator branches to with `f(block)`
+    pub fn map_targets(&mut self, mut f: impl FnMut(BlockId) -> BlockId) {
+        match self {
+            Terminator::Br(target) => *target = f(*target),
+            Terminator::CondBr(_, then, otherwise) => {
+                *then = f(*then);
+                *otherwise = f(*otherwise);
+            }
+            Terminator::Ret(_) | Terminator::Unreachable | Terminator::Trap => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub id: BlockId,
+    /// Phis come first
+    pub insts: Vec<Inst>,
+    pub term: Terminator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proc {
+    pub name: String,
+    pub params: Vec<(Reg, Ty)>,
+    pub ret: Ty,
+    /// Control starts at the first block
+    pub blocks: Vec<Block>,
+}
+
+impl Proc {
+    pub fn block(&self, id: BlockId) -> Option<&Block> {
+        self.blocks.iter().find(|block| block.id == id)
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> Option<&mut Block> {
+        self.blocks.iter_mut().find(|block| block.id == id)
+    }
+
+    /// The phis of `target` with the value each takes on the edge from
+    /// `from`, as `(dest, value)`. Where `from` branches to `target` more than
+    /// once the phis have a value per branch, `nth` picks the branch.
+    pub fn phi_moves(&self, from: BlockId, target: BlockId, nth: usize) -> Vec<(Reg, Reg)> {
+        let Some(block) = self.block(target) else {
+            return vec![];
+        };
+        block
+            .insts
+            .iter()
+            .filter_map(|inst| match &inst.op {
+                Op::Phi(incoming) => {
+                    let mut values = incoming.iter().filter(|&&(_, pred)| pred == from);
+                    let &(value, _) = values.clone().nth(nth).or_else(|| values.next())?;
+                    Some((inst.dest?, value))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Blocks reachable from the first one, each before its successors
+    /// except along loop back edges
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let Some(entry) = self.blocks.first() else {
+            return vec![];
+        };
+        let succs: HashMap<BlockId, Vec<BlockId>> = self
+            .blocks
+            .iter()
+            .map(|block| (block.id, block.term.successors()))
+            .collect();
+
+        let mut order = vec![];
+        let mut visited = HashSet::from([entry.id]);
+        let mut stack = vec![(entry.id, 0)];
+        while let Some((block, next)) = stack.pop() {
+            match succs.get(&block).and_then(|succs| succs.get(next)) {
+                Some(&succ) => {
+                    stack.push((block, next + 1));
+                    if visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Immediate dominator of every block reachable from the first one,
+    /// which is its own. Uses the iterative algorithm of Cooper, Harvey and
+    /// Kennedy, "A Simple, Fast Dominance Algorithm".
+    pub fn dominators(&self) -> HashMap<BlockId, BlockId> {
+        let order = self.reverse_postorder();
+        let Some(&entry) = order.first() else {
+            return HashMap::new();
+        };
+        let index: HashMap<BlockId, usize> = order.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+        let preds = self.predecessors();
+
+        let mut idom = HashMap::from([(entry, entry)]);
+        let intersect = |idom: &HashMap<BlockId, BlockId>, mut a: BlockId, mut b: BlockId| {
+            while a != b {
+                while index[&a] > index[&b] {
+                    a = idom[&a];
+                }
+                while index[&b] > index[&a] {
+                    b = idom[&b];
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let mut new = None;
+                for &pred in &preds[&block] {
+                    if !idom.contains_key(&pred) {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => pred,
+                        Some(other) => intersect(&idom, pred, other),
+                    });
+                }
+                if let Some(new) = new {
+                    if idom.insert(block, new) != Some(new) {
+                        changed = true;
+                    }
+                }
+            }
+        }
+        idom
+    }
+
+    /// Predecessors of every block, in block order, a block branching to
+    /// another twice counts twice
+    pub fn predecessors(&self) -> HashMap<BlockId, Vec<BlockId>> {
+        let mut preds: HashMap<BlockId, Vec<BlockId>> =
+            self.blocks.iter().map(|block| (block.id, vec![])).collect();
+        for block in &self.blocks {
+            for succ in block.term.successors() {
+                preds.entry(succ).or_default().push(block.id);
+            }
+        }
+        preds
+    }
+
+    /// Type of every register, from the parameters and instructions
+    pub fn reg_types(&self) -> HashMap<Reg, Ty> {
+        let mut types: HashMap<Reg, Ty> = self.params.iter().copied().collect();
+        for inst in self.blocks.iter().flat_map(|block| &block.insts) {
+            if let Some(dest) = inst.dest {
+                types.insert(dest, inst.result_type());
+            }
+        }
+        types
+    }
+
+    /// A register number above every register in use
+    pub fn next_reg(&self) -> u32 {
+        let params = self.params.iter().map(|(reg, _)| reg.0);
+        let dests = self
+            .blocks
+            .iter()
+            .flat_map(|block| &block.insts)
+            .filter_map(|inst| inst.dest.map(|reg| reg.0));
+        params.chain(dests).max().map_or(0, |max| max + 1)
+    }
+
+    /// Number registers and blocks from 0 in the order they appear
+    pub fn renumber(&mut self) {
+        let blocks: HashMap<BlockId, BlockId> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| (block.id, BlockId(i as u32)))
+            .collect();
+
+        let mut regs = HashMap::new();
+        for (reg, _) in &mut self.params {
+            let new = Reg(regs.len() as u32);
+            regs.insert(*reg, new);
+            *reg = new;
+        }
+        for inst in self.blocks.iter_mut().flat_map(|block| &mut block.insts) {
+            if let Some(dest) = &mut inst.dest {
+                let new = Reg(regs.len() as u32);
+                regs.insert(*dest, new);
+                *dest = new;
+            }
+        }
+
+        let reg = |reg: Reg| regs.get(&reg).copied().unwrap_or(reg);
+        let block_id = |block: BlockId| blocks.get(&block).copied().unwrap_or(block);
+        for block in &mut self.blocks {
+            block.id = block_id(block.id);
+            for inst in &mut block.insts {
+                inst.map_uses(reg);
+                if let Op::Phi(incoming) = &mut inst.op {
+                    for (_, pred) in incoming {
+                        *pred = block_id(*pred);
+                    }
+                }
+            }
+            block.term.map_uses(reg);
+            block.term.map_targets(block_id);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    /// Type of the scalar read with `load` and written with `store`, or
+    /// `ptr` for memory whose address `addr` takes
+    pub ty: Ty,
+    /// Bytes of memory a `ptr` global holds, 0 for scalars
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    /// Proc run when the program starts, made of the top level statements
+    pub entry: String,
+    /// Globals start out as 0, false, the null string or zeroed memory until
+    /// written
+    pub globals: Vec<Global>,
+    pub procs: Vec<Proc>,
+}
+
+impl Module {
+    pub fn proc(&self, name: &str) -> Option<&Proc> {
+        self.procs.iter().find(|proc| proc.name == name)
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Global> {
+        self.globals.iter().find(|global| global.name == name)
+    }
+}
+
+/// Mnemonic of a unary operator
+pub fn unary_name(op: UnaryOpKind) -> &'static str {
+    match op {
+        UnaryOpKind::Neg => "neg",
+        UnaryOpKind::Not => "not",
+    }
+}
+
+/// Mnemonic of a binary operator
+pub fn binary_name(op: BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "add",
+        BinOpKind::Sub => "sub",
+        BinOpKind::Mul => "mul",
+        BinOpKind::Div => "div",
+        BinOpKind::Eq => "eq",
+        BinOpKind::Ne => "ne",
+        BinOpKind::Lt => "lt",
+        BinOpKind::Le => "le",
+        BinOpKind::Gt => "gt",
+        BinOpKind::Ge => "ge",
+    }
+}
+
+impl fmt::Display for Const {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Const::Int(n) => write!(f, "{}", n),
+            Const::Bool(b) => write!(f, "{}", b),
+            Const::Str(s) => write!(f, "{}", Json::String(s.clone())),
+        }
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(dest) = self.dest {
+            write!(f, "{} = ", dest)?;
+        }
+        match &self.op {
+            Op::Const(value) => write!(f, "const {} {}", self.ty, value),
+            Op::Unary(op, rhs) => write!(f, "{} {} {}", unary_name(*op), self.ty, rhs),
+            Op::Binary(op, lhs, rhs) => {
+                write!(f, "{} {} {}, {}", binary_name(*op), self.ty, lhs, rhs)
+            }
+            Op::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "call {} @{}({})", self.ty, name, args.join(", "))
+            }
+            Op::Load(name) => write!(f, "load {} @{}", self.ty, name),
+            Op::Store(name, value) => write!(f, "store {} {}, @{}", self.ty, value, name),
+            Op::Print(value) => write!(f, "print {} {}", self.ty, value),
+            Op::Copy(value) => write!(f, "copy {} {}", self.ty, value),
+            Op::Phi(incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(reg, block)| format!("[{}, {}]", reg, block))
+                    .collect();
+                write!(f, "phi {} {}", self.ty, incoming.join(", "))
+            }
+            Op::Alloca(size) => write!(f, "alloca {} {}", self.ty, size),
+            Op::Addr(name) => write!(f, "addr {} @{}", self.ty, name),
+            Op::Offset(address, offset) => {
+                write!(f, "offset {} {}, {}", self.ty, address, offset)
+            }
+            Op::Index(address, index, size) => {
+                write!(f, "index {} {}, {}, {}", self.ty, address, index, size)
+            }
+            Op::Read(address) => write!(f, "read {} {}", self.ty, address),
+            Op::Write(value, address) => write!(f, "write {} {}, {}", self.ty, value, address),
+            Op::MemCopy(to, from, size) => {
+                write!(f, "memcopy {} {}, {}, {}", self.ty, to, from, size)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Proc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(reg, ty)| format!("{}: {}", reg, ty))
+            .collect();
+        write!(f, "proc @{}({})", self.name, params.join(", "))?;
+        if self.ret != Ty::Void {
+            write!(f, ": {}", self.ret)?;
+        }
+        writeln!(f, " {{")?;
+
+        for block in &self.blocks {
+            writeln!(f, "{}:", block.id)?;
+            for inst in &block.insts {
+                writeln!(f, "    {}", inst)?;
+            }
+            match &block.term {
+                Terminator::Br(target) => writeln!(f, "    br {}", target)?,
+                Terminator::CondBr(cond, then, otherwise) => {
+                    writeln!(f, "    br {}, {}, {}", cond, then, otherwise)?
+                }
+                Terminator::Ret(Some(value)) => writeln!(f, "    ret {} {}", self.ret, value)?,
+                Terminator::Ret(None) => writeln!(f, "    ret")?,
+                Terminator::Unreachable => writeln!(f, "    unreachable")?,
+                Terminator::Trap => writeln!(f, "    trap")?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "entry @{}", self.entry)?;
+        if !self.globals.is_empty() {
+            writeln!(f)?;
+        }
+        for global in &self.globals {
+            write!(f, "global @{}: {}", global.name, global.ty)?;
+            if global.ty == Ty::Ptr {
+                write!(f, " {}", global.size)?;
+            }
+            writeln!(f)?;
+        }
+        for proc in &self.procs {
+            write!(f, "\n{}", proc)?;
+        }
+        Ok(())
+    }
+}
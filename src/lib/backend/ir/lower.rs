@@ -0,0 +1,1208 @@
+//! Lowering from the checked, constant folded AST into the IR.
+//!
+//! Top level procs become procs of the same name and top level statements
+//! become the entry proc. Top level variables become globals, every other
+//! variable is a local kept in registers.
+//!
+//! SSA form is built while lowering, following Braun et al., "Simple and
+//! Efficient Construction of Static Single Assignment Form". The register
+//! holding each local is tracked per block, and reading a local in a block
+//! that doesn't write it asks its predecessors, placing a phi where they
+//! could disagree. Blocks whose predecessors aren't all known yet, such as a
+//! loop header before the end of the body, get placeholder phis that are
+//! filled in once the block is sealed. Phis that turn out to pick the same
+//! value on every edge are removed at the end.
+//!
+//! Structs, tagged unions, arrays and slices are kept in memory, laid out
+//! like this with every scalar taking 8 bytes:
+//!
+//! - a struct is its fields in declaration order
+//! - an array is its elements one after another
+//! - a slice is the address of its first element, then its length
+//! - a tagged union is its tag, then the payload of its variant, taking as
+//!   much room as the largest one
+//!
+//! Enums without a payload are ints holding the tag. Variables of these
+//! types own a slot their value stays in, so slices pointing into it see
+//! assignments. Like in `bytecode::compile`, expressions naming a place
+//! lower to its address, and their value is copied into a new slot when
+//! used as a value. Procs returning a value kept in memory take the address
+//! to write it to as their first parameter and return nothing.
+//!
+//! Indexes and slice bounds are always checked, a failed check branches to
+//! a block ending in `trap`.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::backend::ast::{
+    self, ExprId, ExprKind, Name, NodeId, Pattern, PatternKind, StmtId, StmtKind, AST,
+};
+use crate::errors::LowerError;
+use crate::frontend::consteval::Value;
+use crate::frontend::lexer::token::Span;
+use crate::frontend::resolver::{DefKind, Resolution};
+use crate::types::Type;
+
+/// A local being built into SSA form: a definition, or the result of a case expression
+type Var = usize;
+
+/// A block being built, it has no terminator until control leaves it
+struct Building {
+    insts: Vec<Inst>,
+    term: Option<Terminator>,
+    preds: Vec<BlockId>,
+    sealed: bool,
+    /// Phis of an unsealed block waiting for its predecessors
+    incomplete: Vec<(Var, Reg)>,
+}
+
+/// Builds one proc
+#[derive(Default)]
+struct ProcBuilder {
+    blocks: Vec<Building>,
+    /// Block instructions are added to, None after a return or branch
+    current: Option<BlockId>,
+    /// Register holding each local at the end of each block written so far
+    defs: HashMap<(Var, BlockId), Reg>,
+    var_types: HashMap<Var, Ty>,
+    next_reg: u32,
+    /// Blocks in the order control first entered them, which is how they're printed
+    order: Vec<BlockId>,
+    /// Block failed bounds checks branch to, made the first time one does
+    trap: Option<BlockId>,
+}
+
+impl ProcBuilder {
+    fn reg(&mut self) -> Reg {
+        self.next_reg += 1;
+        Reg(self.next_reg - 1)
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push(Building {
+            insts: vec![],
+            term: None,
+            preds: vec![],
+            sealed: false,
+            incomplete: vec![],
+        });
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+
+    fn building(&mut self, block: BlockId) -> &mut Building {
+        &mut self.blocks[block.0 as usize]
+    }
+
+    /// Add an instruction to the current block, returning the register it writes
+    fn push(&mut self, ty: Ty, op: Op) -> Reg {
+        let reg = self.reg();
+        self.push_inst(Inst {
+            dest: Some(reg),
+            ty,
+            op,
+        });
+        reg
+    }
+
+    fn push_inst(&mut self, inst: Inst) {
+        let current = self
+            .current
+            .expect("instructions are only added to reachable blocks");
+        self.building(current).insts.push(inst);
+    }
+
+    /// End the current block
+    fn terminate(&mut self, term: Terminator) {
+        let current = self
+            .current
+            .take()
+            .expect("only reachable blocks are terminated");
+        for succ in term.successors() {
+            self.building(succ).preds.push(current);
+        }
+        self.building(current).term = Some(term);
+    }
+
+    /// The block ending in `trap`
+    fn trap(&mut self) -> BlockId {
+        if let Some(trap) = self.trap {
+            return trap;
+        }
+        let trap = self.block();
+        self.building(trap).term = Some(Terminator::Trap);
+        self.trap = Some(trap);
+        trap
+    }
+
+    /// Continue in `block`, unless nothing branches to it
+    fn enter(&mut self, block: BlockId) {
+        if !self.blocks[block.0 as usize].preds.is_empty() {
+            self.switch(block);
+        }
+    }
+
+    /// Continue in `block`
+    fn switch(&mut self, block: BlockId) {
+        if !self.order.contains(&block) {
+            self.order.push(block);
+        }
+        self.current = Some(block);
+    }
+
+    fn write(&mut self, var: Var, block: BlockId, reg: Reg) {
+        self.defs.insert((var, block), reg);
+    }
+
+    fn read(&mut self, var: Var, block: BlockId) -> Reg {
+        if let Some(&reg) = self.defs.get(&(var, block)) {
+            return reg;
+        }
+
+        let building = &self.blocks[block.0 as usize];
+        let reg = if !building.sealed {
+            let reg = self.phi(var, block);
+            self.building(block).incomplete.push((var, reg));
+            reg
+        } else if let [pred] = building.preds[..] {
+            self.read(var, pred)
+        } else {
+            // Written before reading the predecessors, so a loop finds the phi
+            let reg = self.phi(var, block);
+            self.write(var, block, reg);
+            self.fill_phi(var, block, reg);
+            reg
+        };
+        self.write(var, block, reg);
+        reg
+    }
+
+    /// Add an empty phi for `var` at the start of `block`
+    fn phi(&mut self, var: Var, block: BlockId) -> Reg {
+        let ty = self.var_types[&var];
+        let reg = self.reg();
+        let building = self.building(block);
+        let at = building
+            .insts
+            .iter()
+            .take_while(|inst| matches!(inst.op, Op::Phi(_)))
+            .count();
+        building.insts.insert(
+            at,
+            Inst {
+                dest: Some(reg),
+                ty,
+                op: Op::Phi(vec![]),
+            },
+        );
+        reg
+    }
+
+    fn fill_phi(&mut self, var: Var, block: BlockId, phi: Reg) {
+        let preds = self.blocks[block.0 as usize].preds.clone();
+        let incoming: Vec<(Reg, BlockId)> = preds
+            .into_iter()
+            .map(|pred| (self.read(var, pred), pred))
+            .collect();
+
+        let building = self.building(block);
+        for inst in &mut building.insts {
+            if inst.dest == Some(phi) {
+                inst.op = Op::Phi(incoming);
+                break;
+            }
+        }
+    }
+
+    /// Mark that every predecessor of `block` is known
+    fn seal(&mut self, block: BlockId) {
+        let incomplete = std::mem::take(&mut self.building(block).incomplete);
+        for (var, phi) in incomplete {
+            self.fill_phi(var, block, phi);
+        }
+        self.building(block).sealed = true;
+    }
+
+    /// Remove phis that pick the same value on every edge, then drop the
+    /// blocks nothing branches to and number what's left
+    fn finish(self, name: String, params: Vec<(Reg, Ty)>, ret: Ty) -> Proc {
+        let ProcBuilder {
+            mut blocks,
+            mut order,
+            trap,
+            ..
+        } = self;
+        // Printed last, out of the way
+        order.extend(trap);
+
+        let mut replaced: HashMap<Reg, Reg> = HashMap::new();
+        let resolve = |replaced: &HashMap<Reg, Reg>, mut reg: Reg| {
+            while let Some(&next) = replaced.get(&reg) {
+                reg = next;
+            }
+            reg
+        };
+        loop {
+            let mut changed = false;
+            for building in &mut blocks {
+                building.insts.retain(|inst| {
+                    let (Some(dest), Op::Phi(incoming)) = (inst.dest, &inst.op) else {
+                        return true;
+                    };
+                    let values: HashSet<Reg> = incoming
+                        .iter()
+                        .map(|&(reg, _)| resolve(&replaced, reg))
+                        .filter(|&reg| reg != dest)
+                        .collect();
+                    match values.into_iter().collect::<Vec<_>>()[..] {
+                        [value] => {
+                            replaced.insert(dest, value);
+                            changed = true;
+                            false
+                        }
+                        _ => true,
+                    }
+                });
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut blocks: Vec<Option<Building>> = blocks.into_iter().map(Some).collect();
+        let blocks = order
+            .into_iter()
+            .filter_map(|id| {
+                let building = blocks[id.0 as usize].take()?;
+                let mut block = Block {
+                    id,
+                    insts: building.insts,
+                    term: building.term?,
+                };
+                for inst in &mut block.insts {
+                    inst.map_uses(|reg| resolve(&replaced, reg));
+                }
+                block.term.map_uses(|reg| resolve(&replaced, reg));
+                Some(block)
+            })
+            .collect();
+
+        let mut proc = Proc {
+            name,
+            params,
+            ret,
+            blocks,
+        };
+        proc.renumber();
+        proc
+    }
+}
+
+/// Lowers a checked AST into an IR module
+pub struct Lowerer<'a> {
+    resolution: &'a Resolution,
+    types: &'a HashMap<NodeId, Type>,
+    /// Values of the constants, by the definition they were declared as
+    consts: HashMap<usize, Value>,
+    /// Globals by the definition they were declared as
+    globals: HashMap<usize, String>,
+    /// Symbols of the procs by their definition
+    procs: HashMap<usize, String>,
+    /// Tags of the variants of enums by their definition
+    tags: HashMap<usize, i32>,
+    builder: ProcBuilder,
+    /// Address the proc being lowered writes its result to, if it's kept in memory
+    result: Option<Reg>,
+    /// Locals standing for the results of case expressions, numbered after the definitions
+    next_var: Var,
+}
+
+impl<'a> Lowerer<'a> {
+    pub fn new(
+        resolution: &'a Resolution,
+        types: &'a HashMap<NodeId, Type>,
+        consts: &'a HashMap<NodeId, Value>,
+    ) -> Lowerer<'a> {
+        let consts = consts
+            .iter()
+            .filter_map(|(id, value)| Some((*resolution.names.get(id)?, value.clone())))
+            .collect();
+        Lowerer {
+            resolution,
+            types,
+            consts,
+            globals: HashMap::new(),
+            procs: HashMap::new(),
+            tags: HashMap::new(),
+            builder: ProcBuilder::default(),
+            result: None,
+            next_var: resolution.defs.len(),
+        }
+    }
+
+    fn unsupported(span: Span, what: &str) -> LowerError {
+        LowerError {
+            span,
+            message: format!("{} can't be lowered to IR yet", what),
+        }
+    }
+
+    /// Whether values of `type_` are kept in memory, registers holding their address
+    fn in_memory(&self, type_: &Type) -> bool {
+        match type_ {
+            Type::Array(_, _) | Type::Slice(_) => true,
+            Type::Named(_) => {
+                self.resolution.struct_def(type_).is_some()
+                    || self.resolution.is_tagged_union(type_)
+            }
+            _ => false,
+        }
+    }
+
+    /// IR type of the values of a Lune type
+    fn ty(&self, type_: &Type) -> Ty {
+        match type_ {
+            Type::Int => Ty::Int,
+            Type::Bool => Ty::Bool,
+            Type::String => Ty::Str,
+            Type::Void => Ty::Void,
+            _ if self.in_memory(type_) => Ty::Ptr,
+            // Enums without a payload are their tag
+            Type::Named(_) => Ty::Int,
+            _ => unreachable!("array lengths are evaluated before lowering"),
+        }
+    }
+
+    /// Bytes a value of `type_` takes in memory
+    fn size(&self, type_: &Type) -> u32 {
+        match type_ {
+            Type::Array(element, len) => self.size(element) * *len as u32,
+            Type::Slice(_) => 16,
+            Type::Named(_) => {
+                if let Some(def) = self.resolution.struct_def(type_) {
+                    return def.fields.iter().map(|(_, type_)| self.size(type_)).sum();
+                }
+                if !self.resolution.is_tagged_union(type_) {
+                    return 8;
+                }
+                let variants = self.resolution.variants(type_);
+                let payloads = variants.iter().map(|variant| {
+                    let fields = variant.fields.iter();
+                    fields.map(|(_, type_)| self.size(type_)).sum::<u32>()
+                });
+                8 + payloads.max().unwrap_or(0)
+            }
+            _ => 8,
+        }
+    }
+
+    /// Offset and type of the field `name` of the struct `type_`
+    fn field(&self, type_: &Type, name: &str) -> (u32, &'a Type) {
+        let def = self
+            .resolution
+            .struct_def(type_)
+            .expect("fields are accessed on structs");
+        let mut offset = 0;
+        for (field, type_) in &def.fields {
+            if field == name {
+                return (offset, type_);
+            }
+            offset += self.size(type_);
+        }
+        unreachable!("the checker made sure the field exists");
+    }
+
+    fn type_of(&self, ast: &AST, expr: ExprId) -> &'a Type {
+        let types = self.types;
+        &types[&ast[expr].id]
+    }
+
+    fn def(&self, name: &Name) -> usize {
+        self.resolution.names[&name.id]
+    }
+
+    pub fn lower(&mut self, ast: &AST) -> Result<Module, LowerError> {
+        // Every symbol shares one namespace, the entry proc takes a name no one else has
+        let mut symbols = HashSet::new();
+        let mut globals = vec![];
+        let mut procs = vec![];
+        for &id in &ast.root {
+            match &ast[id].kind {
+                StmtKind::ProcDeclaration(proc) => {
+                    symbols.insert(proc.name.value.clone());
+                    self.procs
+                        .insert(self.def(&proc.name), proc.name.value.clone());
+                    procs.push(proc);
+                }
+                StmtKind::EnumDeclaration(enum_) => {
+                    // Like C, variants without a value count up from the previous one
+                    let mut next = 0;
+                    for variant in &enum_.variants {
+                        let tag = variant.value.unwrap_or(next) as i32;
+                        self.tags.insert(self.def(&variant.name), tag);
+                        next = tag as i128 + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        for &id in &ast.root {
+            if let StmtKind::VarDeclaration(name, type_, _) = &ast[id].kind {
+                let ty = self.ty(type_);
+                let size = match ty {
+                    Ty::Ptr => self.size(type_),
+                    _ => 0,
+                };
+                let symbol = unique(&symbols, &name.value);
+                symbols.insert(symbol.clone());
+                self.globals.insert(self.def(name), symbol.clone());
+                globals.push(Global {
+                    name: symbol,
+                    ty,
+                    size,
+                });
+            }
+        }
+
+        let mut module = Module {
+            entry: unique(&symbols, "main"),
+            globals,
+            procs: vec![],
+        };
+        for proc in procs {
+            module.procs.push(self.proc(ast, proc)?);
+        }
+
+        self.builder = ProcBuilder::default();
+        self.result = None;
+        let start = self.builder.block();
+        self.builder.seal(start);
+        self.builder.switch(start);
+        // Globals start out zeroed, which isn't a string, so a string global
+        // read before its initializer runs is empty like in the VM
+        let strings: Vec<String> = module
+            .globals
+            .iter()
+            .filter(|global| global.ty == Ty::Str)
+            .map(|global| global.name.clone())
+            .collect();
+        if !strings.is_empty() {
+            let empty = self
+                .builder
+                .push(Ty::Str, Op::Const(Const::Str(String::new())));
+            for global in strings {
+                self.builder.push_inst(Inst {
+                    dest: None,
+                    ty: Ty::Str,
+                    op: Op::Store(global, empty),
+                });
+            }
+        }
+        self.block(ast, &ast.root)?;
+        if self.builder.current.is_some() {
+            self.builder.terminate(Terminator::Ret(None));
+        }
+        let builder = std::mem::take(&mut self.builder);
+        let entry = builder.finish(module.entry.clone(), vec![], Ty::Void);
+        module.procs.push(entry);
+
+        Ok(module)
+    }
+
+    fn proc(&mut self, ast: &AST, proc: &ast::Proc) -> Result<super::Proc, LowerError> {
+        self.builder = ProcBuilder::default();
+        let start = self.builder.block();
+        self.builder.seal(start);
+        self.builder.switch(start);
+
+        let mut params = vec![];
+        self.result = None;
+        if self.in_memory(&proc.return_type) {
+            let reg = self.builder.reg();
+            params.push((reg, Ty::Ptr));
+            self.result = Some(reg);
+        }
+        for param in &proc.params {
+            let ty = self.ty(&param.type_);
+            let reg = self.builder.reg();
+            let var = self.def(&param.name);
+            self.builder.var_types.insert(var, ty);
+            self.builder.write(var, start, reg);
+            params.push((reg, ty));
+        }
+        let ret = match self.result {
+            Some(_) => Ty::Void,
+            None => self.ty(&proc.return_type),
+        };
+
+        self.block(ast, &proc.body)?;
+        if self.builder.current.is_some() {
+            // The checker makes sure procs returning a value always do
+            let term = match proc.return_type {
+                Type::Void => Terminator::Ret(None),
+                _ => Terminator::Unreachable,
+            };
+            self.builder.terminate(term);
+        }
+
+        let builder = std::mem::take(&mut self.builder);
+        Ok(builder.finish(proc.name.value.clone(), params, ret))
+    }
+
+    fn block(&mut self, ast: &AST, stmts: &[StmtId]) -> Result<(), LowerError> {
+        for &stmt in stmts {
+            // Statements after a return can never run
+            if self.builder.current.is_none() {
+                break;
+            }
+            self.stmt(ast, stmt)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, ast: &AST, id: StmtId) -> Result<(), LowerError> {
+        match &ast[id].kind {
+            StmtKind::Expr(expr) => {
+                if let ExprKind::Call(_, _) = ast[*expr].kind {
+                    self.call(ast, *expr)?;
+                } else {
+                    self.expr(ast, *expr)?;
+                }
+            }
+            StmtKind::VarDeclaration(name, type_, value) => {
+                let var = self.def(name);
+                match self.globals.get(&var).cloned() {
+                    Some(global) if self.in_memory(type_) => {
+                        let value = self.place(ast, *value)?;
+                        let address = self.builder.push(Ty::Ptr, Op::Addr(global));
+                        self.store_at(type_, value, address);
+                    }
+                    Some(global) => {
+                        let value = self.expr(ast, *value)?;
+                        self.builder.push_inst(Inst {
+                            dest: None,
+                            ty: self.ty(type_),
+                            op: Op::Store(global, value),
+                        });
+                    }
+                    // A copy, so the slot belongs to the variable
+                    None => {
+                        let value = self.expr(ast, *value)?;
+                        self.assign(var, self.ty(type_), value);
+                    }
+                }
+            }
+            // Uses of constants are replaced by their value
+            StmtKind::ConstDeclaration(_, _, _) => {}
+            StmtKind::Assignment(target, value) => {
+                let type_ = self.type_of(ast, *target);
+                match &ast[*target].kind {
+                    ExprKind::Variable(name) if !self.in_memory(type_) => {
+                        let value = self.expr(ast, *value)?;
+                        let var = self.def(name);
+                        match self.globals.get(&var) {
+                            Some(global) => {
+                                self.builder.push_inst(Inst {
+                                    dest: None,
+                                    ty: self.ty(type_),
+                                    op: Op::Store(global.clone(), value),
+                                });
+                            }
+                            None => self.assign(var, self.ty(type_), value),
+                        }
+                    }
+                    // Like the VM, the value is lowered before the index is checked
+                    ExprKind::Index(object, index) => {
+                        let (data, len, size) = self.elements(ast, *object)?;
+                        let index = self.expr(ast, *index)?;
+                        let value = self.place(ast, *value)?;
+                        let address = self.element(data, len, index, size);
+                        self.store_at(type_, value, address);
+                    }
+                    _ => {
+                        let address = self.address(ast, *target)?;
+                        let value = self.place(ast, *value)?;
+                        self.store_at(type_, value, address);
+                    }
+                }
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                let cond = self.expr(ast, *cond)?;
+                let then_block = self.builder.block();
+                let join = self.builder.block();
+                let else_block = match otherwise {
+                    Some(_) => self.builder.block(),
+                    None => join,
+                };
+                self.builder
+                    .terminate(Terminator::CondBr(cond, then_block, else_block));
+                self.builder.seal(then_block);
+
+                self.builder.switch(then_block);
+                self.block(ast, then)?;
+                self.branch(join);
+
+                if let Some(otherwise) = otherwise {
+                    self.builder.seal(else_block);
+                    self.builder.switch(else_block);
+                    self.block(ast, otherwise)?;
+                    self.branch(join);
+                }
+                self.builder.seal(join);
+                self.builder.enter(join);
+            }
+            StmtKind::While(cond, body) => {
+                let header = self.builder.block();
+                let body_block = self.builder.block();
+                let exit = self.builder.block();
+                self.builder.terminate(Terminator::Br(header));
+
+                // The header stays unsealed until the body branches back to it
+                self.builder.switch(header);
+                let cond = self.expr(ast, *cond)?;
+                self.builder
+                    .terminate(Terminator::CondBr(cond, body_block, exit));
+                self.builder.seal(body_block);
+
+                self.builder.switch(body_block);
+                self.block(ast, body)?;
+                self.branch(header);
+                self.builder.seal(header);
+                self.builder.seal(exit);
+                self.builder.enter(exit);
+            }
+            StmtKind::Return(value) => {
+                let value = match (value, self.result) {
+                    (Some(value), Some(result)) => {
+                        let type_ = self.type_of(ast, *value);
+                        let value = self.place(ast, *value)?;
+                        self.store_at(type_, value, result);
+                        None
+                    }
+                    (Some(value), None) => Some(self.expr(ast, *value)?),
+                    (None, _) => None,
+                };
+                self.builder.terminate(Terminator::Ret(value));
+            }
+            StmtKind::Case(scrutinee, arms) => {
+                let (scrutinee, subject, ty) = self.scrutinee(ast, *scrutinee)?;
+                let join = self.builder.block();
+                for arm in arms {
+                    let (body, next) = self.pattern(subject, ty, &arm.pattern)?;
+                    self.builder.switch(body);
+                    self.bind(scrutinee, &arm.pattern);
+                    self.block(ast, &arm.body)?;
+                    self.branch(join);
+                    match next {
+                        Some(next) => self.builder.switch(next),
+                        None => break,
+                    }
+                }
+                // Patterns are exhaustive, so the last test never fails
+                if self.builder.current.is_some() {
+                    self.builder.terminate(Terminator::Unreachable);
+                }
+                self.builder.seal(join);
+                self.builder.enter(join);
+            }
+            StmtKind::ProcDeclaration(_)
+            | StmtKind::StructDeclaration(_)
+            | StmtKind::EnumDeclaration(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Write `value` to a local
+    fn assign(&mut self, var: Var, ty: Ty, value: Reg) {
+        self.builder.var_types.insert(var, ty);
+        let current = self
+            .builder
+            .current
+            .expect("assignments are only lowered in reachable blocks");
+        self.builder.write(var, current, value);
+    }
+
+    /// Branch to `target`, unless control already left the current block
+    fn branch(&mut self, target: BlockId) {
+        if self.builder.current.is_some() {
+            self.builder.terminate(Terminator::Br(target));
+        }
+    }
+
+    /// Go on if `ok` is true, else trap
+    fn check(&mut self, ok: Reg) {
+        let next = self.builder.block();
+        let trap = self.builder.trap();
+        self.builder.terminate(Terminator::CondBr(ok, next, trap));
+        self.builder.seal(next);
+        self.builder.switch(next);
+    }
+
+    /// Address `offset` bytes past `address`
+    fn offset(&mut self, address: Reg, offset: u32) -> Reg {
+        if offset == 0 {
+            return address;
+        }
+        self.builder.push(Ty::Ptr, Op::Offset(address, offset))
+    }
+
+    /// A new slot for a value of `type_`
+    fn alloca(&mut self, type_: &Type) -> Reg {
+        let size = self.size(type_);
+        self.builder.push(Ty::Ptr, Op::Alloca(size))
+    }
+
+    /// Write `value` of type `type_` to `address`, copying it there if it's
+    /// kept in memory
+    fn store_at(&mut self, type_: &Type, value: Reg, address: Reg) {
+        let (ty, op) = match self.in_memory(type_) {
+            true => (Ty::Ptr, Op::MemCopy(address, value, self.size(type_))),
+            false => (self.ty(type_), Op::Write(value, address)),
+        };
+        self.builder.push_inst(Inst { dest: None, ty, op });
+    }
+
+    /// The value of type `type_` at `address`, which is the address itself
+    /// if it's kept in memory
+    fn load_at(&mut self, type_: &Type, address: Reg) -> Reg {
+        match self.in_memory(type_) {
+            true => address,
+            false => self.builder.push(self.ty(type_), Op::Read(address)),
+        }
+    }
+
+    /// Lower the scrutinee of a case, returning it and what patterns test,
+    /// which for a tagged union is its tag, and the type of that
+    fn scrutinee(&mut self, ast: &AST, id: ExprId) -> Result<(Reg, Reg, Ty), LowerError> {
+        let type_ = self.type_of(ast, id);
+        let value = self.place(ast, id)?;
+        if self.in_memory(type_) {
+            let tag = self.builder.push(Ty::Int, Op::Read(value));
+            return Ok((value, tag, Ty::Int));
+        }
+        Ok((value, value, self.ty(type_)))
+    }
+
+    /// Test `scrutinee` against `pattern`, returning the sealed blocks to run
+    /// when it matches and when it doesn't, unless it matches everything
+    fn pattern(
+        &mut self,
+        scrutinee: Reg,
+        ty: Ty,
+        pattern: &Pattern,
+    ) -> Result<(BlockId, Option<BlockId>), LowerError> {
+        let matched = self.builder.block();
+        let cond = match &pattern.kind {
+            PatternKind::Wildcard => {
+                self.builder.terminate(Terminator::Br(matched));
+                self.builder.seal(matched);
+                return Ok((matched, None));
+            }
+            PatternKind::Int(n) => {
+                let n = self.int(*n);
+                self.builder
+                    .push(Ty::Int, Op::Binary(BinOpKind::Eq, scrutinee, n))
+            }
+            PatternKind::Str(s) => {
+                let s = self.builder.push(Ty::Str, Op::Const(Const::Str(s.clone())));
+                self.builder
+                    .push(ty, Op::Binary(BinOpKind::Eq, scrutinee, s))
+            }
+            PatternKind::Range(start, end) => {
+                let start = self.int(*start);
+                let above = self
+                    .builder
+                    .push(Ty::Int, Op::Binary(BinOpKind::Ge, scrutinee, start));
+                let upper = self.builder.block();
+                let failed = self.builder.block();
+                self.builder
+                    .terminate(Terminator::CondBr(above, upper, failed));
+                self.builder.seal(upper);
+
+                self.builder.switch(upper);
+                let end = self.int(*end);
+                let below = self
+                    .builder
+                    .push(Ty::Int, Op::Binary(BinOpKind::Le, scrutinee, end));
+                self.builder
+                    .terminate(Terminator::CondBr(below, matched, failed));
+                self.builder.seal(matched);
+                self.builder.seal(failed);
+                return Ok((matched, Some(failed)));
+            }
+            PatternKind::Variant(name, _) => {
+                let tag = self.tags[&self.def(name)];
+                let tag = self.builder.push(Ty::Int, Op::Const(Const::Int(tag)));
+                self.builder
+                    .push(Ty::Int, Op::Binary(BinOpKind::Eq, scrutinee, tag))
+            }
+        };
+
+        let failed = self.builder.block();
+        self.builder
+            .terminate(Terminator::CondBr(cond, matched, failed));
+        self.builder.seal(matched);
+        self.builder.seal(failed);
+        Ok((matched, Some(failed)))
+    }
+
+    /// Give the names `pattern` binds the payload of the tagged union at
+    /// `scrutinee`, copying what's kept in memory
+    fn bind(&mut self, scrutinee: Reg, pattern: &Pattern) {
+        let PatternKind::Variant(name, bindings) = &pattern.kind else {
+            return;
+        };
+        let resolution = self.resolution;
+        let variant = &resolution.defs[self.def(name)];
+        let mut offset = 8;
+        for (binding, (_, type_)) in bindings.iter().zip(&variant.fields) {
+            // `_` binds nothing
+            if let Some(&var) = resolution.names.get(&binding.id) {
+                let address = self.offset(scrutinee, offset);
+                let value = match self.in_memory(type_) {
+                    true => self.copy(type_, address),
+                    false => self.load_at(type_, address),
+                };
+                self.assign(var, self.ty(type_), value);
+            }
+            offset += self.size(type_);
+        }
+    }
+
+    /// An int from a pattern, the checker made sure it fits
+    fn int(&mut self, n: i128) -> Reg {
+        self.builder.push(Ty::Int, Op::Const(Const::Int(n as i32)))
+    }
+
+    /// Lower a call, returning the register holding its result unless it's void
+    fn call(&mut self, ast: &AST, id: ExprId) -> Result<Option<Reg>, LowerError> {
+        let ExprKind::Call(name, args) = &ast[id].kind else {
+            unreachable!("only calls are lowered as calls");
+        };
+        let def = self.def(name);
+        match self.resolution.defs[def].kind {
+            DefKind::Builtin if name.value == "print" => {
+                let value = self.expr(ast, args[0])?;
+                let ty = self.ty(self.type_of(ast, args[0]));
+                self.builder.push_inst(Inst {
+                    dest: None,
+                    ty,
+                    op: Op::Print(value),
+                });
+                Ok(None)
+            }
+            DefKind::Builtin if name.value == "len" => {
+                let (_, len, _) = self.elements(ast, args[0])?;
+                Ok(Some(len))
+            }
+            DefKind::Variant => Ok(Some(self.variant(ast, name, args)?)),
+            DefKind::Proc => {
+                let type_ = self.type_of(ast, id);
+                let result = match self.in_memory(type_) {
+                    true => Some(self.alloca(type_)),
+                    false => None,
+                };
+                let mut regs: Vec<Reg> = result.into_iter().collect();
+                for &arg in args {
+                    regs.push(self.expr(ast, arg)?);
+                }
+
+                let ty = match result {
+                    Some(_) => Ty::Void,
+                    None => self.ty(type_),
+                };
+                let op = Op::Call(self.procs[&def].clone(), regs);
+                if ty == Ty::Void {
+                    self.builder.push_inst(Inst { dest: None, ty, op });
+                    return Ok(result);
+                }
+                Ok(Some(self.builder.push(ty, op)))
+            }
+            _ => Err(Self::unsupported(name.span, &format!("`{}`", name.value))),
+        }
+    }
+
+    /// Construct the variant `name` with the payload `args`
+    fn variant(&mut self, ast: &AST, name: &Name, args: &[ExprId]) -> Result<Reg, LowerError> {
+        let def = &self.resolution.defs[self.def(name)];
+        let tag = self.tags[&self.def(name)];
+        let tag = self.builder.push(Ty::Int, Op::Const(Const::Int(tag)));
+        if !self.in_memory(&def.type_) {
+            return Ok(tag);
+        }
+
+        let value = self.alloca(&def.type_);
+        self.builder.push_inst(Inst {
+            dest: None,
+            ty: Ty::Int,
+            op: Op::Write(tag, value),
+        });
+        let mut offset = 8;
+        for (&arg, (_, type_)) in args.iter().zip(&def.fields) {
+            let arg = self.place(ast, arg)?;
+            let address = self.offset(value, offset);
+            self.store_at(type_, arg, address);
+            offset += self.size(type_);
+        }
+        Ok(value)
+    }
+
+    /// Lower the array or slice `object`, returning the address of its first
+    /// element, its length and the size of an element
+    fn elements(&mut self, ast: &AST, object: ExprId) -> Result<(Reg, Reg, u32), LowerError> {
+        let type_ = self.type_of(ast, object);
+        let address = self.place(ast, object)?;
+        let size = self.size(
+            type_
+                .element()
+                .expect("only arrays and slices have elements"),
+        );
+        if let Type::Array(_, len) = type_ {
+            let len = self
+                .builder
+                .push(Ty::Int, Op::Const(Const::Int(*len as i32)));
+            return Ok((address, len, size));
+        }
+        let data = self.builder.push(Ty::Ptr, Op::Read(address));
+        let len = self.offset(address, 8);
+        let len = self.builder.push(Ty::Int, Op::Read(len));
+        Ok((data, len, size))
+    }
+
+    /// Address of the element at `index`, trapping unless it's in bounds
+    fn element(&mut self, data: Reg, len: Reg, index: Reg, size: u32) -> Reg {
+        let zero = self.builder.push(Ty::Int, Op::Const(Const::Int(0)));
+        let above = self
+            .builder
+            .push(Ty::Int, Op::Binary(BinOpKind::Ge, index, zero));
+        self.check(above);
+        let below = self
+            .builder
+            .push(Ty::Int, Op::Binary(BinOpKind::Lt, index, len));
+        self.check(below);
+        self.builder.push(Ty::Ptr, Op::Index(data, index, size))
+    }
+
+    /// Address of the field or element `id` names, or of the value `id`
+    /// lowers to if it's kept in memory
+    fn address(&mut self, ast: &AST, id: ExprId) -> Result<Reg, LowerError> {
+        match &ast[id].kind {
+            ExprKind::FieldAccess(object, field) => {
+                let (offset, _) = self.field(self.type_of(ast, *object), &field.value);
+                let object = self.place(ast, *object)?;
+                Ok(self.offset(object, offset))
+            }
+            ExprKind::Index(object, index) => {
+                let (data, len, size) = self.elements(ast, *object)?;
+                let index = self.expr(ast, *index)?;
+                Ok(self.element(data, len, index, size))
+            }
+            _ => self.place(ast, id),
+        }
+    }
+
+    /// Copy of the value of type `type_` at `address` in a new slot
+    fn copy(&mut self, type_: &Type, address: Reg) -> Reg {
+        let slot = self.alloca(type_);
+        self.store_at(type_, address, slot);
+        slot
+    }
+
+    /// Lower `id` as a value, copying what's kept in memory out of the place
+    /// it names so the result belongs to the caller
+    fn expr(&mut self, ast: &AST, id: ExprId) -> Result<Reg, LowerError> {
+        let reg = self.place(ast, id)?;
+        let is_place = match &ast[id].kind {
+            ExprKind::Variable(name) => {
+                self.resolution.defs[self.def(name)].kind != DefKind::Variant
+            }
+            ExprKind::FieldAccess(_, _) | ExprKind::Index(_, _) => true,
+            _ => false,
+        };
+        let type_ = self.type_of(ast, id);
+        if is_place && self.in_memory(type_) {
+            return Ok(self.copy(type_, reg));
+        }
+        Ok(reg)
+    }
+
+    /// Lower `id` without copying what it reads, for values kept in memory
+    /// the result is the address of where they are
+    fn place(&mut self, ast: &AST, id: ExprId) -> Result<Reg, LowerError> {
+        let expr = &ast[id];
+        let reg = match &expr.kind {
+            ExprKind::IntLit(n) => self.builder.push(Ty::Int, Op::Const(Const::Int(*n as i32))),
+            ExprKind::StringLit(s) => self.builder.push(Ty::Str, Op::Const(Const::Str(s.clone()))),
+            ExprKind::BoolLit(b) => self.builder.push(Ty::Bool, Op::Const(Const::Bool(*b))),
+            ExprKind::Variable(name) => {
+                let def = self.def(name);
+                let type_ = self.type_of(ast, id);
+                if let Some(value) = self.consts.get(&def) {
+                    let (ty, value) = match value {
+                        Value::Int(n) => (Ty::Int, Const::Int(*n)),
+                        Value::Str(s) => (Ty::Str, Const::Str(s.clone())),
+                        Value::Bool(b) => (Ty::Bool, Const::Bool(*b)),
+                    };
+                    self.builder.push(ty, Op::Const(value))
+                } else if self.resolution.defs[def].kind == DefKind::Variant {
+                    self.variant(ast, name, &[])?
+                } else if let Some(global) = self.globals.get(&def) {
+                    let op = match self.in_memory(type_) {
+                        true => Op::Addr(global.clone()),
+                        false => Op::Load(global.clone()),
+                    };
+                    self.builder.push(self.ty(type_), op)
+                } else {
+                    let current = self
+                        .builder
+                        .current
+                        .expect("expressions are only lowered in reachable blocks");
+                    self.builder.read(def, current)
+                }
+            }
+            ExprKind::Call(_, _) => match self.call(ast, id)? {
+                Some(reg) => reg,
+                None => {
+                    return Err(Self::unsupported(
+                        expr.span,
+                        "using the result of a void proc",
+                    ))
+                }
+            },
+            // The negated literal may be too big to be an int on its own
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs)
+                if matches!(ast[*rhs].kind, ExprKind::IntLit(_)) =>
+            {
+                let ExprKind::IntLit(n) = ast[*rhs].kind else {
+                    unreachable!();
+                };
+                self.builder
+                    .push(Ty::Int, Op::Const(Const::Int((-(n as i64)) as i32)))
+            }
+            ExprKind::UnaryOp(op, rhs) => {
+                let ty = self.ty(self.type_of(ast, id));
+                let rhs = self.expr(ast, *rhs)?;
+                self.builder.push(ty, Op::Unary(*op, rhs))
+            }
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let ty = self.ty(self.type_of(ast, *lhs));
+                let lhs = self.expr(ast, *lhs)?;
+                let rhs = self.expr(ast, *rhs)?;
+                self.builder.push(ty, Op::Binary(*op, lhs, rhs))
+            }
+            ExprKind::Case(scrutinee, arms) => {
+                let ty = self.ty(self.type_of(ast, id));
+                let (scrutinee, subject, subject_ty) = self.scrutinee(ast, *scrutinee)?;
+
+                // The result is a local written by every arm, so the join gets a phi
+                let var = self.next_var;
+                self.next_var += 1;
+                self.builder.var_types.insert(var, ty);
+
+                let join = self.builder.block();
+                for arm in arms {
+                    let (body, next) = self.pattern(subject, subject_ty, &arm.pattern)?;
+                    self.builder.switch(body);
+                    self.bind(scrutinee, &arm.pattern);
+                    let value = self.expr(ast, arm.body)?;
+                    let end = self.builder.current.expect("expressions don't return");
+                    self.builder.write(var, end, value);
+                    self.branch(join);
+                    match next {
+                        Some(next) => self.builder.switch(next),
+                        None => break,
+                    }
+                }
+                if self.builder.current.is_some() {
+                    self.builder.terminate(Terminator::Unreachable);
+                }
+                self.builder.seal(join);
+                self.builder.switch(join);
+                self.builder.read(var, join)
+            }
+            // Fields are written in declaration order, whatever order the literal has
+            ExprKind::StructLit(_, fields) => {
+                let type_ = self.type_of(ast, id);
+                let def = self
+                    .resolution
+                    .struct_def(type_)
+                    .expect("struct literals have a struct type");
+                let value = self.alloca(type_);
+                let mut offset = 0;
+                for (field, type_) in &def.fields {
+                    let (_, field) = fields
+                        .iter()
+                        .find(|(name, _)| name.value == *field)
+                        .unwrap();
+                    let field = self.place(ast, *field)?;
+                    let address = self.offset(value, offset);
+                    self.store_at(type_, field, address);
+                    offset += self.size(type_);
+                }
+                value
+            }
+            ExprKind::ArrayLit(elements) => {
+                let type_ = self.type_of(ast, id);
+                let element_type = type_.element().expect("array literals have an array type");
+                let size = self.size(element_type);
+                let value = self.alloca(type_);
+                for (i, &element) in elements.iter().enumerate() {
+                    let element = self.place(ast, element)?;
+                    let address = self.offset(value, i as u32 * size);
+                    self.store_at(element_type, element, address);
+                }
+                value
+            }
+            ExprKind::FieldAccess(_, _) | ExprKind::Index(_, _) => {
+                let address = self.address(ast, id)?;
+                self.load_at(self.type_of(ast, id), address)
+            }
+            // Checks 0 <= start <= end <= len after lowering the bounds, like the VM
+            ExprKind::Slice(object, start, end) => {
+                let (data, len, size) = self.elements(ast, *object)?;
+                let zero = self.builder.push(Ty::Int, Op::Const(Const::Int(0)));
+                let start = match start {
+                    Some(start) => self.expr(ast, *start)?,
+                    None => zero,
+                };
+                let end = match end {
+                    Some(end) => self.expr(ast, *end)?,
+                    None => len,
+                };
+                for (low, high) in [(zero, start), (start, end), (end, len)] {
+                    let ok = self
+                        .builder
+                        .push(Ty::Int, Op::Binary(BinOpKind::Le, low, high));
+                    self.check(ok);
+                }
+
+                let first = self.builder.push(Ty::Ptr, Op::Index(data, start, size));
+                let len = self
+                    .builder
+                    .push(Ty::Int, Op::Binary(BinOpKind::Sub, end, start));
+                let slice = self.alloca(self.type_of(ast, id));
+                let at = self.offset(slice, 8);
+                for (ty, value, address) in [(Ty::Ptr, first, slice), (Ty::Int, len, at)] {
+                    self.builder.push_inst(Inst {
+                        dest: None,
+                        ty,
+                        op: Op::Write(value, address),
+                    });
+                }
+                slice
+            }
+        };
+        Ok(reg)
+    }
+}
+
+/// `name`, or `name_1`, `name_2` and so on if it's taken
+fn unique(taken: &HashSet<String>, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 0;
+    while taken.contains(&candidate) {
+        n += 1;
+        candidate = format!("{}_{}", name, n);
+    }
+    candidate
+}
@@ -0,0 +1,248 @@
+//! Optimizations over the IR.
+//!
+//! Each pass rewrites a module in place and reports whether it changed
+//! anything. The pass manager runs a list of passes, checking the module
+//! after each one, and can repeat the list until none of them make progress.
+//!
+//! `-O1` cleans up what lowering leaves behind, `-O2` also inlines small
+//! procs and removes repeated computations.
+
+use std::collections::{HashMap, HashSet};
+
+use super::verify::verify;
+use super::*;
+use crate::errors::VerifyError;
+
+// Folding operators on known values and branches on known conditions
+pub mod constprop;
+
+// Replacing copies with what they copy
+pub mod copyprop;
+
+// Removing instructions whose results are never used
+pub mod dce;
+
+// Reusing values computed by a dominating instruction
+pub mod cse;
+
+// Inlining calls to small procs
+pub mod inline;
+
+// Removing unreachable blocks and merging straight line code
+pub mod simplify_cfg;
+
+/// A transformation of a module
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// Rewrite `module`, returning whether anything changed
+    fn run(&mut self, module: &mut Module) -> bool;
+}
+
+/// Names accepted by `--passes`
+pub const PASSES: [&str; 6] = [
+    "constprop",
+    "copyprop",
+    "dce",
+    "cse",
+    "inline",
+    "simplify-cfg",
+];
+
+/// The pass called `name`
+pub fn pass(name: &str) -> Option<Box<dyn Pass>> {
+    let pass: Box<dyn Pass> = match name {
+        "constprop" => Box::new(constprop::ConstProp),
+        "copyprop" => Box::new(copyprop::CopyProp),
+        "dce" => Box::new(dce::Dce),
+        "cse" => Box::new(cse::Cse),
+        "inline" => Box::new(inline::Inline::default()),
+        "simplify-cfg" => Box::new(simplify_cfg::SimplifyCfg),
+        _ => return None,
+    };
+    Some(pass)
+}
+
+/// Runs passes over a module
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    /// How many times the passes may be run before giving up on reaching a
+    /// point where none of them change anything
+    rounds: usize,
+}
+
+impl PassManager {
+    pub fn new() -> PassManager {
+        PassManager {
+            passes: vec![],
+            rounds: 1,
+        }
+    }
+
+    /// The passes of an optimization level, 0 to 2
+    pub fn for_level(level: u8) -> PassManager {
+        let (names, rounds): (&[&str], usize) = match level {
+            0 => (&[], 1),
+            1 => (
+                &[
+                    "simplify-cfg",
+                    "constprop",
+                    "copyprop",
+                    "dce",
+                    "simplify-cfg",
+                ],
+                1,
+            ),
+            _ => (
+                &[
+                    "inline",
+                    "simplify-cfg",
+                    "constprop",
+                    "copyprop",
+                    "cse",
+                    "dce",
+                ],
+                10,
+            ),
+        };
+        let mut manager = PassManager::new().rounds(rounds);
+        for name in names {
+            manager.add(pass(name).unwrap());
+        }
+        manager
+    }
+
+    /// Run the passes called `names` once each, in order
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<PassManager, String> {
+        let mut manager = PassManager::new();
+        for name in names {
+            let pass = pass(name).ok_or_else(|| {
+                format!(
+                    "unknown pass `{}`, expected one of {}",
+                    name,
+                    PASSES.join(", ")
+                )
+            })?;
+            manager.add(pass);
+        }
+        Ok(manager)
+    }
+
+    pub fn add(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn rounds(mut self, rounds: usize) -> PassManager {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Run the passes over `module`, returning whether anything changed.
+    /// Stops at the first pass that leaves the module invalid.
+    pub fn run(&mut self, module: &mut Module) -> Result<bool, VerifyError> {
+        let mut changed = false;
+        for _ in 0..self.rounds {
+            let mut round = false;
+            for pass in &mut self.passes {
+                if pass.run(module) {
+                    verify(module).map_err(|err| VerifyError {
+                        proc: err.proc,
+                        message: format!(
+                            "pass `{}` produced invalid IR: {}",
+                            pass.name(),
+                            err.message
+                        ),
+                    })?;
+                    round = true;
+                }
+            }
+            changed |= round;
+            if !round {
+                break;
+            }
+        }
+        if changed {
+            for proc in &mut module.procs {
+                proc.renumber();
+            }
+        }
+        Ok(changed)
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> PassManager {
+        PassManager::new()
+    }
+}
+
+/// Replace every use of a key of `replaced` with its value, following
+/// chains of replacements
+fn replace_uses(proc: &mut Proc, replaced: &HashMap<Reg, Reg>) {
+    let resolve = |mut reg: Reg| {
+        while let Some(&next) = replaced.get(&reg) {
+            reg = next;
+        }
+        reg
+    };
+    for block in &mut proc.blocks {
+        for inst in &mut block.insts {
+            inst.map_uses(resolve);
+        }
+        block.term.map_uses(resolve);
+    }
+}
+
+/// Remove the blocks that can't be reached from the first one, and their
+/// edges from the phis of the blocks that can
+fn remove_unreachable(proc: &mut Proc) -> bool {
+    let reachable: HashSet<BlockId> = proc.reverse_postorder().into_iter().collect();
+    if reachable.len() == proc.blocks.len() {
+        return false;
+    }
+    let (kept, removed): (Vec<Block>, Vec<Block>) = proc
+        .blocks
+        .drain(..)
+        .partition(|block| reachable.contains(&block.id));
+    proc.blocks = kept;
+    for dead in removed {
+        for succ in dead.term.successors() {
+            if let Some(block) = proc.block_mut(succ) {
+                remove_incoming(block, dead.id);
+            }
+        }
+    }
+    true
+}
+
+/// Remove one edge from `pred` from the phis of `block`
+fn remove_incoming(block: &mut Block, pred: BlockId) {
+    for inst in &mut block.insts {
+        if let Op::Phi(incoming) = &mut inst.op {
+            if let Some(i) = incoming.iter().position(|&(_, p)| p == pred) {
+                incoming.remove(i);
+            }
+        }
+    }
+}
+
+/// Make the phis of `block` name `to` where they named `from`
+fn rename_incoming(block: &mut Block, from: BlockId, to: BlockId) {
+    for inst in &mut block.insts {
+        if let Op::Phi(incoming) = &mut inst.op {
+            for (_, pred) in incoming {
+                if *pred == from {
+                    *pred = to;
+                }
+            }
+        }
+    }
+}
+
+/// Whether `block` starts with a phi
+fn has_phis(block: &Block) -> bool {
+    block
+        .insts
+        .first()
+        .is_some_and(|inst| matches!(inst.op, Op::Phi(_)))
+}
@@ -0,0 +1,120 @@
+//! Constant propagation.
+//!
+//! Operators whose operands are constants become constants, with the same
+//! wrapping semantics as at run time. A division that would trap is left
+//! alone. Phis whose values are all the same constant are folded too, and a
+//! conditional branch on a constant becomes a plain branch. The blocks that
+//! can then no longer be reached are removed, so that later passes never
+//! see a loop cut off from the entry.
+
+use std::collections::HashMap;
+
+use super::*;
+
+pub struct ConstProp;
+
+impl Pass for ConstProp {
+    fn name(&self) -> &'static str {
+        "constprop"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        let mut changed = false;
+        for proc in &mut module.procs {
+            changed |= propagate(proc);
+        }
+        changed
+    }
+}
+
+fn propagate(proc: &mut Proc) -> bool {
+    let mut changed = false;
+    let mut consts: HashMap<Reg, Const> = HashMap::new();
+    let mut pruned = vec![];
+
+    for id in proc.reverse_postorder() {
+        let block = proc.block_mut(id).unwrap();
+        for inst in &mut block.insts {
+            if let (Some(dest), Op::Const(value)) = (inst.dest, &inst.op) {
+                consts.insert(dest, value.clone());
+                continue;
+            }
+            let Some(value) = fold(&inst.op, &consts) else {
+                continue;
+            };
+            inst.ty = inst.result_type();
+            inst.op = Op::Const(value.clone());
+            consts.insert(inst.dest.unwrap(), value);
+            changed = true;
+        }
+        // Folded phis may now come before ones that weren't
+        block
+            .insts
+            .sort_by_key(|inst| !matches!(inst.op, Op::Phi(_)));
+
+        if let Terminator::CondBr(cond, then, otherwise) = block.term {
+            if let Some(Const::Bool(cond)) = consts.get(&cond) {
+                let (taken, dropped) = if *cond {
+                    (then, otherwise)
+                } else {
+                    (otherwise, then)
+                };
+                block.term = Terminator::Br(taken);
+                pruned.push((id, dropped));
+                changed = true;
+            }
+        }
+    }
+
+    for (pred, dropped) in pruned {
+        if let Some(block) = proc.block_mut(dropped) {
+            remove_incoming(block, pred);
+        }
+    }
+    remove_unreachable(proc);
+    changed
+}
+
+/// The value of `op` if its operands are known
+fn fold(op: &Op, consts: &HashMap<Reg, Const>) -> Option<Const> {
+    match op {
+        Op::Unary(op, rhs) => match (op, consts.get(rhs)?) {
+            (UnaryOpKind::Neg, Const::Int(n)) => Some(Const::Int(n.wrapping_neg())),
+            (UnaryOpKind::Not, Const::Bool(b)) => Some(Const::Bool(!b)),
+            _ => None,
+        },
+        Op::Binary(op, lhs, rhs) => binary(*op, consts.get(lhs)?, consts.get(rhs)?),
+        Op::Copy(value) => consts.get(value).cloned(),
+        Op::Phi(incoming) => {
+            let (first, rest) = incoming.split_first()?;
+            let value = consts.get(&first.0)?;
+            rest.iter()
+                .all(|(reg, _)| consts.get(reg) == Some(value))
+                .then(|| value.clone())
+        }
+        _ => None,
+    }
+}
+
+fn binary(op: BinOpKind, lhs: &Const, rhs: &Const) -> Option<Const> {
+    let value = match (lhs, rhs) {
+        (&Const::Int(a), &Const::Int(b)) => match op {
+            BinOpKind::Add => Const::Int(a.wrapping_add(b)),
+            BinOpKind::Sub => Const::Int(a.wrapping_sub(b)),
+            BinOpKind::Mul => Const::Int(a.wrapping_mul(b)),
+            BinOpKind::Div => Const::Int(a.checked_div(b)?),
+            BinOpKind::Eq => Const::Bool(a == b),
+            BinOpKind::Ne => Const::Bool(a != b),
+            BinOpKind::Lt => Const::Bool(a < b),
+            BinOpKind::Le => Const::Bool(a <= b),
+            BinOpKind::Gt => Const::Bool(a > b),
+            BinOpKind::Ge => Const::Bool(a >= b),
+        },
+        _ => match op {
+            BinOpKind::Eq => Const::Bool(lhs == rhs),
+            BinOpKind::Ne => Const::Bool(lhs != rhs),
+            _ => return None,
+        },
+    };
+    Some(value)
+}
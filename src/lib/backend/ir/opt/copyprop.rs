@@ -0,0 +1,105 @@
+//! Copy propagation.
+//!
+//! Uses of a `copy` read the copied register instead, and so do uses of a
+//! phi that only ever takes one value besides its own. That value must not
+//! be computed from the phi, which only happens in code that can't be
+//! reached, or the phi would end up used before it is written.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+pub struct CopyProp;
+
+impl Pass for CopyProp {
+    fn name(&self) -> &'static str {
+        "copyprop"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        let mut changed = false;
+        for proc in &mut module.procs {
+            changed |= propagate(proc);
+        }
+        changed
+    }
+}
+
+fn propagate(proc: &mut Proc) -> bool {
+    let mut replaced: HashMap<Reg, Reg> = HashMap::new();
+    let resolve = |replaced: &HashMap<Reg, Reg>, mut reg: Reg| {
+        while let Some(&next) = replaced.get(&reg) {
+            reg = next;
+        }
+        reg
+    };
+
+    // The registers each instruction reads
+    let mut operands: HashMap<Reg, Vec<Reg>> = HashMap::new();
+    for inst in proc.blocks.iter().flat_map(|block| &block.insts) {
+        if let Some(dest) = inst.dest {
+            operands.insert(dest, inst.uses());
+        }
+    }
+
+    // Removing a phi can make another one trivial, so go on until nothing
+    // more is found
+    loop {
+        let mut changed = false;
+        for block in &mut proc.blocks {
+            block.insts.retain(|inst| {
+                let Some(dest) = inst.dest else {
+                    return true;
+                };
+                let value = match &inst.op {
+                    Op::Copy(value) => resolve(&replaced, *value),
+                    Op::Phi(incoming) => {
+                        let values: HashSet<Reg> = incoming
+                            .iter()
+                            .map(|&(reg, _)| resolve(&replaced, reg))
+                            .filter(|&reg| reg != dest)
+                            .collect();
+                        match values.into_iter().collect::<Vec<_>>()[..] {
+                            [value] if !derived(&operands, &replaced, value, dest) => value,
+                            _ => return true,
+                        }
+                    }
+                    _ => return true,
+                };
+                replaced.insert(dest, value);
+                changed = true;
+                false
+            });
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    replace_uses(proc, &replaced);
+    !replaced.is_empty()
+}
+
+/// Whether `value` is computed from `phi`, looking through the registers
+/// already replaced
+fn derived(
+    operands: &HashMap<Reg, Vec<Reg>>,
+    replaced: &HashMap<Reg, Reg>,
+    value: Reg,
+    phi: Reg,
+) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack = vec![value];
+    while let Some(mut reg) = stack.pop() {
+        while let Some(&next) = replaced.get(&reg) {
+            reg = next;
+        }
+        if reg == phi {
+            return true;
+        }
+        if seen.insert(reg) {
+            stack.extend(operands.get(&reg).into_iter().flatten());
+        }
+    }
+    false
+}
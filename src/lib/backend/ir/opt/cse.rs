@@ -0,0 +1,87 @@
+//! Common subexpression elimination.
+//!
+//! Walks the dominator tree remembering the instructions seen on the way
+//! down. An instruction computing the same thing as one that dominates it is
+//! removed and its uses read the earlier result. Only pure instructions are
+//! considered, plus division since the first one traps before the second
+//! would. Loads aren't, as a call or store in between could change the
+//! global.
+
+use std::collections::HashMap;
+
+use super::*;
+
+pub struct Cse;
+
+impl Pass for Cse {
+    fn name(&self) -> &'static str {
+        "cse"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        let mut changed = false;
+        for proc in &mut module.procs {
+            changed |= eliminate(proc);
+        }
+        changed
+    }
+}
+
+fn eliminate(proc: &mut Proc) -> bool {
+    let Some(entry) = proc.blocks.first().map(|block| block.id) else {
+        return false;
+    };
+    let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+    for (block, idom) in proc.dominators() {
+        if block != idom {
+            children.entry(idom).or_default().push(block);
+        }
+    }
+
+    let mut replaced: HashMap<Reg, Reg> = HashMap::new();
+    let mut stack = vec![(entry, HashMap::new())];
+    while let Some((id, mut seen)) = stack.pop() {
+        let block = proc.block_mut(id).unwrap();
+        block.insts.retain_mut(|inst| {
+            // Operands are written in a dominating block, so were already
+            // replaced if they are going to be
+            inst.map_uses(|reg| replaced.get(&reg).copied().unwrap_or(reg));
+            let Some(key) = key(inst) else {
+                return true;
+            };
+            match seen.get(&key) {
+                Some(&earlier) => {
+                    replaced.insert(inst.dest.unwrap(), earlier);
+                    false
+                }
+                None => {
+                    seen.insert(key, inst.dest.unwrap());
+                    true
+                }
+            }
+        });
+        for &child in children.get(&id).into_iter().flatten() {
+            stack.push((child, seen.clone()));
+        }
+    }
+
+    replace_uses(proc, &replaced);
+    !replaced.is_empty()
+}
+
+/// What an instruction computes, if it can be reused, with the operands of
+/// commutative operators in order
+fn key(inst: &Inst) -> Option<(Op, Ty)> {
+    inst.dest?;
+    let op = match &inst.op {
+        Op::Const(_) | Op::Unary(_, _) => inst.op.clone(),
+        &Op::Binary(op, lhs, rhs) => match op {
+            BinOpKind::Add | BinOpKind::Mul | BinOpKind::Eq | BinOpKind::Ne => {
+                Op::Binary(op, lhs.min(rhs), lhs.max(rhs))
+            }
+            _ => inst.op.clone(),
+        },
+        _ => return None,
+    };
+    Some((op, inst.ty))
+}
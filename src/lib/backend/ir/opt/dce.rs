@@ -0,0 +1,59 @@
+//! Dead code elimination.
+//!
+//! Instructions with side effects and terminators are live, and so is every
+//! instruction writing a register a live one reads. Everything else goes.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+pub struct Dce;
+
+impl Pass for Dce {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        let mut changed = false;
+        for proc in &mut module.procs {
+            changed |= eliminate(proc);
+        }
+        changed
+    }
+}
+
+fn eliminate(proc: &mut Proc) -> bool {
+    let defs: HashMap<Reg, &Inst> = proc
+        .blocks
+        .iter()
+        .flat_map(|block| &block.insts)
+        .filter_map(|inst| Some((inst.dest?, inst)))
+        .collect();
+
+    let mut work: Vec<Reg> = vec![];
+    for block in &proc.blocks {
+        for inst in block.insts.iter().filter(|inst| !inst.is_pure()) {
+            work.extend(inst.uses());
+        }
+        work.extend(block.term.uses());
+    }
+    let mut live = HashSet::new();
+    while let Some(reg) = work.pop() {
+        if live.insert(reg) {
+            if let Some(inst) = defs.get(&reg) {
+                work.extend(inst.uses());
+            }
+        }
+    }
+
+    let mut changed = false;
+    for block in &mut proc.blocks {
+        let before = block.insts.len();
+        block
+            .insts
+            .retain(|inst| !inst.is_pure() || inst.dest.is_some_and(|dest| live.contains(&dest)));
+        changed |= block.insts.len() != before;
+    }
+    changed
+}
@@ -0,0 +1,184 @@
+//! Inlining of small procs.
+//!
+//! A call is replaced with a copy of the callee's blocks when the callee is
+//! small and makes no calls itself, which also keeps recursion out. The
+//! block holding the call is split in two: the first half copies the
+//! arguments into the callee's parameters and branches to its first block,
+//! and its returns branch to the second half, where a phi picks the result.
+
+use std::collections::HashMap;
+
+use super::*;
+
+pub struct Inline {
+    /// Most instructions a proc can have to be inlined
+    pub threshold: usize,
+}
+
+impl Default for Inline {
+    fn default() -> Inline {
+        Inline { threshold: 20 }
+    }
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        let callees: HashMap<String, Proc> = module
+            .procs
+            .iter()
+            .filter(|proc| proc.name != module.entry && self.inlinable(proc))
+            .map(|proc| (proc.name.clone(), proc.clone()))
+            .collect();
+        if callees.is_empty() {
+            return false;
+        }
+
+        let mut changed = false;
+        for proc in &mut module.procs {
+            while let Some((block, at, callee)) = find_call(proc, &callees) {
+                inline_call(proc, block, at, callee);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+impl Inline {
+    fn inlinable(&self, proc: &Proc) -> bool {
+        let insts = proc.blocks.iter().flat_map(|block| &block.insts);
+        let returns = proc
+            .blocks
+            .iter()
+            .any(|block| matches!(block.term, Terminator::Ret(_)));
+        returns
+            && insts.clone().count() <= self.threshold
+            && !insts.clone().any(|inst| matches!(inst.op, Op::Call(_, _)))
+    }
+}
+
+/// The first call in `proc` to one of `callees`
+fn find_call<'a>(
+    proc: &Proc,
+    callees: &'a HashMap<String, Proc>,
+) -> Option<(usize, usize, &'a Proc)> {
+    for (i, block) in proc.blocks.iter().enumerate() {
+        for (at, inst) in block.insts.iter().enumerate() {
+            if let Op::Call(name, _) = &inst.op {
+                if let Some(callee) = callees.get(name) {
+                    return Some((i, at, callee));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Replace the call at `at` in the `index`th block of `proc` with the body
+/// of `callee`
+fn inline_call(proc: &mut Proc, index: usize, at: usize, callee: &Proc) {
+    let next_reg = proc.next_reg();
+    let mut next_block = proc.blocks.iter().map(|block| block.id.0).max().unwrap() + 1;
+    let mut fresh_block = || {
+        next_block += 1;
+        BlockId(next_block - 1)
+    };
+
+    // Split the block after the call
+    let block = &mut proc.blocks[index];
+    let call = block.insts[at].clone();
+    let rest = block.insts.split_off(at + 1);
+    block.insts.pop();
+    let mut cont = Block {
+        id: fresh_block(),
+        insts: rest,
+        term: block.term.clone(),
+    };
+    let Op::Call(_, args) = call.op else {
+        unreachable!()
+    };
+
+    // Fresh names for everything the callee writes
+    let regs: HashMap<Reg, Reg> = callee
+        .params
+        .iter()
+        .map(|&(reg, _)| reg)
+        .chain(
+            callee
+                .blocks
+                .iter()
+                .flat_map(|block| &block.insts)
+                .filter_map(|inst| inst.dest),
+        )
+        .zip(next_reg..)
+        .map(|(reg, new)| (reg, Reg(new)))
+        .collect();
+    let blocks: HashMap<BlockId, BlockId> = callee
+        .blocks
+        .iter()
+        .map(|block| (block.id, fresh_block()))
+        .collect();
+
+    let block = &mut proc.blocks[index];
+    for (&(param, ty), &arg) in callee.params.iter().zip(&args) {
+        block.insts.push(Inst {
+            dest: Some(regs[&param]),
+            ty,
+            op: Op::Copy(arg),
+        });
+    }
+    block.term = Terminator::Br(blocks[&callee.blocks[0].id]);
+    let from = block.id;
+
+    let mut body = vec![];
+    let mut results = vec![];
+    for block in &callee.blocks {
+        let mut block = block.clone();
+        block.id = blocks[&block.id];
+        for inst in &mut block.insts {
+            inst.dest = inst.dest.map(|dest| regs[&dest]);
+            inst.map_uses(|reg| regs[&reg]);
+            if let Op::Phi(incoming) = &mut inst.op {
+                for (_, pred) in incoming {
+                    *pred = blocks[pred];
+                }
+            }
+        }
+        block.term.map_uses(|reg| regs[&reg]);
+        block.term.map_targets(|target| blocks[&target]);
+        if let Terminator::Ret(value) = block.term {
+            results.extend(value.map(|value| (value, block.id)));
+            block.term = Terminator::Br(cont.id);
+        }
+        body.push(block);
+    }
+
+    if let Some(dest) = call.dest {
+        let op = match results.len() {
+            1 => Op::Copy(results[0].0),
+            _ => Op::Phi(results),
+        };
+        cont.insts.insert(
+            0,
+            Inst {
+                dest: Some(dest),
+                ty: call.ty,
+                op,
+            },
+        );
+    }
+
+    // Control now reaches the successors of the call from the second half,
+    // including the first half if it looped back to itself
+    for succ in cont.term.successors() {
+        if let Some(block) = proc.block_mut(succ) {
+            rename_incoming(block, from, cont.id);
+        }
+    }
+    body.push(cont);
+    proc.blocks.splice(index + 1..index + 1, body);
+}
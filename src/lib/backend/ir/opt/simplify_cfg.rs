@@ -0,0 +1,140 @@
+//! Control flow graph simplification.
+//!
+//! Removes blocks that can't be reached, turns conditional branches with a
+//! single target into plain ones, merges a block into its predecessor when
+//! it is the only one and is only left for it, and skips empty blocks that
+//! only branch on. Phis of a block with a single predecessor become copies.
+
+use super::*;
+
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &'static str {
+        "simplify-cfg"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        let mut changed = false;
+        for proc in &mut module.procs {
+            while same_targets(proc) | remove_unreachable(proc) | merge(proc) | skip_empty(proc) {
+                changed = true;
+            }
+            changed |= single_phis(proc);
+        }
+        changed
+    }
+}
+
+/// `br %c, bbn, bbn` to `br bbn`
+fn same_targets(proc: &mut Proc) -> bool {
+    let mut changed = false;
+    for i in 0..proc.blocks.len() {
+        let block = &mut proc.blocks[i];
+        let Terminator::CondBr(_, then, otherwise) = block.term else {
+            continue;
+        };
+        if then != otherwise {
+            continue;
+        }
+        block.term = Terminator::Br(then);
+        let id = block.id;
+        remove_incoming(proc.block_mut(then).unwrap(), id);
+        changed = true;
+    }
+    changed
+}
+
+/// Merge a block into the one before it if that is its only predecessor and
+/// always branches to it
+fn merge(proc: &mut Proc) -> bool {
+    let preds = proc.predecessors();
+    let Some((pred, succ)) = proc.blocks.iter().find_map(|block| match block.term {
+        Terminator::Br(succ) if succ != block.id && preds[&succ] == [block.id] => {
+            Some((block.id, succ))
+        }
+        _ => None,
+    }) else {
+        return false;
+    };
+
+    let index = proc
+        .blocks
+        .iter()
+        .position(|block| block.id == succ)
+        .unwrap();
+    let mut merged = proc.blocks.remove(index);
+    for inst in &mut merged.insts {
+        if let Op::Phi(incoming) = &inst.op {
+            inst.op = Op::Copy(incoming[0].0);
+        }
+    }
+    for next in merged.term.successors() {
+        rename_incoming(proc.block_mut(next).unwrap(), succ, pred);
+    }
+    let block = proc.block_mut(pred).unwrap();
+    block.insts.extend(merged.insts);
+    block.term = merged.term;
+    true
+}
+
+/// Make the predecessors of an empty block that only branches on branch
+/// directly to where it goes. The target's phis get the same value from
+/// each of them, so this is only done when none of them already branch
+/// there.
+fn skip_empty(proc: &mut Proc) -> bool {
+    let preds = proc.predecessors();
+    let entry = proc.blocks[0].id;
+    let found = proc.blocks.iter().find_map(|block| {
+        let Terminator::Br(target) = block.term else {
+            return None;
+        };
+        if block.id == entry || target == block.id || !block.insts.is_empty() {
+            return None;
+        }
+        let clash = has_phis(proc.block(target).unwrap())
+            && preds[&block.id]
+                .iter()
+                .any(|pred| preds[&target].contains(pred));
+        (!clash).then_some((block.id, target))
+    });
+    let Some((empty, target)) = found else {
+        return false;
+    };
+
+    let from = &preds[&empty];
+    for &pred in from {
+        let block = proc.block_mut(pred).unwrap();
+        block
+            .term
+            .map_targets(|succ| if succ == empty { target } else { succ });
+    }
+    for inst in &mut proc.block_mut(target).unwrap().insts {
+        if let Op::Phi(incoming) = &mut inst.op {
+            let i = incoming
+                .iter()
+                .position(|&(_, pred)| pred == empty)
+                .unwrap();
+            let (value, _) = incoming.remove(i);
+            incoming.extend(from.iter().map(|&pred| (value, pred)));
+        }
+    }
+    proc.blocks.retain(|block| block.id != empty);
+    true
+}
+
+/// Phis of a block with one predecessor to copies
+fn single_phis(proc: &mut Proc) -> bool {
+    let mut changed = false;
+    for block in &mut proc.blocks {
+        for inst in &mut block.insts {
+            if let Op::Phi(incoming) = &inst.op {
+                if let [(value, _)] = incoming[..] {
+                    inst.op = Op::Copy(value);
+                    changed = true;
+                }
+            }
+        }
+    }
+    changed
+}
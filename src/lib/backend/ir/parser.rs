@@ -0,0 +1,492 @@
+//! Reads the text format printed by `Display for Module` back into a module.
+//! `#` starts a comment running to the end of the line. The result isn't
+//! verified, run `verify` on it before use.
+
+use unicode_xid::UnicodeXID;
+
+use super::*;
+use crate::errors::ReadError;
+
+const BINARY: [BinOpKind; 10] = [
+    BinOpKind::Add,
+    BinOpKind::Sub,
+    BinOpKind::Mul,
+    BinOpKind::Div,
+    BinOpKind::Eq,
+    BinOpKind::Ne,
+    BinOpKind::Lt,
+    BinOpKind::Le,
+    BinOpKind::Gt,
+    BinOpKind::Ge,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Keywords, mnemonics, types and block labels
+    Word(String),
+    /// `@name`
+    Symbol(String),
+    /// `%n`
+    Reg(u32),
+    Int(i64),
+    Str(String),
+    Punct(char),
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Symbol(name) => write!(f, "`@{}`", name),
+            Token::Reg(n) => write!(f, "`%{}`", n),
+            Token::Int(n) => write!(f, "`{}`", n),
+            Token::Str(s) => write!(f, "{}", Json::String(s.clone())),
+            Token::Punct(c) => write!(f, "`{}`", c),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Split the source into tokens and the offsets they start at
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ReadError> {
+    let error = |position: usize, message: &str| ReadError {
+        position,
+        message: message.into(),
+    };
+    let word_end = |from: usize| {
+        source[from..]
+            .char_indices()
+            .find(|&(_, c)| !c.is_xid_continue())
+            .map_or(source.len(), |(i, _)| from + i)
+    };
+
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let (token, end) = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '#' => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                continue;
+            }
+            '@' | '%' => {
+                let mut end = word_end(start + 1);
+                // Names from imported modules are qualified, eg `@geo.area`
+                while c == '@' && source[end..].starts_with('.') {
+                    end = word_end(end + 1);
+                }
+                let name = &source[start + 1..end];
+                if name.is_empty() {
+                    return Err(error(start, &format!("expected a name after `{}`", c)));
+                }
+                if c == '@' {
+                    (Token::Symbol(name.into()), end)
+                } else {
+                    let n = name.parse().map_err(|_| error(start, "invalid register"))?;
+                    (Token::Reg(n), end)
+                }
+            }
+            '"' => {
+                // Find the closing quote, skipping escapes
+                let bytes = source.as_bytes();
+                let mut end = start + 1;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                if end >= bytes.len() {
+                    return Err(error(start, "unterminated string"));
+                }
+                match Json::parse(&source[start..end + 1]) {
+                    Ok(Json::String(s)) => (Token::Str(s), end + 1),
+                    _ => return Err(error(start, "invalid string")),
+                }
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let end = word_end(start + 1);
+                let n = source[start..end]
+                    .parse()
+                    .map_err(|_| error(start, "invalid integer"))?;
+                (Token::Int(n), end)
+            }
+            c if c == '_' || c.is_xid_start() => {
+                let end = word_end(start);
+                (Token::Word(source[start..end].into()), end)
+            }
+            '(' | ')' | '{' | '}' | '[' | ']' | ',' | ':' | '=' => (Token::Punct(c), start + 1),
+            c => return Err(error(start, &format!("unexpected character '{}'", c))),
+        };
+
+        tokens.push((token, start));
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+    }
+    tokens.push((Token::Eof, source.len()));
+    Ok(tokens)
+}
+
+/// Parses the IR text format
+pub struct IrParser {
+    tokens: Vec<(Token, usize)>,
+    current: usize,
+}
+
+impl IrParser {
+    pub fn new(source: &str) -> Result<IrParser, ReadError> {
+        Ok(IrParser {
+            tokens: tokenize(source)?,
+            current: 0,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.current].0.clone();
+        if token != Token::Eof {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String) -> ReadError {
+        ReadError {
+            position: self.tokens[self.current].1,
+            message,
+        }
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ReadError> {
+        Err(self.error(format!("expected {}, found {}", expected, self.peek())))
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Token::Word(w) if w == word)
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ReadError> {
+        if !self.is_word(word) {
+            return self.unexpected(&format!("`{}`", word));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), ReadError> {
+        if self.peek() != &Token::Punct(c) {
+            return self.unexpected(&format!("`{}`", c));
+        }
+        self.next();
+        Ok(())
+    }
+
+    /// Consume `c` if it's next
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.peek() == &Token::Punct(c);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn symbol(&mut self) -> Result<String, ReadError> {
+        match self.peek() {
+            Token::Symbol(name) => {
+                let name = name.clone();
+                self.next();
+                Ok(name)
+            }
+            _ => self.unexpected("a name such as `@main`"),
+        }
+    }
+
+    fn reg(&mut self) -> Result<Reg, ReadError> {
+        match self.peek() {
+            &Token::Reg(n) => {
+                self.next();
+                Ok(Reg(n))
+            }
+            _ => self.unexpected("a register such as `%0`"),
+        }
+    }
+
+    fn block_id(&mut self) -> Result<BlockId, ReadError> {
+        let id = match self.peek() {
+            Token::Word(word) => word.strip_prefix("bb").and_then(|n| n.parse().ok()),
+            _ => None,
+        };
+        match id {
+            Some(n) => {
+                self.next();
+                Ok(BlockId(n))
+            }
+            None => self.unexpected("a block such as `bb0`"),
+        }
+    }
+
+    /// A number of bytes
+    fn size(&mut self) -> Result<u32, ReadError> {
+        match self.peek() {
+            &Token::Int(n) => {
+                let size = u32::try_from(n).map_err(|_| self.error("invalid size".into()))?;
+                self.next();
+                Ok(size)
+            }
+            _ => self.unexpected("a size"),
+        }
+    }
+
+    fn ty(&mut self) -> Result<Ty, ReadError> {
+        let ty = match self.peek() {
+            Token::Word(word) => match word.as_str() {
+                "int" => Ty::Int,
+                "bool" => Ty::Bool,
+                "str" => Ty::Str,
+                "ptr" => Ty::Ptr,
+                "void" => Ty::Void,
+                _ => return self.unexpected("a type"),
+            },
+            _ => return self.unexpected("a type"),
+        };
+        self.next();
+        Ok(ty)
+    }
+
+    pub fn parse(&mut self) -> Result<Module, ReadError> {
+        self.expect_word("entry")?;
+        let mut module = Module {
+            entry: self.symbol()?,
+            globals: vec![],
+            procs: vec![],
+        };
+
+        loop {
+            match self.peek() {
+                Token::Word(word) if word == "global" => {
+                    self.next();
+                    let name = self.symbol()?;
+                    self.expect_punct(':')?;
+                    let ty = self.ty()?;
+                    // Globals holding memory are followed by their size
+                    let size = match ty {
+                        Ty::Ptr => self.size()?,
+                        _ => 0,
+                    };
+                    module.globals.push(Global { name, ty, size });
+                }
+                Token::Word(word) if word == "proc" => {
+                    let proc = self.proc()?;
+                    module.procs.push(proc);
+                }
+                Token::Eof => return Ok(module),
+                _ => return self.unexpected("`global` or `proc`"),
+            }
+        }
+    }
+
+    fn proc(&mut self) -> Result<Proc, ReadError> {
+        self.expect_word("proc")?;
+        let name = self.symbol()?;
+
+        self.expect_punct('(')?;
+        let mut params = vec![];
+        while !self.eat_punct(')') {
+            if !params.is_empty() {
+                self.expect_punct(',')?;
+            }
+            let reg = self.reg()?;
+            self.expect_punct(':')?;
+            params.push((reg, self.ty()?));
+        }
+        let ret = if self.eat_punct(':') {
+            self.ty()?
+        } else {
+            Ty::Void
+        };
+
+        self.expect_punct('{')?;
+        let mut blocks = vec![];
+        while !self.eat_punct('}') {
+            blocks.push(self.block(ret)?);
+        }
+        Ok(Proc {
+            name,
+            params,
+            ret,
+            blocks,
+        })
+    }
+
+    fn block(&mut self, ret: Ty) -> Result<Block, ReadError> {
+        let id = self.block_id()?;
+        self.expect_punct(':')?;
+
+        let mut insts = vec![];
+        loop {
+            let term = match self.peek() {
+                Token::Word(word) if word == "br" => {
+                    self.next();
+                    if let Token::Reg(_) = self.peek() {
+                        let cond = self.reg()?;
+                        self.expect_punct(',')?;
+                        let then = self.block_id()?;
+                        self.expect_punct(',')?;
+                        Terminator::CondBr(cond, then, self.block_id()?)
+                    } else {
+                        Terminator::Br(self.block_id()?)
+                    }
+                }
+                Token::Word(word) if word == "ret" => {
+                    self.next();
+                    if ret == Ty::Void {
+                        Terminator::Ret(None)
+                    } else {
+                        if self.ty()? != ret {
+                            return Err(self.error(format!("expected the return type `{}`", ret)));
+                        }
+                        Terminator::Ret(Some(self.reg()?))
+                    }
+                }
+                Token::Word(word) if word == "unreachable" => {
+                    self.next();
+                    Terminator::Unreachable
+                }
+                Token::Word(word) if word == "trap" => {
+                    self.next();
+                    Terminator::Trap
+                }
+                _ => {
+                    insts.push(self.inst()?);
+                    continue;
+                }
+            };
+            return Ok(Block { id, insts, term });
+        }
+    }
+
+    fn inst(&mut self) -> Result<Inst, ReadError> {
+        let dest = match self.peek() {
+            Token::Reg(_) => {
+                let dest = self.reg()?;
+                self.expect_punct('=')?;
+                Some(dest)
+            }
+            _ => None,
+        };
+
+        let Token::Word(mnemonic) = self.peek().clone() else {
+            return self.unexpected("an instruction");
+        };
+        let binary = BINARY.into_iter().find(|&op| binary_name(op) == mnemonic);
+        let known = [
+            "const", "call", "load", "store", "print", "copy", "phi", "neg", "not", "alloca",
+            "addr", "offset", "index", "read", "write", "memcopy",
+        ];
+        if binary.is_none() && !known.contains(&mnemonic.as_str()) {
+            return Err(self.error(format!("unknown instruction `{}`", mnemonic)));
+        }
+        self.next();
+        let ty = self.ty()?;
+
+        let op = match mnemonic.as_str() {
+            "const" => {
+                // Errors point at the constant, not what follows it
+                let error = self.error("constant doesn't fit in an int".into());
+                let value = match self.next() {
+                    Token::Int(n) => Const::Int(i32::try_from(n).map_err(|_| error)?),
+                    Token::Word(word) if word == "true" => Const::Bool(true),
+                    Token::Word(word) if word == "false" => Const::Bool(false),
+                    Token::Str(s) => Const::Str(s),
+                    _ => {
+                        self.current -= 1;
+                        return self.unexpected("a constant");
+                    }
+                };
+                Op::Const(value)
+            }
+            "call" => {
+                let name = self.symbol()?;
+                self.expect_punct('(')?;
+                let mut args = vec![];
+                while !self.eat_punct(')') {
+                    if !args.is_empty() {
+                        self.expect_punct(',')?;
+                    }
+                    args.push(self.reg()?);
+                }
+                Op::Call(name, args)
+            }
+            "load" => Op::Load(self.symbol()?),
+            "store" => {
+                let value = self.reg()?;
+                self.expect_punct(',')?;
+                Op::Store(self.symbol()?, value)
+            }
+            "print" => Op::Print(self.reg()?),
+            "copy" => Op::Copy(self.reg()?),
+            "phi" => {
+                let mut incoming = vec![];
+                loop {
+                    self.expect_punct('[')?;
+                    let reg = self.reg()?;
+                    self.expect_punct(',')?;
+                    incoming.push((reg, self.block_id()?));
+                    self.expect_punct(']')?;
+                    if !self.eat_punct(',') {
+                        break;
+                    }
+                }
+                Op::Phi(incoming)
+            }
+            "alloca" => Op::Alloca(self.size()?),
+            "addr" => Op::Addr(self.symbol()?),
+            "offset" => {
+                let address = self.reg()?;
+                self.expect_punct(',')?;
+                Op::Offset(address, self.size()?)
+            }
+            "index" => {
+                let address = self.reg()?;
+                self.expect_punct(',')?;
+                let index = self.reg()?;
+                self.expect_punct(',')?;
+                Op::Index(address, index, self.size()?)
+            }
+            "read" => Op::Read(self.reg()?),
+            "write" => {
+                let value = self.reg()?;
+                self.expect_punct(',')?;
+                Op::Write(value, self.reg()?)
+            }
+            "memcopy" => {
+                let to = self.reg()?;
+                self.expect_punct(',')?;
+                let from = self.reg()?;
+                self.expect_punct(',')?;
+                Op::MemCopy(to, from, self.size()?)
+            }
+            "neg" => Op::Unary(UnaryOpKind::Neg, self.reg()?),
+            "not" => Op::Unary(UnaryOpKind::Not, self.reg()?),
+            _ => {
+                let op = binary.expect("mnemonics were checked above");
+                let lhs = self.reg()?;
+                self.expect_punct(',')?;
+                Op::Binary(op, lhs, self.reg()?)
+            }
+        };
+
+        let inst = Inst { dest, ty, op };
+        if dest.is_some() == (inst.result_type() == Ty::Void) {
+            let message = match dest {
+                Some(_) => format!("`{}` doesn't produce a value", mnemonic),
+                None => format!("the result of `{}` must be written to a register", mnemonic),
+            };
+            return Err(self.error(message));
+        }
+        Ok(inst)
+    }
+}
@@ -0,0 +1,386 @@
+//! Checks that a module is well formed: branches name blocks of their proc,
+//! every register is written once and before each of its uses, phis have
+//! one value per predecessor, and operands have the types their
+//! instructions expect. What memory holds isn't tracked, reading an int
+//! from where a string was written isn't caught.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::errors::VerifyError;
+
+/// Check `module`, returning the first problem found
+pub fn verify(module: &Module) -> Result<(), VerifyError> {
+    let mut symbols = HashSet::new();
+    for name in module
+        .globals
+        .iter()
+        .map(|g| &g.name)
+        .chain(module.procs.iter().map(|p| &p.name))
+    {
+        if !symbols.insert(name) {
+            return Err(error(
+                None,
+                format!("`@{}` is defined more than once", name),
+            ));
+        }
+    }
+    for global in &module.globals {
+        if global.ty == Ty::Void {
+            return Err(error(
+                None,
+                format!("global `@{}` can't be void", global.name),
+            ));
+        }
+        if global.ty != Ty::Ptr && global.size > 0 {
+            return Err(error(
+                None,
+                format!(
+                    "only `ptr` globals hold memory, `@{}` is `{}`",
+                    global.name, global.ty
+                ),
+            ));
+        }
+    }
+    match module.proc(&module.entry) {
+        Some(proc) if proc.params.is_empty() && proc.ret == Ty::Void => {}
+        Some(_) => {
+            let message = format!(
+                "entry proc `@{}` can't take parameters or return a value",
+                module.entry
+            );
+            return Err(error(None, message));
+        }
+        None => {
+            return Err(error(
+                None,
+                format!("entry proc `@{}` is not defined", module.entry),
+            ))
+        }
+    }
+
+    for proc in &module.procs {
+        Verifier::new(module, proc).verify()?;
+    }
+    Ok(())
+}
+
+fn error(proc: Option<&Proc>, message: String) -> VerifyError {
+    VerifyError {
+        proc: proc.map(|proc| proc.name.clone()),
+        message,
+    }
+}
+
+/// Verifies one proc
+struct Verifier<'a> {
+    module: &'a Module,
+    proc: &'a Proc,
+    preds: HashMap<BlockId, Vec<BlockId>>,
+    /// Type of every register
+    types: HashMap<Reg, Ty>,
+    /// Block and index of the instruction writing each register, parameters
+    /// are written before the first instruction of the first block
+    defs: HashMap<Reg, (BlockId, Option<usize>)>,
+    /// Immediate dominator of every block reachable from the first one
+    idom: HashMap<BlockId, BlockId>,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(module: &'a Module, proc: &'a Proc) -> Verifier<'a> {
+        Verifier {
+            module,
+            proc,
+            preds: proc.predecessors(),
+            types: HashMap::new(),
+            defs: HashMap::new(),
+            idom: HashMap::new(),
+        }
+    }
+
+    fn error(&self, message: String) -> VerifyError {
+        error(Some(self.proc), message)
+    }
+
+    fn verify(mut self) -> Result<(), VerifyError> {
+        let Some(entry) = self.proc.blocks.first() else {
+            return Err(self.error("proc has no blocks".into()));
+        };
+
+        let mut ids = HashSet::new();
+        for block in &self.proc.blocks {
+            if !ids.insert(block.id) {
+                return Err(self.error(format!("{} is defined more than once", block.id)));
+            }
+        }
+        for block in &self.proc.blocks {
+            for succ in block.term.successors() {
+                if !ids.contains(&succ) {
+                    return Err(self.error(format!("{} branches to undefined {}", block.id, succ)));
+                }
+            }
+        }
+        if !self.preds[&entry.id].is_empty() {
+            return Err(self.error(format!("first block {} can't be branched to", entry.id)));
+        }
+
+        for &(reg, ty) in &self.proc.params {
+            if ty == Ty::Void {
+                return Err(self.error(format!("parameter {} can't be void", reg)));
+            }
+            self.define(reg, ty, entry.id, None)?;
+        }
+        for block in &self.proc.blocks {
+            for (i, inst) in block.insts.iter().enumerate() {
+                if let Some(dest) = inst.dest {
+                    self.define(dest, inst.result_type(), block.id, Some(i))?;
+                }
+            }
+        }
+
+        self.idom = self.proc.dominators();
+        for block in &self.proc.blocks {
+            self.block(block)?;
+        }
+        Ok(())
+    }
+
+    fn define(
+        &mut self,
+        reg: Reg,
+        ty: Ty,
+        block: BlockId,
+        at: Option<usize>,
+    ) -> Result<(), VerifyError> {
+        if self.defs.insert(reg, (block, at)).is_some() {
+            return Err(self.error(format!("{} is written more than once", reg)));
+        }
+        self.types.insert(reg, ty);
+        Ok(())
+    }
+
+    /// Whether `a` dominates `b`, anything dominates a block that can't be reached
+    fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        if !self.idom.contains_key(&b) {
+            return true;
+        }
+        let mut block = b;
+        loop {
+            if block == a {
+                return true;
+            }
+            let idom = self.idom[&block];
+            if idom == block {
+                return false;
+            }
+            block = idom;
+        }
+    }
+
+    /// Check `reg` is written before the instruction at `at` in `block`, or
+    /// before the end of `block` if `at` is None, and has type `ty`
+    fn use_reg(
+        &self,
+        reg: Reg,
+        ty: Option<Ty>,
+        block: BlockId,
+        at: Option<usize>,
+    ) -> Result<Ty, VerifyError> {
+        let Some(&(def_block, def_at)) = self.defs.get(&reg) else {
+            return Err(self.error(format!("{} is used but never written", reg)));
+        };
+        let before = match (def_at, at) {
+            _ if def_block != block => self.dominates(def_block, block),
+            (None, _) | (Some(_), None) => true,
+            (Some(def_at), Some(at)) => def_at < at,
+        };
+        if !before {
+            return Err(self.error(format!("{} is used in {} before it is written", reg, block)));
+        }
+
+        let actual = self.types[&reg];
+        match ty {
+            Some(ty) if ty != actual => Err(self.error(format!(
+                "{} in {} is `{}`, expected `{}`",
+                reg, block, actual, ty
+            ))),
+            _ => Ok(actual),
+        }
+    }
+
+    fn block(&self, block: &Block) -> Result<(), VerifyError> {
+        let mut phis = true;
+        for (i, inst) in block.insts.iter().enumerate() {
+            let at = Some(i);
+            let expect = |reg: Reg, ty: Ty| self.use_reg(reg, Some(ty), block.id, at);
+            let fail = |message: String| {
+                Err(self.error(format!("in {}: {}: {}", block.id, inst, message)))
+            };
+
+            if inst.dest.is_some() == matches!(inst.result_type(), Ty::Void) {
+                return fail("only instructions with a result write to a register".into());
+            }
+            if !matches!(inst.op, Op::Phi(_)) {
+                phis = false;
+            }
+
+            match &inst.op {
+                Op::Const(value) => {
+                    let ty = match value {
+                        Const::Int(_) => Ty::Int,
+                        Const::Bool(_) => Ty::Bool,
+                        Const::Str(_) => Ty::Str,
+                    };
+                    if ty != inst.ty {
+                        return fail(format!("constant is `{}`", ty));
+                    }
+                }
+                Op::Unary(op, rhs) => {
+                    let ty = match op {
+                        UnaryOpKind::Neg => Ty::Int,
+                        UnaryOpKind::Not => Ty::Bool,
+                    };
+                    if inst.ty != ty {
+                        return fail(format!("`{}` only applies to `{}`", unary_name(*op), ty));
+                    }
+                    expect(*rhs, ty)?;
+                }
+                Op::Binary(op, lhs, rhs) => {
+                    let allowed = match op {
+                        BinOpKind::Eq | BinOpKind::Ne => !matches!(inst.ty, Ty::Void | Ty::Ptr),
+                        _ => inst.ty == Ty::Int,
+                    };
+                    if !allowed {
+                        return fail(format!(
+                            "`{}` doesn't apply to `{}`",
+                            binary_name(*op),
+                            inst.ty
+                        ));
+                    }
+                    expect(*lhs, inst.ty)?;
+                    expect(*rhs, inst.ty)?;
+                }
+                Op::Call(name, args) => {
+                    let Some(callee) = self.module.proc(name) else {
+                        return fail(format!("no proc `@{}`", name));
+                    };
+                    if name == &self.module.entry {
+                        return fail("the entry proc can't be called".into());
+                    }
+                    if callee.ret != inst.ty {
+                        return fail(format!("`@{}` returns `{}`", name, callee.ret));
+                    }
+                    if callee.params.len() != args.len() {
+                        return fail(format!(
+                            "`@{}` takes {} arguments",
+                            name,
+                            callee.params.len()
+                        ));
+                    }
+                    for (&arg, &(_, ty)) in args.iter().zip(&callee.params) {
+                        expect(arg, ty)?;
+                    }
+                }
+                Op::Load(name) | Op::Store(name, _) => {
+                    let Some(global) = self.module.global(name) else {
+                        return fail(format!("no global `@{}`", name));
+                    };
+                    if global.ty == Ty::Ptr {
+                        return fail(format!("`@{}` holds memory, take its address", name));
+                    }
+                    if global.ty != inst.ty {
+                        return fail(format!("`@{}` is `{}`", name, global.ty));
+                    }
+                    if let Op::Store(_, value) = &inst.op {
+                        expect(*value, inst.ty)?;
+                    }
+                }
+                Op::Print(value) => {
+                    if matches!(inst.ty, Ty::Void | Ty::Ptr) {
+                        return fail(format!("can't print `{}`", inst.ty));
+                    }
+                    expect(*value, inst.ty)?;
+                }
+                Op::Copy(value) => {
+                    if inst.ty == Ty::Void {
+                        return fail("can't copy `void`".into());
+                    }
+                    expect(*value, inst.ty)?;
+                }
+                Op::Phi(incoming) => {
+                    if !phis {
+                        return fail("phis must come before other instructions".into());
+                    }
+                    let mut preds = self.preds[&block.id].clone();
+                    for &(reg, pred) in incoming {
+                        let Some(i) = preds.iter().position(|&p| p == pred) else {
+                            return fail(format!("{} is not a predecessor", pred));
+                        };
+                        preds.remove(i);
+                        self.use_reg(reg, Some(inst.ty), pred, None)?;
+                    }
+                    if let Some(pred) = preds.first() {
+                        return fail(format!("no value for predecessor {}", pred));
+                    }
+                }
+                Op::Alloca(_) | Op::Addr(_) | Op::Offset(_, _) | Op::Index(_, _, _) => {
+                    if inst.ty != Ty::Ptr {
+                        return fail("addresses are `ptr`".into());
+                    }
+                    match &inst.op {
+                        Op::Addr(name) => match self.module.global(name) {
+                            Some(global) if global.ty == Ty::Ptr => {}
+                            Some(_) => return fail(format!("`@{}` doesn't hold memory", name)),
+                            None => return fail(format!("no global `@{}`", name)),
+                        },
+                        Op::Offset(address, _) => {
+                            expect(*address, Ty::Ptr)?;
+                        }
+                        Op::Index(address, index, _) => {
+                            expect(*address, Ty::Ptr)?;
+                            expect(*index, Ty::Int)?;
+                        }
+                        _ => {}
+                    }
+                }
+                Op::Read(address) | Op::Write(_, address) => {
+                    if inst.ty == Ty::Void {
+                        return fail("can't read or write `void`".into());
+                    }
+                    expect(*address, Ty::Ptr)?;
+                    if let Op::Write(value, _) = &inst.op {
+                        expect(*value, inst.ty)?;
+                    }
+                }
+                Op::MemCopy(to, from, _) => {
+                    if inst.ty != Ty::Ptr {
+                        return fail("addresses are `ptr`".into());
+                    }
+                    expect(*to, Ty::Ptr)?;
+                    expect(*from, Ty::Ptr)?;
+                }
+            }
+        }
+
+        match &block.term {
+            Terminator::CondBr(cond, _, _) => {
+                self.use_reg(*cond, Some(Ty::Bool), block.id, None)?;
+            }
+            Terminator::Ret(value) => match (value, self.proc.ret) {
+                (None, Ty::Void) => {}
+                (Some(value), ty) if ty != Ty::Void => {
+                    self.use_reg(*value, Some(ty), block.id, None)?;
+                }
+                _ => {
+                    let message = format!(
+                        "in {}: return doesn't match the return type `{}`",
+                        block.id, self.proc.ret
+                    );
+                    return Err(self.error(message));
+                }
+            },
+            Terminator::Br(_) | Terminator::Unreachable | Terminator::Trap => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+//! LLVM IR generation.
+//!
+//! Emits a textual LLVM module (`.ll`) from an IR module, for `clang` or
+//! `llc` to compile and link against the C library, which it uses to print.
+//! Pointers are opaque, so LLVM 15 or later reads it as is and LLVM 14 needs
+//! `-opaque-pointers`.
+//!
+//! The IR is already in SSA form, so registers become LLVM values and phis
+//! become LLVM phis, there is no need for allocas and `mem2reg`. Constants
+//! and copies don't become instructions: their uses refer to the constant or
+//! the copied value directly. Globals become `internal` LLVM globals read and
+//! written with `load` and `store`.
+//!
+//! Memory is handled in bytes: `alloca`s become allocas of byte arrays at
+//! the start of the entry block, so a loop doesn't grow the stack, and
+//! `offset` and `index` become `getelementptr`s. `memcopy` calls
+//! `llvm.memmove`, as `llvm.memcpy` doesn't allow copying a value onto
+//! itself.
+//!
+//! Ints are `i32` and wrap around on overflow, so `add`, `sub` and `mul`
+//! don't carry `nsw`, which would make overflow undefined instead. Division
+//! by zero and of `-2147483648` by `-1` are undefined for `sdiv`, so
+//! division calls a helper that stops the program on them first. Bools are
+//! `i1` and strings are pointers to NUL terminated bytes.
+//!
+//! A program stops like the C build does, only without the source location:
+//! `lune_fail` flushes what was printed, writes why it stopped to stderr and
+//! calls `llvm.trap`. `trap` stops it for an index out of bounds.
+//!
+//! Every symbol is encoded with `cgen::encode`, so none clash with `main`,
+//! the C library or the helpers.
+
+use std::collections::HashMap;
+
+use super::ast::{BinOpKind, UnaryOpKind};
+use super::cgen::encode;
+use super::ir::*;
+
+const RUNTIME: &str = r#"declare i32 @printf(ptr, ...)
+declare i32 @puts(ptr)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @llvm.trap() cold noreturn nounwind
+declare void @llvm.memmove.p0.p0.i64(ptr, ptr, i64, i1)
+
+@lune.int = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@lune.true = private unnamed_addr constant [5 x i8] c"true\00"
+@lune.false = private unnamed_addr constant [6 x i8] c"false\00"
+@lune.error = private unnamed_addr constant [11 x i8] c"error: %s\0A\00"
+@lune.div_zero = private unnamed_addr constant [26 x i8] c"attempt to divide by zero\00"
+@lune.div_overflow = private unnamed_addr constant [32 x i8] c"attempt to divide with overflow\00"
+@lune.bounds = private unnamed_addr constant [20 x i8] c"index out of bounds\00"
+
+define internal void @lune_print_int(i32 %value) {
+  %1 = call i32 (ptr, ...) @printf(ptr @lune.int, i32 %value)
+  ret void
+}
+
+define internal void @lune_print_str(ptr %value) {
+  %1 = call i32 @puts(ptr %value)
+  ret void
+}
+
+define internal void @lune_print_bool(i1 %value) {
+  %1 = select i1 %value, ptr @lune.true, ptr @lune.false
+  %2 = call i32 @puts(ptr %1)
+  ret void
+}
+
+define internal i1 @lune_str_eq(ptr %a, ptr %b) {
+  %1 = call i32 @strcmp(ptr %a, ptr %b)
+  %2 = icmp eq i32 %1, 0
+  ret i1 %2
+}
+
+define internal void @lune_fail(ptr %message) cold noreturn {
+  %1 = call i32 @fflush(ptr null)
+  %2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @lune.error, ptr %message)
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @lune_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %by_zero, label %nonzero
+by_zero:
+  call void @lune_fail(ptr @lune.div_zero)
+  unreachable
+nonzero:
+  %min = icmp eq i32 %a, -2147483648
+  %minus_one = icmp eq i32 %b, -1
+  %overflow = and i1 %min, %minus_one
+  br i1 %overflow, label %overflows, label %ok
+overflows:
+  call void @lune_fail(ptr @lune.div_overflow)
+  unreachable
+ok:
+  %1 = sdiv i32 %a, %b
+  ret i32 %1
+}
+"#;
+
+/// LLVM type of an IR type
+fn llvm_type(ty: Ty) -> &'static str {
+    match ty {
+        Ty::Int => "i32",
+        Ty::Bool => "i1",
+        Ty::Str | Ty::Ptr => "ptr",
+        Ty::Void => "void",
+    }
+}
+
+/// Contents of a `c"..."` string constant with its NUL terminator, escaping
+/// everything outside printable ASCII as hex
+fn llvm_string(value: &str) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+            _ => out += &format!("\\{:02X}", byte),
+        }
+    }
+    out + "\\00"
+}
+
+/// Generates LLVM IR from a verified IR module
+pub struct LlvmGen {
+    /// String constants, emitted as `@.str.<n>`
+    strings: Vec<String>,
+    out: String,
+}
+
+impl LlvmGen {
+    pub fn new() -> LlvmGen {
+        LlvmGen {
+            strings: vec![],
+            out: String::new(),
+        }
+    }
+
+    /// Generate the LLVM module for `module`
+    pub fn generate(&mut self, module: &Module) -> String {
+        let mut procs = String::new();
+        for proc in &module.procs {
+            procs += &self.proc(module, proc);
+        }
+
+        self.out = RUNTIME.into();
+        if !module.globals.is_empty() {
+            self.out += "\n";
+        }
+        for global in &module.globals {
+            if global.ty == Ty::Ptr {
+                self.out += &format!(
+                    "@{} = internal global [{} x i8] zeroinitializer, align 8\n",
+                    encode(&global.name),
+                    global.size
+                );
+                continue;
+            }
+            let zero = match global.ty {
+                Ty::Int => "0",
+                Ty::Bool => "false",
+                _ => "null",
+            };
+            self.out += &format!(
+                "@{} = internal global {} {}\n",
+                encode(&global.name),
+                llvm_type(global.ty),
+                zero
+            );
+        }
+        if !self.strings.is_empty() {
+            self.out += "\n";
+        }
+        for (i, value) in self.strings.iter().enumerate() {
+            self.out += &format!(
+                "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\"\n",
+                i,
+                value.len() + 1,
+                llvm_string(value)
+            );
+        }
+        self.out += &procs;
+        self.out += &format!(
+            "\ndefine i32 @main() {{\n  call void @{}()\n  ret i32 0\n}}\n",
+            encode(&module.entry)
+        );
+        std::mem::take(&mut self.out)
+    }
+
+    fn string(&mut self, value: &str) -> String {
+        let i = match self.strings.iter().position(|other| other == value) {
+            Some(i) => i,
+            None => {
+                self.strings.push(value.into());
+                self.strings.len() - 1
+            }
+        };
+        format!("@.str.{}", i)
+    }
+
+    fn proc(&mut self, module: &Module, proc: &Proc) -> String {
+        let defs: HashMap<Reg, &Inst> = proc
+            .blocks
+            .iter()
+            .flat_map(|block| &block.insts)
+            .filter_map(|inst| Some((inst.dest?, inst)))
+            .collect();
+        let mut value = |reg: Reg| -> String {
+            let mut reg = reg;
+            loop {
+                match defs.get(&reg).map(|inst| &inst.op) {
+                    Some(Op::Copy(src)) => reg = *src,
+                    Some(Op::Const(Const::Int(n))) => return n.to_string(),
+                    Some(Op::Const(Const::Bool(b))) => return b.to_string(),
+                    Some(Op::Const(Const::Str(s))) => return self.string(s),
+                    Some(Op::Addr(name)) => return format!("@{}", encode(name)),
+                    _ => return format!("%r{}", reg.0),
+                }
+            }
+        };
+
+        let params: Vec<String> = proc
+            .params
+            .iter()
+            .map(|&(reg, ty)| format!("{} %r{}", llvm_type(ty), reg.0))
+            .collect();
+        let mut out = format!(
+            "\ndefine internal {} @{}({}) {{\n",
+            llvm_type(proc.ret),
+            encode(&proc.name),
+            params.join(", ")
+        );
+        let types = proc.reg_types();
+        let typed = |value: &mut dyn FnMut(Reg) -> String, reg: Reg| {
+            format!("{} {}", llvm_type(types[&reg]), value(reg))
+        };
+
+        for (i, block) in proc.blocks.iter().enumerate() {
+            out += &format!("{}:\n", block.id);
+            if i == 0 {
+                for (dest, size) in proc
+                    .blocks
+                    .iter()
+                    .flat_map(|block| &block.insts)
+                    .filter_map(|inst| match inst.op {
+                        Op::Alloca(size) => Some((inst.dest?, size)),
+                        _ => None,
+                    })
+                {
+                    out += &format!("  %r{} = alloca [{} x i8], align 8\n", dest.0, size);
+                }
+            }
+            for inst in &block.insts {
+                let dest = inst.dest.map(|dest| format!("%r{} = ", dest.0));
+                let dest = dest.as_deref().unwrap_or("");
+                let ty = llvm_type(inst.ty);
+                let line = match &inst.op {
+                    Op::Const(_) | Op::Copy(_) | Op::Addr(_) | Op::Alloca(_) => continue,
+                    Op::Unary(UnaryOpKind::Neg, rhs) => format!("sub i32 0, {}", value(*rhs)),
+                    Op::Unary(UnaryOpKind::Not, rhs) => format!("xor i1 {}, true", value(*rhs)),
+                    Op::Binary(op, lhs, rhs) => {
+                        let (l, r) = (value(*lhs), value(*rhs));
+                        let cmp = |cond: &str| format!("icmp {} {} {}, {}", cond, ty, l, r);
+                        match op {
+                            BinOpKind::Eq | BinOpKind::Ne if inst.ty == Ty::Str => {
+                                let eq = format!("call i1 @lune_str_eq(ptr {}, ptr {})", l, r);
+                                if *op == BinOpKind::Eq {
+                                    eq
+                                } else {
+                                    let dest = inst.dest.unwrap().0;
+                                    out += &format!("  %r{}.eq = {}\n", dest, eq);
+                                    format!("xor i1 %r{}.eq, true", dest)
+                                }
+                            }
+                            BinOpKind::Add => format!("add i32 {}, {}", l, r),
+                            BinOpKind::Sub => format!("sub i32 {}, {}", l, r),
+                            BinOpKind::Mul => format!("mul i32 {}, {}", l, r),
+                            BinOpKind::Div => format!("call i32 @lune_div(i32 {}, i32 {})", l, r),
+                            BinOpKind::Eq => cmp("eq"),
+                            BinOpKind::Ne => cmp("ne"),
+                            BinOpKind::Lt => cmp("slt"),
+                            BinOpKind::Le => cmp("sle"),
+                            BinOpKind::Gt => cmp("sgt"),
+                            BinOpKind::Ge => cmp("sge"),
+                        }
+                    }
+                    Op::Call(name, args) => {
+                        let callee = module.proc(name).unwrap();
+                        let args: Vec<String> =
+                            args.iter().map(|&arg| typed(&mut value, arg)).collect();
+                        format!(
+                            "call {} @{}({})",
+                            llvm_type(callee.ret),
+                            encode(name),
+                            args.join(", ")
+                        )
+                    }
+                    Op::Load(name) => format!("load {}, ptr @{}", ty, encode(name)),
+                    Op::Store(name, reg) => {
+                        format!("store {}, ptr @{}", typed(&mut value, *reg), encode(name))
+                    }
+                    Op::Print(reg) => {
+                        let helper = match inst.ty {
+                            Ty::Int => "lune_print_int",
+                            Ty::Bool => "lune_print_bool",
+                            Ty::Str => "lune_print_str",
+                            Ty::Ptr | Ty::Void => {
+                                unreachable!("only ints, bools and strings are printed")
+                            }
+                        };
+                        format!("call void @{}({})", helper, typed(&mut value, *reg))
+                    }
+                    Op::Offset(address, offset) => format!(
+                        "getelementptr inbounds i8, ptr {}, i64 {}",
+                        value(*address),
+                        offset
+                    ),
+                    Op::Index(address, index, size) => {
+                        let dest = inst.dest.unwrap().0;
+                        out += &format!("  %r{}.i = sext i32 {} to i64\n", dest, value(*index));
+                        format!(
+                            "getelementptr inbounds [{} x i8], ptr {}, i64 %r{}.i",
+                            size,
+                            value(*address),
+                            dest
+                        )
+                    }
+                    Op::Read(address) => format!("load {}, ptr {}", ty, value(*address)),
+                    Op::Write(reg, address) => {
+                        format!("store {}, ptr {}", typed(&mut value, *reg), value(*address))
+                    }
+                    Op::MemCopy(to, from, size) => format!(
+                        "call void @llvm.memmove.p0.p0.i64(ptr {}, ptr {}, i64 {}, i1 false)",
+                        value(*to),
+                        value(*from),
+                        size
+                    ),
+                    Op::Phi(incoming) => {
+                        // A conditional branch to the same block twice
+                        // becomes a plain one, so each predecessor is
+                        // listed once
+                        let mut seen = vec![];
+                        let mut entries = vec![];
+                        for &(reg, pred) in incoming {
+                            if !seen.contains(&pred) {
+                                seen.push(pred);
+                                entries.push(format!("[ {}, %{} ]", value(reg), pred));
+                            }
+                        }
+                        format!("phi {} {}", ty, entries.join(", "))
+                    }
+                };
+                out += &format!("  {}{}\n", dest, line);
+            }
+
+            let term = match &block.term {
+                Terminator::Br(target) => format!("br label %{}", target),
+                Terminator::CondBr(_, then, otherwise) if then == otherwise => {
+                    format!("br label %{}", then)
+                }
+                Terminator::CondBr(cond, then, otherwise) => format!(
+                    "br i1 {}, label %{}, label %{}",
+                    value(*cond),
+                    then,
+                    otherwise
+                ),
+                Terminator::Ret(Some(reg)) => format!("ret {}", typed(&mut value, *reg)),
+                Terminator::Ret(None) => "ret void".into(),
+                Terminator::Unreachable => "unreachable".into(),
+                Terminator::Trap => "call void @lune_fail(ptr @lune.bounds)\n  unreachable".into(),
+            };
+            out += &format!("  {}\n", term);
+        }
+        out + "}\n"
+    }
+}
+
+impl Default for LlvmGen {
+    fn default() -> LlvmGen {
+        LlvmGen::new()
+    }
+}
@@ -0,0 +1,514 @@
+//! AST traversal.
+//!
+//! * `Visitor<T>` walks the tree by reference. Every method defaults to
+//!   visiting the node's children through the matching `walk_*` function,
+//!   so a visitor only overrides the nodes it cares about.
+//! * `VisitorMut` walks the tree with mutable access for in-place rewrites.
+//! * `Folder` rebuilds the tree node by node, each method returning the id
+//!   of the replacement node.
+//!
+//! Nodes are addressed by their arena ids, so every method is handed the
+//! `AST` alongside the id. Overridden methods call the `walk_*`/`fold_*`
+//! functions themselves when they still want to recurse into the children.
+
+use super::ast::*;
+
+///
+/// Visitor trait that structs can inherit from to walk the AST
+///
+pub trait Visitor<T: Default> {
+    fn visit_stmt(&mut self, ast: &AST, stmt: StmtId) -> T {
+        walk_stmt(self, ast, stmt);
+        T::default()
+    }
+
+    fn visit_expr(&mut self, ast: &AST, expr: ExprId) -> T {
+        walk_expr(self, ast, expr);
+        T::default()
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) -> T {
+        walk_pattern(self, pattern);
+        T::default()
+    }
+
+    fn visit_name(&mut self, _name: &Name) -> T {
+        T::default()
+    }
+}
+
+pub fn walk_block<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, ast: &AST, stmts: &[StmtId]) {
+    for &stmt in stmts {
+        visitor.visit_stmt(ast, stmt);
+    }
+}
+
+pub fn walk_stmt<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, ast: &AST, stmt: StmtId) {
+    match &ast[stmt].kind {
+        StmtKind::Expr(expr) => {
+            visitor.visit_expr(ast, *expr);
+        }
+        StmtKind::VarDeclaration(name, _, value) | StmtKind::ConstDeclaration(name, _, value) => {
+            visitor.visit_name(name);
+            visitor.visit_expr(ast, *value);
+        }
+        StmtKind::Assignment(target, value) => {
+            visitor.visit_expr(ast, *target);
+            visitor.visit_expr(ast, *value);
+        }
+        StmtKind::If(cond, then, otherwise) => {
+            visitor.visit_expr(ast, *cond);
+            walk_block(visitor, ast, then);
+            if let Some(otherwise) = otherwise {
+                walk_block(visitor, ast, otherwise);
+            }
+        }
+        StmtKind::While(cond, body) => {
+            visitor.visit_expr(ast, *cond);
+            walk_block(visitor, ast, body);
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(ast, *value);
+            }
+        }
+        StmtKind::ProcDeclaration(proc) => {
+            visitor.visit_name(&proc.name);
+            for param in &proc.params {
+                visitor.visit_name(&param.name);
+            }
+            walk_block(visitor, ast, &proc.body);
+        }
+        StmtKind::StructDeclaration(struct_) => {
+            visitor.visit_name(&struct_.name);
+            for field in &struct_.fields {
+                visitor.visit_name(&field.name);
+            }
+        }
+        StmtKind::EnumDeclaration(enum_) => {
+            visitor.visit_name(&enum_.name);
+            for variant in &enum_.variants {
+                visitor.visit_name(&variant.name);
+                for field in &variant.fields {
+                    visitor.visit_name(&field.name);
+                }
+            }
+        }
+        StmtKind::Case(scrutinee, arms) => {
+            visitor.visit_expr(ast, *scrutinee);
+            for arm in arms {
+                visitor.visit_pattern(&arm.pattern);
+                walk_block(visitor, ast, &arm.body);
+            }
+        }
+    }
+}
+
+pub fn walk_expr<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, ast: &AST, expr: ExprId) {
+    match &ast[expr].kind {
+        ExprKind::IntLit(_) | ExprKind::StringLit(_) | ExprKind::BoolLit(_) => {}
+        ExprKind::Variable(name) => {
+            visitor.visit_name(name);
+        }
+        ExprKind::Call(name, args) => {
+            visitor.visit_name(name);
+            for &arg in args {
+                visitor.visit_expr(ast, arg);
+            }
+        }
+        ExprKind::UnaryOp(_, rhs) => {
+            visitor.visit_expr(ast, *rhs);
+        }
+        ExprKind::BinOp(lhs, _, rhs) => {
+            visitor.visit_expr(ast, *lhs);
+            visitor.visit_expr(ast, *rhs);
+        }
+        ExprKind::StructLit(name, fields) => {
+            visitor.visit_name(name);
+            for (field, value) in fields {
+                visitor.visit_name(field);
+                visitor.visit_expr(ast, *value);
+            }
+        }
+        ExprKind::FieldAccess(object, field) => {
+            visitor.visit_expr(ast, *object);
+            visitor.visit_name(field);
+        }
+        ExprKind::Case(scrutinee, arms) => {
+            visitor.visit_expr(ast, *scrutinee);
+            for arm in arms {
+                visitor.visit_pattern(&arm.pattern);
+                visitor.visit_expr(ast, arm.body);
+            }
+        }
+        ExprKind::ArrayLit(elements) => {
+            for &element in elements {
+                visitor.visit_expr(ast, element);
+            }
+        }
+        ExprKind::Index(object, index) => {
+            visitor.visit_expr(ast, *object);
+            visitor.visit_expr(ast, *index);
+        }
+        ExprKind::Slice(object, start, end) => {
+            visitor.visit_expr(ast, *object);
+            for &bound in start.iter().chain(end) {
+                visitor.visit_expr(ast, bound);
+            }
+        }
+    }
+}
+
+pub fn walk_pattern<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    if let PatternKind::Variant(name, bindings) = &pattern.kind {
+        visitor.visit_name(name);
+        for binding in bindings {
+            visitor.visit_name(binding);
+        }
+    }
+}
+
+///
+/// Visitor that can rewrite the AST in place
+///
+pub trait VisitorMut {
+    /// Visit a list of statements, override to add or remove statements
+    fn visit_block_mut(&mut self, ast: &mut AST, stmts: &mut Block) {
+        walk_block_mut(self, ast, stmts);
+    }
+
+    fn visit_stmt_mut(&mut self, ast: &mut AST, stmt: StmtId) {
+        walk_stmt_mut(self, ast, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, ast: &mut AST, expr: ExprId) {
+        walk_expr_mut(self, ast, expr);
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern);
+    }
+
+    fn visit_name_mut(&mut self, _name: &mut Name) {}
+}
+
+/// Visit the top level statements of `ast`
+pub fn visit_ast_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut AST) {
+    let mut root = std::mem::take(&mut ast.root);
+    visitor.visit_block_mut(ast, &mut root);
+    ast.root = root;
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut AST, stmts: &mut Block) {
+    for &stmt in stmts.iter() {
+        visitor.visit_stmt_mut(ast, stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut AST, stmt: StmtId) {
+    // The kind is taken out while its children are visited, so the visitor
+    // has the rest of the arena to itself
+    let mut kind = std::mem::replace(&mut ast[stmt].kind, StmtKind::Return(None));
+    match &mut kind {
+        StmtKind::Expr(expr) => visitor.visit_expr_mut(ast, *expr),
+        StmtKind::VarDeclaration(name, _, value) | StmtKind::ConstDeclaration(name, _, value) => {
+            visitor.visit_name_mut(name);
+            visitor.visit_expr_mut(ast, *value);
+        }
+        StmtKind::Assignment(target, value) => {
+            visitor.visit_expr_mut(ast, *target);
+            visitor.visit_expr_mut(ast, *value);
+        }
+        StmtKind::If(cond, then, otherwise) => {
+            visitor.visit_expr_mut(ast, *cond);
+            visitor.visit_block_mut(ast, then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_block_mut(ast, otherwise);
+            }
+        }
+        StmtKind::While(cond, body) => {
+            visitor.visit_expr_mut(ast, *cond);
+            visitor.visit_block_mut(ast, body);
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(ast, *value);
+            }
+        }
+        StmtKind::ProcDeclaration(proc) => {
+            visitor.visit_name_mut(&mut proc.name);
+            for param in &mut proc.params {
+                visitor.visit_name_mut(&mut param.name);
+            }
+            visitor.visit_block_mut(ast, &mut proc.body);
+        }
+        StmtKind::StructDeclaration(struct_) => {
+            visitor.visit_name_mut(&mut struct_.name);
+            for field in &mut struct_.fields {
+                visitor.visit_name_mut(&mut field.name);
+            }
+        }
+        StmtKind::EnumDeclaration(enum_) => {
+            visitor.visit_name_mut(&mut enum_.name);
+            for variant in &mut enum_.variants {
+                visitor.visit_name_mut(&mut variant.name);
+                for field in &mut variant.fields {
+                    visitor.visit_name_mut(&mut field.name);
+                }
+            }
+        }
+        StmtKind::Case(scrutinee, arms) => {
+            visitor.visit_expr_mut(ast, *scrutinee);
+            for arm in arms {
+                visitor.visit_pattern_mut(&mut arm.pattern);
+                visitor.visit_block_mut(ast, &mut arm.body);
+            }
+        }
+    }
+    ast[stmt].kind = kind;
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut AST, expr: ExprId) {
+    let mut kind = std::mem::replace(&mut ast[expr].kind, ExprKind::BoolLit(false));
+    match &mut kind {
+        ExprKind::IntLit(_) | ExprKind::StringLit(_) | ExprKind::BoolLit(_) => {}
+        ExprKind::Variable(name) => visitor.visit_name_mut(name),
+        ExprKind::Call(name, args) => {
+            visitor.visit_name_mut(name);
+            for &arg in args.iter() {
+                visitor.visit_expr_mut(ast, arg);
+            }
+        }
+        ExprKind::UnaryOp(_, rhs) => visitor.visit_expr_mut(ast, *rhs),
+        ExprKind::BinOp(lhs, _, rhs) => {
+            visitor.visit_expr_mut(ast, *lhs);
+            visitor.visit_expr_mut(ast, *rhs);
+        }
+        ExprKind::StructLit(name, fields) => {
+            visitor.visit_name_mut(name);
+            for (field, value) in fields.iter_mut() {
+                visitor.visit_name_mut(field);
+                visitor.visit_expr_mut(ast, *value);
+            }
+        }
+        ExprKind::FieldAccess(object, field) => {
+            visitor.visit_expr_mut(ast, *object);
+            visitor.visit_name_mut(field);
+        }
+        ExprKind::Case(scrutinee, arms) => {
+            visitor.visit_expr_mut(ast, *scrutinee);
+            for arm in arms.iter_mut() {
+                visitor.visit_pattern_mut(&mut arm.pattern);
+                visitor.visit_expr_mut(ast, arm.body);
+            }
+        }
+        ExprKind::ArrayLit(elements) => {
+            for &element in elements.iter() {
+                visitor.visit_expr_mut(ast, element);
+            }
+        }
+        ExprKind::Index(object, index) => {
+            visitor.visit_expr_mut(ast, *object);
+            visitor.visit_expr_mut(ast, *index);
+        }
+        ExprKind::Slice(object, start, end) => {
+            visitor.visit_expr_mut(ast, *object);
+            for &bound in start.iter().chain(end.iter()) {
+                visitor.visit_expr_mut(ast, bound);
+            }
+        }
+    }
+    ast[expr].kind = kind;
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    if let PatternKind::Variant(name, bindings) = &mut pattern.kind {
+        visitor.visit_name_mut(name);
+        for binding in bindings {
+            visitor.visit_name_mut(binding);
+        }
+    }
+}
+
+///
+/// Rebuilds the AST, each method returns the id of the replacement node.
+/// Returning a freshly added node replaces the old one, which is dropped
+/// when `fold_ast` compacts the arenas.
+///
+pub trait Folder {
+    /// Fold a list of statements, override to add or remove statements
+    fn fold_block(&mut self, ast: &mut AST, stmts: Block) -> Block {
+        fold_block(self, ast, stmts)
+    }
+
+    fn fold_stmt(&mut self, ast: &mut AST, stmt: StmtId) -> StmtId {
+        fold_stmt(self, ast, stmt)
+    }
+
+    fn fold_expr(&mut self, ast: &mut AST, expr: ExprId) -> ExprId {
+        fold_expr(self, ast, expr)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        fold_pattern(self, pattern)
+    }
+
+    fn fold_name(&mut self, name: Name) -> Name {
+        name
+    }
+}
+
+pub fn fold_ast<F: Folder + ?Sized>(folder: &mut F, ast: AST) -> AST {
+    let mut ast = ast;
+    let root = std::mem::take(&mut ast.root);
+    ast.root = folder.fold_block(&mut ast, root);
+    ast.compact()
+}
+
+pub fn fold_block<F: Folder + ?Sized>(folder: &mut F, ast: &mut AST, stmts: Block) -> Block {
+    stmts.into_iter().map(|stmt| folder.fold_stmt(ast, stmt)).collect()
+}
+
+pub fn fold_stmt<F: Folder + ?Sized>(folder: &mut F, ast: &mut AST, stmt: StmtId) -> StmtId {
+    let kind = match std::mem::replace(&mut ast[stmt].kind, StmtKind::Return(None)) {
+        StmtKind::Expr(expr) => StmtKind::Expr(folder.fold_expr(ast, expr)),
+        StmtKind::VarDeclaration(name, type_, value) => {
+            StmtKind::VarDeclaration(folder.fold_name(name), type_, folder.fold_expr(ast, value))
+        }
+        StmtKind::ConstDeclaration(name, type_, value) => {
+            StmtKind::ConstDeclaration(folder.fold_name(name), type_, folder.fold_expr(ast, value))
+        }
+        StmtKind::Assignment(target, value) => {
+            let target = folder.fold_expr(ast, target);
+            StmtKind::Assignment(target, folder.fold_expr(ast, value))
+        }
+        StmtKind::If(cond, then, otherwise) => StmtKind::If(
+            folder.fold_expr(ast, cond),
+            folder.fold_block(ast, then),
+            otherwise.map(|otherwise| folder.fold_block(ast, otherwise)),
+        ),
+        StmtKind::While(cond, body) => {
+            StmtKind::While(folder.fold_expr(ast, cond), folder.fold_block(ast, body))
+        }
+        StmtKind::Return(value) => {
+            StmtKind::Return(value.map(|value| folder.fold_expr(ast, value)))
+        }
+        StmtKind::ProcDeclaration(proc) => StmtKind::ProcDeclaration(Proc {
+            name: folder.fold_name(proc.name),
+            params: proc
+                .params
+                .into_iter()
+                .map(|param| Param {
+                    name: folder.fold_name(param.name),
+                    type_: param.type_,
+                })
+                .collect(),
+            return_type: proc.return_type,
+            body: folder.fold_block(ast, proc.body),
+        }),
+        StmtKind::StructDeclaration(struct_) => StmtKind::StructDeclaration(Struct {
+            name: folder.fold_name(struct_.name),
+            fields: struct_
+                .fields
+                .into_iter()
+                .map(|field| Field {
+                    name: folder.fold_name(field.name),
+                    type_: field.type_,
+                })
+                .collect(),
+        }),
+        StmtKind::EnumDeclaration(enum_) => StmtKind::EnumDeclaration(Enum {
+            name: folder.fold_name(enum_.name),
+            variants: enum_
+                .variants
+                .into_iter()
+                .map(|variant| Variant {
+                    name: folder.fold_name(variant.name),
+                    fields: variant
+                        .fields
+                        .into_iter()
+                        .map(|field| Field {
+                            name: folder.fold_name(field.name),
+                            type_: field.type_,
+                        })
+                        .collect(),
+                    value: variant.value,
+                })
+                .collect(),
+        }),
+        StmtKind::Case(scrutinee, arms) => StmtKind::Case(
+            folder.fold_expr(ast, scrutinee),
+            arms.into_iter()
+                .map(|arm| Arm {
+                    pattern: folder.fold_pattern(arm.pattern),
+                    body: folder.fold_block(ast, arm.body),
+                })
+                .collect(),
+        ),
+    };
+
+    ast[stmt].kind = kind;
+    stmt
+}
+
+pub fn fold_expr<F: Folder + ?Sized>(folder: &mut F, ast: &mut AST, expr: ExprId) -> ExprId {
+    let kind = match std::mem::replace(&mut ast[expr].kind, ExprKind::BoolLit(false)) {
+        kind @ (ExprKind::IntLit(_) | ExprKind::StringLit(_) | ExprKind::BoolLit(_)) => kind,
+        ExprKind::Variable(name) => ExprKind::Variable(folder.fold_name(name)),
+        ExprKind::Call(name, args) => ExprKind::Call(
+            folder.fold_name(name),
+            args.into_iter().map(|arg| folder.fold_expr(ast, arg)).collect(),
+        ),
+        ExprKind::UnaryOp(op, rhs) => ExprKind::UnaryOp(op, folder.fold_expr(ast, rhs)),
+        ExprKind::BinOp(lhs, op, rhs) => {
+            let lhs = folder.fold_expr(ast, lhs);
+            ExprKind::BinOp(lhs, op, folder.fold_expr(ast, rhs))
+        }
+        ExprKind::StructLit(name, fields) => ExprKind::StructLit(
+            folder.fold_name(name),
+            fields
+                .into_iter()
+                .map(|(field, value)| (folder.fold_name(field), folder.fold_expr(ast, value)))
+                .collect(),
+        ),
+        ExprKind::FieldAccess(object, field) => {
+            let object = folder.fold_expr(ast, object);
+            ExprKind::FieldAccess(object, folder.fold_name(field))
+        }
+        ExprKind::Case(scrutinee, arms) => ExprKind::Case(
+            folder.fold_expr(ast, scrutinee),
+            arms.into_iter()
+                .map(|arm| Arm {
+                    pattern: folder.fold_pattern(arm.pattern),
+                    body: folder.fold_expr(ast, arm.body),
+                })
+                .collect(),
+        ),
+        ExprKind::ArrayLit(elements) => ExprKind::ArrayLit(
+            elements.into_iter().map(|element| folder.fold_expr(ast, element)).collect(),
+        ),
+        ExprKind::Index(object, index) => {
+            let object = folder.fold_expr(ast, object);
+            ExprKind::Index(object, folder.fold_expr(ast, index))
+        }
+        ExprKind::Slice(object, start, end) => {
+            let object = folder.fold_expr(ast, object);
+            let start = start.map(|start| folder.fold_expr(ast, start));
+            ExprKind::Slice(object, start, end.map(|end| folder.fold_expr(ast, end)))
+        }
+    };
+
+    ast[expr].kind = kind;
+    expr
+}
+
+pub fn fold_pattern<F: Folder + ?Sized>(folder: &mut F, pattern: Pattern) -> Pattern {
+    let kind = match pattern.kind {
+        PatternKind::Variant(name, bindings) => PatternKind::Variant(
+            folder.fold_name(name),
+            bindings.into_iter().map(|binding| folder.fold_name(binding)).collect(),
+        ),
+        kind => kind,
+    };
+    Pattern { kind, ..pattern }
+}
@@ -0,0 +1,461 @@
+//! WebAssembly text generation.
+//!
+//! Emits a module in the WebAssembly text format (`.wat`) from an IR module.
+//! The host provides one import, `env.print`, which writes the given number
+//! of bytes of the exported `memory` starting at the given address. Running
+//! the program is calling the exported `main`.
+//!
+//! Ints and bools are `i32`. Division traps on zero and on
+//! `-2147483648 / -1` as `i32.div_s` does. Strings are NUL terminated bytes
+//! in linear memory, laid out by data segments after the runtime's own
+//! data, and a string value is its address. Globals become mutable wasm
+//! globals.
+//!
+//! Addresses are `i32`s too. Globals holding memory come after the strings,
+//! each an immutable wasm global holding its address. The memory of
+//! `alloca`s is on a stack growing down from the end of memory: a proc
+//! using any moves `$lune_sp` down by its frame on entry and back up when
+//! it returns. `memcopy` is a `memory.copy` and `trap` is `unreachable`.
+//!
+//! WebAssembly only has structured control flow, so the blocks of a proc
+//! are dispatched on from a loop: a `br_table` on the `$block` local jumps
+//! to the end of the nested `block` preceding the code of the target. A
+//! branch stores the target's index in `$block` and restarts the loop, or
+//! falls through when it targets the next block. Phis are locals set on the
+//! edges leading to their block, with all their values pushed on the stack
+//! before any is set.
+//!
+//! Every symbol is encoded with `cgen::encode`, so none clash with the
+//! runtime's functions.
+
+use std::collections::HashMap;
+
+use super::ast::{BinOpKind, UnaryOpKind};
+use super::cgen::encode;
+use super::ir::*;
+
+/// Addresses of the runtime's data: the strings it prints for bools, a
+/// newline, and room to format an int with its sign and a newline
+const TRUE: u32 = 0;
+const FALSE: u32 = 5;
+const NEWLINE: u32 = 11;
+const SCRATCH: u32 = 12;
+/// Where string constants start
+const STRINGS: u32 = 24;
+/// Pages of memory for the stack of `alloca`s
+const STACK_PAGES: u32 = 16;
+
+const RUNTIME: &str = r#"  (import "env" "print" (func $print (param i32 i32)))
+
+  (data (i32.const 0) "true\00false\00\0a")
+
+  ;; Print the int and a newline, formatting it backwards from the end of
+  ;; the scratch space
+  (func $lune_print_int (param $value i32)
+    (local $n i64) (local $at i32)
+    (i32.store8 (i32.const {end}) (i32.const 10))
+    (local.set $at (i32.const {end}))
+    (local.set $n (i64.extend_i32_s (local.get $value)))
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (local.set $n (i64.sub (i64.const 0) (local.get $n)))))
+    (loop $digits
+      (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+      (i32.store8 (local.get $at)
+        (i32.add (i32.wrap_i64 (i64.rem_u (local.get $n) (i64.const 10))) (i32.const 48)))
+      (local.set $n (i64.div_u (local.get $n) (i64.const 10)))
+      (br_if $digits (i64.ne (local.get $n) (i64.const 0))))
+    (if (i32.lt_s (local.get $value) (i32.const 0))
+      (then
+        (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+        (i32.store8 (local.get $at) (i32.const 45))))
+    (call $print (local.get $at) (i32.sub (i32.const {after}) (local.get $at))))
+
+  ;; Print the string at $s and a newline
+  (func $lune_print_str (param $s i32)
+    (local $len i32)
+    (block $done
+      (loop $scan
+        (br_if $done (i32.eqz (i32.load8_u (i32.add (local.get $s) (local.get $len)))))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (br $scan)))
+    (call $print (local.get $s) (local.get $len))
+    (call $print (i32.const {newline}) (i32.const 1)))
+
+  (func $lune_print_bool (param $b i32)
+    (call $lune_print_str (select (i32.const {true}) (i32.const {false}) (local.get $b))))
+
+  ;; Whether the strings at $a and $b are equal
+  (func $lune_str_eq (param $a i32) (param $b i32) (result i32)
+    (local $c i32)
+    (loop $next
+      (local.set $c (i32.load8_u (local.get $a)))
+      (if (i32.ne (local.get $c) (i32.load8_u (local.get $b)))
+        (then (return (i32.const 0))))
+      (if (i32.eqz (local.get $c))
+        (then (return (i32.const 1))))
+      (local.set $a (i32.add (local.get $a) (i32.const 1)))
+      (local.set $b (i32.add (local.get $b) (i32.const 1)))
+      (br $next))
+    (unreachable))
+"#;
+
+/// Contents of a WAT string with its NUL terminator, escaping everything
+/// outside printable ASCII as hex
+fn wat_string(value: &str) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+            _ => out += &format!("\\{:02x}", byte),
+        }
+    }
+    out + "\\00"
+}
+
+/// Generates WebAssembly text from a verified IR module
+pub struct WatGen {
+    /// String constants and their addresses
+    strings: Vec<(String, u32)>,
+    /// Index of every block of the proc being generated
+    blocks: HashMap<BlockId, usize>,
+    /// Frame offset of the memory of every `alloca` of the proc being generated
+    allocas: HashMap<Reg, u32>,
+    /// Bytes of the frame of the proc being generated
+    frame: u32,
+    out: String,
+}
+
+impl WatGen {
+    pub fn new() -> WatGen {
+        WatGen {
+            strings: vec![],
+            blocks: HashMap::new(),
+            allocas: HashMap::new(),
+            frame: 0,
+            out: String::new(),
+        }
+    }
+
+    /// Generate the WebAssembly module for `module`
+    pub fn generate(&mut self, module: &Module) -> String {
+        let runtime = RUNTIME
+            .replace("{end}", &(SCRATCH + 11).to_string())
+            .replace("{after}", &(SCRATCH + 12).to_string())
+            .replace("{newline}", &NEWLINE.to_string())
+            .replace("{true}", &TRUE.to_string())
+            .replace("{false}", &FALSE.to_string());
+
+        self.out = String::new();
+        for proc in &module.procs {
+            self.proc(proc);
+        }
+        let procs = std::mem::take(&mut self.out);
+
+        self.out = "(module\n".into();
+        self.out += &runtime;
+        let mut end = self
+            .strings
+            .last()
+            .map_or(STRINGS, |(value, at)| at + value.len() as u32 + 1);
+        let mut memory = vec![];
+        for global in module.globals.iter().filter(|global| global.ty == Ty::Ptr) {
+            let at = end.div_ceil(8) * 8;
+            memory.push((&global.name, at));
+            end = at + global.size;
+        }
+        let allocas = module
+            .procs
+            .iter()
+            .flat_map(|proc| &proc.blocks)
+            .flat_map(|block| &block.insts)
+            .any(|inst| matches!(inst.op, Op::Alloca(_)));
+        let mut pages = end.div_ceil(65536).max(1);
+        if allocas {
+            pages += STACK_PAGES;
+        }
+        self.out += &format!("\n  (memory (export \"memory\") {})\n", pages);
+        for (value, at) in &self.strings {
+            self.out += &format!("  (data (i32.const {}) \"{}\")\n", at, wat_string(value));
+        }
+        if !module.globals.is_empty() || allocas {
+            self.out += "\n";
+        }
+        for global in module.globals.iter().filter(|global| global.ty != Ty::Ptr) {
+            self.out += &format!(
+                "  (global ${} (mut i32) (i32.const 0))\n",
+                encode(&global.name)
+            );
+        }
+        for (name, at) in memory {
+            self.out += &format!("  (global ${} i32 (i32.const {}))\n", encode(name), at);
+        }
+        if allocas {
+            self.out += &format!(
+                "  (global $lune_sp (mut i32) (i32.const {}))\n",
+                pages * 65536
+            );
+        }
+        self.out += &procs;
+        self.out += &format!(
+            "\n  (func (export \"main\")\n    call ${})\n)\n",
+            encode(&module.entry)
+        );
+        std::mem::take(&mut self.out)
+    }
+
+    fn line(&mut self, depth: usize, line: &str) {
+        self.out += &"  ".repeat(depth);
+        self.out += line;
+        self.out += "\n";
+    }
+
+    fn string(&mut self, value: &str) -> u32 {
+        if let Some((_, at)) = self.strings.iter().find(|(other, _)| other == value) {
+            return *at;
+        }
+        let at = self
+            .strings
+            .last()
+            .map_or(STRINGS, |(last, at)| at + last.len() as u32 + 1);
+        self.strings.push((value.into(), at));
+        at
+    }
+
+    fn proc(&mut self, proc: &Proc) {
+        self.blocks = proc
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| (block.id, i))
+            .collect();
+
+        let mut signature = format!("\n  (func ${}", encode(&proc.name));
+        for (reg, _) in &proc.params {
+            signature += &format!(" (param $r{} i32)", reg.0);
+        }
+        if proc.ret != Ty::Void {
+            signature += " (result i32)";
+        }
+        self.out += &signature;
+        self.out += "\n";
+
+        let mut locals = String::from("(local $block i32)");
+        self.allocas.clear();
+        self.frame = 0;
+        for inst in proc.blocks.iter().flat_map(|block| &block.insts) {
+            if let Some(dest) = inst.dest {
+                locals += &format!(" (local $r{} i32)", dest.0);
+            }
+            if let (Some(dest), Op::Alloca(size)) = (inst.dest, &inst.op) {
+                self.allocas.insert(dest, self.frame);
+                self.frame += size.div_ceil(8) * 8;
+            }
+        }
+        if self.frame > 0 {
+            locals += " (local $frame i32)";
+        }
+        self.line(2, &locals);
+        if self.frame > 0 {
+            self.line(2, "global.get $lune_sp");
+            self.line(2, &format!("i32.const {}", self.frame));
+            self.line(2, "i32.sub");
+            self.line(2, "local.tee $frame");
+            self.line(2, "global.set $lune_sp");
+        }
+
+        let n = proc.blocks.len();
+        self.line(2, "loop $dispatch");
+        for i in (0..n).rev() {
+            self.line(3, &format!("block $bb{}", i));
+        }
+        self.line(4, "local.get $block");
+        let labels: Vec<String> = (0..n).map(|i| format!("$bb{}", i)).collect();
+        self.line(4, &format!("br_table {}", labels.join(" ")));
+        for (i, block) in proc.blocks.iter().enumerate() {
+            self.line(3, &format!("end ;; {}", block.id));
+            for inst in &block.insts {
+                self.inst(inst);
+            }
+            self.terminator(proc, i, block);
+        }
+        self.line(2, "end");
+        if proc.ret != Ty::Void {
+            self.line(2, "unreachable");
+        }
+        self.out.pop();
+        self.out += ")\n";
+    }
+
+    fn get(&mut self, reg: Reg) {
+        self.line(3, &format!("local.get $r{}", reg.0));
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match &inst.op {
+            Op::Const(Const::Int(n)) => self.line(3, &format!("i32.const {}", n)),
+            Op::Const(Const::Bool(b)) => self.line(3, &format!("i32.const {}", *b as i32)),
+            Op::Const(Const::Str(s)) => {
+                let at = self.string(s);
+                self.line(3, &format!("i32.const {}", at));
+            }
+            Op::Unary(UnaryOpKind::Neg, rhs) => {
+                self.line(3, "i32.const 0");
+                self.get(*rhs);
+                self.line(3, "i32.sub");
+            }
+            Op::Unary(UnaryOpKind::Not, rhs) => {
+                self.get(*rhs);
+                self.line(3, "i32.eqz");
+            }
+            Op::Binary(op, lhs, rhs) => {
+                self.get(*lhs);
+                self.get(*rhs);
+                let line = match op {
+                    BinOpKind::Eq | BinOpKind::Ne if inst.ty == Ty::Str => {
+                        self.line(3, "call $lune_str_eq");
+                        if *op == BinOpKind::Eq {
+                            None
+                        } else {
+                            Some("i32.eqz")
+                        }
+                    }
+                    BinOpKind::Add => Some("i32.add"),
+                    BinOpKind::Sub => Some("i32.sub"),
+                    BinOpKind::Mul => Some("i32.mul"),
+                    BinOpKind::Div => Some("i32.div_s"),
+                    BinOpKind::Eq => Some("i32.eq"),
+                    BinOpKind::Ne => Some("i32.ne"),
+                    BinOpKind::Lt => Some("i32.lt_s"),
+                    BinOpKind::Le => Some("i32.le_s"),
+                    BinOpKind::Gt => Some("i32.gt_s"),
+                    BinOpKind::Ge => Some("i32.ge_s"),
+                };
+                if let Some(line) = line {
+                    self.line(3, line);
+                }
+            }
+            Op::Call(name, args) => {
+                for &arg in args {
+                    self.get(arg);
+                }
+                self.line(3, &format!("call ${}", encode(name)));
+            }
+            Op::Load(name) => self.line(3, &format!("global.get ${}", encode(name))),
+            Op::Store(name, value) => {
+                self.get(*value);
+                self.line(3, &format!("global.set ${}", encode(name)));
+            }
+            Op::Print(value) => {
+                let helper = match inst.ty {
+                    Ty::Int => "lune_print_int",
+                    Ty::Bool => "lune_print_bool",
+                    Ty::Str => "lune_print_str",
+                    Ty::Ptr | Ty::Void => unreachable!("only ints, bools and strings are printed"),
+                };
+                self.get(*value);
+                self.line(3, &format!("call ${}", helper));
+            }
+            Op::Copy(value) => self.get(*value),
+            Op::Alloca(_) => {
+                self.line(3, "local.get $frame");
+                self.line(
+                    3,
+                    &format!("i32.const {}", self.allocas[&inst.dest.unwrap()]),
+                );
+                self.line(3, "i32.add");
+            }
+            Op::Addr(name) => self.line(3, &format!("global.get ${}", encode(name))),
+            Op::Offset(address, offset) => {
+                self.get(*address);
+                self.line(3, &format!("i32.const {}", offset));
+                self.line(3, "i32.add");
+            }
+            Op::Index(address, index, size) => {
+                self.get(*address);
+                self.get(*index);
+                self.line(3, &format!("i32.const {}", size));
+                self.line(3, "i32.mul");
+                self.line(3, "i32.add");
+            }
+            Op::Read(address) => {
+                self.get(*address);
+                self.line(3, "i32.load");
+            }
+            Op::Write(value, address) => {
+                self.get(*address);
+                self.get(*value);
+                self.line(3, "i32.store");
+            }
+            Op::MemCopy(to, from, size) => {
+                self.get(*to);
+                self.get(*from);
+                self.line(3, &format!("i32.const {}", size));
+                self.line(3, "memory.copy");
+            }
+            // Set on the edges leading here
+            Op::Phi(_) => return,
+        }
+        if let Some(dest) = inst.dest {
+            self.line(3, &format!("local.set $r{}", dest.0));
+        }
+    }
+
+    /// Set the phis of `target` for the edge from `from`, taking the `nth`
+    /// value from `from` where it branches there more than once, then go to
+    /// `target`. `next` is the index of the block after the one branching.
+    fn edge(&mut self, proc: &Proc, from: BlockId, target: BlockId, nth: usize, next: usize) {
+        let phis = proc.phi_moves(from, target, nth);
+        for &(_, value) in &phis {
+            self.get(value);
+        }
+        for &(dest, _) in phis.iter().rev() {
+            self.line(3, &format!("local.set $r{}", dest.0));
+        }
+
+        let index = self.blocks[&target];
+        if index != next {
+            self.line(3, &format!("i32.const {}", index));
+            self.line(3, "local.set $block");
+            self.line(3, "br $dispatch");
+        }
+    }
+
+    fn terminator(&mut self, proc: &Proc, index: usize, block: &Block) {
+        let next = index + 1;
+        match block.term {
+            Terminator::Br(target) => self.edge(proc, block.id, target, 0, next),
+            Terminator::CondBr(cond, then, otherwise) => {
+                self.get(cond);
+                self.line(3, "if");
+                self.edge(proc, block.id, then, 0, usize::MAX);
+                self.line(3, "end");
+                self.edge(
+                    proc,
+                    block.id,
+                    otherwise,
+                    (then == otherwise) as usize,
+                    next,
+                );
+            }
+            Terminator::Ret(value) => {
+                if let Some(value) = value {
+                    self.get(value);
+                }
+                if self.frame > 0 {
+                    self.line(3, "local.get $frame");
+                    self.line(3, &format!("i32.const {}", self.frame));
+                    self.line(3, "i32.add");
+                    self.line(3, "global.set $lune_sp");
+                }
+                self.line(3, "return");
+            }
+            Terminator::Unreachable | Terminator::Trap => self.line(3, "unreachable"),
+        }
+    }
+}
+
+impl Default for WatGen {
+    fn default() -> WatGen {
+        WatGen::new()
+    }
+}
@@ -0,0 +1,266 @@
+//! Incremental compilation cache of `lune build` for projects.
+//!
+//! Every module of a project is compiled to its own object file under
+//! `target/.cache`, with a record of what it was compiled from: a hash of its
+//! tokens and where they are, its checked interface, and the interfaces of
+//! the modules it can see, those it imports directly or not. A module is
+//! only compiled again when one of those changed, the objects of the others
+//! are reused and linked with it.
+//!
+//! The interface of a module is the signature of each of its top level
+//! items, with the values of constants as they are compiled into the
+//! modules using them. The whole program is still checked on every build.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+use crate::backend::ast::*;
+use crate::frontend::lexer::lexer::Lexer;
+use crate::frontend::lexer::token::TokenKind;
+use crate::frontend::modules::SourceFile;
+use crate::json::Json;
+use crate::frontend::analysis::Analysis;
+
+/// FNV-1a hash of `bytes` in hex, stable across builds of the compiler
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// A module of a checked program, as the cache sees it
+pub struct ModuleInfo {
+    /// Index of its file in the program
+    pub file: usize,
+    /// Name of the module, empty for the file being compiled
+    pub module: String,
+    /// Top level statements of the module in the folded AST
+    pub root: Block,
+    /// Hash of the path of the file and of its tokens with their positions
+    pub tokens: String,
+    /// Signatures of the top level items, one per line
+    pub interface: String,
+}
+
+/// The modules of a checked program in the order their top level statements
+/// run
+pub fn modules(analysis: &Analysis) -> Vec<ModuleInfo> {
+    let ast = &analysis.folded;
+    let mut roots: Vec<(usize, Block)> = vec![];
+    for &id in &ast.root {
+        let file = analysis
+            .files
+            .iter()
+            .rposition(|file| file.base <= ast[id].span.start);
+        let file = file.unwrap_or(0);
+        match roots.iter_mut().find(|(other, _)| *other == file) {
+            Some((_, root)) => root.push(id),
+            None => roots.push((file, vec![id])),
+        }
+    }
+    // Modules without statements run nothing, so their place doesn't matter
+    for file in 0..analysis.files.len() {
+        if !roots.iter().any(|(other, _)| *other == file) {
+            roots.push((file, vec![]));
+        }
+    }
+
+    let modules = roots.into_iter().map(|(file, root)| ModuleInfo {
+        file,
+        module: analysis.files[file].module.clone(),
+        tokens: tokens(&analysis.files[file]),
+        interface: interface(analysis, &root),
+        root,
+    });
+    modules.collect()
+}
+
+fn tokens(file: &SourceFile) -> String {
+    let mut text = format!("{}\n", file.path.display());
+    let tokens = Lexer::new(&file.source).scan_all().unwrap_or_default();
+    // Doc comments don't change the code, their positions are those of the
+    // tokens after them
+    let tokens = tokens
+        .iter()
+        .filter(|token| !matches!(token.kind, TokenKind::DocComment(_)));
+    for token in tokens {
+        // Positions end up in the messages of failed runtime checks
+        let (line, column) = file.lines.position(&file.source, token.start);
+        text += &format!("{}:{} {:?}\n", line, column, token.kind);
+    }
+    hash(text.as_bytes())
+}
+
+fn interface(analysis: &Analysis, root: &[StmtId]) -> String {
+    let ast = &analysis.folded;
+    let resolution = &analysis.resolution;
+    let signature = |name: &Name| match resolution.lookup(name) {
+        Some(def) => def.signature(),
+        None => name.value.clone(),
+    };
+
+    let mut lines = vec![];
+    for &id in root {
+        match &ast[id].kind {
+            StmtKind::ProcDeclaration(proc) => lines.push(signature(&proc.name)),
+            StmtKind::StructDeclaration(struct_) => lines.push(signature(&struct_.name)),
+            StmtKind::VarDeclaration(name, _, _) => lines.push(signature(name)),
+            StmtKind::ConstDeclaration(name, _, _) => match analysis.consts.get(&name.id) {
+                Some(value) => lines.push(format!("{} = {:?}", signature(name), value)),
+                None => lines.push(signature(name)),
+            },
+            StmtKind::EnumDeclaration(enum_) => {
+                lines.push(signature(&enum_.name));
+                for variant in &enum_.variants {
+                    let line = signature(&variant.name);
+                    lines.push(match variant.value {
+                        Some(value) => format!("    {} = {}", line, value),
+                        None => format!("    {}", line),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Indices of the files `file` imports, directly or not
+pub fn visible(files: &[SourceFile], file: usize) -> Vec<usize> {
+    let mut seen = vec![];
+    let mut work = files[file].imports.clone();
+    while let Some(next) = work.pop() {
+        if next != file && !seen.contains(&next) {
+            seen.push(next);
+            work.extend(&files[next].imports);
+        }
+    }
+    seen.sort();
+    seen
+}
+
+/// What the object of a module was compiled from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Compiler and options it was compiled with
+    pub options: String,
+    pub tokens: String,
+    pub interface: String,
+    /// Hash of the interface of every module it can see, by module name
+    pub imports: Vec<(String, String)>,
+    /// Modules whose top level statements `main` runs, for the module that
+    /// defines it
+    pub inits: Vec<String>,
+}
+
+impl Record {
+    /// Why an object compiled from `self` can't be used in place of one
+    /// compiled from `current`, None if it can
+    pub fn stale(&self, current: &Record) -> Option<String> {
+        if self.options != current.options {
+            return Some("compiler or options changed".into());
+        }
+        if self.tokens != current.tokens {
+            return Some("source changed".into());
+        }
+        let old: HashMap<&str, &str> = self
+            .imports
+            .iter()
+            .map(|(module, hash)| (module.as_str(), hash.as_str()))
+            .collect();
+        for (module, hash) in &current.imports {
+            match old.get(module.as_str()) {
+                Some(old) if old == hash => {}
+                Some(_) => return Some(format!("interface of {} changed", module)),
+                None => return Some(format!("{} is imported", module)),
+            }
+        }
+        if self.imports.len() != current.imports.len() {
+            return Some("imports changed".into());
+        }
+        if self.inits != current.inits {
+            return Some("modules changed".into());
+        }
+        None
+    }
+
+    fn to_json(&self) -> Json {
+        let imports = self.imports.iter();
+        let imports = imports.map(|(module, hash)| (module.as_str(), hash.as_str().into()));
+        let inits = self.inits.iter().map(|module| module.as_str().into());
+        Json::object(vec![
+            ("options", self.options.as_str().into()),
+            ("tokens", self.tokens.as_str().into()),
+            ("interface", self.interface.as_str().into()),
+            ("imports", Json::object(imports.collect())),
+            ("inits", inits.collect::<Vec<Json>>().into()),
+        ])
+    }
+
+    fn from_json(json: &Json) -> Option<Record> {
+        let string = |key| json.get(key)?.as_str().map(String::from);
+        let imports = match json.get("imports")? {
+            Json::Object(fields) => fields
+                .iter()
+                .map(|(module, hash)| Some((module.clone(), hash.as_str()?.into())))
+                .collect::<Option<_>>()?,
+            _ => return None,
+        };
+        let inits = json.get("inits")?.as_array()?.iter();
+        let inits = inits.map(|module| module.as_str().map(String::from));
+        Some(Record {
+            options: string("options")?,
+            tokens: string("tokens")?,
+            interface: string("interface")?,
+            imports,
+            inits: inits.collect::<Option<_>>()?,
+        })
+    }
+}
+
+/// The cache directory of a project
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Cache {
+        Cache { dir }
+    }
+
+    /// Path of a file of `module` with `extension`: `c` for the generated C,
+    /// `o` for the object and `json` for the record
+    pub fn file(&self, module: &str, extension: &str) -> PathBuf {
+        // Module names can't be empty, so the file being compiled gets its own
+        match module {
+            "" => self.dir.join(format!("entry.{}", extension)),
+            module => self
+                .dir
+                .join("modules")
+                .join(format!("{}.{}", module, extension)),
+        }
+    }
+
+    /// The record of what the object of `module` was compiled from, None if
+    /// there is none or it can't be read
+    pub fn load(&self, module: &str) -> Option<Record> {
+        let path = self.file(module, "json");
+        let json = Json::parse(&std::fs::read_to_string(path).ok()?).ok()?;
+        Record::from_json(&json)
+    }
+
+    /// Record what the object of `module` was compiled from
+    pub fn store(&self, module: &str, record: &Record) -> io::Result<()> {
+        let path = self.file(module, "json");
+        std::fs::write(path, record.to_json().pretty() + "\n")
+    }
+
+    /// Create the directories `file` puts files in
+    pub fn create_dirs(&self) -> io::Result<()> {
+        std::fs::create_dir_all(self.dir.join("modules"))
+    }
+}
@@ -0,0 +1,563 @@
+//! Documentation generator behind `lune doc`.
+//!
+//! Every input file is a module. For each one we write `<module>.html` and
+//! `<module>.md` listing its structs, enums, constants, procs and globals with their signatures and `##`
+//! doc comments, plus an `index.html`/`index.md` and a `search-index.json`.
+//! The HTML index embeds the same search index, so the pages work offline
+//! straight from disk.
+//!
+//! A file imported by another input is documented as a module, with only
+//! its `pub` items.
+//!
+//! Names in backticks inside doc comments link to the item they name,
+//! preferring items of the same module. Struct and enum types in signatures
+//! link to their declaration, so Markdown signatures are written as HTML.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::backend::ast::*;
+use crate::json::Json;
+use crate::types::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemKind {
+    Struct,
+    Enum,
+    Const,
+    Proc,
+    Var,
+}
+
+impl ItemKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            ItemKind::Struct => "struct",
+            ItemKind::Enum => "enum",
+            ItemKind::Const => "const",
+            ItemKind::Proc => "proc",
+            ItemKind::Var => "var",
+        }
+    }
+
+    fn section(&self) -> &'static str {
+        match self {
+            ItemKind::Struct => "Structs",
+            ItemKind::Enum => "Enums",
+            ItemKind::Const => "Constants",
+            ItemKind::Proc => "Procedures",
+            ItemKind::Var => "Globals",
+        }
+    }
+}
+
+/// A documented top level declaration
+#[derive(Debug, Clone)]
+pub struct DocItem {
+    pub kind: ItemKind,
+    pub name: String,
+    /// Parameter names and types of a proc or fields of a struct, empty otherwise
+    pub params: Vec<(String, Type)>,
+    /// Variants of an enum
+    pub variants: Vec<DocVariant>,
+    /// Return type of a proc, the type of a global or constant, or the struct or enum itself
+    pub type_: Type,
+    pub doc: Option<String>,
+}
+
+impl DocItem {
+    /// Declaration as written in source, eg `proc add(a: int, b: int): int`
+    pub fn signature(&self) -> String {
+        self.signature_with(&|type_| type_.to_string())
+    }
+
+    /// Declaration as written in source, with the types in it rendered by `type_`
+    fn signature_with(&self, type_: &impl Fn(&Type) -> String) -> String {
+        let params = fields(&self.params, type_);
+
+        match self.kind {
+            ItemKind::Struct => format!("struct {} {{ {} }}", self.name, params),
+            ItemKind::Enum => {
+                let variants: Vec<String> = self
+                    .variants
+                    .iter()
+                    .map(|variant| variant.signature_with(type_))
+                    .collect();
+                format!("enum {} {{ {} }}", self.name, variants.join(", "))
+            }
+            ItemKind::Const => format!("const {}: {}", self.name, type_(&self.type_)),
+            ItemKind::Var => format!("var {}: {}", self.name, type_(&self.type_)),
+            ItemKind::Proc => {
+                let mut sig = format!("proc {}({})", self.name, params);
+                if self.type_ != Type::Void {
+                    sig += &format!(": {}", type_(&self.type_));
+                }
+                sig
+            }
+        }
+    }
+
+    /// Anchor of the item in its module page, eg `proc.add`
+    fn anchor(&self) -> String {
+        format!("{}.{}", self.kind.keyword(), self.name)
+    }
+
+    /// First line of the doc comment
+    fn summary(&self) -> &str {
+        self.doc
+            .as_deref()
+            .and_then(|doc| doc.lines().next())
+            .unwrap_or("")
+    }
+}
+
+/// A variant of a documented enum
+#[derive(Debug, Clone)]
+pub struct DocVariant {
+    pub name: String,
+    /// Field names and types of the payload, empty without one
+    pub fields: Vec<(String, Type)>,
+    pub value: Option<i128>,
+}
+
+impl DocVariant {
+    fn new(variant: &Variant) -> DocVariant {
+        DocVariant {
+            name: variant.name.value.clone(),
+            fields: variant
+                .fields
+                .iter()
+                .map(|f| (f.name.value.clone(), f.type_.clone()))
+                .collect(),
+            value: variant.value,
+        }
+    }
+
+    /// The variant as written in source, eg `Circle(radius: int)` or `Red = 1`,
+    /// with the types in it rendered by `type_`
+    fn signature_with(&self, type_: &impl Fn(&Type) -> String) -> String {
+        let mut sig = self.name.clone();
+        if !self.fields.is_empty() {
+            sig += &format!("({})", fields(&self.fields, type_));
+        }
+        if let Some(value) = self.value {
+            sig += &format!(" = {}", value);
+        }
+        sig
+    }
+}
+
+/// Fields or parameters as written in source, eg `a: int, b: int`
+fn fields(fields: &[(String, Type)], type_: &impl Fn(&Type) -> String) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, field)| format!("{}: {}", name, type_(field)))
+        .collect();
+    fields.join(", ")
+}
+
+/// The documented items of one module, in source order
+#[derive(Debug, Clone)]
+pub struct ModuleDoc {
+    pub name: String,
+    pub items: Vec<DocItem>,
+}
+
+impl ModuleDoc {
+    /// Collect the declarations among the top level statements `root` of a
+    /// parsed module, only the public ones if `public_only`
+    pub fn new(name: &str, ast: &AST, root: &[StmtId], public_only: bool) -> ModuleDoc {
+        let mut items = vec![];
+        for &id in root {
+            if public_only && !ast.public.contains(&ast[id].id) {
+                continue;
+            }
+            let doc = ast.docs.get(&ast[id].id).cloned();
+            let item = match &ast[id].kind {
+                StmtKind::ProcDeclaration(proc) => DocItem {
+                    kind: ItemKind::Proc,
+                    name: proc.name.value.clone(),
+                    params: proc
+                        .params
+                        .iter()
+                        .map(|p| (p.name.value.clone(), p.type_.clone()))
+                        .collect(),
+                    variants: vec![],
+                    type_: proc.return_type.clone(),
+                    doc,
+                },
+                StmtKind::StructDeclaration(struct_) => DocItem {
+                    kind: ItemKind::Struct,
+                    name: struct_.name.value.clone(),
+                    params: struct_
+                        .fields
+                        .iter()
+                        .map(|f| (f.name.value.clone(), f.type_.clone()))
+                        .collect(),
+                    variants: vec![],
+                    type_: Type::Named(struct_.name.value.clone()),
+                    doc,
+                },
+                StmtKind::EnumDeclaration(enum_) => DocItem {
+                    kind: ItemKind::Enum,
+                    name: enum_.name.value.clone(),
+                    params: vec![],
+                    variants: enum_.variants.iter().map(DocVariant::new).collect(),
+                    type_: Type::Named(enum_.name.value.clone()),
+                    doc,
+                },
+                StmtKind::VarDeclaration(name, type_, _) => DocItem {
+                    kind: ItemKind::Var,
+                    name: name.value.clone(),
+                    params: vec![],
+                    variants: vec![],
+                    type_: type_.clone(),
+                    doc,
+                },
+                StmtKind::ConstDeclaration(name, type_, _) => DocItem {
+                    kind: ItemKind::Const,
+                    name: name.value.clone(),
+                    params: vec![],
+                    variants: vec![],
+                    type_: type_.clone(),
+                    doc,
+                },
+                _ => continue,
+            };
+            items.push(item);
+        }
+
+        ModuleDoc {
+            name: name.into(),
+            items,
+        }
+    }
+
+    fn items_of(&self, kind: ItemKind) -> impl Iterator<Item = &DocItem> {
+        self.items.iter().filter(move |item| item.kind == kind)
+    }
+}
+
+/// Write the HTML and Markdown docs for `modules` into `out`
+pub fn write_docs(modules: &[ModuleDoc], out: &Path) -> io::Result<()> {
+    fs::create_dir_all(out)?;
+
+    let index = search_index(modules);
+    fs::write(out.join("search-index.json"), index.pretty() + "\n")?;
+    fs::write(out.join("index.html"), html_index(modules, &index))?;
+    fs::write(out.join("index.md"), markdown_index(modules))?;
+
+    for module in modules {
+        fs::write(out.join(format!("{}.html", module.name)), html_module(modules, module))?;
+        fs::write(out.join(format!("{}.md", module.name)), markdown_module(modules, module))?;
+    }
+    Ok(())
+}
+
+/// Link to the item called `name` as seen from `from`, as `(module, anchor)`
+fn resolve_link(modules: &[ModuleDoc], from: &ModuleDoc, name: &str) -> Option<(String, String)> {
+    let find = |module: &ModuleDoc| {
+        module
+            .items
+            .iter()
+            .find(|item| item.name == name)
+            .map(|item| (module.name.clone(), item.anchor()))
+    };
+    find(from).or_else(|| modules.iter().find_map(find))
+}
+
+/// Link to the struct or enum the named type `name` refers to as seen from
+/// `from`, as `(module, anchor)`. A qualified name such as `shapes.geo.Point`
+/// names the module by its last segment.
+fn resolve_type(modules: &[ModuleDoc], from: &ModuleDoc, name: &str) -> Option<(String, String)> {
+    let (module, name) = match name.rsplit_once('.') {
+        Some((path, name)) => (path.rsplit('.').next(), name),
+        None => (None, name),
+    };
+    let find = |doc: &ModuleDoc| {
+        doc.items
+            .iter()
+            .filter(|item| matches!(item.kind, ItemKind::Struct | ItemKind::Enum))
+            .find(|item| item.name == name)
+            .map(|item| (doc.name.clone(), item.anchor()))
+    };
+    match module {
+        Some(module) => modules.iter().filter(|doc| doc.name == module).find_map(find),
+        None => find(from).or_else(|| modules.iter().find_map(find)),
+    }
+}
+
+/// Render doc text, calling `code` for every backticked span and `text` for the rest
+fn render_doc(doc: &str, text: impl Fn(&str) -> String, code: impl Fn(&str) -> String) -> String {
+    let mut out = String::new();
+    for (i, part) in doc.split('`').enumerate() {
+        // Odd parts are inside backticks, an unclosed backtick is kept as text
+        if i % 2 == 1 && i < doc.matches('`').count() {
+            out += &code(part);
+        } else {
+            if i % 2 == 1 {
+                out += &text("`");
+            }
+            out += &text(part);
+        }
+    }
+    out
+}
+
+fn search_index(modules: &[ModuleDoc]) -> Json {
+    let entries: Vec<Json> = modules
+        .iter()
+        .flat_map(|module| {
+            module.items.iter().map(|item| {
+                Json::object(vec![
+                    ("name", item.name.clone().into()),
+                    ("kind", item.kind.keyword().into()),
+                    ("module", module.name.clone().into()),
+                    ("url", format!("{}.html#{}", module.name, item.anchor()).into()),
+                    ("signature", item.signature().into()),
+                    ("summary", item.summary().into()),
+                ])
+            })
+        })
+        .collect();
+    entries.into()
+}
+
+// HTML
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: 2em auto; }
+pre, code { background: #f4f4f4; padding: 0 0.2em; }
+pre { padding: 0.5em; }
+.item { margin-bottom: 2em; }";
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out += "&amp;",
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '"' => out += "&quot;",
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLE,
+        body
+    )
+}
+
+/// `type_` as HTML, its struct and enum types linking to their declaration in
+/// the `ext` pages
+fn html_type(modules: &[ModuleDoc], module: &ModuleDoc, type_: &Type, ext: &str) -> String {
+    match type_ {
+        Type::Named(name) => match resolve_type(modules, module, name) {
+            Some((target, anchor)) => {
+                format!("<a href=\"{}.{}#{}\">{}</a>", target, ext, anchor, escape_html(name))
+            }
+            None => escape_html(name),
+        },
+        Type::Array(element, len) => {
+            format!("[{}]{}", len, html_type(modules, module, element, ext))
+        }
+        Type::Slice(element) => format!("[]{}", html_type(modules, module, element, ext)),
+        Type::ConstArray(element, len) => {
+            format!("[{}]{}", escape_html(len), html_type(modules, module, element, ext))
+        }
+        Type::Int | Type::String | Type::Bool | Type::Void => type_.to_string(),
+    }
+}
+
+fn html_doc(modules: &[ModuleDoc], module: &ModuleDoc, doc: &str) -> String {
+    let paragraphs: Vec<String> = doc
+        .split("\n\n")
+        .map(|paragraph| {
+            let text = render_doc(paragraph, escape_html, |code| {
+                let html = format!("<code>{}</code>", escape_html(code));
+                match resolve_link(modules, module, code) {
+                    Some((target, anchor)) => {
+                        format!("<a href=\"{}.html#{}\">{}</a>", target, anchor, html)
+                    }
+                    None => html,
+                }
+            });
+            format!("<p>{}</p>\n", text)
+        })
+        .collect();
+    paragraphs.concat()
+}
+
+fn html_module(modules: &[ModuleDoc], module: &ModuleDoc) -> String {
+    let mut body = format!(
+        "<p><a href=\"index.html\">Index</a></p>\n<h1>Module <code>{}</code></h1>\n",
+        escape_html(&module.name)
+    );
+
+    for kind in [ItemKind::Struct, ItemKind::Enum, ItemKind::Const, ItemKind::Proc, ItemKind::Var] {
+        if module.items_of(kind).next().is_none() {
+            continue;
+        }
+
+        body += &format!("<h2>{}</h2>\n", kind.section());
+        let type_ = |type_: &Type| html_type(modules, module, type_, "html");
+        for item in module.items_of(kind) {
+            body += &format!(
+                "<div class=\"item\" id=\"{}\">\n<pre><code>{}</code></pre>\n",
+                item.anchor(),
+                item.signature_with(&type_)
+            );
+            if let Some(doc) = &item.doc {
+                body += &html_doc(modules, module, doc);
+            }
+
+            if kind != ItemKind::Var && !item.params.is_empty() {
+                let heading = if kind == ItemKind::Struct { "Fields" } else { "Parameters" };
+                body += &format!("<h4>{}</h4>\n<ul>\n", heading);
+                for (name, param) in &item.params {
+                    let param = type_(param);
+                    body += &format!("<li><code>{}: {}</code></li>\n", escape_html(name), param);
+                }
+                body += "</ul>\n";
+            }
+            if !item.variants.is_empty() {
+                body += "<h4>Variants</h4>\n<ul>\n";
+                for variant in &item.variants {
+                    body += &format!("<li><code>{}</code></li>\n", variant.signature_with(&type_));
+                }
+                body += "</ul>\n";
+            }
+            if kind == ItemKind::Proc {
+                body += &format!("<h4>Returns</h4>\n<p><code>{}</code></p>\n", type_(&item.type_));
+            }
+            body += "</div>\n";
+        }
+    }
+
+    html_page(&format!("{} - Lune docs", module.name), &body)
+}
+
+const SEARCH_SCRIPT: &str = "const input = document.getElementById('search');
+const results = document.getElementById('results');
+input.addEventListener('input', () => {
+  const query = input.value.toLowerCase();
+  results.innerHTML = '';
+  if (!query) return;
+  for (const entry of SEARCH_INDEX) {
+    if (!entry.name.toLowerCase().includes(query)) continue;
+    const li = document.createElement('li');
+    const a = document.createElement('a');
+    a.href = entry.url;
+    a.textContent = entry.module + '::' + entry.name;
+    li.append(a, ' ' + entry.summary);
+    results.append(li);
+  }
+});";
+
+fn html_index(modules: &[ModuleDoc], index: &Json) -> String {
+    let mut body = String::from(
+        "<h1>Lune docs</h1>\n<input id=\"search\" placeholder=\"Search\" autofocus>\n\
+         <ul id=\"results\"></ul>\n<h2>Modules</h2>\n<ul>\n",
+    );
+    for module in modules {
+        body += &format!(
+            "<li><a href=\"{0}.html\">{0}</a></li>\n",
+            escape_html(&module.name)
+        );
+    }
+    body += "</ul>\n";
+
+    // `</` can't appear inside a script element
+    let index = index.to_string().replace("</", "<\\/");
+    body += &format!(
+        "<script>\nconst SEARCH_INDEX = {};\n{}\n</script>\n",
+        index, SEARCH_SCRIPT
+    );
+    html_page("Lune docs", &body)
+}
+
+// Markdown
+
+fn markdown_doc(modules: &[ModuleDoc], module: &ModuleDoc, doc: &str) -> String {
+    render_doc(doc, |text| text.into(), |code| {
+        match resolve_link(modules, module, code) {
+            Some((target, anchor)) => format!("[`{}`]({}.md#{})", code, target, anchor),
+            None => format!("`{}`", code),
+        }
+    })
+}
+
+fn markdown_module(modules: &[ModuleDoc], module: &ModuleDoc) -> String {
+    let mut out = format!("[Index](index.md)\n\n# Module `{}`\n", module.name);
+
+    for kind in [ItemKind::Struct, ItemKind::Enum, ItemKind::Const, ItemKind::Proc, ItemKind::Var] {
+        if module.items_of(kind).next().is_none() {
+            continue;
+        }
+
+        out += &format!("\n## {}\n", kind.section());
+        // Links don't work inside code spans, types are linked in HTML ones
+        let type_ = |type_: &Type| html_type(modules, module, type_, "md");
+        for item in module.items_of(kind) {
+            out += &format!(
+                "\n<a id=\"{}\"></a>\n### `{}`\n\n<pre><code>{}</code></pre>\n",
+                item.anchor(),
+                item.name,
+                item.signature_with(&type_)
+            );
+            if let Some(doc) = &item.doc {
+                out += &format!("\n{}\n", markdown_doc(modules, module, doc));
+            }
+
+            if kind != ItemKind::Var && !item.params.is_empty() {
+                let heading = if kind == ItemKind::Struct { "Fields" } else { "Parameters" };
+                out += &format!("\n**{}**\n\n", heading);
+                for (name, param) in &item.params {
+                    out += &format!("- <code>{}: {}</code>\n", name, type_(param));
+                }
+            }
+            if !item.variants.is_empty() {
+                out += "\n**Variants**\n\n";
+                for variant in &item.variants {
+                    out += &format!("- <code>{}</code>\n", variant.signature_with(&type_));
+                }
+            }
+            if kind == ItemKind::Proc {
+                out += &format!("\n**Returns** <code>{}</code>\n", type_(&item.type_));
+            }
+        }
+    }
+    out
+}
+
+fn markdown_index(modules: &[ModuleDoc]) -> String {
+    let mut out = String::from("# Lune docs\n\n## Modules\n\n");
+    for module in modules {
+        out += &format!("- [{0}]({0}.md)\n", module.name);
+    }
+
+    let mut items: Vec<(&ModuleDoc, &DocItem)> = modules
+        .iter()
+        .flat_map(|module| module.items.iter().map(move |item| (module, item)))
+        .collect();
+    items.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+
+    out += "\n## All items\n\n";
+    for (module, item) in items {
+        out += &format!(
+            "- [`{}`]({}.md#{}) {}\n",
+            item.name,
+            module.name,
+            item.anchor(),
+            item.summary()
+        );
+    }
+    out
+}
@@ -1,4 +1,4 @@
-use crate::frontend::lexer::token::Token;
+use crate::frontend::lexer::token::{Span, Token};
 use std::fmt;
 
 #[derive(Debug)]
@@ -15,6 +15,80 @@ pub struct ParseError {
     pub message: String,
 }
 
+/// An import that can't be loaded, or a name a module doesn't export, at a
+/// span of the merged program
+#[derive(Debug)]
+pub struct ModuleError {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ResolveError {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ConstError {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct FoldError {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct LowerError {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An IR module breaking an invariant, in the named proc if it isn't about the whole module
+#[derive(Debug)]
+pub struct VerifyError {
+    pub proc: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ReadError {
+    pub position: usize,
+    pub message: String,
+}
+
+/// A `.lbc` file that can't be loaded, at a byte offset into it
+#[derive(Debug)]
+pub struct BytecodeError {
+    pub position: usize,
+    pub message: String,
+}
+
+/// A program stopping while the VM runs it, at a source location if the
+/// instruction has one
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub location: Option<String>,
+    pub message: String,
+}
+
+/// A `lune.toml` that can't be read or is invalid, at `path:line:column` or
+/// at the path if it's about the whole file
+#[derive(Debug)]
+pub struct ManifestError {
+    pub location: String,
+    pub message: String,
+}
+
 impl fmt::Display for LexerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -34,3 +108,107 @@ impl fmt::Display for ParseError {
         )
     }
 }
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ModuleError: error at line {}, position {}: {}",
+            self.span.line, self.span.start, self.message
+        )
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ResolveError: error at line {}, position {}: {}",
+            self.span.line, self.span.start, self.message
+        )
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TypeError: error at line {}, position {}: {}",
+            self.span.line, self.span.start, self.message
+        )
+    }
+}
+
+impl fmt::Display for ConstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ConstError: error at line {}, position {}: {}",
+            self.span.line, self.span.start, self.message
+        )
+    }
+}
+
+impl fmt::Display for FoldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FoldError: error at line {}, position {}: {}",
+            self.span.line, self.span.start, self.message
+        )
+    }
+}
+
+impl fmt::Display for LowerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LowerError: error at line {}, position {}: {}",
+            self.span.line, self.span.start, self.message
+        )
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.proc {
+            Some(proc) => write!(f, "VerifyError: in @{}: {}", proc, self.message),
+            None => write!(f, "VerifyError: {}", self.message),
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ReadError: error at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "BytecodeError: error at byte {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "RuntimeError: {}: {}", location, self.message),
+            None => write!(f, "RuntimeError: {}", self.message),
+        }
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ManifestError: {}: {}", self.location, self.message)
+    }
+}
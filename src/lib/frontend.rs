@@ -1,2 +1,16 @@
 pub mod lexer;
 pub mod parser;
+
+// Loading the modules a file imports
+pub mod modules;
+
+// Compile time evaluation of constants
+pub mod consteval;
+
+// Semantic analysis
+pub mod resolver;
+pub mod checker;
+
+// Running the whole frontend over a file, for the compiler, the language
+// server and the docs
+pub mod analysis;
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::backend::ast::{Block, NodeId, AST};
+use crate::backend::fold::ConstFolder;
+use crate::backend::visit::fold_ast;
+use crate::frontend::checker::TypeChecker;
+use crate::frontend::consteval::{ConstEvaluator, Value};
+use crate::frontend::lexer::lexer::Lexer;
+use crate::frontend::lexer::token::{Span, Token};
+use crate::frontend::modules::{file_at, ModuleLoader, SourceFile};
+use crate::frontend::parser::Parser;
+use crate::frontend::resolver::{Resolution, Resolver};
+use crate::types::Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+    /// Which stage produced the diagnostic, eg "parser"
+    pub source: &'static str,
+}
+
+/// Everything the frontend knows about one version of a document
+pub struct Analysis {
+    pub source: String,
+    pub lines: LineIndex,
+    /// The document and the files of the modules it imports, spans of the
+    /// imported files start past the end of the document
+    pub files: Vec<SourceFile>,
+    /// Tokens lexed so far, complete unless there was a lexer error
+    pub tokens: Vec<Token>,
+    /// Empty unless the document parsed, the top level statements of the
+    /// modules it imports come before its own
+    pub ast: AST,
+    /// Value of every constant that could be evaluated, by the id of its name
+    pub consts: HashMap<NodeId, Value>,
+    pub resolution: Resolution,
+    /// Type of every expression the checker could type, and of the nodes
+    /// constant folding added
+    pub types: HashMap<NodeId, Type>,
+    /// The AST after constant folding, empty unless the document checked
+    pub folded: AST,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    /// Analyze a document that isn't a file, its imports are looked up in
+    /// the current directory
+    pub fn new(source: &str) -> Analysis {
+        Analysis::with_path(source, Path::new("<input>"), ModuleLoader::new(&[]))
+    }
+
+    /// Run the lexer and parser over `source`, the contents of `path`, load
+    /// the modules it imports with `loader`, and run the constant evaluator,
+    /// resolver and type checker over the program. Then fold constants if it
+    /// checked.
+    pub fn with_path(source: &str, path: &Path, mut loader: ModuleLoader) -> Analysis {
+        let mut analysis = Analysis {
+            source: source.into(),
+            lines: LineIndex::new(source),
+            files: vec![SourceFile::new(path, source)],
+            tokens: vec![],
+            ast: AST::new(),
+            consts: HashMap::new(),
+            resolution: Resolution::default(),
+            types: HashMap::new(),
+            folded: AST::new(),
+            diagnostics: vec![],
+        };
+
+        // Keep every token the parser pulls for highlighting, and the rest
+        // of them if it stops early
+        let mut tokens = vec![];
+        let mut lexer = Lexer::new(source).inspect(|token| {
+            if let Ok(token) = token {
+                tokens.push(token.clone());
+            }
+        });
+        let mut parser = Parser::new(lexer.by_ref());
+        let parsed = parser.parse();
+        let diagnostic = match (&parsed, parser.lexer_error()) {
+            (_, Some(err)) => Some(Diagnostic {
+                span: Span::new(err.position, err.position + 1, err.line),
+                message: err.message.clone(),
+                source: "lexer",
+            }),
+            (Err(err), None) => Some(Diagnostic {
+                span: err.curr_token.span(),
+                message: err.message.clone(),
+                source: "parser",
+            }),
+            (Ok(_), None) => None,
+        };
+        lexer.for_each(drop);
+        analysis.tokens = tokens;
+        match (parsed, diagnostic) {
+            (Ok(ast), None) => analysis.ast = ast,
+            (_, diagnostic) => {
+                analysis.diagnostics.extend(diagnostic);
+                return analysis;
+            }
+        }
+
+        let ast = std::mem::take(&mut analysis.ast);
+        analysis.ast = loader.load(path, source, ast);
+        analysis.files = loader.files;
+        for err in loader.errors {
+            analysis.diagnostics.push(Diagnostic {
+                span: err.span,
+                message: err.message,
+                source: "module",
+            });
+        }
+        if !analysis.diagnostics.is_empty() {
+            return analysis;
+        }
+
+        let mut evaluator = ConstEvaluator::new();
+        analysis.consts = evaluator.evaluate(&mut analysis.ast);
+        for err in evaluator.errors {
+            analysis.diagnostics.push(Diagnostic {
+                span: err.span,
+                message: err.message,
+                source: "const",
+            });
+        }
+
+        let mut resolver = Resolver::new();
+        analysis.resolution = resolver.resolve(&analysis.ast);
+        for err in resolver.errors {
+            analysis.diagnostics.push(Diagnostic {
+                span: err.span,
+                message: err.message,
+                source: "resolver",
+            });
+        }
+
+        let mut checker = TypeChecker::new(&analysis.resolution);
+        checker.check(&analysis.ast);
+        for err in checker.errors {
+            analysis.diagnostics.push(Diagnostic {
+                span: err.span,
+                message: err.message,
+                source: "typeck",
+            });
+        }
+        analysis.types = checker.types;
+        if !analysis.diagnostics.is_empty() {
+            return analysis;
+        }
+
+        let mut folder = ConstFolder::new(&mut analysis.types);
+        analysis.folded = fold_ast(&mut folder, analysis.ast.clone());
+        for err in folder.errors {
+            analysis.diagnostics.push(Diagnostic {
+                span: err.span,
+                message: err.message,
+                source: "fold",
+            });
+        }
+
+        analysis
+    }
+
+    /// The file an offset of the program is in
+    pub fn file_at(&self, offset: usize) -> Option<&SourceFile> {
+        file_at(&self.files, offset)
+    }
+
+    /// Top level statements of the document itself, without those of the
+    /// modules it imports
+    pub fn file_root(&self) -> Block {
+        let end = self.source.len();
+        let root = self.ast.root.iter().copied();
+        root.filter(|&id| self.ast[id].span.start <= end).collect()
+    }
+
+    /// The span in the document that `span` comes from, either itself or the
+    /// import that loads the file it is in, directly or not
+    pub fn in_document(&self, span: Span) -> Span {
+        let mut span = span;
+        while let Some(imported_at) = self.file_at(span.start).and_then(|file| file.imported_at) {
+            span = imported_at;
+        }
+        span
+    }
+}
+
+/// Converts between byte offsets and LSP line/UTF-16 column positions
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> LineIndex {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex { line_starts }
+    }
+
+    /// Line and UTF-16 column of a byte offset
+    pub fn position(&self, source: &str, offset: usize) -> (usize, usize) {
+        let offset = usize::min(offset, source.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+
+        let start = self.line_starts[line];
+        let column = source
+            .get(start..offset)
+            .map(|text| text.encode_utf16().count())
+            .unwrap_or(offset - start);
+        (line, column)
+    }
+
+    /// Byte offset of a line and UTF-16 column, clamped to the line
+    pub fn offset(&self, source: &str, line: usize, column: usize) -> usize {
+        let Some(&start) = self.line_starts.get(line) else {
+            return source.len();
+        };
+
+        let mut units = 0;
+        for (i, c) in source[start..].char_indices() {
+            if units >= column || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        source.len()
+    }
+}
@@ -0,0 +1,740 @@
+use std::collections::HashMap;
+
+use crate::backend::ast::*;
+use crate::errors::TypeError;
+use crate::frontend::lexer::token::Span;
+use crate::frontend::resolver::{DefKind, Resolution};
+use crate::types::Type;
+
+/// Type checks a resolved AST
+pub struct TypeChecker<'res> {
+    resolution: &'res Resolution,
+    /// Return type of the proc being checked, None at the top level
+    return_type: Option<Type>,
+    /// Whether the value of a constant is being checked
+    in_const: bool,
+    /// Type of every expression that could be typed, keyed by node id
+    pub types: HashMap<NodeId, Type>,
+    pub errors: Vec<TypeError>,
+}
+
+impl<'res> TypeChecker<'res> {
+    pub fn new(resolution: &'res Resolution) -> TypeChecker<'res> {
+        TypeChecker {
+            resolution,
+            return_type: None,
+            in_const: false,
+            types: HashMap::new(),
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(TypeError { span, message });
+    }
+
+    /// Whether `type_` only names declared types, undeclared ones were reported by the resolver
+    fn is_known(&self, type_: &Type) -> bool {
+        match type_ {
+            Type::Named(name) => self.resolution.types.contains_key(name),
+            Type::Array(element, _) | Type::Slice(element) => self.is_known(element),
+            // The length couldn't be evaluated, which was reported already
+            Type::ConstArray(_, _) => false,
+            _ => true,
+        }
+    }
+
+    /// Report a mismatch unless either type is unknown because of an earlier error
+    fn expect_type(&mut self, expected: &Type, found: Option<Type>, span: Span) {
+        if !self.is_known(expected) {
+            return;
+        }
+        if let Some(found) = found {
+            if *expected != found {
+                self.error(
+                    span,
+                    format!(
+                        "mismatched types: expected `{}`, found `{}`",
+                        expected, found
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Whether every path through `stmts` ends in a return
+    fn always_returns(ast: &AST, stmts: &[StmtId]) -> bool {
+        match stmts.last().map(|&s| &ast[s].kind) {
+            Some(StmtKind::Return(_)) => true,
+            Some(StmtKind::If(_, then, Some(otherwise))) => {
+                Self::always_returns(ast, then) && Self::always_returns(ast, otherwise)
+            }
+            // A case that isn't exhaustive is an error of its own
+            Some(StmtKind::Case(_, arms)) => {
+                !arms.is_empty() && arms.iter().all(|arm| Self::always_returns(ast, &arm.body))
+            }
+            _ => false,
+        }
+    }
+
+    /// Check the patterns of a case against the type of its scrutinee, and that
+    /// they are reachable and cover every value
+    fn check_patterns(&mut self, type_: &Type, patterns: &[&Pattern], span: Span) {
+        match type_ {
+            Type::Int => self.check_int_patterns(patterns, span),
+            Type::String => self.check_str_patterns(patterns, span),
+            Type::Named(_) if !self.resolution.variants(type_).is_empty() => {
+                self.check_variant_patterns(type_, patterns, span)
+            }
+            _ => self.error(span, format!("cannot use `case` on a value of type `{}`", type_)),
+        }
+    }
+
+    fn mismatched_pattern(&mut self, pattern: &Pattern, type_: &Type) {
+        self.error(
+            pattern.span,
+            format!("mismatched types: pattern `{}` can't match a value of type `{}`", pattern, type_),
+        );
+    }
+
+    fn check_int_patterns(&mut self, patterns: &[&Pattern], span: Span) {
+        let (min, max) = (i32::MIN as i128, i32::MAX as i128);
+        let mut covered = Intervals::default();
+        for pattern in patterns {
+            let (start, end) = match pattern.kind {
+                PatternKind::Wildcard => (min, max),
+                PatternKind::Int(n) => (n, n),
+                PatternKind::Range(start, end) => (start, end),
+                _ => {
+                    self.mismatched_pattern(pattern, &Type::Int);
+                    continue;
+                }
+            };
+
+            if start < min || end > max {
+                self.error(pattern.span, "pattern out of range for i32".into());
+            } else if start > end {
+                self.error(pattern.span, format!("range pattern `{}` is empty", pattern));
+            } else if covered.contains(start, end) {
+                self.error(pattern.span, "unreachable pattern".into());
+            } else {
+                covered.insert(start, end);
+            }
+        }
+
+        if let Some((start, end)) = covered.first_gap(min, max) {
+            let gap = if start == end {
+                start.to_string()
+            } else {
+                format!("{}..{}", start, end)
+            };
+            self.error(span, format!("non-exhaustive patterns: `{}` not covered", gap));
+        }
+    }
+
+    fn check_str_patterns(&mut self, patterns: &[&Pattern], span: Span) {
+        let mut wildcard = false;
+        let mut seen: Vec<&str> = vec![];
+        for pattern in patterns {
+            let unreachable = match &pattern.kind {
+                PatternKind::Wildcard => std::mem::replace(&mut wildcard, true),
+                PatternKind::Str(s) if seen.contains(&s.as_str()) => true,
+                PatternKind::Str(s) => {
+                    seen.push(s);
+                    wildcard
+                }
+                _ => {
+                    self.mismatched_pattern(pattern, &Type::String);
+                    continue;
+                }
+            };
+            if unreachable {
+                self.error(pattern.span, "unreachable pattern".into());
+            }
+        }
+
+        if !wildcard {
+            self.error(span, "non-exhaustive patterns: `_` not covered".into());
+        }
+    }
+
+    fn check_variant_patterns(&mut self, type_: &Type, patterns: &[&Pattern], span: Span) {
+        let variants: Vec<String> = self
+            .resolution
+            .variants(type_)
+            .iter()
+            .map(|variant| variant.name.clone())
+            .collect();
+
+        let mut wildcard = false;
+        let mut seen: Vec<&str> = vec![];
+        for pattern in patterns {
+            let name = match &pattern.kind {
+                PatternKind::Wildcard => {
+                    if wildcard || seen.len() == variants.len() {
+                        self.error(pattern.span, "unreachable pattern".into());
+                    }
+                    wildcard = true;
+                    continue;
+                }
+                PatternKind::Variant(name, bindings) => {
+                    let Some(def) = self.resolution.lookup(name) else {
+                        continue;
+                    };
+                    if def.kind != DefKind::Variant {
+                        self.error(name.span, format!("`{}` is not a variant", name.value));
+                        continue;
+                    }
+                    if def.type_ != *type_ {
+                        self.error(
+                            name.span,
+                            format!("`{}` is a variant of `{}`, not `{}`", name.value, def.type_, type_),
+                        );
+                        continue;
+                    }
+                    // No bindings at all ignores the payload
+                    if !bindings.is_empty() && bindings.len() != def.fields.len() {
+                        self.error(
+                            pattern.span,
+                            format!(
+                                "`{}` has {} field(s) but the pattern binds {}",
+                                name.value,
+                                def.fields.len(),
+                                bindings.len()
+                            ),
+                        );
+                    }
+                    &name.value
+                }
+                _ => {
+                    self.mismatched_pattern(pattern, type_);
+                    continue;
+                }
+            };
+
+            if wildcard || seen.contains(&name.as_str()) {
+                self.error(pattern.span, "unreachable pattern".into());
+            }
+            seen.push(name);
+        }
+
+        let missing: Vec<String> = variants
+            .iter()
+            .filter(|variant| !seen.contains(&variant.as_str()))
+            .map(|variant| format!("`{}`", variant))
+            .collect();
+        if !wildcard && !missing.is_empty() {
+            self.error(
+                span,
+                format!("non-exhaustive patterns: {} not covered", missing.join(", ")),
+            );
+        }
+    }
+
+    fn check_block(&mut self, ast: &AST, stmts: &[StmtId]) {
+        for &stmt in stmts {
+            self.check_stmt(ast, stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, ast: &AST, id: StmtId) {
+        let stmt = &ast[id];
+        match &stmt.kind {
+            StmtKind::Expr(expr) => {
+                self.check_expr(ast, *expr);
+            }
+            StmtKind::VarDeclaration(_, type_, value) => {
+                let found = self.check_expr(ast, *value);
+                self.expect_type(type_, found, ast[*value].span);
+            }
+            StmtKind::ConstDeclaration(name, type_, value) => {
+                self.in_const = true;
+                let found = self.check_expr(ast, *value);
+                self.in_const = false;
+
+                if matches!(type_, Type::Int | Type::String | Type::Bool) {
+                    self.expect_type(type_, found, ast[*value].span);
+                } else if self.is_known(type_) {
+                    self.error(
+                        name.span,
+                        format!("constants must be an `int`, `str` or `bool`, found `{}`", type_),
+                    );
+                }
+            }
+            StmtKind::Assignment(target, value) => {
+                let found = self.check_expr(ast, *value);
+                if let ExprKind::Variable(name) = &ast[*target].kind {
+                    let kind = self.resolution.lookup(name).map(|def| def.kind);
+                    match kind {
+                        Some(DefKind::Proc | DefKind::Builtin) => {
+                            let message = format!("cannot assign to proc `{}`", name.value);
+                            return self.error(name.span, message);
+                        }
+                        Some(DefKind::Const) => {
+                            let message = format!("cannot assign to constant `{}`", name.value);
+                            return self.error(name.span, message);
+                        }
+                        Some(DefKind::Struct) => {
+                            let message = format!("cannot assign to struct `{}`", name.value);
+                            return self.error(name.span, message);
+                        }
+                        Some(DefKind::Enum) => {
+                            let message = format!("cannot assign to enum `{}`", name.value);
+                            return self.error(name.span, message);
+                        }
+                        Some(DefKind::Variant) => {
+                            let message = format!("cannot assign to variant `{}`", name.value);
+                            return self.error(name.span, message);
+                        }
+                        _ => {}
+                    }
+                }
+
+                if let Some(expected) = self.check_expr(ast, *target) {
+                    self.expect_type(&expected, found, ast[*value].span);
+                }
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                let found = self.check_expr(ast, *cond);
+                self.expect_type(&Type::Bool, found, ast[*cond].span);
+                self.check_block(ast, then);
+                if let Some(otherwise) = otherwise {
+                    self.check_block(ast, otherwise);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                let found = self.check_expr(ast, *cond);
+                self.expect_type(&Type::Bool, found, ast[*cond].span);
+                self.check_block(ast, body);
+            }
+            StmtKind::Return(value) => {
+                let found = value.and_then(|v| self.check_expr(ast, v));
+
+                match (self.return_type.clone(), value) {
+                    (None, _) => self.error(stmt.span, "`return` outside of a proc".into()),
+                    (Some(Type::Void), Some(value)) => self.error(
+                        ast[*value].span,
+                        "proc has no return type but returns a value".into(),
+                    ),
+                    (Some(Type::Void), None) => {}
+                    (Some(expected), None) => self.error(
+                        stmt.span,
+                        format!("expected a return value of type `{}`", expected),
+                    ),
+                    (Some(expected), Some(value)) => {
+                        self.expect_type(&expected, found, ast[*value].span)
+                    }
+                }
+            }
+            StmtKind::ProcDeclaration(proc) => {
+                self.return_type = Some(proc.return_type.clone());
+                self.check_block(ast, &proc.body);
+                self.return_type = None;
+
+                if proc.return_type != Type::Void && !Self::always_returns(ast, &proc.body) {
+                    self.error(
+                        proc.name.span,
+                        format!(
+                            "proc `{}` does not return a value on every path",
+                            proc.name.value
+                        ),
+                    );
+                }
+            }
+            StmtKind::Case(scrutinee, arms) => {
+                if let Some(type_) = self.check_expr(ast, *scrutinee) {
+                    let patterns: Vec<&Pattern> = arms.iter().map(|arm| &arm.pattern).collect();
+                    self.check_patterns(&type_, &patterns, stmt.span);
+                }
+                for arm in arms {
+                    self.check_block(ast, &arm.body);
+                }
+            }
+            // Field types and variant values are checked by the resolver
+            StmtKind::StructDeclaration(_) | StmtKind::EnumDeclaration(_) => {}
+        }
+    }
+
+    /// Infer and record the type of an expression, None if it couldn't be determined
+    fn check_expr(&mut self, ast: &AST, id: ExprId) -> Option<Type> {
+        let type_ = self.infer_expr(ast, id)?;
+        self.types.insert(ast[id].id, type_.clone());
+        Some(type_)
+    }
+
+    fn infer_expr(&mut self, ast: &AST, id: ExprId) -> Option<Type> {
+        let expr = &ast[id];
+        match &expr.kind {
+            ExprKind::IntLit(n) => {
+                if *n > i32::MAX as u128 {
+                    self.error(expr.span, "literal out of range for i32".into());
+                }
+                Some(Type::Int)
+            }
+            ExprKind::StringLit(_) => Some(Type::String),
+            ExprKind::BoolLit(_) => Some(Type::Bool),
+            ExprKind::Variable(name) => {
+                let def = self.resolution.lookup(name)?;
+                match def.kind {
+                    DefKind::Var | DefKind::Const | DefKind::Param => Some(def.type_.clone()),
+                    DefKind::Proc | DefKind::Builtin => {
+                        self.error(
+                            name.span,
+                            format!("`{}` is a proc, not a value", name.value),
+                        );
+                        None
+                    }
+                    DefKind::Struct => {
+                        self.error(
+                            name.span,
+                            format!("`{}` is a struct, not a value", name.value),
+                        );
+                        None
+                    }
+                    DefKind::Enum => {
+                        self.error(
+                            name.span,
+                            format!("`{}` is an enum, not a value", name.value),
+                        );
+                        None
+                    }
+                    DefKind::Variant if !def.fields.is_empty() => {
+                        self.error(
+                            name.span,
+                            format!(
+                                "`{}` takes {} argument(s) but 0 were given",
+                                name.value,
+                                def.fields.len()
+                            ),
+                        );
+                        None
+                    }
+                    DefKind::Variant => Some(def.type_.clone()),
+                }
+            }
+            ExprKind::Call(name, args) => {
+                let arg_types: Vec<Option<Type>> =
+                    args.iter().map(|&a| self.check_expr(ast, a)).collect();
+                let def = self.resolution.lookup(name)?;
+
+                match def.kind {
+                    DefKind::Var | DefKind::Const | DefKind::Param | DefKind::Struct | DefKind::Enum => {
+                        self.error(name.span, format!("`{}` is not a proc", name.value));
+                        return None;
+                    }
+                    DefKind::Builtin => {
+                        // print accepts a single int, str or bool, len an array or slice
+                        if args.len() != 1 {
+                            self.error(expr.span, format!("`{}` takes 1 argument", name.value));
+                        } else if let Some(type_) = &arg_types[0] {
+                            let message = match name.value.as_str() {
+                                "len" if type_.element().is_none() => {
+                                    format!("cannot take the length of a value of type `{}`", type_)
+                                }
+                                "print" if !matches!(type_, Type::Int | Type::String | Type::Bool) => {
+                                    format!("cannot print a value of type `{}`", type_)
+                                }
+                                _ => String::new(),
+                            };
+                            if !message.is_empty() {
+                                self.error(ast[args[0]].span, message);
+                            }
+                        }
+                    }
+                    // Variants with a payload are constructed like a call
+                    DefKind::Proc | DefKind::Variant => {
+                        let params = def.params.clone();
+                        if params.len() != args.len() {
+                            self.error(
+                                expr.span,
+                                format!(
+                                    "`{}` takes {} argument(s) but {} were given",
+                                    name.value,
+                                    params.len(),
+                                    args.len()
+                                ),
+                            );
+                        }
+                        for ((param, found), &arg) in params.iter().zip(arg_types).zip(args) {
+                            self.expect_type(param, found, ast[arg].span);
+                        }
+                    }
+                }
+
+                Some(def.type_.clone())
+            }
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs)
+                if matches!(ast[*rhs].kind, ExprKind::IntLit(n) if n == i32::MIN.unsigned_abs() as u128) =>
+            {
+                // i32::MIN is only in range once negated
+                self.types.insert(ast[*rhs].id, Type::Int);
+                Some(Type::Int)
+            }
+            ExprKind::UnaryOp(op, rhs) => {
+                let found = self.check_expr(ast, *rhs)?;
+                let expected = match op {
+                    UnaryOpKind::Not => Type::Bool,
+                    UnaryOpKind::Neg => Type::Int,
+                };
+
+                if found != expected {
+                    self.error(
+                        expr.span,
+                        format!("cannot apply unary `{}` to `{}`", op, found),
+                    );
+                    return None;
+                }
+                Some(expected)
+            }
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let lhs_type = self.check_expr(ast, *lhs);
+                let rhs_type = self.check_expr(ast, *rhs);
+                let (lhs_type, rhs_type) = (lhs_type?, rhs_type?);
+
+                let result = match op {
+                    // C can't compare structs, tagged unions or arrays, so neither can we
+                    BinOpKind::Eq | BinOpKind::Ne
+                        if lhs_type == rhs_type && self.is_comparable(&lhs_type) =>
+                    {
+                        Some(Type::Bool)
+                    }
+                    BinOpKind::Gt | BinOpKind::Ge | BinOpKind::Lt | BinOpKind::Le
+                        if lhs_type == Type::Int && rhs_type == Type::Int =>
+                    {
+                        Some(Type::Bool)
+                    }
+                    BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul | BinOpKind::Div
+                        if lhs_type == Type::Int && rhs_type == Type::Int =>
+                    {
+                        Some(Type::Int)
+                    }
+                    // Concatenated at compile time, strings have no runtime allocation
+                    BinOpKind::Add if lhs_type == Type::String && rhs_type == Type::String => {
+                        if !self.in_const {
+                            self.error(
+                                expr.span,
+                                "strings can only be concatenated in constants".into(),
+                            );
+                        }
+                        Some(Type::String)
+                    }
+                    _ => None,
+                };
+
+                if result.is_none() {
+                    self.error(
+                        expr.span,
+                        format!(
+                            "cannot apply `{}` to `{}` and `{}`",
+                            op, lhs_type, rhs_type
+                        ),
+                    );
+                }
+                result
+            }
+            ExprKind::StructLit(name, fields) => {
+                let found: Vec<Option<Type>> =
+                    fields.iter().map(|&(_, value)| self.check_expr(ast, value)).collect();
+                let def = self.resolution.lookup(name)?;
+                if def.kind != DefKind::Struct {
+                    self.error(name.span, format!("`{}` is not a struct", name.value));
+                    return None;
+                }
+
+                for (i, ((field, value), found)) in fields.iter().zip(found).enumerate() {
+                    if fields[..i].iter().any(|(prev, _)| prev.value == field.value) {
+                        self.error(
+                            field.span,
+                            format!("field `{}` is specified more than once", field.value),
+                        );
+                        continue;
+                    }
+
+                    match def.field(&field.value) {
+                        Some(expected) => self.expect_type(expected, found, ast[*value].span),
+                        None => self.error(
+                            field.span,
+                            format!("struct `{}` has no field `{}`", def.name, field.value),
+                        ),
+                    }
+                }
+
+                let missing: Vec<String> = def
+                    .fields
+                    .iter()
+                    .filter(|(field, _)| fields.iter().all(|(given, _)| given.value != *field))
+                    .map(|(field, _)| format!("`{}`", field))
+                    .collect();
+                if !missing.is_empty() {
+                    let noun = if missing.len() == 1 { "field" } else { "fields" };
+                    self.error(
+                        expr.span,
+                        format!("missing {} {} in `{}` literal", noun, missing.join(", "), def.name),
+                    );
+                }
+
+                Some(def.type_.clone())
+            }
+            ExprKind::Case(scrutinee, arms) => {
+                if let Some(type_) = self.check_expr(ast, *scrutinee) {
+                    let patterns: Vec<&Pattern> = arms.iter().map(|arm| &arm.pattern).collect();
+                    self.check_patterns(&type_, &patterns, expr.span);
+                }
+
+                // Every arm must produce the type of the first
+                let mut result: Option<Type> = None;
+                for arm in arms {
+                    let found = self.check_expr(ast, arm.body);
+                    match &result {
+                        Some(expected) => {
+                            let expected = expected.clone();
+                            self.expect_type(&expected, found, ast[arm.body].span);
+                        }
+                        None => result = found,
+                    }
+                }
+                if result == Some(Type::Void) {
+                    self.error(expr.span, "`case` arms must produce a value".into());
+                    return None;
+                }
+                result
+            }
+            ExprKind::ArrayLit(elements) => {
+                let Some((&first, rest)) = elements.split_first() else {
+                    self.error(expr.span, "cannot infer the type of an empty array".into());
+                    return None;
+                };
+
+                // Every element must have the type of the first
+                let element = self.check_expr(ast, first);
+                for &other in rest {
+                    let found = self.check_expr(ast, other);
+                    if let Some(element) = &element {
+                        self.expect_type(element, found, ast[other].span);
+                    }
+                }
+                if element == Some(Type::Void) {
+                    self.error(ast[first].span, "array elements must have a value".into());
+                    return None;
+                }
+                Some(Type::Array(Box::new(element?), elements.len()))
+            }
+            ExprKind::Index(object, index) => {
+                let found = self.check_expr(ast, *index);
+                self.expect_type(&Type::Int, found, ast[*index].span);
+
+                let type_ = self.check_expr(ast, *object)?;
+                let Some(element) = type_.element() else {
+                    self.error(expr.span, format!("cannot index into a value of type `{}`", type_));
+                    return None;
+                };
+
+                // Constant indexes into arrays are checked now instead of at run time
+                if let (Type::Array(_, len), ExprKind::IntLit(n)) = (&type_, &ast[*index].kind) {
+                    if *n >= *len as u128 {
+                        self.error(
+                            ast[*index].span,
+                            format!("index out of bounds: the len is {} but the index is {}", len, n),
+                        );
+                    }
+                }
+                Some(element.clone())
+            }
+            ExprKind::Slice(object, start, end) => {
+                for &bound in start.iter().chain(end) {
+                    let found = self.check_expr(ast, bound);
+                    self.expect_type(&Type::Int, found, ast[bound].span);
+                }
+
+                let type_ = self.check_expr(ast, *object)?;
+                match &type_ {
+                    // The slice points into the array, which has to outlive the expression
+                    Type::Array(_, _) if !ast[*object].kind.is_place(ast) => {
+                        self.error(
+                            ast[*object].span,
+                            "cannot slice a temporary array, store it in a variable first".into(),
+                        );
+                        None
+                    }
+                    Type::Array(element, _) | Type::Slice(element) => {
+                        Some(Type::Slice(element.clone()))
+                    }
+                    _ => {
+                        self.error(expr.span, format!("cannot slice a value of type `{}`", type_));
+                        None
+                    }
+                }
+            }
+            ExprKind::FieldAccess(object, field) => {
+                let type_ = self.check_expr(ast, *object)?;
+                let Some(def) = self.resolution.struct_def(&type_) else {
+                    self.error(field.span, format!("type `{}` has no fields", type_));
+                    return None;
+                };
+
+                let found = def.field(&field.value).cloned();
+                if found.is_none() {
+                    self.error(
+                        field.span,
+                        format!("struct `{}` has no field `{}`", def.name, field.value),
+                    );
+                }
+                found
+            }
+        }
+    }
+
+    /// Whether values of `type_` can be compared with `==`
+    fn is_comparable(&self, type_: &Type) -> bool {
+        match type_ {
+            Type::Void | Type::Array(_, _) | Type::Slice(_) => false,
+            Type::Named(_) => {
+                !self.resolution.variants(type_).is_empty()
+                    && !self.resolution.is_tagged_union(type_)
+            }
+            _ => true,
+        }
+    }
+
+    pub fn check(&mut self, ast: &AST) {
+        self.check_block(ast, &ast.root);
+    }
+}
+
+/// Union of disjoint inclusive ranges of integers, kept sorted
+#[derive(Default)]
+struct Intervals(Vec<(i128, i128)>);
+
+impl Intervals {
+    /// Whether every value in `start..end` is already in the union
+    fn contains(&self, start: i128, end: i128) -> bool {
+        self.0.iter().any(|&(s, e)| s <= start && end <= e)
+    }
+
+    fn insert(&mut self, start: i128, end: i128) {
+        self.0.push((start, end));
+        self.0.sort();
+
+        // Merge overlapping and adjacent ranges
+        let mut merged: Vec<(i128, i128)> = vec![];
+        for &(s, e) in &self.0 {
+            match merged.last_mut() {
+                Some(last) if s <= last.1 + 1 => last.1 = last.1.max(e),
+                _ => merged.push((s, e)),
+            }
+        }
+        self.0 = merged;
+    }
+
+    /// First range of values in `min..max` that isn't in the union
+    fn first_gap(&self, min: i128, max: i128) -> Option<(i128, i128)> {
+        let mut next = min;
+        for &(s, e) in &self.0 {
+            if s > next {
+                return Some((next, s - 1));
+            }
+            next = next.max(e + 1);
+        }
+        (next <= max).then_some((next, max))
+    }
+}
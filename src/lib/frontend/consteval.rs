@@ -0,0 +1,470 @@
+//! Compile time evaluation of constants.
+//!
+//! Runs between parsing and name resolution. Every top level `const` is
+//! evaluated, then the places that need a value at compile time are filled
+//! in: array lengths naming a constant become plain lengths, so do `[_]`
+//! lengths of variables initialized with an array literal, and `case`
+//! patterns naming a constant become int or string patterns.
+//!
+//! Constant expressions are made of literals, other constants, arithmetic,
+//! comparisons, string concatenation and calls to procs whose bodies only use
+//! `var`, `if` and `return`. Ints are i32, overflow and division by zero are
+//! errors. Operands of the wrong type are left for the type checker to report.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::ast::*;
+use crate::errors::ConstError;
+use crate::frontend::lexer::token::Span;
+use crate::types::Type;
+
+/// How deeply calls made by a constant can nest
+const MAX_DEPTH: usize = 64;
+
+/// How many calls evaluating all the constants can make
+const MAX_CALLS: usize = 100_000;
+
+/// Value of a constant expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn type_(&self) -> Type {
+        match self {
+            Value::Int(_) => Type::Int,
+            Value::Str(_) => Type::String,
+            Value::Bool(_) => Type::Bool,
+        }
+    }
+}
+
+/// What running a statement at compile time did
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+/// Replace the `_` lengths in `type_` with those of the array literal `value`,
+/// the first element of a literal gives the lengths of nested arrays
+fn infer_lengths(ast: &AST, type_: &mut Type, value: ExprId) {
+    let ExprKind::ArrayLit(elements) = &ast[value].kind else {
+        return;
+    };
+    let Some(&first) = elements.first() else {
+        return;
+    };
+    match type_ {
+        Type::ConstArray(element, len) if len == "_" => {
+            infer_lengths(ast, element, first);
+            *type_ = Type::Array(element.clone(), elements.len());
+        }
+        Type::Array(element, _) | Type::ConstArray(element, _) => {
+            infer_lengths(ast, element, first);
+        }
+        _ => {}
+    }
+}
+
+/// Evaluates the constants of an AST and fills in where they are used
+#[derive(Default)]
+pub struct ConstEvaluator {
+    /// Top level constants and procs by name
+    decls: HashMap<String, StmtId>,
+    /// Top level variables, which constants can't use
+    globals: HashSet<String>,
+    /// Values of the constants evaluated so far
+    values: HashMap<String, Value>,
+    /// Constants without a value, the reason was already reported
+    failed: HashSet<String>,
+    /// Constants being evaluated, to catch one that depends on itself
+    evaluating: Vec<String>,
+    /// Locals of the procs being called, innermost last
+    frames: Vec<HashMap<String, Value>>,
+    depth: usize,
+    calls: usize,
+    pub errors: Vec<ConstError>,
+}
+
+impl ConstEvaluator {
+    pub fn new() -> ConstEvaluator {
+        ConstEvaluator::default()
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(ConstError { span, message });
+    }
+
+    /// Evaluate every constant in `ast`, then fill in the array lengths and
+    /// patterns that name one. Returns the values by the id of the name of
+    /// their declaration.
+    pub fn evaluate(&mut self, ast: &mut AST) -> HashMap<NodeId, Value> {
+        // Constants can use constants and procs declared after them
+        let mut consts = vec![];
+        for &stmt in &ast.root {
+            match &ast[stmt].kind {
+                StmtKind::ConstDeclaration(name, _, _) => {
+                    consts.push(name.clone());
+                    self.decls.entry(name.value.clone()).or_insert(stmt);
+                }
+                StmtKind::ProcDeclaration(proc) => {
+                    self.decls.entry(proc.name.value.clone()).or_insert(stmt);
+                }
+                StmtKind::VarDeclaration(name, _, _) => {
+                    self.globals.insert(name.value.clone());
+                }
+                _ => {}
+            }
+        }
+
+        let mut values = HashMap::new();
+        for name in consts {
+            if let Some(value) = self.const_value(ast, &name.value, name.span) {
+                values.insert(name.id, value);
+            }
+        }
+
+        for i in 0..ast.stmts.len() {
+            let StmtKind::VarDeclaration(_, type_, value) = &ast.stmts[i].kind else {
+                continue;
+            };
+            let mut type_ = type_.clone();
+            infer_lengths(ast, &mut type_, *value);
+            if let StmtKind::VarDeclaration(_, declared, _) = &mut ast.stmts[i].kind {
+                *declared = type_;
+            }
+        }
+        for stmt in &mut ast.stmts {
+            self.fill_stmt(&mut stmt.kind);
+        }
+        for expr in &mut ast.exprs {
+            if let ExprKind::Case(_, arms) = &mut expr.kind {
+                for arm in arms {
+                    self.fill_pattern(&mut arm.pattern);
+                }
+            }
+        }
+        values
+    }
+
+    /// Fill in the constants used by the types or patterns of a statement
+    fn fill_stmt(&mut self, kind: &mut StmtKind) {
+        match kind {
+            StmtKind::VarDeclaration(name, type_, _)
+            | StmtKind::ConstDeclaration(name, type_, _) => self.fill_type(type_, name.span),
+            StmtKind::ProcDeclaration(proc) => {
+                self.fill_type(&mut proc.return_type, proc.name.span);
+                for param in &mut proc.params {
+                    self.fill_type(&mut param.type_, param.name.span);
+                }
+            }
+            StmtKind::StructDeclaration(struct_) => {
+                for field in &mut struct_.fields {
+                    self.fill_type(&mut field.type_, field.name.span);
+                }
+            }
+            StmtKind::EnumDeclaration(enum_) => {
+                for field in enum_.variants.iter_mut().flat_map(|v| &mut v.fields) {
+                    self.fill_type(&mut field.type_, field.name.span);
+                }
+            }
+            StmtKind::Case(_, arms) => {
+                for arm in arms {
+                    self.fill_pattern(&mut arm.pattern);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace array lengths naming a constant with its value, `span` is where
+    /// the type is used
+    fn fill_type(&mut self, type_: &mut Type, span: Span) {
+        match type_ {
+            Type::Array(element, _) | Type::Slice(element) => self.fill_type(element, span),
+            Type::ConstArray(element, name) => {
+                self.fill_type(element, span);
+                let len = match self.values.get(name.as_str()) {
+                    Some(&Value::Int(len)) if len > 0 => len as usize,
+                    Some(Value::Int(len)) => {
+                        let message =
+                            format!("array length `{}` must be positive, it is {}", name, len);
+                        return self.error(span, message);
+                    }
+                    Some(value) => {
+                        let message = format!(
+                            "array length `{}` must be an `int`, found `{}`",
+                            name,
+                            value.type_()
+                        );
+                        return self.error(span, message);
+                    }
+                    None if self.failed.contains(name.as_str()) => return,
+                    None if name == "_" => {
+                        let message = "cannot infer the array length, `[_]` needs an array literal to take it from";
+                        return self.error(span, message.into());
+                    }
+                    None => {
+                        let message = format!("cannot find constant `{}` in this scope", name);
+                        return self.error(span, message);
+                    }
+                };
+                *type_ = Type::Array(element.clone(), len);
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace a pattern naming an int or string constant with its value
+    fn fill_pattern(&self, pattern: &mut Pattern) {
+        let PatternKind::Variant(name, bindings) = &pattern.kind else {
+            return;
+        };
+        if !bindings.is_empty() {
+            return;
+        }
+        match self.values.get(&name.value) {
+            Some(&Value::Int(n)) => pattern.kind = PatternKind::Int(n as i128),
+            Some(Value::Str(s)) => pattern.kind = PatternKind::Str(s.clone()),
+            _ => {}
+        }
+    }
+
+    /// Value of the constant `name`, evaluated the first time it's needed.
+    /// `span` is where it's used.
+    fn const_value(&mut self, ast: &AST, name: &str, span: Span) -> Option<Value> {
+        if let Some(value) = self.values.get(name) {
+            return Some(value.clone());
+        }
+        if self.failed.contains(name) {
+            return None;
+        }
+        if self.evaluating.iter().any(|other| other == name) {
+            self.error(span, format!("constant `{}` depends on itself", name));
+            return None;
+        }
+        let &stmt = self.decls.get(name)?;
+        let StmtKind::ConstDeclaration(_, type_, value) = &ast[stmt].kind else {
+            return None;
+        };
+        // Constants of other types are reported by the type checker
+        if !matches!(type_, Type::Int | Type::String | Type::Bool) {
+            self.failed.insert(name.into());
+            return None;
+        }
+
+        // The value doesn't depend on the locals of a proc using the constant
+        let frames = std::mem::take(&mut self.frames);
+        self.evaluating.push(name.into());
+        let result = self.eval(ast, *value);
+        self.evaluating.pop();
+        self.frames = frames;
+
+        match &result {
+            Some(value) => {
+                self.values.insert(name.into(), value.clone());
+            }
+            None => {
+                self.failed.insert(name.into());
+            }
+        }
+        result
+    }
+
+    /// Evaluate a constant expression, None if it has no value. Errors the
+    /// type checker or the resolver would report too are left to them.
+    fn eval(&mut self, ast: &AST, id: ExprId) -> Option<Value> {
+        let expr = &ast[id];
+        match &expr.kind {
+            // Out of range literals are reported by the type checker
+            ExprKind::IntLit(n) => i32::try_from(*n).ok().map(Value::Int),
+            ExprKind::StringLit(s) => Some(Value::Str(s.clone())),
+            ExprKind::BoolLit(b) => Some(Value::Bool(*b)),
+            ExprKind::Variable(name) => {
+                if let Some(value) = self.frames.last().and_then(|frame| frame.get(&name.value)) {
+                    return Some(value.clone());
+                }
+                if self.globals.contains(&name.value) {
+                    let message = format!("cannot use variable `{}` in a constant", name.value);
+                    self.error(name.span, message);
+                    return None;
+                }
+                self.const_value(ast, &name.value, name.span)
+            }
+            ExprKind::UnaryOp(UnaryOpKind::Neg, rhs) if matches!(ast[*rhs].kind, ExprKind::IntLit(n) if n == i32::MIN.unsigned_abs() as u128) =>
+            {
+                // i32::MIN is only in range once negated
+                Some(Value::Int(i32::MIN))
+            }
+            ExprKind::UnaryOp(op, rhs) => match (op, self.eval(ast, *rhs)?) {
+                (UnaryOpKind::Neg, Value::Int(n)) => {
+                    let result = n.checked_neg().map(Value::Int);
+                    if result.is_none() {
+                        self.error(
+                            expr.span,
+                            format!("attempt to negate `{}`, which would overflow", n),
+                        );
+                    }
+                    result
+                }
+                (UnaryOpKind::Not, Value::Bool(b)) => Some(Value::Bool(!b)),
+                _ => None,
+            },
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let lhs = self.eval(ast, *lhs)?;
+                let rhs = self.eval(ast, *rhs)?;
+                self.binop(lhs, *op, rhs, expr.span)
+            }
+            ExprKind::Call(name, args) => self.call(ast, name, args, expr.span),
+            _ => {
+                self.error(
+                    expr.span,
+                    "this expression can't be evaluated at compile time".into(),
+                );
+                None
+            }
+        }
+    }
+
+    fn binop(&mut self, lhs: Value, op: BinOpKind, rhs: Value, span: Span) -> Option<Value> {
+        let value = match (lhs, rhs) {
+            (Value::Int(a), Value::Int(b)) if op.is_comparison() => Value::Bool(match op {
+                BinOpKind::Eq => a == b,
+                BinOpKind::Ne => a != b,
+                BinOpKind::Lt => a < b,
+                BinOpKind::Le => a <= b,
+                BinOpKind::Gt => a > b,
+                _ => a >= b,
+            }),
+            (Value::Int(a), Value::Int(b)) => {
+                let result = match op {
+                    BinOpKind::Add => a.checked_add(b),
+                    BinOpKind::Sub => a.checked_sub(b),
+                    BinOpKind::Mul => a.checked_mul(b),
+                    _ if b == 0 => {
+                        self.error(span, format!("attempt to divide `{}` by zero", a));
+                        return None;
+                    }
+                    _ => a.checked_div(b),
+                };
+                match result {
+                    Some(n) => Value::Int(n),
+                    None => {
+                        let message = format!(
+                            "attempt to compute `{} {} {}`, which would overflow",
+                            a, op, b
+                        );
+                        self.error(span, message);
+                        return None;
+                    }
+                }
+            }
+            (Value::Str(a), Value::Str(b)) => match op {
+                BinOpKind::Add => Value::Str(a + &b),
+                BinOpKind::Eq => Value::Bool(a == b),
+                BinOpKind::Ne => Value::Bool(a != b),
+                _ => return None,
+            },
+            (Value::Bool(a), Value::Bool(b)) => match op {
+                BinOpKind::Eq => Value::Bool(a == b),
+                BinOpKind::Ne => Value::Bool(a != b),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Call a proc at compile time
+    fn call(&mut self, ast: &AST, name: &Name, args: &[ExprId], span: Span) -> Option<Value> {
+        let Some(&stmt) = self.decls.get(&name.value) else {
+            if matches!(name.value.as_str(), "print" | "len") {
+                self.error(
+                    name.span,
+                    format!("cannot call `{}` in a constant", name.value),
+                );
+            }
+            return None;
+        };
+        let StmtKind::ProcDeclaration(proc) = &ast[stmt].kind else {
+            return None;
+        };
+        let args: Vec<Value> = args
+            .iter()
+            .map(|&arg| self.eval(ast, arg))
+            .collect::<Option<_>>()?;
+        if args.len() != proc.params.len() {
+            return None;
+        }
+
+        self.calls += 1;
+        if self.calls == MAX_CALLS {
+            let message = format!("constant evaluation took more than {} calls", MAX_CALLS);
+            self.error(span, message);
+        }
+        if self.depth == MAX_DEPTH {
+            let message = format!("constant evaluation nested more than {} calls", MAX_DEPTH);
+            self.error(span, message);
+        }
+        if self.calls >= MAX_CALLS || self.depth == MAX_DEPTH {
+            return None;
+        }
+
+        let params = proc.params.iter().map(|param| param.name.value.clone());
+        self.frames.push(params.zip(args).collect());
+        self.depth += 1;
+        let flow = self.exec_block(ast, &proc.body);
+        self.depth -= 1;
+        self.frames.pop();
+
+        // Falling off the end is reported by the type checker
+        match flow? {
+            Flow::Return(value) => Some(value),
+            Flow::Next => None,
+        }
+    }
+
+    fn exec_block(&mut self, ast: &AST, stmts: &[StmtId]) -> Option<Flow> {
+        // Variables declared in the block go out of scope after it. Nothing
+        // else in the frame can change, there are no assignments.
+        let frame = self.frames.last().cloned();
+        let mut flow = Some(Flow::Next);
+        for &stmt in stmts {
+            flow = self.exec(ast, stmt);
+            if !matches!(flow, Some(Flow::Next)) {
+                break;
+            }
+        }
+        if let (Some(frame), Some(last)) = (frame, self.frames.last_mut()) {
+            *last = frame;
+        }
+        flow
+    }
+
+    /// Run a statement of a proc called at compile time
+    fn exec(&mut self, ast: &AST, id: StmtId) -> Option<Flow> {
+        match &ast[id].kind {
+            StmtKind::VarDeclaration(name, _, value) => {
+                let value = self.eval(ast, *value)?;
+                self.frames.last_mut()?.insert(name.value.clone(), value);
+                Some(Flow::Next)
+            }
+            StmtKind::If(cond, then, otherwise) => match (self.eval(ast, *cond)?, otherwise) {
+                (Value::Bool(true), _) => self.exec_block(ast, then),
+                (Value::Bool(false), Some(otherwise)) => self.exec_block(ast, otherwise),
+                (Value::Bool(false), None) => Some(Flow::Next),
+                _ => None,
+            },
+            StmtKind::Return(Some(value)) => Some(Flow::Return(self.eval(ast, *value)?)),
+            StmtKind::Return(None) => None,
+            _ => {
+                let message = "procs called in constants can only use `var`, `if` and `return`";
+                self.error(ast[id].span, message.into());
+                None
+            }
+        }
+    }
+}
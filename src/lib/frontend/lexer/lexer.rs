@@ -1,8 +1,8 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
-use std::vec;
+use unicode_xid::UnicodeXID;
 
-//use crate::errors::LexerError;
+use crate::errors::LexerError;
 use super::token::{Token, TokenKind};
 
 lazy_static! {
@@ -13,32 +13,45 @@ lazy_static! {
         ("while", TokenKind::While),
         ("case", TokenKind::Case),
         ("proc", TokenKind::Proc),
+        ("struct", TokenKind::Struct),
+        ("enum", TokenKind::Enum),
         ("ptr", TokenKind::Ptr),
         ("var", TokenKind::Var),
+        ("const", TokenKind::Const),
+        ("return", TokenKind::Return),
+        ("true", TokenKind::True),
+        ("false", TokenKind::False),
+        ("import", TokenKind::Import),
+        ("pub", TokenKind::Pub),
         ("int", TokenKind::IntType),
-        ("str", TokenKind::StrType)
+        ("str", TokenKind::StrType),
+        ("bool", TokenKind::BoolType)
     ]);
 }
 
 pub struct Lexer<'lex> {
-    // start and current position in the stream
+    // start and current byte offsets in the source
     start: usize,
     current: usize,
     line: usize,
 
     source: &'lex str,
-    pub tokens: Vec<Token>,
+    /// Token produced by the last step, waiting to be yielded
+    pending: Option<Token>,
+    /// Set once Eof or an error has been yielded
+    finished: bool,
 }
 
 impl<'lex> Lexer<'lex> {
-    pub fn new(source: &str) -> Lexer {
+    pub fn new(source: &str) -> Lexer<'_> {
         Lexer {
             start: 0,
             current: 0,
             line: 0,
 
             source,
-            tokens: vec![],
+            pending: None,
+            finished: false,
         }
     }
 
@@ -48,192 +61,269 @@ impl<'lex> Lexer<'lex> {
 
     /// Peek ahead and return the character
     fn peek(&self) -> char {
-        self.source.chars().nth(self.current).unwrap_or('\0')
+        self.peek_next(0)
     }
 
     /// Peek ahead by `ahead` characters and return the character
     fn peek_next(&self, ahead: usize) -> char {
-        self.source
+        // Only decodes up to `ahead` characters past the cursor, so this stays
+        // constant time however far into the source we are
+        self.source[self.current..]
             .chars()
-            .nth(self.current + ahead)
+            .nth(ahead)
             .unwrap_or('\0')
     }
 
-    /// Advance the lexer position
+    /// Advance the lexer position past the current character
     fn advance(&mut self) {
-        self.current += 1;
+        if !self.at_end() {
+            self.current += self.peek().len_utf8();
+        }
     }
 
-    /// Push a token of `kind` and advance the lexer by `length`
+    /// Advance the lexer by `length` bytes and emit a token of `kind` spanning from the start
     fn push(&mut self, length: usize, kind: TokenKind) {
-        self.tokens
-            .push(Token::of(kind, self.start, self.current, self.line));
         self.current += length;
+        self.pending = Some(Token::of(kind, self.start, self.current, self.line));
     }
 
-    fn expect(&self, c: char, msg: &str) {
-        if self.peek() != c {}
+    fn error(&self, message: &str) -> LexerError {
+        LexerError {
+            position: self.start,
+            line: self.line,
+            message: message.into(),
+        }
     }
 
-    /// Scan hexadecimal format integers
-    fn scan_hex_int(&mut self) {
-        let hex_chars = "0123456789ABCDEFabcdef";
-
-        // Skip over 0x
-        self.advance();
-        self.advance();
+    fn expect(&self, c: char, msg: &str) -> Result<(), LexerError> {
+        if self.peek() != c {
+            return Err(self.error(msg));
+        }
+        Ok(())
+    }
 
-        while hex_chars.contains(self.peek()) {
+    /// Skip over digits in `radix` and `_` separators, `push_int` checks that
+    /// each separator is between two digits
+    fn scan_digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
             self.advance();
         }
-
-        let lexed: String = self.source[self.start + 2..self.current].into();
-        self.push(
-            0,
-            TokenKind::IntLit(i32::from_str_radix(&lexed, 16).expect("failed to encode")),
-        );
     }
 
-    /// Scan binary format integers
-    fn scan_bin_int(&mut self) {
-        let bin_chars = "01";
-
-        // Skip over 0b
-        self.advance();
-        self.advance();
+    /// Parse the digits between `self.start + skip` and the current position in `radix`
+    fn push_int(&mut self, skip: usize, radix: u32, name: &str) -> Result<(), LexerError> {
+        let digits: String = self.source[self.start + skip..self.current]
+            .chars()
+            .filter(|&c| c != '_')
+            .collect();
 
-        while bin_chars.contains(self.peek()) {
-            self.advance();
+        if digits.is_empty() {
+            return Err(self.error(&format!("expected digits in {} literal", name)));
+        }
+        let text = &self.source[self.start + skip..self.current];
+        if text.starts_with('_') || text.ends_with('_') || text.contains("__") {
+            return Err(self.error(&format!("`_` must be between digits in {} literal", name)));
+        }
+        if self.peek().is_xid_continue() {
+            let message = format!("invalid digit '{}' in {} literal", self.peek(), name);
+            return Err(self.error(&message));
         }
 
-        let lexed: String = self.source[self.start + 2..self.current].into();
-        self.push(
-            0,
-            TokenKind::IntLit(i32::from_str_radix(&lexed, 2).expect("failed to encode")),
-        );
+        // Literals stay unsized until the type checker gives them a type
+        let value = u128::from_str_radix(&digits, radix)
+            .map_err(|_| self.error("integer literal is too large"))?;
+        self.push(0, TokenKind::IntLit(value));
+        Ok(())
     }
 
-    fn scan_int(&mut self) {
-        let mut has_radix = false;
-
-        if self.peek() == '0' {
-            self.advance();
-        }
+    /// Scan integers with a radix prefix such as 0x
+    fn scan_radix_int(&mut self, radix: u32, name: &str) -> Result<(), LexerError> {
+        // Skip over the prefix
+        self.advance();
+        self.advance();
 
-        // NOTE: should we support underscores in integer literals?
-        while self.peek().is_ascii_digit() {
-            self.advance();
-        }
+        self.scan_digits(radix);
+        self.push_int(2, radix, name)
+    }
 
+    fn scan_int(&mut self) -> Result<(), LexerError> {
         // Match on the radix
-        match self.peek() {
-            'b' => {
-                has_radix = true;
-                self.scan_bin_int()
-            }
-            'x' => {
-                has_radix = true;
-                self.scan_hex_int()
-            }
+        match (self.peek(), self.peek_next(1)) {
+            ('0', 'b') => return self.scan_radix_int(2, "binary"),
+            ('0', 'o') => return self.scan_radix_int(8, "octal"),
+            ('0', 'x') => return self.scan_radix_int(16, "hexadecimal"),
             _ => {}
         }
 
-        if !has_radix {
-            // Parse as decimal
-            let lexed: String = self.source[self.start..self.current].into();
-            self.push(
-                0,
-                TokenKind::IntLit(i32::from_str_radix(&lexed, 10).expect("failed to encode")),
-            );
-        }
+        self.scan_digits(10);
+
+        // Parse as decimal
+        self.push_int(0, 10, "decimal")
     }
 
+    /// Scan an identifier or keyword, following UAX #31 with a leading `_` allowed
     fn scan_ident(&mut self) {
-        while self.peek().is_ascii_alphabetic() || self.peek() == '_' {
+        while self.peek().is_xid_continue() {
             self.advance();
         }
 
-        let lexed: String = self.source[self.start..self.current].into();
+        let lexed = &self.source[self.start..self.current];
 
-        match KEYWORDS.get(&*lexed) {
+        match KEYWORDS.get(lexed) {
             Some(k) => self.push(0, k.clone()),
-            None => self.push(0, TokenKind::Identifier(lexed)),
+            None => self.push(0, TokenKind::Identifier(lexed.into())),
         }
     }
 
-    fn scan_string(&mut self) {
+    fn scan_string(&mut self) -> Result<(), LexerError> {
         self.advance();
         while self.peek() != '"' && !self.at_end() {
+            if self.peek() == '\n' {
+                self.line += 1;
+            }
             self.advance();
         }
 
-        let lexed: String = self.source[self.start+1..self.current].into();
+        let lexed: String = self.source[self.start + 1..self.current].into();
 
-        self.expect('"', "unexpected end of string");
-        self.push(2, TokenKind::StringLit(lexed));
+        self.expect('"', "unterminated string literal")?;
+        self.push(1, TokenKind::StringLit(lexed));
+        Ok(())
     }
 
-    pub fn scan(&mut self) -> Vec<Token> {
-        while !self.at_end() {
-            self.start = self.current;
-
+    /// Skip a `#[ ... ]#` comment, which can be nested
+    fn scan_block_comment(&mut self) -> Result<(), LexerError> {
+        let mut depth = 0;
+        loop {
             match (self.peek(), self.peek_next(1)) {
-                ('(', _) => self.push(1, TokenKind::LParen),
-                (')', _) => self.push(1, TokenKind::RParen),
-                ('{', _) => self.push(1, TokenKind::LBrace),
-                ('}', _) => self.push(1, TokenKind::RBrace),
-                (',', _) => self.push(1, TokenKind::Comma),
-                ('.', _) => self.push(1, TokenKind::Dot),
-                ('+', '=') => self.push(2, TokenKind::PlusEqual),
-                ('+', _) => self.push(1, TokenKind::Plus),
-                ('-', '=') => self.push(2, TokenKind::MinusEqual),
-                ('-', _) => self.push(1, TokenKind::Minus),
-                ('*', _) => self.push(1, TokenKind::Star),
-                ('/', _) => self.push(1, TokenKind::Slash),
-
-                (':', _) => self.push(1, TokenKind::Colon),
-                ('!', '=') => self.push(2, TokenKind::BangEqual),
-                ('=', '=') => self.push(2, TokenKind::EqualEqual),
-                ('<', '=') => self.push(2, TokenKind::LessEqual),
-                ('>', '=') => self.push(2, TokenKind::GreaterEqual),
-
-                ('=', _) => self.push(1, TokenKind::Equal),
-                ('<', _) => self.push(1, TokenKind::Less),
-                ('>', _) => self.push(1, TokenKind::Greater),
-
-                ('"', _) => self.scan_string(),
-                (w, _) if w.is_whitespace() => self.advance(),
-                (digit, _) if digit.is_digit(10) => self.scan_int(),
-                (ident, _) if ident.is_ascii_alphabetic() => self.scan_ident(),
-
-                // Comments
-                ('#', _) => {
-                    while self.peek() != '\n' && !self.at_end() {
-                        self.advance();
-                    }
-
-                    if self.peek() == '\n' {
-                        self.advance();
-                    }
-
-                    self.line += 1;
+                _ if self.at_end() => return Err(self.error("unterminated block comment")),
+                ('#', '[') => depth += 1,
+                (']', '#') => depth -= 1,
+                ('\n', _) => self.line += 1,
+                _ => {
+                    self.advance();
+                    continue;
                 }
+            }
 
-                // Newlines
-                ('\n', _) => {
-                    self.push(1, TokenKind::NewLine);
-                    self.line += 1;
-                }
+            // Skip over the newline or the two character delimiter
+            if self.peek() != '\n' {
+                self.advance();
+            }
+            self.advance();
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Scan a `##` doc comment, the parser attaches it to the next declaration
+    fn scan_doc_comment(&mut self) {
+        // Skip over ##
+        self.advance();
+        self.advance();
+
+        while self.peek() != '\n' && !self.at_end() {
+            self.advance();
+        }
+
+        let text = &self.source[self.start + 2..self.current];
+        let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+        self.push(0, TokenKind::DocComment(text.into()));
+    }
+
+    /// Scan the whole source up front, stopping at the first error
+    pub fn scan_all(&mut self) -> Result<Vec<Token>, LexerError> {
+        self.collect()
+    }
 
-                ('\\', '\n') => {
-                    // Continuation, just advance
+    /// Scan one character sequence, which may or may not produce a token
+    fn step(&mut self) -> Result<(), LexerError> {
+        self.start = self.current;
+        if self.at_end() {
+            self.finished = true;
+            self.push(0, TokenKind::Eof);
+            return Ok(());
+        }
+
+        match (self.peek(), self.peek_next(1)) {
+            ('(', _) => self.push(1, TokenKind::LParen),
+            (')', _) => self.push(1, TokenKind::RParen),
+            ('{', _) => self.push(1, TokenKind::LBrace),
+            ('}', _) => self.push(1, TokenKind::RBrace),
+            ('[', _) => self.push(1, TokenKind::LBracket),
+            (']', _) => self.push(1, TokenKind::RBracket),
+            (',', _) => self.push(1, TokenKind::Comma),
+            ('.', '.') => self.push(2, TokenKind::DotDot),
+            ('.', _) => self.push(1, TokenKind::Dot),
+            ('+', '=') => self.push(2, TokenKind::PlusEqual),
+            ('+', _) => self.push(1, TokenKind::Plus),
+            ('-', '=') => self.push(2, TokenKind::MinusEqual),
+            ('-', _) => self.push(1, TokenKind::Minus),
+            ('*', _) => self.push(1, TokenKind::Star),
+            ('/', _) => self.push(1, TokenKind::Slash),
+
+            (':', _) => self.push(1, TokenKind::Colon),
+            ('!', '=') => self.push(2, TokenKind::BangEqual),
+            ('=', '=') => self.push(2, TokenKind::EqualEqual),
+            ('=', '>') => self.push(2, TokenKind::FatArrow),
+            ('<', '=') => self.push(2, TokenKind::LessEqual),
+            ('>', '=') => self.push(2, TokenKind::GreaterEqual),
+
+            ('!', _) => self.push(1, TokenKind::Bang),
+            ('=', _) => self.push(1, TokenKind::Equal),
+            ('<', _) => self.push(1, TokenKind::Less),
+            ('>', _) => self.push(1, TokenKind::Greater),
+
+            ('"', _) => self.scan_string()?,
+
+            // Newlines
+            ('\n', _) => {
+                self.advance();
+                self.line += 1;
+            }
+
+            (w, _) if w.is_whitespace() => self.advance(),
+            (digit, _) if digit.is_ascii_digit() => self.scan_int()?,
+            (ident, _) if ident == '_' || ident.is_xid_start() => self.scan_ident(),
+
+            // Comments
+            ('#', '[') => self.scan_block_comment()?,
+            ('#', '#') if self.peek_next(2) != '#' => self.scan_doc_comment(),
+            ('#', _) => {
+                while self.peek() != '\n' && !self.at_end() {
                     self.advance();
-                    self.line += 1;
                 }
-                (_, _) => self.advance(),
+            }
+
+            ('\\', '\n') => {
+                // Continuation, just advance
+                self.advance();
+                self.advance();
+                self.line += 1;
+            }
+            (c, _) => return Err(self.error(&format!("unexpected character '{}'", c))),
+        }
+        Ok(())
+    }
+}
+
+/// Yields tokens on demand, ending with Eof. Nothing is yielded after Eof or an error.
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.pending.take() {
+                return Some(Ok(token));
+            }
+            if self.finished {
+                return None;
+            }
+            if let Err(err) = self.step() {
+                self.finished = true;
+                return Some(Err(err));
             }
         }
-        self.push(1, TokenKind::Eof);
-        self.tokens.clone()
     }
 }
@@ -6,8 +6,11 @@ pub enum TokenKind {
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Comma,
     Dot,
+    DotDot,
     Plus,
     Minus,
     Star,
@@ -25,6 +28,7 @@ pub enum TokenKind {
     LessEqual,
     PlusEqual,
     MinusEqual,
+    FatArrow,
 
     // reserved keywords
     If,
@@ -33,22 +37,61 @@ pub enum TokenKind {
     While,
     Case,
     Proc,
+    Struct,
+    Enum,
     Ptr,
     Var,
+    Const,
+    Return,
+    True,
+    False,
+    Import,
+    Pub,
 
     // types
     IntType,
     StrType,
+    BoolType,
 
     // literals
     Identifier(String),
     StringLit(String),
-    IntLit(i32),
+    IntLit(u128),
+
+    /// `##` comment documenting the declaration after it, without the `##`
+    DocComment(String),
 
     NewLine,
     Eof,
 }
 
+/// Source range of a token or AST node as byte offsets, `end` is exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize) -> Span {
+        Span { start, end, line }
+    }
+
+    /// Return a span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+        }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
@@ -66,25 +109,43 @@ impl Token {
             line,
         }
     }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.end, self.line)
+    }
 }
 
 impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let str_val = match &*self {
+        let str_val = match self {
             TokenKind::LParen => "(",
             TokenKind::RParen => ")",
             TokenKind::LBrace => "{",
             TokenKind::RBrace => "}",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
             TokenKind::Comma => ",",
             TokenKind::Dot => ".",
+            TokenKind::DotDot => "..",
             TokenKind::Plus => "+",
             TokenKind::Minus => "-",
             TokenKind::Star => "*",
             TokenKind::Slash => "/",
+            TokenKind::Colon => ":",
+            TokenKind::Bang => "!",
+            TokenKind::BangEqual => "!=",
+            TokenKind::Equal => "=",
+            TokenKind::EqualEqual => "==",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::PlusEqual => "+=",
+            TokenKind::MinusEqual => "-=",
+            TokenKind::FatArrow => "=>",
             _ => "unknown",
         };
 
         write!(f, "{}", str_val)
     }
 }
-
@@ -0,0 +1,609 @@
+//! Modules and imports.
+//!
+//! `import "shapes/geo"` or `import shapes.geo` loads `shapes/geo.lune`,
+//! looked up next to the importing file first and then in each directory of
+//! the search path. A file is loaded once however many files import it, and
+//! a file importing itself, directly or through other files, is an error.
+//!
+//! The modules are merged into one program, every module after those it
+//! imports, so their top level statements run first. To keep top level names
+//! unique, the items of an imported module are renamed to `<module>.<item>`,
+//! where the module name is the path of the file relative to the directory
+//! of the file being compiled, or of the search path directory it was found
+//! in, eg `shapes.geo.area`. References are renamed to match, `geo.area` in
+//! an importing file included, so later passes see a single program. Items
+//! can only be used from other modules if they are declared `pub`.
+//!
+//! Packages the program depends on are imported by name: `import geometry`
+//! loads the entry file of the dependency `geometry` and `import
+//! geometry.vec` loads `vec.lune` from its source directories.
+//!
+//! Spans stay byte offsets into a single source: each file gets its own
+//! range of offsets, starting at its `SourceFile::base`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::backend::ast::*;
+use crate::backend::visit::{walk_block_mut, walk_expr_mut, walk_stmt_mut, VisitorMut};
+use crate::errors::ModuleError;
+use crate::frontend::lexer::lexer::Lexer;
+use crate::frontend::lexer::token::Span;
+use crate::frontend::parser::Parser;
+use crate::frontend::analysis::LineIndex;
+use crate::types::Type;
+
+/// A file of the program
+pub struct SourceFile {
+    pub path: PathBuf,
+    /// Name the items of the module are qualified with, empty for the file
+    /// being compiled
+    pub module: String,
+    pub source: String,
+    /// Offset the spans in the file start at
+    pub base: usize,
+    pub lines: LineIndex,
+    /// Span of the import that loaded the file, None for the file being compiled
+    pub imported_at: Option<Span>,
+    /// Files it imports, by index
+    pub imports: Vec<usize>,
+}
+
+impl SourceFile {
+    /// The file being compiled, `source` is its contents
+    pub fn new(path: &Path, source: &str) -> SourceFile {
+        SourceFile {
+            path: path.to_path_buf(),
+            module: String::new(),
+            source: source.into(),
+            base: 0,
+            lines: LineIndex::new(source),
+            imported_at: None,
+            imports: vec![],
+        }
+    }
+
+    /// Line and UTF-16 column of an offset of the program in the file, both from 0
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.saturating_sub(self.base);
+        self.lines.position(&self.source, offset)
+    }
+}
+
+/// A package the program depends on
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    /// Name it is imported by
+    pub name: String,
+    /// File `import <name>` loads
+    pub entry: PathBuf,
+    /// Directories `import <name>.<module>` looks in
+    pub source_dirs: Vec<PathBuf>,
+}
+
+/// The file an offset of the program is in, None if there are no files
+pub fn file_at(files: &[SourceFile], offset: usize) -> Option<&SourceFile> {
+    files.iter().rev().find(|file| file.base <= offset)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A loaded file and what the names in it can refer to
+struct Module {
+    ast: AST,
+    /// Module every import name refers to, by index
+    imports: HashMap<String, usize>,
+    /// Top level names and whether they are `pub`
+    items: HashMap<String, bool>,
+}
+
+impl Module {
+    fn new(ast: AST) -> Module {
+        let mut items = HashMap::new();
+        for &id in &ast.root {
+            let public = ast.public.contains(&ast[id].id);
+            let mut add = |name: &Name| items.insert(name.value.clone(), public);
+            match &ast[id].kind {
+                StmtKind::VarDeclaration(name, _, _) | StmtKind::ConstDeclaration(name, _, _) => {
+                    add(name);
+                }
+                StmtKind::ProcDeclaration(proc) => {
+                    add(&proc.name);
+                }
+                StmtKind::StructDeclaration(struct_) => {
+                    add(&struct_.name);
+                }
+                // Variants are exported along with their enum
+                StmtKind::EnumDeclaration(enum_) => {
+                    add(&enum_.name);
+                    for variant in &enum_.variants {
+                        add(&variant.name);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Module {
+            ast,
+            imports: HashMap::new(),
+            items,
+        }
+    }
+}
+
+/// Loads the modules a file imports and merges them into one program
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    dependencies: Vec<Dependency>,
+    /// Directories module names are relative to, the directory of the file
+    /// being compiled first
+    roots: Vec<PathBuf>,
+    /// Every file loaded, the file being compiled first
+    pub files: Vec<SourceFile>,
+    /// Module of every file, by the same index
+    modules: Vec<Module>,
+    /// Index of every file loaded, by canonical path
+    loaded: HashMap<PathBuf, usize>,
+    /// Modules whose imports are being loaded, innermost last
+    stack: Vec<usize>,
+    /// Modules in the order they are merged, each after those it imports
+    order: Vec<usize>,
+    next_id: usize,
+    pub errors: Vec<ModuleError>,
+}
+
+impl ModuleLoader {
+    pub fn new(search_path: &[PathBuf]) -> ModuleLoader {
+        ModuleLoader {
+            search_path: search_path.to_vec(),
+            dependencies: vec![],
+            roots: vec![],
+            files: vec![],
+            modules: vec![],
+            loaded: HashMap::new(),
+            stack: vec![],
+            order: vec![],
+            next_id: 0,
+            errors: vec![],
+        }
+    }
+
+    /// Also load imports from these packages
+    pub fn with_dependencies(mut self, dependencies: Vec<Dependency>) -> ModuleLoader {
+        self.dependencies = dependencies;
+        self
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(ModuleError { span, message });
+    }
+
+    /// Offset the spans of the next file loaded start at, one past the end of
+    /// the last file so an offset at the end of a file stays in it
+    fn next_base(&self) -> usize {
+        self.files
+            .last()
+            .map_or(0, |file| file.base + file.source.len() + 1)
+    }
+
+    /// Load everything `ast` imports and merge it all into one program.
+    /// `ast` was parsed from `source`, the contents of `path`.
+    pub fn load(&mut self, path: &Path, source: &str, ast: AST) -> AST {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        self.roots = std::iter::once(dir.unwrap_or(Path::new(".")))
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(canonical)
+            .collect();
+
+        self.next_id = ast.node_count();
+        self.loaded.insert(canonical(path), 0);
+        self.files.push(SourceFile::new(path, source));
+        self.modules.push(Module::new(ast));
+
+        self.load_imports(0);
+        if self.errors.is_empty() {
+            for module in 0..self.modules.len() {
+                self.qualify(module);
+            }
+        }
+
+        let mut program = std::mem::take(&mut self.modules[0].ast);
+        let entry = std::mem::take(&mut program.root);
+        let mut root = vec![];
+        for &module in &self.order[..self.order.len() - 1] {
+            let ast = std::mem::take(&mut self.modules[module].ast);
+            root.extend(program.append(ast));
+        }
+        root.extend(entry);
+        program.root = root;
+        program
+    }
+
+    /// Load the modules `module` imports that aren't loaded yet, and the ones
+    /// they import
+    fn load_imports(&mut self, module: usize) {
+        self.stack.push(module);
+        for import in self.modules[module].ast.imports.clone() {
+            let Some(path) = self.find(module, &import.path) else {
+                self.error(import.span, format!("cannot find module `{}`", import.path));
+                continue;
+            };
+
+            let target = match self.loaded.get(&canonical(&path)) {
+                Some(&target) if self.stack.contains(&target) => {
+                    let start = self.stack.iter().position(|&m| m == target).unwrap();
+                    let cycle: Vec<String> = self.stack[start..]
+                        .iter()
+                        .chain([&target])
+                        .map(|&m| self.display_name(m))
+                        .collect();
+                    self.error(import.span, format!("import cycle: {}", cycle.join(" -> ")));
+                    continue;
+                }
+                Some(&target) => target,
+                None => {
+                    let Some(target) = self.parse_file(path, import.span) else {
+                        continue;
+                    };
+                    self.load_imports(target);
+                    target
+                }
+            };
+            self.modules[module].imports.insert(import.name, target);
+            self.files[module].imports.push(target);
+        }
+        self.stack.pop();
+        self.order.push(module);
+    }
+
+    /// Path of the file an import of `module` refers to, None if there is none
+    fn find(&self, module: usize, path: &str) -> Option<PathBuf> {
+        let with_extension = |path: &str| match path.ends_with(".lune") {
+            true => path.to_string(),
+            false => format!("{}.lune", path),
+        };
+
+        // Dependencies come first, the first component of the path names them
+        let (package, rest) = path.split_once('/').unwrap_or((path, ""));
+        if let Some(dependency) = self.dependencies.iter().find(|d| d.name == package) {
+            if rest.is_empty() {
+                return Some(dependency.entry.clone()).filter(|entry| entry.is_file());
+            }
+            let file = with_extension(rest);
+            let mut candidates = dependency.source_dirs.iter().map(|dir| dir.join(&file));
+            return candidates.find(|candidate| candidate.is_file());
+        }
+
+        let file = with_extension(path);
+        let dir = self.files[module].path.parent().unwrap_or(Path::new(""));
+        std::iter::once(dir)
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(&file))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Name the items of the file at `path` are qualified with, modules of
+    /// dependencies start with the name of the dependency
+    fn module_name(&self, path: &Path) -> String {
+        let path = canonical(path);
+        let mut parts = vec![];
+        let mut relative = None;
+        for dependency in &self.dependencies {
+            relative = match canonical(&dependency.entry) == path {
+                true => Some(PathBuf::new()),
+                false => dependency.source_dirs.iter().find_map(|dir| {
+                    let relative = path.strip_prefix(canonical(dir)).ok();
+                    relative.map(Path::to_path_buf)
+                }),
+            };
+            if relative.is_some() {
+                parts.push(dependency.name.clone());
+                break;
+            }
+        }
+        let relative = relative.unwrap_or_else(|| {
+            let relative = self
+                .roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .unwrap_or(Path::new(path.file_name().unwrap_or_default()));
+            relative.to_path_buf()
+        });
+        let relative = relative.with_extension("");
+        parts.extend(
+            relative
+                .iter()
+                .map(|part| part.to_string_lossy().into_owned()),
+        );
+
+        // Files found in different directories can have the same relative path
+        let name = parts.join(".");
+        let mut unique = name.clone();
+        let mut n = 2;
+        while self.files.iter().any(|file| file.module == unique) {
+            unique = format!("{}{}", name, n);
+            n += 1;
+        }
+        unique
+    }
+
+    /// Module name for messages, the file name for the file being compiled
+    fn display_name(&self, module: usize) -> String {
+        let file = &self.files[module];
+        match file.module.as_str() {
+            "" => file
+                .path
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into()),
+            name => name.into(),
+        }
+    }
+
+    /// Read, lex and parse the file at `path`, returning its module index. A
+    /// file that doesn't lex or parse is still added so its errors have a
+    /// location.
+    fn parse_file(&mut self, path: PathBuf, imported_at: Span) -> Option<usize> {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                self.error(
+                    imported_at,
+                    format!("couldn't read {}: {}", path.display(), err),
+                );
+                return None;
+            }
+        };
+
+        let base = self.next_base();
+        let index = self.files.len();
+        self.loaded.insert(canonical(&path), index);
+        self.files.push(SourceFile {
+            module: self.module_name(&path),
+            path,
+            lines: LineIndex::new(&source),
+            source,
+            base,
+            imported_at: Some(imported_at),
+            imports: vec![],
+        });
+
+        let tokens = Lexer::new(&self.files[index].source).map(|token| {
+            token.map(|mut token| {
+                token.start += base;
+                token.end += base;
+                token
+            })
+        });
+        let mut parser = Parser::new(tokens).with_ids_from(self.next_id);
+        let ast = parser.parse().map_err(|err| match parser.lexer_error() {
+            Some(err) => ModuleError {
+                span: Span::new(base + err.position, base + err.position + 1, err.line),
+                message: err.message.clone(),
+            },
+            None => ModuleError {
+                span: err.curr_token.span(),
+                message: err.message,
+            },
+        });
+
+        let ast = ast.unwrap_or_else(|err| {
+            self.errors.push(err);
+            AST::new()
+        });
+        self.next_id = usize::max(self.next_id, ast.node_count());
+        self.modules.push(Module::new(ast));
+        Some(index)
+    }
+
+    /// Rename the items of `module` and the references to items of any module
+    /// to their qualified names
+    fn qualify(&mut self, module: usize) {
+        let mut ast = std::mem::take(&mut self.modules[module].ast);
+        let mut qualifier = Qualifier {
+            prefix: &self.files[module].module,
+            module: &self.modules[module],
+            modules: &self.modules,
+            files: &self.files,
+            scopes: vec![],
+            errors: vec![],
+        };
+        for stmt in ast.root.clone() {
+            qualifier.visit_stmt_mut(&mut ast, stmt);
+        }
+
+        let errors = qualifier.errors;
+        self.errors.extend(errors);
+        self.modules[module].ast = ast;
+    }
+}
+
+/// Renames the names of one module to their qualified names
+struct Qualifier<'a> {
+    /// Module name the module's own items are qualified with, empty in the
+    /// file being compiled
+    prefix: &'a str,
+    module: &'a Module,
+    modules: &'a [Module],
+    files: &'a [SourceFile],
+    /// Names declared by the enclosing procs, blocks and case arms
+    scopes: Vec<HashSet<String>>,
+    errors: Vec<ModuleError>,
+}
+
+impl Qualifier<'_> {
+    /// Qualified name of the item `value` refers to, None if it stays as it
+    /// is. Locals can shadow items, except in types and array lengths.
+    fn qualify(&mut self, value: &str, span: Span, shadowed: bool) -> Option<String> {
+        // The parser only qualifies names with the name of an import, a missing
+        // item is left for the resolver to report
+        if let Some((import, item)) = value.split_once('.') {
+            let &target = self.module.imports.get(import)?;
+            let &public = self.modules[target].items.get(item)?;
+            if !public {
+                self.errors.push(ModuleError {
+                    span,
+                    message: format!("`{}` is private to module `{}`", item, import),
+                });
+            }
+            return Some(format!("{}.{}", self.files[target].module, item));
+        }
+
+        let shadowed = shadowed && self.scopes.iter().any(|scope| scope.contains(value));
+        if self.prefix.is_empty() || shadowed || !self.module.items.contains_key(value) {
+            return None;
+        }
+        Some(format!("{}.{}", self.prefix, value))
+    }
+
+    fn reference(&mut self, name: &mut Name) {
+        if let Some(value) = self.qualify(&name.value, name.span, true) {
+            name.value = value;
+        }
+    }
+
+    /// Qualify a top level declaration, or declare a local in the innermost scope
+    fn declare(&mut self, name: &mut Name) {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.value.clone());
+            }
+            None if !self.prefix.is_empty() => {
+                name.value = format!("{}.{}", self.prefix, name.value);
+            }
+            None => {}
+        }
+    }
+
+    /// Qualify the named types in `type_`, `span` is where the type is used
+    fn qualify_type(&mut self, type_: &mut Type, span: Span) {
+        match type_ {
+            Type::Named(name) => {
+                if let Some(value) = self.qualify(name, span, false) {
+                    *name = value;
+                }
+            }
+            Type::ConstArray(element, len) => {
+                if let Some(value) = self.qualify(len, span, false) {
+                    *len = value;
+                }
+                self.qualify_type(element, span);
+            }
+            Type::Array(element, _) | Type::Slice(element) => self.qualify_type(element, span),
+            Type::Int | Type::String | Type::Bool | Type::Void => {}
+        }
+    }
+
+    fn with_scope<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        self.scopes.push(HashSet::new());
+        f(self);
+        self.scopes.pop();
+    }
+}
+
+impl VisitorMut for Qualifier<'_> {
+    fn visit_block_mut(&mut self, ast: &mut AST, stmts: &mut Block) {
+        self.with_scope(|q| walk_block_mut(q, ast, stmts));
+    }
+
+    fn visit_stmt_mut(&mut self, ast: &mut AST, stmt: StmtId) {
+        let mut kind = std::mem::replace(&mut ast[stmt].kind, StmtKind::Return(None));
+        match &mut kind {
+            StmtKind::VarDeclaration(name, type_, value)
+            | StmtKind::ConstDeclaration(name, type_, value) => {
+                // The value can't refer to the variable being declared
+                self.visit_expr_mut(ast, *value);
+                self.qualify_type(type_, name.span);
+                self.declare(name);
+            }
+            StmtKind::ProcDeclaration(proc) => {
+                self.qualify_type(&mut proc.return_type, proc.name.span);
+                self.declare(&mut proc.name);
+                self.with_scope(|q| {
+                    for param in &mut proc.params {
+                        q.qualify_type(&mut param.type_, param.name.span);
+                        q.declare(&mut param.name);
+                    }
+                    q.visit_block_mut(ast, &mut proc.body);
+                });
+            }
+            StmtKind::StructDeclaration(struct_) => {
+                self.declare(&mut struct_.name);
+                for field in &mut struct_.fields {
+                    self.qualify_type(&mut field.type_, field.name.span);
+                }
+            }
+            StmtKind::EnumDeclaration(enum_) => {
+                self.declare(&mut enum_.name);
+                for variant in &mut enum_.variants {
+                    self.declare(&mut variant.name);
+                    for field in &mut variant.fields {
+                        self.qualify_type(&mut field.type_, field.name.span);
+                    }
+                }
+            }
+            StmtKind::Case(scrutinee, arms) => {
+                self.visit_expr_mut(ast, *scrutinee);
+                for arm in arms {
+                    self.with_scope(|q| {
+                        q.visit_pattern_mut(&mut arm.pattern);
+                        q.visit_block_mut(ast, &mut arm.body);
+                    });
+                }
+            }
+            _ => {
+                ast[stmt].kind = kind;
+                walk_stmt_mut(self, ast, stmt);
+                return;
+            }
+        }
+        ast[stmt].kind = kind;
+    }
+
+    fn visit_expr_mut(&mut self, ast: &mut AST, expr: ExprId) {
+        let mut kind = std::mem::replace(&mut ast[expr].kind, ExprKind::BoolLit(false));
+        match &mut kind {
+            ExprKind::Variable(name) => self.reference(name),
+            ExprKind::Call(name, args) => {
+                self.reference(name);
+                for &arg in args.iter() {
+                    self.visit_expr_mut(ast, arg);
+                }
+            }
+            // Field names depend on the type of the value, they aren't items
+            ExprKind::StructLit(name, fields) => {
+                self.reference(name);
+                for &(_, value) in fields.iter() {
+                    self.visit_expr_mut(ast, value);
+                }
+            }
+            ExprKind::FieldAccess(object, _) => self.visit_expr_mut(ast, *object),
+            ExprKind::Case(scrutinee, arms) => {
+                self.visit_expr_mut(ast, *scrutinee);
+                for arm in arms.iter_mut() {
+                    self.with_scope(|q| {
+                        q.visit_pattern_mut(&mut arm.pattern);
+                        q.visit_expr_mut(ast, arm.body);
+                    });
+                }
+            }
+            _ => {
+                ast[expr].kind = kind;
+                walk_expr_mut(self, ast, expr);
+                return;
+            }
+        }
+        ast[expr].kind = kind;
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        if let PatternKind::Variant(name, bindings) = &mut pattern.kind {
+            self.reference(name);
+            for binding in bindings {
+                self.declare(binding);
+            }
+        }
+    }
+}
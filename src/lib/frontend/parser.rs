@@ -1,21 +1,98 @@
-use crate::errors::ParseError;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use crate::frontend::lexer::token::{Token, TokenKind};
+use crate::errors::{LexerError, ParseError};
+
+use crate::frontend::lexer::token::{Span, Token, TokenKind};
 use crate::types::Type;
 
 // AST
 use crate::backend::ast::*;
 
-pub struct Parser {
+/// Parses a stream of tokens, such as a `Lexer`, pulling tokens on demand.
+/// A token slice can be parsed with `Parser::new(tokens.into_iter().map(Ok))`.
+pub struct Parser<I: Iterator<Item = Result<Token, LexerError>>> {
+    /// Number of tokens consumed so far
     position: usize,
-    tokens: Vec<Token>,
+    tokens: I,
+    /// Tokens pulled from the stream but not consumed yet, never empty
+    lookahead: VecDeque<Token>,
+    /// Last consumed token
+    previous: Token,
+    /// First error from the token stream, which ends it early
+    lexer_error: Option<LexerError>,
+    /// Doc comment lines not yet followed by a token
+    doc: Vec<String>,
+    /// Doc comments by the offset of the token they precede
+    docs: HashMap<usize, String>,
+    /// Arena the parsed nodes are allocated in
+    ast: AST,
+    /// Names of the imported modules, `name.item` refers to an item of one
+    modules: HashSet<String>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser {
+impl<I: Iterator<Item = Result<Token, LexerError>>> Parser<I> {
+    pub fn new<T: IntoIterator<IntoIter = I>>(tokens: T) -> Parser<I> {
+        let mut parser = Parser {
             position: 0,
-            tokens,
+            tokens: tokens.into_iter(),
+            lookahead: VecDeque::new(),
+            previous: Token::of(TokenKind::Eof, 0, 0, 0),
+            lexer_error: None,
+            doc: vec![],
+            docs: HashMap::new(),
+            ast: AST::new(),
+            modules: HashSet::new(),
+        };
+        parser.fill(0);
+        parser
+    }
+
+    /// Hand out node ids from `next_id`, so the tree can be merged into
+    /// another one with `AST::append`
+    pub fn with_ids_from(mut self, next_id: usize) -> Parser<I> {
+        self.ast = AST::with_ids_from(next_id);
+        self
+    }
+
+    /// The error that ended the token stream early, if any. Once it is set
+    /// `parse` fails with its message
+    pub fn lexer_error(&self) -> Option<&LexerError> {
+        self.lexer_error.as_ref()
+    }
+
+    /// Pull tokens until `ahead` tokens past the current one are buffered,
+    /// or the stream has ended in Eof
+    fn fill(&mut self, ahead: usize) {
+        while self.lookahead.len() <= ahead {
+            let last = self.lookahead.back().unwrap_or(&self.previous);
+            if last.kind == TokenKind::Eof && !self.lookahead.is_empty() {
+                return;
+            }
+
+            let token = match self.tokens.next() {
+                Some(Ok(token)) => token,
+                // Stop at the bad token, `parse` reports the lexer error
+                Some(Err(err)) => {
+                    let token = Token::of(TokenKind::Eof, err.position, err.position, err.line);
+                    self.lexer_error.get_or_insert(err);
+                    token
+                }
+                // Streams are expected to end in Eof, add one if they don't
+                None => {
+                    let span = last.span();
+                    Token::of(TokenKind::Eof, span.end, span.end, span.line)
+                }
+            };
+
+            if let TokenKind::DocComment(text) = token.kind {
+                self.doc.push(text);
+                continue;
+            }
+            if !self.doc.is_empty() {
+                self.docs.insert(token.start, self.doc.join("\n"));
+                self.doc.clear();
+            }
+            self.lookahead.push_back(token);
         }
     }
 
@@ -25,18 +102,27 @@ impl Parser {
 
     /// Return the previous token
     fn prev(&self) -> Token {
-        self.tokens.get(self.position - 1).unwrap().clone()
+        self.previous.clone()
     }
 
-    /// Peek ahead and return the token 
+    /// Peek ahead and return the token
     fn peek(&self) -> Token {
-        self.tokens.get(self.position).unwrap().clone()
+        self.lookahead[0].clone()
+    }
+
+    /// Peek ahead by `ahead` tokens, the last token is always Eof
+    fn peek_next(&mut self, ahead: usize) -> Token {
+        self.fill(ahead);
+        let last = self.lookahead.len() - 1;
+        self.lookahead[usize::min(ahead, last)].clone()
     }
 
     /// Advance the position and return the previous token
     fn advance(&mut self) -> Token {
         if !self.at_end() {
-            self.position += 1
+            self.previous = self.lookahead.pop_front().unwrap();
+            self.position += 1;
+            self.fill(0);
         }
         self.prev()
     }
@@ -50,36 +136,73 @@ impl Parser {
         }
     }
 
+    /// Consume a token of `kind` or fail with `message`
+    fn expect(&mut self, kind: TokenKind, message: &str) -> Result<Token, ParseError> {
+        self.consume(kind).ok_or_else(|| self.error(message))
+    }
+
     /// Return the matched token if there is one, otherwise return None
     /// If we are at the end of the stream, just return None.
     fn matches(&mut self, kinds: &[TokenKind]) -> Option<Token> {
         for kind in kinds {
             if !self.at_end() && self.peek().kind == *kind {
-                //self.advance();
-                return Some(self.peek());
+                return Some(self.advance());
             }
         }
         None
     }
 
+    fn error(&self, message: &str) -> ParseError {
+        // A truncated stream is reported as the lexer error that cut it short
+        let message = match &self.lexer_error {
+            Some(err) => &err.message,
+            None => message,
+        };
+
+        ParseError {
+            curr_token: self.peek(),
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    /// Keep the doc comment before `start` for the declaration `stmt`
+    fn attach_doc(&mut self, start: &Token, stmt: StmtId) {
+        if let Some(doc) = self.docs.remove(&start.start) {
+            let id = self.ast[stmt].id;
+            self.ast.docs.insert(id, doc);
+        }
+    }
+
+    /// Span from `start` up to the end of the previous token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev().span())
+    }
+
     /// Parsing
-    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+    fn parse_expr(&mut self) -> Result<ExprId, ParseError> {
         // expression ::= equality
         self.parse_equality()
     }
 
-    fn parse_equality(&mut self) -> Result<Expr, ParseError> {
+    fn binary(&mut self, lhs: ExprId, op: &Token, rhs: ExprId) -> ExprId {
+        let span = self.ast[lhs].span.to(self.ast[rhs].span);
+        let op = BinOpKind::from_token(&op.kind).expect("not a binary operator");
+        self.ast.add_expr(ExprKind::BinOp(lhs, op, rhs), span)
+    }
+
+    fn parse_equality(&mut self) -> Result<ExprId, ParseError> {
         // equality ::= comparison ( ("!=" | "==") comparison)*
         let mut expr = self.parse_comparison()?;
         while let Some(op) = self.matches(&[TokenKind::BangEqual, TokenKind::EqualEqual]) {
             let rhs = self.parse_comparison()?;
-            expr = Expr::BinOp(Box::new(expr), op, Box::new(rhs));
+            expr = self.binary(expr, &op, rhs);
         }
 
         Ok(expr)
     }
 
-    fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
+    fn parse_comparison(&mut self) -> Result<ExprId, ParseError> {
         // comparison ::= term ( (">" | ">=" | "<" | "<=") term)*
         let mut expr = self.parse_term()?;
         while let Some(op) = self.matches(&[
@@ -89,123 +212,791 @@ impl Parser {
             TokenKind::LessEqual,
         ]) {
             let rhs = self.parse_term()?;
-            expr = Expr::BinOp(Box::new(expr), op, Box::new(rhs));
+            expr = self.binary(expr, &op, rhs);
         }
 
         Ok(expr)
     }
 
-    fn parse_term(&mut self) -> Result<Expr, ParseError> {
+    fn parse_term(&mut self) -> Result<ExprId, ParseError> {
         // term ::= factor ( ("+" | "-") factor)*
         let mut expr = self.parse_factor()?;
         while let Some(op) = self.matches(&[TokenKind::Plus, TokenKind::Minus]) {
             let rhs = self.parse_factor()?;
-            expr = Expr::BinOp(Box::new(expr), op, Box::new(rhs));
+            expr = self.binary(expr, &op, rhs);
         }
 
         Ok(expr)
     }
 
-    fn parse_factor(&mut self) -> Result<Expr, ParseError> {
+    fn parse_factor(&mut self) -> Result<ExprId, ParseError> {
         // factor ::= unary ( ("*" | "/") unary)*
         let mut expr = self.parse_unary()?;
         while let Some(op) = self.matches(&[TokenKind::Star, TokenKind::Slash]) {
             let rhs = self.parse_unary()?;
-            expr = Expr::BinOp(Box::new(expr), op, Box::new(rhs));
+            expr = self.binary(expr, &op, rhs);
         }
 
         Ok(expr)
     }
 
-    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+    fn parse_unary(&mut self) -> Result<ExprId, ParseError> {
         // unary ::= ("!" | "-") unary
-        //        | primary
+        //        | postfix
         if let Some(op) = self.matches(&[TokenKind::Bang, TokenKind::Minus]) {
-            let rhs = self.parse_factor()?;
-            return Ok(Expr::UnaryOp(op, Box::new(rhs)));
+            let rhs = self.parse_unary()?;
+            let span = op.span().to(self.ast[rhs].span);
+            let op = UnaryOpKind::from_token(&op.kind).expect("not a unary operator");
+            return Ok(self.ast.add_expr(ExprKind::UnaryOp(op, rhs), span));
         }
 
-        self.parse_primary()
+        self.parse_postfix()
     }
 
-    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
-        if let TokenKind::IntLit(n) = self.peek().kind {
-            self.advance();
-            return Ok(Expr::IntLit(n));
+    fn parse_postfix(&mut self) -> Result<ExprId, ParseError> {
+        // postfix ::= primary ( "." <Identifier> | "[" expression "]" | "[" range "]" )*
+        // range   ::= expression? ".." expression?
+        let mut expr = self.parse_primary()?;
+        loop {
+            let start = self.ast[expr].span;
+            let kind = if self.consume(TokenKind::Dot).is_some() {
+                ExprKind::FieldAccess(expr, self.parse_name()?)
+            } else if self.consume(TokenKind::LBracket).is_some() {
+                let index = match self.peek().kind {
+                    TokenKind::DotDot => None,
+                    _ => Some(self.parse_expr()?),
+                };
+                let kind = match (index, self.consume(TokenKind::DotDot)) {
+                    (Some(index), None) => ExprKind::Index(expr, index),
+                    (start, _) => {
+                        let end = match self.peek().kind {
+                            TokenKind::RBracket => None,
+                            _ => Some(self.parse_expr()?),
+                        };
+                        ExprKind::Slice(expr, start, end)
+                    }
+                };
+                self.expect(TokenKind::RBracket, "expected ']'")?;
+                kind
+            } else {
+                break;
+            };
+            let span = self.span_from(start);
+            expr = self.ast.add_expr(kind, span);
         }
-        if let TokenKind::StringLit(s) = self.peek().kind {
-            self.advance();
-            return Ok(Expr::StringLit(s));
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<ExprId, ParseError> {
+        // primary ::= literal | path | call | struct_lit | array_lit | case_expr
+        //           | "(" expression ")"
+        let token = self.peek();
+        let span = token.span();
+        let kind = match token.kind {
+            TokenKind::LBracket => {
+                self.advance();
+                return self.parse_array_lit(span);
+            }
+            TokenKind::Case => {
+                self.advance();
+                return self.parse_case_expr(span);
+            }
+            TokenKind::IntLit(n) => ExprKind::IntLit(n),
+            TokenKind::StringLit(s) => ExprKind::StringLit(s),
+            TokenKind::True => ExprKind::BoolLit(true),
+            TokenKind::False => ExprKind::BoolLit(false),
+            TokenKind::Identifier(_) => {
+                let name = self.parse_qualified_name()?;
+                if self.peek().kind == TokenKind::LParen {
+                    return self.parse_call(name);
+                }
+                // `Point { x: ...` is a struct literal, while `if ok { x = ...`
+                // is a condition followed by a block
+                if self.peek().kind == TokenKind::LBrace
+                    && matches!(self.peek_next(1).kind, TokenKind::Identifier(_))
+                    && self.peek_next(2).kind == TokenKind::Colon
+                {
+                    return self.parse_struct_lit(name);
+                }
+                return Ok(self.ast.add_expr(ExprKind::Variable(name.clone()), name.span));
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let expr = self.parse_expr()?;
+                self.expect(TokenKind::RParen, "expected ')' after expression")?;
+                self.ast[expr].span = self.span_from(span);
+                return Ok(expr);
+            }
+            _ => return Err(self.error("invalid expression")),
+        };
+
+        self.advance();
+        Ok(self.ast.add_expr(kind, span))
+    }
+
+    fn parse_array_lit(&mut self, start: Span) -> Result<ExprId, ParseError> {
+        // array_lit ::= "[" ( expression ( "," expression )* ","? )? "]"
+        let mut elements = Vec::new();
+        while self.peek().kind != TokenKind::RBracket {
+            elements.push(self.parse_expr()?);
+            if self.consume(TokenKind::Comma).is_none() {
+                break;
+            }
         }
+        self.expect(TokenKind::RBracket, "expected ']' after array elements")?;
 
-        Err(ParseError {
-            curr_token: self.peek(),
-            position: self.position,
-            message: "invalid expression".into(),
-        })
+        let span = self.span_from(start);
+        Ok(self.ast.add_expr(ExprKind::ArrayLit(elements), span))
     }
 
-    // Statements
-    fn parse_var(&mut self) -> Result<Stmt, ParseError> {
-        let mut name = String::from("");
-        let type_: Type;
-        let mut value: Option<Expr> = None;
+    fn parse_call(&mut self, name: Name) -> Result<ExprId, ParseError> {
+        // call ::= path "(" ( expression ( "," expression )* )? ")"
+        self.expect(TokenKind::LParen, "expected '('")?;
+
+        let mut args = Vec::new();
+        if self.consume(TokenKind::RParen).is_none() {
+            loop {
+                args.push(self.parse_expr()?);
+                if self.consume(TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            self.expect(TokenKind::RParen, "expected ')' after arguments")?;
+        }
+
+        let span = self.span_from(name.span);
+        Ok(self.ast.add_expr(ExprKind::Call(name, args), span))
+    }
+
+    fn parse_struct_lit(&mut self, name: Name) -> Result<ExprId, ParseError> {
+        // struct_lit ::= path "{" field_init ( "," field_init )* ","? "}"
+        // field_init ::= <Identifier> ":" expression
+        self.expect(TokenKind::LBrace, "expected '{'")?;
+
+        let mut fields = Vec::new();
+        loop {
+            let field = self.parse_name()?;
+            self.expect(TokenKind::Colon, "expected a colon")?;
+            fields.push((field, self.parse_expr()?));
 
-        // Consume the identifier
-        match self.advance().kind {
-            TokenKind::Identifier(ident) => name = ident,
-            _ => {},
+            if self.consume(TokenKind::Comma).is_none() || self.peek().kind == TokenKind::RBrace {
+                break;
+            }
         }
+        self.expect(TokenKind::RBrace, "expected '}' after fields")?;
+
+        let span = self.span_from(name.span);
+        Ok(self.ast.add_expr(ExprKind::StructLit(name, fields), span))
+    }
+
+    fn parse_case_expr(&mut self, start: Span) -> Result<ExprId, ParseError> {
+        // case_expr ::= "case" expression "{" expr_arm ( "," expr_arm )* ","? "}"
+        // expr_arm  ::= pattern "=>" expression
+        let scrutinee = self.parse_expr()?;
+        self.expect(TokenKind::LBrace, "expected '{' after case value")?;
+
+        let mut arms = Vec::new();
+        loop {
+            let pattern = self.parse_pattern()?;
+            self.expect(TokenKind::FatArrow, "expected '=>' after pattern")?;
+            arms.push(Arm {
+                pattern,
+                body: self.parse_expr()?,
+            });
+
+            if self.consume(TokenKind::Comma).is_none() || self.peek().kind == TokenKind::RBrace {
+                break;
+            }
+        }
+        self.expect(TokenKind::RBrace, "expected '}' after case arms")?;
+
+        let span = self.span_from(start);
+        Ok(self.ast.add_expr(ExprKind::Case(scrutinee, arms), span))
+    }
+
+    /// Parse an integer literal with an optional minus sign
+    fn parse_int(&mut self) -> Result<i128, ParseError> {
+        let negative = self.consume(TokenKind::Minus).is_some();
+        let TokenKind::IntLit(n) = self.peek().kind else {
+            return Err(self.error("expected an integer"));
+        };
+        let n = i128::try_from(n).map_err(|_| self.error("integer literal is too large"))?;
+
+        self.advance();
+        Ok(if negative { -n } else { n })
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        // pattern ::= "_" | <String> | int ( ".." int )?
+        //          | path ( "(" <Identifier> ( "," <Identifier> )* ")" )?
+        // int     ::= "-"? <Number>
+        let start = self.peek().span();
+        let kind = match self.peek().kind {
+            TokenKind::Identifier(name) if name == "_" => {
+                self.advance();
+                PatternKind::Wildcard
+            }
+            TokenKind::Identifier(_) => {
+                let name = self.parse_qualified_name()?;
+                let mut bindings = Vec::new();
+                if self.consume(TokenKind::LParen).is_some() {
+                    loop {
+                        bindings.push(self.parse_name()?);
+                        if self.consume(TokenKind::Comma).is_none() {
+                            break;
+                        }
+                    }
+                    self.expect(TokenKind::RParen, "expected ')' after bindings")?;
+                }
+                PatternKind::Variant(name, bindings)
+            }
+            TokenKind::StringLit(s) => {
+                self.advance();
+                PatternKind::Str(s)
+            }
+            TokenKind::IntLit(_) | TokenKind::Minus => {
+                let start = self.parse_int()?;
+                match self.consume(TokenKind::DotDot) {
+                    Some(_) => PatternKind::Range(start, self.parse_int()?),
+                    None => PatternKind::Int(start),
+                }
+            }
+            _ => return Err(self.error("expected a pattern")),
+        };
+
+        Ok(Pattern {
+            kind,
+            span: self.span_from(start),
+        })
+    }
 
-        match self.consume(TokenKind::Colon) {
-            Some(_) => {}
-            None => panic!("error: expected a colon"),
+    fn parse_name(&mut self) -> Result<Name, ParseError> {
+        let token = self.peek();
+        let span = token.span();
+        match token.kind {
+            TokenKind::Identifier(value) => {
+                self.advance();
+                Ok(self.ast.name(value, span))
+            }
+            _ => Err(self.error("expected an identifier")),
         }
+    }
 
-        match self.peek().kind {
-            TokenKind::IntType => type_ = Type::Int,
-            TokenKind::StrType => type_ = Type::String,
-            _ => panic!("error: invalid type"),
+    /// Parse an identifier, qualified with the module it comes from if it
+    /// starts with the name of an imported module
+    fn parse_path(&mut self) -> Result<(String, Span), ParseError> {
+        // path ::= ( <Module> "." )? <Identifier>
+        let start = self.peek().span();
+        let TokenKind::Identifier(first) = self.peek().kind else {
+            return Err(self.error("expected an identifier"));
+        };
+        self.advance();
+        if !self.modules.contains(&first) || self.peek().kind != TokenKind::Dot {
+            return Ok((first, start));
         }
 
         self.advance();
+        let TokenKind::Identifier(item) = self.peek().kind else {
+            return Err(self.error(&format!("expected a name from module `{}`", first)));
+        };
+        let end = self.advance();
+        Ok((format!("{}.{}", first, item), start.to(end.span())))
+    }
+
+    fn parse_qualified_name(&mut self) -> Result<Name, ParseError> {
+        let (value, span) = self.parse_path()?;
+        Ok(self.ast.name(value, span))
+    }
 
-        if matches!(self.peek().kind, TokenKind::Equal) {
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        // type ::= "int" | "str" | "bool" | path | "[" ( <Number> | path | "_" )? "]" type
+        if self.consume(TokenKind::LBracket).is_some() {
+            if self.consume(TokenKind::RBracket).is_some() {
+                return Ok(Type::Slice(Box::new(self.parse_type()?)));
+            }
+
+            // A constant length is filled in once constants are evaluated, and
+            // `_` by the length of the array literal a variable is initialized with
+            if let TokenKind::Identifier(_) = self.peek().kind {
+                let (name, _) = self.parse_path()?;
+                self.expect(TokenKind::RBracket, "expected ']' after array length")?;
+                return Ok(Type::ConstArray(Box::new(self.parse_type()?), name));
+            }
+
+            let len = match self.peek().kind {
+                TokenKind::IntLit(0) => return Err(self.error("arrays must have at least one element")),
+                TokenKind::IntLit(n) if n <= i32::MAX as u128 => n as usize,
+                TokenKind::IntLit(_) => return Err(self.error("array length is too large")),
+                _ => return Err(self.error("expected an array length or ']'")),
+            };
             self.advance();
-            value = Some(self.parse_expr()?);
+            self.expect(TokenKind::RBracket, "expected ']' after array length")?;
+            return Ok(Type::Array(Box::new(self.parse_type()?), len));
+        }
+
+        let type_ = match self.peek().kind {
+            TokenKind::IntType => Type::Int,
+            TokenKind::StrType => Type::String,
+            TokenKind::BoolType => Type::Bool,
+            TokenKind::Identifier(_) => return Ok(Type::Named(self.parse_path()?.0)),
+            _ => return Err(self.error("invalid type")),
+        };
+
+        self.advance();
+        Ok(type_)
+    }
+
+    // Statements
+    fn parse_var(&mut self) -> Result<StmtKind, ParseError> {
+        // var_declare ::= "var" <Identifier> ":" <Type> "=" expression
+        let name = self.parse_name()?;
+        self.expect(TokenKind::Colon, "expected a colon")?;
+        let type_ = self.parse_type()?;
+        self.expect(TokenKind::Equal, "expected '=' followed by a value")?;
+        let value = self.parse_expr()?;
+
+        Ok(StmtKind::VarDeclaration(name, type_, value))
+    }
+
+    fn parse_const(&mut self) -> Result<StmtKind, ParseError> {
+        // const_declare ::= "const" <Identifier> ":" <Type> "=" expression
+        let name = self.parse_name()?;
+        self.expect(TokenKind::Colon, "expected a colon")?;
+        let type_ = self.parse_type()?;
+        self.expect(TokenKind::Equal, "expected '=' followed by a value")?;
+        let value = self.parse_expr()?;
+
+        Ok(StmtKind::ConstDeclaration(name, type_, value))
+    }
+
+    /// Copy the expression `id` with fresh node ids, eg to read an assignment's
+    /// target. Indexes are copied too, so they are evaluated again.
+    fn copy_expr(&mut self, id: ExprId) -> ExprId {
+        let span = self.ast[id].span;
+        let kind = match self.ast[id].kind.clone() {
+            ExprKind::Variable(name) => ExprKind::Variable(self.ast.name(name.value, name.span)),
+            ExprKind::FieldAccess(object, field) => {
+                let object = self.copy_expr(object);
+                ExprKind::FieldAccess(object, self.ast.name(field.value, field.span))
+            }
+            ExprKind::Index(object, index) => {
+                let object = self.copy_expr(object);
+                ExprKind::Index(object, self.copy_expr(index))
+            }
+            kind @ (ExprKind::IntLit(_) | ExprKind::StringLit(_) | ExprKind::BoolLit(_)) => kind,
+            ExprKind::Call(name, args) => ExprKind::Call(
+                self.ast.name(name.value, name.span),
+                args.into_iter().map(|arg| self.copy_expr(arg)).collect(),
+            ),
+            ExprKind::UnaryOp(op, rhs) => ExprKind::UnaryOp(op, self.copy_expr(rhs)),
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let lhs = self.copy_expr(lhs);
+                ExprKind::BinOp(lhs, op, self.copy_expr(rhs))
+            }
+            ExprKind::StructLit(name, fields) => ExprKind::StructLit(
+                self.ast.name(name.value, name.span),
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (self.ast.name(field.value, field.span), self.copy_expr(value)))
+                    .collect(),
+            ),
+            ExprKind::Case(scrutinee, arms) => ExprKind::Case(
+                self.copy_expr(scrutinee),
+                arms.into_iter()
+                    .map(|arm| {
+                        let kind = match arm.pattern.kind {
+                            PatternKind::Variant(name, bindings) => PatternKind::Variant(
+                                self.ast.name(name.value, name.span),
+                                bindings.into_iter().map(|b| self.ast.name(b.value, b.span)).collect(),
+                            ),
+                            kind => kind,
+                        };
+                        Arm {
+                            pattern: Pattern { kind, ..arm.pattern },
+                            body: self.copy_expr(arm.body),
+                        }
+                    })
+                    .collect(),
+            ),
+            ExprKind::ArrayLit(elements) => {
+                ExprKind::ArrayLit(elements.into_iter().map(|e| self.copy_expr(e)).collect())
+            }
+            ExprKind::Slice(object, start, end) => {
+                let object = self.copy_expr(object);
+                let start = start.map(|start| self.copy_expr(start));
+                ExprKind::Slice(object, start, end.map(|end| self.copy_expr(end)))
+            }
+        };
+        self.ast.add_expr(kind, span)
+    }
+
+    fn parse_assignment(&mut self, target: ExprId) -> Result<StmtKind, ParseError> {
+        // assignment ::= place ("=" | "+=" | "-=") expression
+        // place      ::= <Identifier> ( "." <Identifier> | "[" expression "]" )*
+        if !self.ast[target].kind.is_place(&self.ast) {
+            return Err(self.error("invalid assignment target"));
+        }
+        let op = self.advance();
+        let value = self.parse_expr()?;
+
+        // Desugar compound assignment into a binary operation on the target
+        let value = match op.kind {
+            TokenKind::PlusEqual | TokenKind::MinusEqual => {
+                let kind = if op.kind == TokenKind::PlusEqual {
+                    TokenKind::Plus
+                } else {
+                    TokenKind::Minus
+                };
+                let current = self.copy_expr(target);
+                self.binary(current, &Token::of(kind, op.start, op.end, op.line), value)
+            }
+            _ => value,
+        };
+
+        Ok(StmtKind::Assignment(target, value))
+    }
+
+    fn parse_block(&mut self) -> Result<Block, ParseError> {
+        // block ::= "{" statement* "}"
+        self.expect(TokenKind::LBrace, "expected '{'")?;
+
+        let mut stmts = Vec::new();
+        while self.peek().kind != TokenKind::RBrace {
+            if self.at_end() {
+                return Err(self.error("expected '}' to close the block"));
+            }
+            stmts.push(self.parse_stmt()?);
+        }
+
+        self.advance();
+        Ok(stmts)
+    }
+
+    fn parse_if(&mut self) -> Result<StmtKind, ParseError> {
+        // if_statement ::= "if" expression block ( "else" ( if_statement | block ) )?
+        let cond = self.parse_expr()?;
+        let then = self.parse_block()?;
+
+        let otherwise = match self.consume(TokenKind::Else) {
+            Some(_) if self.peek().kind == TokenKind::If => {
+                let start = self.advance().span();
+                let kind = self.parse_if()?;
+                let span = self.span_from(start);
+                Some(vec![self.ast.add_stmt(kind, span)])
+            }
+            Some(_) => Some(self.parse_block()?),
+            None => None,
+        };
+
+        Ok(StmtKind::If(cond, then, otherwise))
+    }
+
+    fn parse_while(&mut self) -> Result<StmtKind, ParseError> {
+        // while_statement ::= "while" expression block
+        let cond = self.parse_expr()?;
+        let body = self.parse_block()?;
+
+        Ok(StmtKind::While(cond, body))
+    }
+
+    fn parse_return(&mut self, keyword: &Token) -> Result<StmtKind, ParseError> {
+        // return ::= "return" expression?
+        // The value has to start on the same line as the keyword
+        if self.peek().line != keyword.line || self.peek().kind == TokenKind::RBrace {
+            return Ok(StmtKind::Return(None));
+        }
+
+        Ok(StmtKind::Return(Some(self.parse_expr()?)))
+    }
+
+    fn parse_proc(&mut self) -> Result<StmtKind, ParseError> {
+        // proc_declare ::= "proc" <Identifier> "(" params? ")" ( ":" <Type> )? block
+        // params       ::= <Identifier> ":" <Type> ( "," <Identifier> ":" <Type> )*
+        let name = self.parse_name()?;
+        self.expect(TokenKind::LParen, "expected '(' after proc name")?;
+
+        let mut params = Vec::new();
+        if self.consume(TokenKind::RParen).is_none() {
+            loop {
+                let name = self.parse_name()?;
+                self.expect(TokenKind::Colon, "expected a colon")?;
+                let type_ = self.parse_type()?;
+                params.push(Param { name, type_ });
+
+                if self.consume(TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            self.expect(TokenKind::RParen, "expected ')' after parameters")?;
+        }
+
+        let return_type = match self.consume(TokenKind::Colon) {
+            Some(_) => self.parse_type()?,
+            None => Type::Void,
+        };
+
+        let body = self.parse_block()?;
+
+        Ok(StmtKind::ProcDeclaration(Proc {
+            name,
+            params,
+            return_type,
+            body,
+        }))
+    }
+
+    /// Parse comma separated `name: type` fields up to `close`, allowing a trailing comma
+    fn parse_fields(&mut self, close: TokenKind) -> Result<Vec<Field>, ParseError> {
+        let mut fields = Vec::new();
+        loop {
+            let name = self.parse_name()?;
+            self.expect(TokenKind::Colon, "expected a colon")?;
+            let type_ = self.parse_type()?;
+            fields.push(Field { name, type_ });
+
+            if self.consume(TokenKind::Comma).is_none() || self.peek().kind == close {
+                break;
+            }
+        }
+        Ok(fields)
+    }
+
+    fn parse_struct(&mut self) -> Result<StmtKind, ParseError> {
+        // struct_declare ::= "struct" <Identifier> "{" fields ","? "}"
+        // fields         ::= <Identifier> ":" <Type> ( "," <Identifier> ":" <Type> )*
+        let name = self.parse_name()?;
+        self.expect(TokenKind::LBrace, "expected '{' after struct name")?;
+        if self.peek().kind == TokenKind::RBrace {
+            return Err(self.error("structs must have at least one field"));
+        }
+
+        let fields = self.parse_fields(TokenKind::RBrace)?;
+        self.expect(TokenKind::RBrace, "expected '}' after fields")?;
+
+        Ok(StmtKind::StructDeclaration(Struct { name, fields }))
+    }
+
+    fn parse_enum(&mut self) -> Result<StmtKind, ParseError> {
+        // enum_declare ::= "enum" <Identifier> "{" variant ( "," variant )* ","? "}"
+        // variant      ::= <Identifier> ( "(" fields ")" )? ( "=" int )?
+        let name = self.parse_name()?;
+        self.expect(TokenKind::LBrace, "expected '{' after enum name")?;
+        if self.peek().kind == TokenKind::RBrace {
+            return Err(self.error("enums must have at least one variant"));
+        }
+
+        let mut variants = Vec::new();
+        loop {
+            let name = self.parse_name()?;
+            let mut fields = Vec::new();
+            if self.consume(TokenKind::LParen).is_some() {
+                fields = self.parse_fields(TokenKind::RParen)?;
+                self.expect(TokenKind::RParen, "expected ')' after fields")?;
+            }
+            let value = match self.consume(TokenKind::Equal) {
+                Some(_) => Some(self.parse_int()?),
+                None => None,
+            };
+            variants.push(Variant { name, fields, value });
+
+            if self.consume(TokenKind::Comma).is_none() || self.peek().kind == TokenKind::RBrace {
+                break;
+            }
         }
+        self.expect(TokenKind::RBrace, "expected '}' after variants")?;
 
-        Ok(Stmt::VarDeclaration(
-            Name { value: name },
-            type_,
-            value.expect("expected value"),
-        ))
+        Ok(StmtKind::EnumDeclaration(Enum { name, variants }))
     }
 
-    pub fn parse_stmt(&mut self) -> Result<Stmt, ParseError> {
-        match self.peek().kind {
+    fn parse_case(&mut self) -> Result<StmtKind, ParseError> {
+        // case_stmt ::= "case" expression "{" stmt_arm* "}"
+        // stmt_arm  ::= pattern "=>" ( block ","? | statement ( "," | &"}" ) )
+        let scrutinee = self.parse_expr()?;
+        self.expect(TokenKind::LBrace, "expected '{' after case value")?;
+
+        let mut arms = Vec::new();
+        while self.consume(TokenKind::RBrace).is_none() {
+            if self.at_end() {
+                return Err(self.error("expected '}' to close the case"));
+            }
+
+            let pattern = self.parse_pattern()?;
+            self.expect(TokenKind::FatArrow, "expected '=>' after pattern")?;
+            let body = if self.peek().kind == TokenKind::LBrace {
+                let body = self.parse_block()?;
+                self.consume(TokenKind::Comma);
+                body
+            } else {
+                let body = vec![self.parse_stmt()?];
+                if self.peek().kind != TokenKind::RBrace {
+                    self.expect(TokenKind::Comma, "expected ',' after case arm")?;
+                }
+                body
+            };
+            arms.push(Arm { pattern, body });
+        }
+
+        Ok(StmtKind::Case(scrutinee, arms))
+    }
+
+    pub fn parse_stmt(&mut self) -> Result<StmtId, ParseError> {
+        let start = self.peek();
+
+        let kind = match start.kind {
             TokenKind::Var => {
                 self.advance();
-                return self.parse_var();
+                self.parse_var()?
+            }
+            TokenKind::If => {
+                self.advance();
+                self.parse_if()?
+            }
+            TokenKind::While => {
+                self.advance();
+                self.parse_while()?
+            }
+            TokenKind::Return => {
+                self.advance();
+                self.parse_return(&start)?
+            }
+            TokenKind::Case => {
+                self.advance();
+                self.parse_case()?
+            }
+            TokenKind::Proc => return Err(self.error("procs can only be declared at the top level")),
+            TokenKind::Struct => {
+                return Err(self.error("structs can only be declared at the top level"))
+            }
+            TokenKind::Enum => return Err(self.error("enums can only be declared at the top level")),
+            TokenKind::Const => {
+                return Err(self.error("constants can only be declared at the top level"))
             }
-            _ => Err(ParseError {
-                curr_token: self.peek(),
-                position: self.position,
-                message: "invalid statement type".into(),
-            }),
+            TokenKind::Import => {
+                return Err(self.error("imports must come before everything else in the file"))
+            }
+            TokenKind::Pub => {
+                return Err(self.error("only top level declarations can be `pub`"))
+            }
+            _ => {
+                let expr = self.parse_expr()?;
+                match self.peek().kind {
+                    TokenKind::Equal | TokenKind::PlusEqual | TokenKind::MinusEqual => {
+                        self.parse_assignment(expr)?
+                    }
+                    _ => StmtKind::Expr(expr),
+                }
+            }
+        };
+
+        let span = self.span_from(start.span());
+        let stmt = self.ast.add_stmt(kind, span);
+        if start.kind == TokenKind::Var {
+            self.attach_doc(&start, stmt);
         }
+        Ok(stmt)
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
-        let mut stmts: Vec<Stmt> = Vec::new();
+    /// Parse a top level declaration, either a proc, a struct, an enum, a constant,
+    /// a global variable or a statement
+    fn parse_decl(&mut self) -> Result<StmtId, ParseError> {
+        // decl ::= "pub"? ( proc_declare | struct_declare | enum_declare
+        //                 | const_declare | var_declare )
+        //        | statement
+        let start = self.peek();
+        let public = self.consume(TokenKind::Pub).is_some();
+        let keyword = self.peek();
+
+        if public
+            || matches!(
+                keyword.kind,
+                TokenKind::Proc | TokenKind::Struct | TokenKind::Enum | TokenKind::Const
+            )
+        {
+            let kind = match self.advance().kind {
+                TokenKind::Proc => self.parse_proc()?,
+                TokenKind::Struct => self.parse_struct()?,
+                TokenKind::Enum => self.parse_enum()?,
+                TokenKind::Const => self.parse_const()?,
+                TokenKind::Var => self.parse_var()?,
+                _ => {
+                    return Err(ParseError {
+                        curr_token: keyword,
+                        position: self.position - 1,
+                        message: "expected a declaration after `pub`".into(),
+                    })
+                }
+            };
+            let span = self.span_from(start.span());
+            let stmt = self.ast.add_stmt(kind, span);
+            self.attach_doc(&start, stmt);
+            if public {
+                let id = self.ast[stmt].id;
+                self.ast.public.insert(id);
+            }
+            return Ok(stmt);
+        }
+
+        self.parse_stmt()
+    }
 
+    fn parse_import(&mut self, start: Span) -> Result<Import, ParseError> {
+        // import ::= "import" ( <String> | <Identifier> ( "." <Identifier> )* )
+        let mut path = String::new();
         loop {
-            stmts.push(self.parse_stmt()?);
-            if self.at_end() {
-                // We've reached the end, break out
+            match self.peek().kind {
+                TokenKind::StringLit(value) if path.is_empty() => {
+                    path = value;
+                    break;
+                }
+                TokenKind::Identifier(part) => path += &part,
+                _ => return Err(self.error("expected a module path after `import`")),
+            }
+            if self.peek_next(1).kind != TokenKind::Dot {
                 break;
             }
+            self.advance();
+            self.advance();
+            path.push('/');
         }
-        Ok(stmts)
+
+        // The module is referred to by the file name without the extension
+        let file = path.rsplit('/').next().unwrap_or_default();
+        let name = file.strip_suffix(".lune").unwrap_or(file).to_string();
+        let mut chars = name.chars();
+        let identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_');
+        if !identifier {
+            return Err(self.error(&format!("module name `{}` isn't an identifier", name)));
+        }
+        if !self.modules.insert(name.clone()) {
+            return Err(self.error(&format!("module `{}` is already imported", name)));
+        }
+
+        self.advance();
+        Ok(Import {
+            path,
+            name,
+            span: self.span_from(start),
+        })
+    }
+
+    pub fn parse(&mut self) -> Result<AST, ParseError> {
+        let mut stmts: Block = Vec::new();
+
+        while let Some(keyword) = self.consume(TokenKind::Import) {
+            let import = self.parse_import(keyword.span())?;
+            self.ast.imports.push(import);
+        }
+        while !self.at_end() {
+            stmts.push(self.parse_decl()?);
+        }
+        if self.lexer_error.is_some() {
+            return Err(self.error("unexpected end of input"));
+        }
+
+        let mut ast = std::mem::take(&mut self.ast);
+        ast.root = stmts;
+        Ok(ast)
     }
 }
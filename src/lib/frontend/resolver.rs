@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+
+use crate::backend::ast::*;
+use crate::errors::ResolveError;
+use crate::frontend::lexer::token::Span;
+use crate::types::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefKind {
+    Var,
+    /// Top level constant, its value is known at compile time
+    Const,
+    Param,
+    Proc,
+    /// Procs provided by the runtime such as `print` and `len`
+    Builtin,
+    Struct,
+    Enum,
+    /// Variant of an enum, its type is the enum
+    Variant,
+}
+
+/// A declared name and everything we know about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefKind,
+    /// Span of the declaring name, empty for builtins
+    pub span: Span,
+    /// Type of the variable, or the return type of a proc
+    pub type_: Type,
+    /// Parameter types of a proc
+    pub params: Vec<Type>,
+    /// Field names and types of a struct or an enum variant, in declaration order
+    pub fields: Vec<(String, Type)>,
+    /// Text of the `##` comments before the declaration
+    pub doc: Option<String>,
+}
+
+impl Definition {
+    /// Human readable signature, eg `var x: int` or `proc add(int, int): int`
+    pub fn signature(&self) -> String {
+        match self.kind {
+            DefKind::Var => format!("var {}: {}", self.name, self.type_),
+            DefKind::Const => format!("const {}: {}", self.name, self.type_),
+            DefKind::Param => format!("{}: {}", self.name, self.type_),
+            DefKind::Proc | DefKind::Builtin => {
+                let params: Vec<String> = self.params.iter().map(|p| p.to_string()).collect();
+                let mut sig = format!("proc {}({})", self.name, params.join(", "));
+                if self.type_ != Type::Void {
+                    sig += &format!(": {}", self.type_);
+                }
+                sig
+            }
+            DefKind::Struct => {
+                let fields: Vec<String> = self
+                    .fields
+                    .iter()
+                    .map(|(name, type_)| format!("{}: {}", name, type_))
+                    .collect();
+                format!("struct {} {{ {} }}", self.name, fields.join(", "))
+            }
+            DefKind::Enum => format!("enum {}", self.name),
+            DefKind::Variant if self.fields.is_empty() => format!("{}: {}", self.name, self.type_),
+            DefKind::Variant => {
+                let fields: Vec<String> = self
+                    .fields
+                    .iter()
+                    .map(|(name, type_)| format!("{}: {}", name, type_))
+                    .collect();
+                format!("{}({}): {}", self.name, fields.join(", "), self.type_)
+            }
+        }
+    }
+
+    /// Type of the field `name` of a struct
+    pub fn field(&self, name: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, type_)| type_)
+    }
+}
+
+/// Result of name resolution
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub defs: Vec<Definition>,
+    /// Maps every resolved name, declarations included, to its definition
+    pub names: HashMap<NodeId, usize>,
+    /// Every name visited, used to find the name under a cursor
+    pub spans: Vec<(Span, NodeId)>,
+    /// Definition of every struct and enum, by name
+    pub types: HashMap<String, usize>,
+}
+
+impl Resolution {
+    /// Look up the definition a name in the AST refers to
+    pub fn lookup(&self, name: &Name) -> Option<&Definition> {
+        self.names.get(&name.id).map(|&id| &self.defs[id])
+    }
+
+    /// Find the name covering `offset` and return its span and definition
+    pub fn name_at(&self, offset: usize) -> Option<(Span, &Definition)> {
+        self.spans
+            .iter()
+            .find(|(span, _)| span.contains(offset))
+            .and_then(|(span, node)| self.names.get(node).map(|&id| (*span, &self.defs[id])))
+    }
+
+    /// Look up the definition of a named type
+    pub fn type_def(&self, type_: &Type) -> Option<&Definition> {
+        match type_ {
+            Type::Named(name) => self.types.get(name).map(|&id| &self.defs[id]),
+            _ => None,
+        }
+    }
+
+    /// Look up the definition of a struct type, None for other types
+    pub fn struct_def(&self, type_: &Type) -> Option<&Definition> {
+        self.type_def(type_).filter(|def| def.kind == DefKind::Struct)
+    }
+
+    /// Variants of an enum type in declaration order, empty for other types
+    pub fn variants(&self, type_: &Type) -> Vec<&Definition> {
+        self.defs
+            .iter()
+            .filter(|def| def.kind == DefKind::Variant && def.type_ == *type_)
+            .collect()
+    }
+
+    /// Whether `type_` is an enum with a payload on any variant
+    pub fn is_tagged_union(&self, type_: &Type) -> bool {
+        self.variants(type_).iter().any(|variant| !variant.fields.is_empty())
+    }
+}
+
+/// Resolves every name in the AST to its declaration
+pub struct Resolver {
+    scopes: Vec<HashMap<String, usize>>,
+    resolution: Resolution,
+    pub errors: Vec<ResolveError>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        let mut resolver = Resolver {
+            scopes: vec![HashMap::new()],
+            resolution: Resolution::default(),
+            errors: vec![],
+        };
+
+        resolver.define_builtin("print", Type::Void);
+        resolver.define_builtin("len", Type::Int);
+        resolver
+    }
+
+    fn define_builtin(&mut self, name: &str, type_: Type) {
+        let id = self.resolution.defs.len();
+        self.resolution.defs.push(Definition {
+            name: name.into(),
+            kind: DefKind::Builtin,
+            span: Span::default(),
+            type_,
+            params: vec![],
+            fields: vec![],
+            doc: None,
+        });
+        self.scopes[0].insert(name.into(), id);
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(ResolveError { span, message });
+    }
+
+    /// Declare `name` in the innermost scope
+    fn declare(&mut self, name: &Name, kind: DefKind, type_: Type, params: Vec<Type>) {
+        let scope = self.scopes.last().unwrap();
+        if let Some(&prev) = scope.get(&name.value) {
+            if self.resolution.defs[prev].kind != DefKind::Builtin {
+                self.error(
+                    name.span,
+                    format!("`{}` is already declared in this scope", name.value),
+                );
+                return;
+            }
+        }
+
+        let id = self.resolution.defs.len();
+        self.resolution.defs.push(Definition {
+            name: name.value.clone(),
+            kind,
+            span: name.span,
+            type_,
+            params,
+            fields: vec![],
+            doc: None,
+        });
+        self.resolution.names.insert(name.id, id);
+        self.resolution.spans.push((name.span, name.id));
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.value.clone(), id);
+    }
+
+    /// Attach the doc comment of the declaration `stmt` to the definition of `name`
+    fn document(&mut self, ast: &AST, stmt: StmtId, name: &Name) {
+        if let Some(&def) = self.resolution.names.get(&name.id) {
+            self.resolution.defs[def].doc = ast.docs.get(&ast[stmt].id).cloned();
+        }
+    }
+
+    /// Report named types that were never declared, `span` is where the type is used
+    fn check_type(&mut self, type_: &Type, span: Span) {
+        match type_ {
+            Type::Named(name) if !self.resolution.types.contains_key(name) => {
+                self.error(span, format!("cannot find type `{}` in this scope", name));
+            }
+            Type::Array(element, _) | Type::Slice(element) | Type::ConstArray(element, _) => {
+                self.check_type(element, span)
+            }
+            _ => {}
+        }
+    }
+
+    /// Check the types of struct or variant fields and that their names are unique
+    fn check_fields(&mut self, fields: &[Field]) {
+        for (i, field) in fields.iter().enumerate() {
+            self.check_type(&field.type_, field.name.span);
+            if fields[..i].iter().any(|f| f.name.value == field.name.value) {
+                self.error(
+                    field.name.span,
+                    format!("field `{}` is already declared", field.name.value),
+                );
+            }
+        }
+    }
+
+    /// Whether a value of `type_` contains a `target` value, directly or nested
+    fn contains_type(&self, type_: &Type, target: &str, seen: &mut Vec<String>) -> bool {
+        // Struct fields and the payloads of every enum variant are held by value
+        let mut members: Vec<&Type> = vec![];
+        if let Some(def) = self.resolution.struct_def(type_) {
+            members.extend(def.fields.iter().map(|(_, field)| field));
+        }
+        for variant in self.resolution.variants(type_) {
+            members.extend(variant.fields.iter().map(|(_, field)| field));
+        }
+
+        // So are the elements of arrays, while slices only point at theirs
+        let mut members = members.into_iter().map(|mut member| {
+            while let Type::Array(element, _) = member {
+                member = element;
+            }
+            member
+        });
+
+        members.any(|member| match member {
+            Type::Named(name) if name == target => true,
+            Type::Named(name) if !seen.contains(name) => {
+                seen.push(name.clone());
+                self.contains_type(member, target, seen)
+            }
+            _ => false,
+        })
+    }
+
+    /// Report a type that holds itself by value, C would need infinite storage
+    fn check_recursive(&mut self, name: &Name, kind: &str) {
+        let type_ = Type::Named(name.value.clone());
+        if self.contains_type(&type_, &name.value, &mut vec![]) {
+            self.error(
+                name.span,
+                format!("recursive {} `{}` has infinite size", kind, name.value),
+            );
+        }
+    }
+
+    /// Check the values of an enum's variants fit in a C enum and are unique
+    fn check_values(&mut self, enum_: &Enum) {
+        let mut next = 0;
+        let mut values: Vec<(i128, &str)> = vec![];
+        for variant in &enum_.variants {
+            let value = variant.value.unwrap_or(next);
+            let name = &variant.name;
+
+            if value < i32::MIN as i128 || value > i32::MAX as i128 {
+                self.error(name.span, format!("value of `{}` is out of range for i32", name.value));
+            } else if let Some((_, prev)) = values.iter().find(|(v, _)| *v == value) {
+                let message = format!("`{}` has the same value as `{}`", name.value, prev);
+                self.error(name.span, message);
+            }
+            values.push((value, &name.value));
+            next = value + 1;
+        }
+    }
+
+    /// Declare the names a pattern binds in the current scope
+    fn resolve_pattern(&mut self, pattern: &Pattern) {
+        let PatternKind::Variant(name, bindings) = &pattern.kind else {
+            return;
+        };
+        self.reference(name);
+
+        // Bindings take the types of the variant's fields, a wrong count is
+        // reported by the type checker
+        let fields: Vec<Type> = match self.resolution.lookup(name) {
+            Some(def) if def.kind == DefKind::Variant => def.params.clone(),
+            _ => vec![],
+        };
+        for (i, binding) in bindings.iter().enumerate() {
+            if binding.value != "_" {
+                let type_ = fields.get(i).cloned().unwrap_or(Type::Void);
+                self.declare(binding, DefKind::Var, type_, vec![]);
+            }
+        }
+    }
+
+    /// Resolve a use of `name` against the enclosing scopes
+    fn reference(&mut self, name: &Name) {
+        self.resolution.spans.push((name.span, name.id));
+        let found = self.scopes.iter().rev().find_map(|s| s.get(&name.value));
+
+        match found {
+            Some(&id) => {
+                self.resolution.names.insert(name.id, id);
+            }
+            None => self.error(
+                name.span,
+                format!("cannot find `{}` in this scope", name.value),
+            ),
+        }
+    }
+
+    fn with_scope<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn resolve_block(&mut self, ast: &AST, stmts: &[StmtId]) {
+        self.with_scope(|r| {
+            for &stmt in stmts {
+                r.resolve_stmt(ast, stmt);
+            }
+        });
+    }
+
+    fn resolve_stmt(&mut self, ast: &AST, id: StmtId) {
+        match &ast[id].kind {
+            StmtKind::Expr(expr) => self.resolve_expr(ast, *expr),
+            StmtKind::VarDeclaration(name, type_, value) => {
+                // The value can't refer to the variable being declared
+                self.resolve_expr(ast, *value);
+                self.check_type(type_, name.span);
+                self.declare(name, DefKind::Var, type_.clone(), vec![]);
+                self.document(ast, id, name);
+            }
+            StmtKind::ConstDeclaration(name, type_, value) => {
+                // The constant itself was declared up front in `resolve`
+                self.resolve_expr(ast, *value);
+                self.check_type(type_, name.span);
+            }
+            StmtKind::Assignment(target, value) => {
+                self.resolve_expr(ast, *target);
+                self.resolve_expr(ast, *value);
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                self.resolve_expr(ast, *cond);
+                self.resolve_block(ast, then);
+                if let Some(otherwise) = otherwise {
+                    self.resolve_block(ast, otherwise);
+                }
+            }
+            StmtKind::While(cond, body) => {
+                self.resolve_expr(ast, *cond);
+                self.resolve_block(ast, body);
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.resolve_expr(ast, *value);
+                }
+            }
+            StmtKind::ProcDeclaration(proc) => {
+                // The proc itself was declared up front in `resolve`
+                self.check_type(&proc.return_type, proc.name.span);
+                self.with_scope(|r| {
+                    for param in &proc.params {
+                        r.check_type(&param.type_, param.name.span);
+                        r.declare(&param.name, DefKind::Param, param.type_.clone(), vec![]);
+                    }
+                    r.resolve_block(ast, &proc.body);
+                });
+            }
+            StmtKind::StructDeclaration(struct_) => {
+                // The struct itself was declared up front in `resolve`
+                self.check_fields(&struct_.fields);
+                self.check_recursive(&struct_.name, "struct");
+            }
+            StmtKind::EnumDeclaration(enum_) => {
+                // The enum and its variants were declared up front in `resolve`
+                for variant in &enum_.variants {
+                    self.check_fields(&variant.fields);
+                }
+                self.check_values(enum_);
+                self.check_recursive(&enum_.name, "enum");
+            }
+            StmtKind::Case(scrutinee, arms) => {
+                self.resolve_expr(ast, *scrutinee);
+                for arm in arms {
+                    self.with_scope(|r| {
+                        r.resolve_pattern(&arm.pattern);
+                        r.resolve_block(ast, &arm.body);
+                    });
+                }
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, ast: &AST, id: ExprId) {
+        match &ast[id].kind {
+            ExprKind::IntLit(_) | ExprKind::StringLit(_) | ExprKind::BoolLit(_) => {}
+            ExprKind::Variable(name) => self.reference(name),
+            ExprKind::Call(name, args) => {
+                self.reference(name);
+                for &arg in args {
+                    self.resolve_expr(ast, arg);
+                }
+            }
+            ExprKind::UnaryOp(_, rhs) => self.resolve_expr(ast, *rhs),
+            ExprKind::BinOp(lhs, _, rhs) => {
+                self.resolve_expr(ast, *lhs);
+                self.resolve_expr(ast, *rhs);
+            }
+            ExprKind::StructLit(name, fields) => {
+                self.reference(name);
+                for (_, value) in fields {
+                    self.resolve_expr(ast, *value);
+                }
+            }
+            // Fields depend on the type of the object, the type checker looks them up
+            ExprKind::FieldAccess(object, _) => self.resolve_expr(ast, *object),
+            ExprKind::ArrayLit(elements) => {
+                for &element in elements {
+                    self.resolve_expr(ast, element);
+                }
+            }
+            ExprKind::Index(object, index) => {
+                self.resolve_expr(ast, *object);
+                self.resolve_expr(ast, *index);
+            }
+            ExprKind::Slice(object, start, end) => {
+                self.resolve_expr(ast, *object);
+                for &bound in start.iter().chain(end) {
+                    self.resolve_expr(ast, bound);
+                }
+            }
+            ExprKind::Case(scrutinee, arms) => {
+                self.resolve_expr(ast, *scrutinee);
+                for arm in arms {
+                    self.with_scope(|r| {
+                        r.resolve_pattern(&arm.pattern);
+                        r.resolve_expr(ast, arm.body);
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn resolve(&mut self, ast: &AST) -> Resolution {
+        // Procs, types and constants can be used before they are declared, so
+        // declare them first
+        for &stmt in &ast.root {
+            match &ast[stmt].kind {
+                StmtKind::ConstDeclaration(name, type_, _) => {
+                    self.declare(name, DefKind::Const, type_.clone(), vec![]);
+                    self.document(ast, stmt, name);
+                }
+                StmtKind::ProcDeclaration(proc) => {
+                    let params = proc.params.iter().map(|p| p.type_.clone()).collect();
+                    self.declare(&proc.name, DefKind::Proc, proc.return_type.clone(), params);
+                    self.document(ast, stmt, &proc.name);
+                }
+                StmtKind::StructDeclaration(struct_) => {
+                    let name = &struct_.name;
+                    let type_ = Type::Named(name.value.clone());
+                    self.declare(name, DefKind::Struct, type_, vec![]);
+                    self.document(ast, stmt, name);
+
+                    if let Some(&def) = self.resolution.names.get(&name.id) {
+                        self.resolution.defs[def].fields = struct_
+                            .fields
+                            .iter()
+                            .map(|field| (field.name.value.clone(), field.type_.clone()))
+                            .collect();
+                        self.resolution.types.insert(name.value.clone(), def);
+                    }
+                }
+                StmtKind::EnumDeclaration(enum_) => {
+                    let type_ = Type::Named(enum_.name.value.clone());
+                    self.declare(&enum_.name, DefKind::Enum, type_.clone(), vec![]);
+                    self.document(ast, stmt, &enum_.name);
+                    if let Some(&def) = self.resolution.names.get(&enum_.name.id) {
+                        self.resolution.types.insert(enum_.name.value.clone(), def);
+                    }
+
+                    // Variants live in the enclosing scope like C enumerators
+                    for variant in &enum_.variants {
+                        let params = variant.fields.iter().map(|f| f.type_.clone()).collect();
+                        self.declare(&variant.name, DefKind::Variant, type_.clone(), params);
+                        if let Some(&def) = self.resolution.names.get(&variant.name.id) {
+                            self.resolution.defs[def].fields = variant
+                                .fields
+                                .iter()
+                                .map(|field| (field.name.value.clone(), field.type_.clone()))
+                                .collect();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for &stmt in &ast.root {
+            self.resolve_stmt(ast, stmt);
+        }
+
+        std::mem::take(&mut self.resolution)
+    }
+}
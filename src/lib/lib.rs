@@ -7,14 +7,64 @@ pub mod backend;
 // Errors
 pub mod errors;
 
+// Minimal JSON reader/writer
+pub mod json;
+
+// Language server
+pub mod lsp;
+
+// Documentation generator
+pub mod doc;
+
+// Package manifests
+pub mod manifest;
+
+// Incremental compilation cache
+pub mod cache;
 
 pub mod types {
+    use std::fmt;
+
     /// Enum of supported types
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum Type {
         // TODO: add pointer for interop with C
         Int,    // i32
         String,
         Bool,
+        Void,   // procs without a return type
+        Named(String), // user defined struct or enum, by name
+        Array(Box<Type>, usize), // [N]T, N elements stored inline
+        Slice(Box<Type>), // []T, pointer and length of elements stored elsewhere
+        ConstArray(Box<Type>, String), // [N]T with a constant N or `_`, an Array once N is known
+    }
+
+    impl Type {
+        /// Element type of an array or slice
+        pub fn element(&self) -> Option<&Type> {
+            match self {
+                Type::Array(element, _) | Type::Slice(element) | Type::ConstArray(element, _) => {
+                    Some(element)
+                }
+                _ => None,
+            }
+        }
+    }
+
+    impl fmt::Display for Type {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let name = match self {
+                Type::Int => "int",
+                Type::String => "str",
+                Type::Bool => "bool",
+                Type::Void => "void",
+                Type::Named(name) => name,
+                Type::Array(element, len) => return write!(f, "[{}]{}", len, element),
+                Type::Slice(element) => return write!(f, "[]{}", element),
+                Type::ConstArray(element, len) => return write!(f, "[{}]{}", len, element),
+            };
+
+            write!(f, "{}", name)
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,5 @@
+// JSON-RPC framing over stdio
+pub mod rpc;
+
+// Request and notification handling
+pub mod server;
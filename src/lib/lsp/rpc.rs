@@ -0,0 +1,53 @@
+use std::io::{self, BufRead, Write};
+
+use crate::json::Json;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read one `Content-Length` framed message, None at the end of the stream.
+/// A body that isn't JSON is an `Err` inside, the messages after it can still
+/// be read
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Result<Json, String>>> {
+    let mut length: Option<usize> = None;
+
+    // Headers are terminated by an empty line
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let parsed = value.trim().parse().map_err(|_| {
+                    invalid_data(format!("invalid Content-Length header: {}", value.trim()))
+                })?;
+                length = Some(parsed);
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid_data("missing Content-Length header".into()))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    let message = match String::from_utf8(body) {
+        Ok(body) => Json::parse(&body).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    Ok(Some(message))
+}
+
+/// Write a `Content-Length` framed message
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::backend::ast::{StmtId, StmtKind};
+use crate::frontend::analysis::Analysis;
+use crate::frontend::lexer::token::{Span, TokenKind};
+use crate::frontend::modules::ModuleLoader;
+use crate::frontend::resolver::DefKind;
+use crate::json::Json;
+use crate::manifest::Project;
+
+use super::rpc::{read_message, write_message};
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// LSP symbol kinds
+const SYMBOL_FIELD: i64 = 8;
+const SYMBOL_ENUM: i64 = 10;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+const SYMBOL_CONSTANT: i64 = 14;
+const SYMBOL_ENUM_MEMBER: i64 = 22;
+const SYMBOL_STRUCT: i64 = 23;
+
+/// Semantic token types, indices are what we report for each token
+const TOKEN_TYPES: [&str; 10] = [
+    "keyword",
+    "type",
+    "function",
+    "variable",
+    "parameter",
+    "number",
+    "string",
+    "operator",
+    "comment",
+    "enumMember",
+];
+
+type RequestResult = Result<Json, (i64, String)>;
+
+/// Language server over any reader and writer, stdio in practice
+pub struct Server<W: Write> {
+    output: W,
+    documents: HashMap<String, Analysis>,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(output: W) -> Server<W> {
+        Server {
+            output,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Serve messages from `input` until `exit` or the end of the stream,
+    /// returning the process exit code
+    pub fn run<R: BufRead>(&mut self, mut input: R) -> io::Result<i32> {
+        while let Some(message) = read_message(&mut input)? {
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    // Without a request there's no id to answer to
+                    let error = Self::error(PARSE_ERROR, error);
+                    let response = Json::object(vec![
+                        ("jsonrpc", "2.0".into()),
+                        ("id", Json::Null),
+                        ("error", error),
+                    ]);
+                    write_message(&mut self.output, &response)?;
+                    continue;
+                }
+            };
+            let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+            if method == "exit" {
+                return Ok(if self.shutdown { 0 } else { 1 });
+            }
+
+            let params = message.get("params").cloned().unwrap_or(Json::Null);
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.handle_request(method, &params) {
+                        Ok(result) => Json::object(vec![
+                            ("jsonrpc", "2.0".into()),
+                            ("id", id.clone()),
+                            ("result", result),
+                        ]),
+                        Err((code, message)) => Json::object(vec![
+                            ("jsonrpc", "2.0".into()),
+                            ("id", id.clone()),
+                            ("error", Self::error(code, message)),
+                        ]),
+                    };
+                    write_message(&mut self.output, &response)?;
+                }
+                None => self.handle_notification(method, &params)?,
+            }
+        }
+
+        Ok(1)
+    }
+
+    fn error(code: i64, message: String) -> Json {
+        Json::object(vec![("code", code.into()), ("message", message.into())])
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> io::Result<()> {
+        let message = Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ]);
+        write_message(&mut self.output, &message)
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Json) -> RequestResult {
+        match method {
+            "initialize" => Ok(Self::capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unhandled method {}", method))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str);
+        let Some(uri) = uri.map(String::from) else {
+            return Ok(());
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params
+                    .path(&["textDocument", "text"])
+                    .and_then(Json::as_str);
+                self.update(uri, text.unwrap_or(""))
+            }
+            "textDocument/didChange" => {
+                // We only advertise full sync, so the last change holds the whole text
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                match text {
+                    Some(text) => self.update(uri, text),
+                    None => Ok(()),
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri, vec![])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn capabilities() -> Json {
+        let legend = Json::object(vec![
+            (
+                "tokenTypes",
+                TOKEN_TYPES
+                    .iter()
+                    .map(|&t| t.into())
+                    .collect::<Vec<Json>>()
+                    .into(),
+            ),
+            ("tokenModifiers", Json::Array(vec![])),
+        ]);
+
+        Json::object(vec![
+            (
+                "capabilities",
+                Json::object(vec![
+                    // Full document sync
+                    ("textDocumentSync", 1i64.into()),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    ("documentSymbolProvider", true.into()),
+                    (
+                        "semanticTokensProvider",
+                        Json::object(vec![("legend", legend), ("full", true.into())]),
+                    ),
+                ]),
+            ),
+            (
+                "serverInfo",
+                Json::object(vec![
+                    ("name", "lune".into()),
+                    ("version", env!("CARGO_PKG_VERSION").into()),
+                ]),
+            ),
+        ])
+    }
+
+    /// Re-analyze a document and publish its diagnostics, those in modules
+    /// it imports are reported at the import. Imports are looked up in the
+    /// project the document is in, if it has a manifest.
+    fn update(&mut self, uri: String, text: &str) -> io::Result<()> {
+        let analysis = match uri.strip_prefix("file://") {
+            Some(path) => {
+                let path = Path::new(path);
+                let dir = path.parent().and_then(Project::find);
+                let project = dir.and_then(|dir| Project::load(&dir).ok());
+                let loader = match project {
+                    Some(project) => project.loader(&[]),
+                    None => ModuleLoader::new(&[]),
+                };
+                Analysis::with_path(text, path, loader)
+            }
+            None => Analysis::new(text),
+        };
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                let span = analysis.in_document(d.span);
+                let mut message = d.message.clone();
+                if span != d.span {
+                    let file = analysis.file_at(d.span.start).unwrap();
+                    let (line, column) = file.position(d.span.start);
+                    let path = file.path.display();
+                    message = format!("{}:{}:{}: {}", path, line + 1, column + 1, message);
+                }
+                Json::object(vec![
+                    ("range", Self::range(&analysis, span)),
+                    // Error severity
+                    ("severity", 1i64.into()),
+                    ("source", format!("lune {}", d.source).into()),
+                    ("message", message.into()),
+                ])
+            })
+            .collect();
+
+        self.documents.insert(uri.clone(), analysis);
+        self.publish_diagnostics(&uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+        self.notify(
+            "textDocument/publishDiagnostics",
+            Json::object(vec![
+                ("uri", uri.into()),
+                ("diagnostics", diagnostics.into()),
+            ]),
+        )
+    }
+
+    /// Position of an offset in the file it is in, which is the document
+    /// unless it's in an imported module
+    fn position(analysis: &Analysis, offset: usize) -> Json {
+        let (line, character) = match analysis.file_at(offset) {
+            Some(file) => file.position(offset),
+            None => analysis.lines.position(&analysis.source, offset),
+        };
+        Json::object(vec![("line", line.into()), ("character", character.into())])
+    }
+
+    fn range(analysis: &Analysis, span: Span) -> Json {
+        Json::object(vec![
+            ("start", Self::position(analysis, span.start)),
+            ("end", Self::position(analysis, span.end)),
+        ])
+    }
+
+    /// Look up the document and cursor offset of a text document position request
+    fn document_at(
+        &self,
+        params: &Json,
+    ) -> Result<(&Analysis, String, Option<usize>), (i64, String)> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, "missing textDocument.uri".to_string()))?;
+        let analysis = self
+            .documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("unknown document {}", uri)))?;
+
+        let line = params.path(&["position", "line"]).and_then(Json::as_i64);
+        let character = params
+            .path(&["position", "character"])
+            .and_then(Json::as_i64);
+        let offset = match (line, character) {
+            (Some(line), Some(character)) => Some(analysis.lines.offset(
+                &analysis.source,
+                line as usize,
+                character as usize,
+            )),
+            _ => None,
+        };
+
+        Ok((analysis, uri.into(), offset))
+    }
+
+    fn hover(&self, params: &Json) -> RequestResult {
+        let (analysis, _, offset) = self.document_at(params)?;
+        let found = offset.and_then(|offset| analysis.resolution.name_at(offset));
+        let Some((span, def)) = found else {
+            return Ok(Json::Null);
+        };
+
+        let mut value = format!("```lune\n{}\n```", def.signature());
+        if let Some(doc) = &def.doc {
+            value += &format!("\n\n{}", doc);
+        }
+
+        Ok(Json::object(vec![
+            (
+                "contents",
+                Json::object(vec![("kind", "markdown".into()), ("value", value.into())]),
+            ),
+            ("range", Self::range(analysis, span)),
+        ]))
+    }
+
+    fn definition(&self, params: &Json) -> RequestResult {
+        let (analysis, uri, offset) = self.document_at(params)?;
+        let found = offset.and_then(|offset| analysis.resolution.name_at(offset));
+
+        let def = match found {
+            Some((_, def)) if def.kind != DefKind::Builtin => def,
+            _ => return Ok(Json::Null),
+        };
+        // Definitions in imported modules are in other files
+        let uri = match analysis.file_at(def.span.start) {
+            Some(file) if file.base != 0 => {
+                let path = file.path.canonicalize().unwrap_or(file.path.clone());
+                format!("file://{}", path.display())
+            }
+            _ => uri,
+        };
+        Ok(Json::object(vec![
+            ("uri", uri.into()),
+            ("range", Self::range(analysis, def.span)),
+        ]))
+    }
+
+    fn symbols(analysis: &Analysis, stmts: &[StmtId]) -> Vec<Json> {
+        let mut symbols = vec![];
+
+        for &id in stmts {
+            let stmt = &analysis.ast[id];
+            let (name, kind, detail, children) = match &stmt.kind {
+                StmtKind::VarDeclaration(name, type_, _) => {
+                    (name, SYMBOL_VARIABLE, type_.to_string(), vec![])
+                }
+                StmtKind::ConstDeclaration(name, type_, _) => {
+                    (name, SYMBOL_CONSTANT, type_.to_string(), vec![])
+                }
+                StmtKind::ProcDeclaration(proc) => {
+                    let detail = analysis
+                        .resolution
+                        .lookup(&proc.name)
+                        .map(|def| def.signature())
+                        .unwrap_or_default();
+                    (
+                        &proc.name,
+                        SYMBOL_FUNCTION,
+                        detail,
+                        Self::symbols(analysis, &proc.body),
+                    )
+                }
+                StmtKind::StructDeclaration(struct_) => {
+                    let fields = struct_
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            Json::object(vec![
+                                ("name", field.name.value.clone().into()),
+                                ("detail", field.type_.to_string().into()),
+                                ("kind", SYMBOL_FIELD.into()),
+                                ("range", Self::range(analysis, field.name.span)),
+                                ("selectionRange", Self::range(analysis, field.name.span)),
+                            ])
+                        })
+                        .collect();
+                    (
+                        &struct_.name,
+                        SYMBOL_STRUCT,
+                        format!("struct {}", struct_.name.value),
+                        fields,
+                    )
+                }
+                StmtKind::EnumDeclaration(enum_) => {
+                    let variants = enum_
+                        .variants
+                        .iter()
+                        .map(|variant| {
+                            let detail = analysis
+                                .resolution
+                                .lookup(&variant.name)
+                                .map(|def| def.signature())
+                                .unwrap_or_default();
+                            Json::object(vec![
+                                ("name", variant.name.value.clone().into()),
+                                ("detail", detail.into()),
+                                ("kind", SYMBOL_ENUM_MEMBER.into()),
+                                ("range", Self::range(analysis, variant.name.span)),
+                                ("selectionRange", Self::range(analysis, variant.name.span)),
+                            ])
+                        })
+                        .collect();
+                    (
+                        &enum_.name,
+                        SYMBOL_ENUM,
+                        format!("enum {}", enum_.name.value),
+                        variants,
+                    )
+                }
+                StmtKind::Case(_, arms) => {
+                    for arm in arms {
+                        symbols.extend(Self::symbols(analysis, &arm.body));
+                    }
+                    continue;
+                }
+                StmtKind::If(_, then, otherwise) => {
+                    symbols.extend(Self::symbols(analysis, then));
+                    if let Some(otherwise) = otherwise {
+                        symbols.extend(Self::symbols(analysis, otherwise));
+                    }
+                    continue;
+                }
+                StmtKind::While(_, body) => {
+                    symbols.extend(Self::symbols(analysis, body));
+                    continue;
+                }
+                _ => continue,
+            };
+
+            symbols.push(Json::object(vec![
+                ("name", name.value.clone().into()),
+                ("detail", detail.into()),
+                ("kind", kind.into()),
+                ("range", Self::range(analysis, stmt.span)),
+                ("selectionRange", Self::range(analysis, name.span)),
+                ("children", children.into()),
+            ]));
+        }
+
+        symbols
+    }
+
+    fn document_symbols(&self, params: &Json) -> RequestResult {
+        let (analysis, _, _) = self.document_at(params)?;
+        Ok(Self::symbols(analysis, &analysis.file_root()).into())
+    }
+
+    /// Index into `TOKEN_TYPES` for a token, None for tokens we don't highlight
+    fn token_type(analysis: &Analysis, kind: &TokenKind, span: Span) -> Option<usize> {
+        let type_ = match kind {
+            TokenKind::If
+            | TokenKind::Else
+            | TokenKind::For
+            | TokenKind::While
+            | TokenKind::Case
+            | TokenKind::Proc
+            | TokenKind::Struct
+            | TokenKind::Enum
+            | TokenKind::Ptr
+            | TokenKind::Var
+            | TokenKind::Const
+            | TokenKind::Return
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Import
+            | TokenKind::Pub => "keyword",
+            TokenKind::IntType | TokenKind::StrType | TokenKind::BoolType => "type",
+            TokenKind::Identifier(name) => match analysis.resolution.name_at(span.start) {
+                Some((_, def)) if matches!(def.kind, DefKind::Proc | DefKind::Builtin) => {
+                    "function"
+                }
+                Some((_, def)) if def.kind == DefKind::Param => "parameter",
+                Some((_, def)) if matches!(def.kind, DefKind::Struct | DefKind::Enum) => "type",
+                Some((_, def)) if def.kind == DefKind::Variant => "enumMember",
+                // Type names in annotations aren't resolved as names
+                None if analysis.resolution.types.contains_key(name) => "type",
+                _ => "variable",
+            },
+            TokenKind::IntLit(_) => "number",
+            TokenKind::StringLit(_) => "string",
+            TokenKind::DocComment(_) => "comment",
+            TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Star
+            | TokenKind::Slash
+            | TokenKind::Bang
+            | TokenKind::BangEqual
+            | TokenKind::Equal
+            | TokenKind::EqualEqual
+            | TokenKind::Greater
+            | TokenKind::GreaterEqual
+            | TokenKind::Less
+            | TokenKind::LessEqual
+            | TokenKind::PlusEqual
+            | TokenKind::MinusEqual
+            | TokenKind::FatArrow
+            | TokenKind::DotDot => "operator",
+            _ => return None,
+        };
+
+        TOKEN_TYPES.iter().position(|&t| t == type_)
+    }
+
+    fn semantic_tokens(&self, params: &Json) -> RequestResult {
+        let (analysis, _, _) = self.document_at(params)?;
+        let mut data: Vec<Json> = vec![];
+        let (mut prev_line, mut prev_start) = (0, 0);
+
+        for token in &analysis.tokens {
+            let span = token.span();
+            let Some(type_) = Self::token_type(analysis, &token.kind, span) else {
+                continue;
+            };
+
+            let (line, start) = analysis.lines.position(&analysis.source, span.start);
+            let (end_line, end) = analysis.lines.position(&analysis.source, span.end);
+            // Tokens can't span lines, multi-line strings are cut at the first line
+            let length = if end_line == line {
+                end - start
+            } else {
+                let line_end = analysis.lines.offset(&analysis.source, line, usize::MAX);
+                analysis.lines.position(&analysis.source, line_end).1 - start
+            };
+
+            let delta_start = if line == prev_line {
+                start - prev_start
+            } else {
+                start
+            };
+            data.extend([
+                (line - prev_line).into(),
+                delta_start.into(),
+                length.into(),
+                type_.into(),
+                0usize.into(),
+            ]);
+            (prev_line, prev_start) = (line, start);
+        }
+
+        Ok(Json::object(vec![("data", data.into())]))
+    }
+}
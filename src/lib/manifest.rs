@@ -0,0 +1,568 @@
+//! Package manifests, the `lune.toml` file at the root of a project.
+//!
+//! A manifest is written in a small subset of TOML: tables of `key = value`
+//! lines whose values are strings, arrays of strings or inline tables.
+//!
+//! ```toml
+//! [package]
+//! name = "app"
+//! version = "0.1.0"
+//! entry = "src/main.lune"   # the default
+//! source-dirs = ["src"]     # the default, searched for imports
+//! libs = ["m"]              # C libraries to link, none by default
+//!
+//! [dependencies]
+//! geometry = { path = "../geometry" }
+//! ```
+//!
+//! Dependencies are other packages on disk, there is no registry. Their
+//! modules are imported as `geometry.<module>`, and `import geometry` loads
+//! their entry file.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::errors::ManifestError;
+use crate::frontend::modules::{Dependency, ModuleLoader};
+use crate::frontend::analysis::LineIndex;
+
+/// File name of a manifest
+pub const MANIFEST: &str = "lune.toml";
+
+/// A package as its manifest describes it, paths are relative to the
+/// directory of the manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    /// File the program starts at, which `import <name>` loads in packages
+    /// depending on this one
+    pub entry: PathBuf,
+    /// Directories imports are looked up in after that of the importing file
+    pub source_dirs: Vec<PathBuf>,
+    /// C libraries to link, eg `m` for `-lm`
+    pub libs: Vec<String>,
+    /// Packages it depends on, by the name they are imported as
+    pub dependencies: Vec<(String, PathBuf)>,
+}
+
+/// Whether `name` can be used as a module name
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl Manifest {
+    /// Manifest of a new package, with the default layout
+    pub fn new(name: &str) -> Manifest {
+        Manifest {
+            name: name.into(),
+            version: "0.1.0".into(),
+            entry: "src/main.lune".into(),
+            source_dirs: vec!["src".into()],
+            libs: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    /// Parse the contents of a manifest, `path` is where errors are reported
+    pub fn parse(source: &str, path: &Path) -> Result<Manifest, ManifestError> {
+        let mut reader = Reader {
+            source,
+            position: 0,
+        };
+        let manifest = reader.document().and_then(Manifest::from_tables);
+        manifest.map_err(|(offset, message)| {
+            let (line, column) = LineIndex::new(source).position(source, offset);
+            ManifestError {
+                location: format!("{}:{}:{}", path.display(), line + 1, column + 1),
+                message,
+            }
+        })
+    }
+
+    fn from_tables(tables: Vec<Entry>) -> Result<Manifest, Error> {
+        let mut manifest = Manifest::new("");
+        let mut package = None;
+        let mut name = None;
+        let mut version = None;
+
+        for table in tables {
+            let Value::Table(entries) = table.value else {
+                unreachable!("tables are read as tables");
+            };
+            match table.key.as_str() {
+                "package" => {
+                    package = Some(table.offset);
+                    for entry in entries {
+                        match entry.key.as_str() {
+                            "name" => name = Some((string(&entry)?, entry.offset)),
+                            "version" => version = Some((string(&entry)?, entry.offset)),
+                            "entry" => manifest.entry = string(&entry)?.into(),
+                            "source-dirs" => {
+                                let dirs = strings(&entry)?.into_iter().map(PathBuf::from);
+                                manifest.source_dirs = dirs.collect();
+                            }
+                            "libs" => manifest.libs = strings(&entry)?,
+                            key => {
+                                let message = format!("unknown key `{}` in [package]", key);
+                                return Err((entry.offset, message));
+                            }
+                        }
+                    }
+                }
+                "dependencies" => {
+                    for entry in entries {
+                        manifest.dependencies.push(dependency(&entry)?);
+                    }
+                }
+                key => return Err((table.offset, format!("unknown table [{}]", key))),
+            }
+        }
+
+        let package = package.ok_or((0, "missing the [package] table".to_string()))?;
+        let (name, offset) = name.ok_or((package, "missing `name` in [package]".to_string()))?;
+        if !is_identifier(&name) {
+            return Err((
+                offset,
+                format!("package name `{}` isn't an identifier", name),
+            ));
+        }
+        let (version, offset) =
+            version.ok_or((package, "missing `version` in [package]".to_string()))?;
+        let parts: Vec<&str> = version.split('.').collect();
+        if parts.len() != 3 || parts.iter().any(|part| part.parse::<u64>().is_err()) {
+            let message = format!("version `{}` isn't major.minor.patch", version);
+            return Err((offset, message));
+        }
+        manifest.name = name;
+        manifest.version = version;
+        Ok(manifest)
+    }
+}
+
+/// The manifest as it would be written to a `lune.toml`
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |values: Vec<String>| {
+            let values: Vec<String> = values.iter().map(|value| quote(value)).collect();
+            format!("[{}]", values.join(", "))
+        };
+        writeln!(f, "[package]")?;
+        writeln!(f, "name = {}", quote(&self.name))?;
+        writeln!(f, "version = {}", quote(&self.version))?;
+        writeln!(f, "entry = {}", quote(&self.entry.to_string_lossy()))?;
+        let dirs = self
+            .source_dirs
+            .iter()
+            .map(|dir| dir.to_string_lossy().into());
+        writeln!(f, "source-dirs = {}", list(dirs.collect()))?;
+        writeln!(f, "libs = {}", list(self.libs.clone()))?;
+        writeln!(f)?;
+        writeln!(f, "[dependencies]")?;
+        for (name, path) in &self.dependencies {
+            writeln!(
+                f,
+                "{} = {{ path = {} }}",
+                name,
+                quote(&path.to_string_lossy())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// A package on disk along with the packages it depends on
+pub struct Project {
+    /// Directory of the manifest
+    pub dir: PathBuf,
+    pub manifest: Manifest,
+    /// Packages it depends on, directly or through other dependencies
+    pub dependencies: Vec<Dependency>,
+    /// C libraries of the package and its dependencies, each once
+    pub libs: Vec<String>,
+}
+
+fn read(dir: &Path) -> Result<Manifest, ManifestError> {
+    let path = dir.join(MANIFEST);
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Manifest::parse(&source, &path),
+        Err(err) => Err(ManifestError {
+            location: path.display().to_string(),
+            message: format!("couldn't read it: {}", err),
+        }),
+    }
+}
+
+impl Project {
+    /// The closest of `dir` and its parents with a manifest
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        let mut dirs = dir.ancestors();
+        dirs.find(|dir| dir.join(MANIFEST).is_file())
+            .map(Path::to_path_buf)
+    }
+
+    /// Load the package in `dir` and the packages it depends on
+    pub fn load(dir: &Path) -> Result<Project, ManifestError> {
+        let manifest = read(dir)?;
+        let mut project = Project {
+            dir: dir.into(),
+            manifest: manifest.clone(),
+            dependencies: vec![],
+            libs: vec![],
+        };
+
+        // Directory of every dependency by name, a package can be depended
+        // on by many packages but a name only refers to one
+        let mut dirs: HashMap<String, PathBuf> = HashMap::new();
+        let mut work = vec![(dir.to_path_buf(), manifest)];
+        while let Some((dir, manifest)) = work.pop() {
+            for lib in manifest.libs {
+                if !project.libs.contains(&lib) {
+                    project.libs.push(lib);
+                }
+            }
+            for (name, path) in manifest.dependencies {
+                let path = dir.join(path);
+                let canonical = path.canonicalize().unwrap_or(path.clone());
+                let error = |message: String| ManifestError {
+                    location: dir.join(MANIFEST).display().to_string(),
+                    message,
+                };
+                match dirs.get(&name) {
+                    Some(other) if *other == canonical => continue,
+                    Some(other) => {
+                        return Err(error(format!(
+                            "dependency `{}` is both {} and {}",
+                            name,
+                            other.display(),
+                            canonical.display()
+                        )));
+                    }
+                    None => {}
+                }
+                if !path.join(MANIFEST).is_file() {
+                    return Err(error(format!(
+                        "dependency `{}` has no {} in {}",
+                        name,
+                        MANIFEST,
+                        path.display()
+                    )));
+                }
+
+                let dependency = read(&path)?;
+                dirs.insert(name.clone(), canonical);
+                project.dependencies.push(Dependency {
+                    name,
+                    entry: path.join(&dependency.entry),
+                    source_dirs: dependency
+                        .source_dirs
+                        .iter()
+                        .map(|d| path.join(d))
+                        .collect(),
+                });
+                work.push((path, dependency));
+            }
+        }
+        Ok(project)
+    }
+
+    /// File the program starts at
+    pub fn entry(&self) -> PathBuf {
+        self.dir.join(&self.manifest.entry)
+    }
+
+    /// Directories imports are looked up in
+    pub fn source_dirs(&self) -> Vec<PathBuf> {
+        let dirs = self.manifest.source_dirs.iter();
+        dirs.map(|dir| self.dir.join(dir)).collect()
+    }
+
+    /// Directory build outputs are written to
+    pub fn target_dir(&self) -> PathBuf {
+        self.dir.join("target")
+    }
+
+    /// A module loader finding imports in the project, after `search_path`
+    pub fn loader(&self, search_path: &[PathBuf]) -> ModuleLoader {
+        let mut dirs = search_path.to_vec();
+        dirs.extend(self.source_dirs());
+        ModuleLoader::new(&dirs).with_dependencies(self.dependencies.clone())
+    }
+}
+
+/// A value in a manifest
+enum Value {
+    Str(String),
+    Array(Vec<Value>),
+    Table(Vec<Entry>),
+}
+
+/// A key and its value, tables are entries of the document
+struct Entry {
+    key: String,
+    value: Value,
+    /// Offset of the key, where errors about the value are reported
+    offset: usize,
+}
+
+/// Offset into the manifest and message of an error
+type Error = (usize, String);
+
+fn string(entry: &Entry) -> Result<String, Error> {
+    match &entry.value {
+        Value::Str(value) => Ok(value.clone()),
+        _ => Err((entry.offset, format!("`{}` must be a string", entry.key))),
+    }
+}
+
+fn strings(entry: &Entry) -> Result<Vec<String>, Error> {
+    let error = || {
+        (
+            entry.offset,
+            format!("`{}` must be an array of strings", entry.key),
+        )
+    };
+    let Value::Array(values) = &entry.value else {
+        return Err(error());
+    };
+    let strings = values.iter().map(|value| match value {
+        Value::Str(value) => Some(value.clone()),
+        _ => None,
+    });
+    strings.collect::<Option<_>>().ok_or_else(error)
+}
+
+/// A dependency given as `name = { path = "..." }`
+fn dependency(entry: &Entry) -> Result<(String, PathBuf), Error> {
+    if !is_identifier(&entry.key) {
+        let message = format!("dependency name `{}` isn't an identifier", entry.key);
+        return Err((entry.offset, message));
+    }
+    match &entry.value {
+        Value::Table(fields) => match fields.as_slice() {
+            [field] if field.key == "path" => Ok((entry.key.clone(), string(field)?.into())),
+            _ => Err((
+                entry.offset,
+                format!("dependency `{}` needs a `path` and nothing else", entry.key),
+            )),
+        },
+        _ => Err((
+            entry.offset,
+            format!("dependency `{}` must be `{{ path = \"...\" }}`", entry.key),
+        )),
+    }
+}
+
+fn insert(entries: &mut Vec<Entry>, entry: Entry) -> Result<(), Error> {
+    if entries.iter().any(|other| other.key == entry.key) {
+        return Err((
+            entry.offset,
+            format!("`{}` is set more than once", entry.key),
+        ));
+    }
+    entries.push(entry);
+    Ok(())
+}
+
+/// Reads the TOML subset manifests are written in
+struct Reader<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn error<T>(&self, message: &str) -> Result<T, Error> {
+        Err((self.position, message.into()))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += c.len_utf8();
+        }
+        found
+    }
+
+    /// Skip spaces and comments, and line breaks too if `lines`
+    fn skip(&mut self, lines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => self.position += 1,
+                '\n' if lines => self.position += 1,
+                '#' => {
+                    let rest = &self.source[self.position..];
+                    self.position += rest.find('\n').unwrap_or(rest.len());
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        self.skip(false);
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            _ => self.error("expected the end of the line"),
+        }
+    }
+
+    /// The tables of the document, each with its entries
+    fn document(&mut self) -> Result<Vec<Entry>, Error> {
+        let mut tables: Vec<Entry> = vec![];
+        loop {
+            self.skip(true);
+            if self.peek().is_none() {
+                return Ok(tables);
+            }
+
+            let offset = self.position;
+            if self.eat('[') {
+                self.skip(false);
+                let key = self.key()?;
+                self.skip(false);
+                if !self.eat(']') {
+                    return self.error("expected `]` after the table name");
+                }
+                self.end_of_line()?;
+                if tables.iter().any(|table| table.key == key) {
+                    return Err((offset, format!("table [{}] is defined more than once", key)));
+                }
+                let value = Value::Table(vec![]);
+                tables.push(Entry { key, value, offset });
+                continue;
+            }
+
+            let entry = self.entry()?;
+            self.end_of_line()?;
+            match tables.last_mut() {
+                Some(Entry {
+                    value: Value::Table(entries),
+                    ..
+                }) => insert(entries, entry)?,
+                _ => {
+                    let message = "keys must be in a table such as [package]";
+                    return Err((offset, message.into()));
+                }
+            }
+        }
+    }
+
+    /// key ::= <bare key> | <String>
+    fn key(&mut self) -> Result<String, Error> {
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let start = self.position;
+        let bare = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        while self.peek().is_some_and(bare) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return self.error("expected a key");
+        }
+        Ok(self.source[start..self.position].into())
+    }
+
+    /// entry ::= key "=" value
+    fn entry(&mut self) -> Result<Entry, Error> {
+        let offset = self.position;
+        let key = self.key()?;
+        self.skip(false);
+        if !self.eat('=') {
+            return self.error("expected `=` after the key");
+        }
+        self.skip(false);
+        let value = self.value()?;
+        Ok(Entry { key, value, offset })
+    }
+
+    /// value ::= <String> | "[" ( value ( "," value )* ","? )? "]"
+    ///         | "{" ( entry ( "," entry )* )? "}"
+    fn value(&mut self) -> Result<Value, Error> {
+        if self.peek() == Some('"') {
+            return Ok(Value::Str(self.string()?));
+        }
+        if self.eat('[') {
+            let mut values = vec![];
+            loop {
+                self.skip(true);
+                if self.eat(']') {
+                    break;
+                }
+                values.push(self.value()?);
+                self.skip(true);
+                if !self.eat(',') {
+                    if !self.eat(']') {
+                        return self.error("expected `,` or `]`");
+                    }
+                    break;
+                }
+            }
+            return Ok(Value::Array(values));
+        }
+        if self.eat('{') {
+            let mut entries = vec![];
+            self.skip(false);
+            if self.eat('}') {
+                return Ok(Value::Table(entries));
+            }
+            loop {
+                self.skip(false);
+                let entry = self.entry()?;
+                insert(&mut entries, entry)?;
+                self.skip(false);
+                if self.eat('}') {
+                    return Ok(Value::Table(entries));
+                }
+                if !self.eat(',') {
+                    return self.error("expected `,` or `}`");
+                }
+            }
+        }
+        self.error("expected a string, an array or a table")
+    }
+
+    /// A string in double quotes, with `\"`, `\\`, `\n` and `\t` escapes
+    fn string(&mut self) -> Result<String, Error> {
+        let start = self.position;
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek().filter(|&c| c != '\n') else {
+                return Err((start, "unterminated string".into()));
+            };
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        _ => return self.error("unknown escape"),
+                    };
+                    self.position += 1;
+                    value.push(escaped);
+                }
+                c => value.push(c),
+            }
+        }
+    }
+}
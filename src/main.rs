@@ -1,29 +1,803 @@
-use lune::frontend::lexer::lexer::Lexer;
-use lune::frontend::lexer::token::Token;
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
 
-use lune::frontend::parser::Parser;
-use lune::backend::ast_dumper::ASTDumper;
-//use lune::backend::ast_printer::ASTPrinter;
+use colored::Colorize;
 
-fn main() {
-    let mut lexer = Lexer::new(
-        r#"var aNumber:int=0x2000
-           var aString:str="hello world""#,
+use lune::backend::asm::AsmGen;
+use lune::backend::ast_dumper::{ASTDumper, AstFormat};
+use lune::backend::bytecode::compile::Compiler;
+use lune::backend::bytecode::vm::Vm;
+use lune::backend::bytecode::Program;
+use lune::backend::cgen::{CGen, Unit};
+use lune::backend::ir::lower::Lowerer;
+use lune::backend::ir::opt::PassManager;
+use lune::backend::ir::parser::IrParser;
+use lune::backend::ir::verify::verify;
+use lune::backend::ir::Module;
+use lune::backend::llvm::LlvmGen;
+use lune::backend::wat::WatGen;
+use lune::cache::{self, Cache, Record};
+use lune::doc::{write_docs, ModuleDoc};
+use lune::frontend::modules::ModuleLoader;
+use lune::frontend::analysis::{Analysis, LineIndex};
+use lune::lsp::server::Server;
+use lune::manifest::{is_identifier, Manifest, Project, MANIFEST};
+
+const USAGE: &str = "usage: lune [options] <file>
+       lune build [--backend=<backend>] [-o <output>] [-v] [options] [<file>]
+       lune run [options] [<file>]
+       lune new <path>
+       lune doc [-I <dir>] [-o <dir>] <files>...
+       lune lsp
+
+Without a file, build and run use the project of the lune.toml in the current
+directory or the closest parent, and build writes to its target directory.
+Projects are built incrementally with the c backend, modules that didn't
+change are reused from target/.cache.
+
+options:
+    --emit=ast              print the AST instead of only checking the file
+    --emit=ast-opt          print the AST after constant folding
+    --emit=ir               print the intermediate representation, a .ir file
+                            is verified and printed back
+    --emit=c                print the generated C
+    --emit=asm              print the generated x86-64 assembly
+    --emit=llvm             print the generated LLVM IR
+    --emit=wat              print the generated WebAssembly text
+    --emit=bytecode         print the disassembled bytecode, a .lbc file is
+                            disassembled as is
+    --ast-format=<format>   AST dump format: tree (default), json or sexpr
+    -O0, -O1, -O2           optimization level of the IR, 0 by default
+    --passes=<passes>       run these comma separated IR passes instead:
+                            constprop, copyprop, dce, cse, inline, simplify-cfg
+    --release               leave out runtime checks such as bounds checks
+    -I <dir>                look for imported modules in this directory too,
+                            after the directory of the importing file
+
+build options:
+    --backend=<backend>     c (default) compiles the generated C with cc,
+                            asm assembles x86-64 assembly with as and ld,
+                            bytecode writes a .lbc file for lune run
+    -o <output>             file to write, the file name without its
+                            extension by default, or with .lbc for bytecode
+    -v, --verbose           report which modules of a project are compiled
+                            and which are reused";
+
+/// What to print after a file checks successfully
+#[derive(Debug, PartialEq)]
+enum Emit {
+    Nothing,
+    Ast,
+    AstOpt,
+    Ir,
+    C,
+    Asm,
+    Llvm,
+    Wat,
+    Bytecode,
+}
+
+struct Options {
+    emit: Emit,
+    ast_format: AstFormat,
+    release: bool,
+    opt_level: u8,
+    /// Passes given with `--passes`, which replace those of the level
+    passes: Option<Vec<String>>,
+    /// Directories given with `-I`, where imports are looked up
+    search_path: Vec<PathBuf>,
+    /// Project being built when no file is given, once it's opened
+    project: Option<Project>,
+    /// File to compile, empty if the project has to be opened
+    path: String,
+}
+
+impl Options {
+    /// Parse the options and file, which can only be left out if `project`
+    fn parse(args: &[String], project: bool) -> Result<Options, String> {
+        let mut emit = Emit::Nothing;
+        let mut ast_format = AstFormat::Tree;
+        let mut release = false;
+        let mut opt_level = 0;
+        let mut passes: Option<Vec<String>> = None;
+        let mut search_path = vec![];
+        let mut path = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--emit=") {
+                emit = match value {
+                    "ast" => Emit::Ast,
+                    "ast-opt" => Emit::AstOpt,
+                    "ir" => Emit::Ir,
+                    "c" => Emit::C,
+                    "asm" => Emit::Asm,
+                    "llvm" => Emit::Llvm,
+                    "wat" => Emit::Wat,
+                    "bytecode" => Emit::Bytecode,
+                    _ => return Err(format!("unknown --emit value `{}`", value)),
+                };
+            } else if let Some(value) = arg.strip_prefix("--ast-format=") {
+                ast_format = value.parse()?;
+            } else if arg == "--release" {
+                release = true;
+            } else if let Some(level) = arg.strip_prefix("-O") {
+                opt_level = match level {
+                    "0" => 0,
+                    "1" => 1,
+                    "2" => 2,
+                    _ => return Err(format!("unknown optimization level `{}`", arg)),
+                };
+            } else if let Some(value) = arg.strip_prefix("--passes=") {
+                passes = Some(value.split(',').map(String::from).collect());
+            } else if arg == "-I" {
+                search_path.push(args.next().ok_or("expected a directory after -I")?.into());
+            } else if arg.starts_with('-') {
+                return Err(format!("unknown option `{}`", arg));
+            } else if path.replace(arg.clone()).is_some() {
+                return Err("expected a single input file".into());
+            }
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None if project => String::new(),
+            None => return Err("missing input file".into()),
+        };
+        if let Some(passes) = &passes {
+            PassManager::from_names(passes.iter().map(String::as_str))?;
+        }
+        Ok(Options {
+            emit,
+            ast_format,
+            release,
+            opt_level,
+            passes,
+            search_path,
+            project: None,
+            path,
+        })
+    }
+
+    /// Open the project in the current directory or a parent if no file was
+    /// given, printing any errors in its manifests. Finding no project is a
+    /// usage error.
+    fn open_project(&mut self) -> Result<bool, String> {
+        if !self.path.is_empty() {
+            return Ok(true);
+        }
+        let cwd = std::env::current_dir().map_err(|err| err.to_string())?;
+        let Some(dir) = Project::find(&cwd) else {
+            return Err(format!(
+                "missing input file, and there's no {} here or in a parent directory",
+                MANIFEST
+            ));
+        };
+        // Paths in messages are relative to the current directory if they can be
+        let dir = dir.strip_prefix(&cwd).unwrap_or(&dir);
+        match Project::load(dir) {
+            Ok(project) => {
+                self.path = project.entry().to_string_lossy().into();
+                self.project = Some(project);
+                Ok(true)
+            }
+            Err(err) => {
+                eprintln!("{}: {}: {}", "error".red().bold(), err.location, err.message);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Loader for the imports of the file, from the search path and project
+    fn loader(&self) -> ModuleLoader {
+        match &self.project {
+            Some(project) => project.loader(&self.search_path),
+            None => ModuleLoader::new(&self.search_path),
+        }
+    }
+
+    /// Run the IR passes asked for over `module`, printing an error if one
+    /// of them breaks it
+    fn optimize(&self, mut module: Module) -> Option<Module> {
+        let mut manager = match &self.passes {
+            Some(passes) => PassManager::from_names(passes.iter().map(String::as_str)).unwrap(),
+            None => PassManager::for_level(self.opt_level),
+        };
+        if let Err(err) = manager.run(&mut module) {
+            eprintln!("{}: internal compiler error: {}", "error".red().bold(), err);
+            return None;
+        }
+        Some(module)
+    }
+}
+
+fn read(path: &str) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => Some(source),
+        Err(err) => {
+            eprintln!("{}: couldn't read {}: {}", "error".red().bold(), path, err);
+            None
+        }
+    }
+}
+
+/// Print an error at a byte offset of a source file
+fn report(path: &str, source: &str, lines: &LineIndex, offset: usize, message: &str) {
+    let (line, column) = lines.position(source, offset);
+    eprintln!(
+        "{}: {}:{}:{}: {}",
+        "error".red().bold(),
+        path,
+        line + 1,
+        column + 1,
+        message
+    );
+}
+
+/// Print an error at an offset of a checked program, in the file it falls in
+fn report_in(analysis: &Analysis, offset: usize, message: &str) {
+    let file = analysis.file_at(offset).expect("a program has at least one file");
+    let path = file.path.to_string_lossy();
+    report(&path, &file.source, &file.lines, offset - file.base, message);
+}
+
+/// Read and check a source file and the modules `loader` finds it imports,
+/// printing any errors
+fn analyze(path: &str, loader: ModuleLoader) -> Option<Analysis> {
+    let source = read(path)?;
+    let analysis = Analysis::with_path(&source, Path::new(path), loader);
+    if !analysis.diagnostics.is_empty() {
+        for diagnostic in &analysis.diagnostics {
+            report_in(&analysis, diagnostic.span.start, &diagnostic.message);
+        }
+        return None;
+    }
+    Some(analysis)
+}
+
+/// Read an IR module printed by `--emit=ir`, printing any errors
+fn read_ir(path: &str) -> Option<Module> {
+    let source = read(path)?;
+    let module = IrParser::new(&source).and_then(|mut parser| parser.parse());
+    let module = match module {
+        Ok(module) => module,
+        Err(err) => {
+            report(path, &source, &LineIndex::new(&source), err.position, &err.message);
+            return None;
+        }
+    };
+    if let Err(err) = verify(&module) {
+        eprintln!("{}: {}: {}", "error".red().bold(), path, err);
+        return None;
+    }
+    Some(module)
+}
+
+/// Read a compiled program written by `lune build --backend=bytecode`,
+/// printing any errors
+fn load(path: &str) -> Option<Program> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("{}: couldn't read {}: {}", "error".red().bold(), path, err);
+            return None;
+        }
+    };
+    match Program::from_bytes(&bytes) {
+        Ok(program) => Some(program),
+        Err(err) => {
+            eprintln!("{}: {}: {}", "error".red().bold(), path, err);
+            None
+        }
+    }
+}
+
+/// Lower a checked file to IR, printing any errors
+fn lower(analysis: &Analysis) -> Option<Module> {
+    let mut lowerer = Lowerer::new(&analysis.resolution, &analysis.types, &analysis.consts);
+    let module = match lowerer.lower(&analysis.folded) {
+        Ok(module) => module,
+        Err(err) => {
+            report_in(analysis, err.span.start, &err.message);
+            return None;
+        }
+    };
+    if let Err(err) = verify(&module) {
+        eprintln!(
+            "{}: internal compiler error: lowering produced invalid IR: {}",
+            "error".red().bold(),
+            err
+        );
+        return None;
+    }
+    Some(module)
+}
+
+/// Write what was asked for to stdout. A reader that stops early, like
+/// `head`, isn't an error.
+fn emit(write: impl FnOnce(&mut io::StdoutLock) -> io::Result<()>) -> i32 {
+    match write(&mut io::stdout().lock()) {
+        Ok(()) => 0,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => 0,
+        Err(err) => {
+            eprintln!("{}: couldn't write the output: {}", "error".red().bold(), err);
+            1
+        }
+    }
+}
+
+/// Check a source file and emit what was asked for
+fn compile(options: &Options) -> i32 {
+    if options.path.ends_with(".ir") {
+        if options.emit != Emit::Ir {
+            eprintln!("{}: IR files can only be read with --emit=ir", "error".red().bold());
+            return 2;
+        }
+        let Some(module) = read_ir(&options.path).and_then(|module| options.optimize(module)) else {
+            return 1;
+        };
+        return emit(|out| write!(out, "{}", module));
+    }
+    if options.path.ends_with(".lbc") {
+        if options.emit != Emit::Bytecode {
+            eprintln!(
+                "{}: bytecode files can only be read with --emit=bytecode or run with lune run",
+                "error".red().bold()
+            );
+            return 2;
+        }
+        let Some(program) = load(&options.path) else {
+            return 1;
+        };
+        return emit(|out| write!(out, "{}", program));
+    }
+
+    let Some(analysis) = analyze(&options.path, options.loader()) else {
+        return 1;
+    };
+
+    match options.emit {
+        Emit::Nothing => 0,
+        Emit::Ast | Emit::AstOpt => {
+            let ast = match options.emit {
+                Emit::Ast => &analysis.ast,
+                _ => &analysis.folded,
+            };
+            let dumper = ASTDumper::new(options.ast_format);
+            emit(|out| dumper.dump_program(ast, &analysis.files, out))
+        }
+        Emit::Ir => {
+            let Some(module) = lower(&analysis).and_then(|module| options.optimize(module)) else {
+                return 1;
+            };
+            emit(|out| write!(out, "{}", module))
+        }
+        Emit::C => emit(|out| write!(out, "{}", generate_c(options, &analysis))),
+        Emit::Bytecode => emit(|out| write!(out, "{}", compile_bytecode(&analysis))),
+        Emit::Asm | Emit::Llvm | Emit::Wat => {
+            let Some(module) = lower(&analysis).and_then(|module| options.optimize(module)) else {
+                return 1;
+            };
+            let text = match options.emit {
+                Emit::Asm => AsmGen::new().generate(&module),
+                Emit::Llvm => LlvmGen::new().generate(&module),
+                _ => WatGen::new().generate(&module),
+            };
+            emit(|out| out.write_all(text.as_bytes()))
+        }
+    }
+}
+
+fn generate_c(options: &Options, analysis: &Analysis) -> String {
+    let mut cgen = CGen::new(&analysis.resolution, &analysis.types, &analysis.consts)
+        .with_files(&analysis.files)
+        .checks(!options.release);
+    cgen.generate(&analysis.folded)
+}
+
+fn compile_bytecode(analysis: &Analysis) -> Program {
+    let mut compiler = Compiler::new(&analysis.resolution, &analysis.types, &analysis.consts)
+        .with_files(&analysis.files);
+    compiler.compile(&analysis.folded)
+}
+
+/// How `lune build` turns a program into an executable
+#[derive(Debug, PartialEq)]
+enum Backend {
+    C,
+    Asm,
+    /// Not an executable but a `.lbc` file for `lune run`
+    Bytecode,
+}
+
+/// Run an external tool, printing why if it fails
+fn run(program: &str, args: &[&Path]) -> bool {
+    match Command::new(program).args(args).status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            eprintln!("{}: `{}` failed with {}", "error".red().bold(), program, status);
+            false
+        }
+        Err(err) => {
+            eprintln!("{}: couldn't run `{}`: {}", "error".red().bold(), program, err);
+            false
+        }
+    }
+}
+
+/// `lune build`, compile a file into an executable
+fn build(args: &[String]) -> Result<i32, String> {
+    let mut backend = Backend::C;
+    let mut output = None;
+    let mut verbose = false;
+    let mut rest = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--backend=") {
+            backend = match value {
+                "c" => Backend::C,
+                "asm" => Backend::Asm,
+                "bytecode" => Backend::Bytecode,
+                _ => return Err(format!("unknown backend `{}`", value)),
+            };
+        } else if arg == "-o" {
+            output = Some(args.next().ok_or("expected a file after -o")?.clone());
+        } else if arg == "-v" || arg == "--verbose" {
+            verbose = true;
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    let mut options = Options::parse(&rest, true)?;
+    if options.emit != Emit::Nothing {
+        return Err("--emit can't be used with build".into());
+    }
+    if !options.open_project()? {
+        return Ok(1);
+    }
+    let ext = if backend == Backend::Bytecode { "lbc" } else { "" };
+    let output = PathBuf::from(output.unwrap_or_else(|| match &options.project {
+        Some(project) => {
+            let path = project.target_dir().join(&project.manifest.name);
+            path.with_extension(ext).to_string_lossy().into()
+        }
+        None => {
+            let path = Path::new(&options.path);
+            path.with_extension(ext).to_string_lossy().into()
+        }
+    }));
+    if output == Path::new(&options.path) {
+        return Err("the executable would overwrite the source, pass -o".into());
+    }
+    let libs = options.project.as_ref().map_or(&[][..], |project| &project.libs);
+    if !libs.is_empty() && backend == Backend::Asm {
+        eprintln!(
+            "{}: C libraries can only be linked with the c backend",
+            "error".red().bold()
+        );
+        return Ok(1);
+    }
+
+    let Some(analysis) = analyze(&options.path, options.loader()) else {
+        return Ok(1);
+    };
+    if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            eprintln!("{}: couldn't create {}: {}", "error".red().bold(), dir.display(), err);
+            return Ok(1);
+        }
+    }
+    if let (Some(project), Backend::C) = (&options.project, &backend) {
+        let built = build_cached(&options, project, &analysis, &output, verbose);
+        return Ok(if built { 0 } else { 1 });
+    }
+    let (text, ext) = match backend {
+        Backend::Bytecode => {
+            let program = compile_bytecode(&analysis);
+            if let Err(err) = std::fs::write(&output, program.to_bytes()) {
+                eprintln!("{}: couldn't write {}: {}", "error".red().bold(), output.display(), err);
+                return Ok(1);
+            }
+            return Ok(0);
+        }
+        Backend::C => (generate_c(&options, &analysis), "c"),
+        Backend::Asm => {
+            let Some(module) = lower(&analysis).and_then(|module| options.optimize(module)) else {
+                return Ok(1);
+            };
+            (AsmGen::new().generate(&module), "s")
+        }
+    };
+    // Intermediate files go next to the output
+    let source = output.with_extension(format!("tmp.{}", ext));
+    if let Err(err) = std::fs::write(&source, text) {
+        eprintln!("{}: couldn't write {}: {}", "error".red().bold(), source.display(), err);
+        return Ok(1);
+    }
+
+    let built = match backend {
+        Backend::C => {
+            let libs: Vec<PathBuf> = libs.iter().map(|lib| format!("-l{}", lib).into()).collect();
+            let mut args = vec![Path::new("-o"), &output, &source];
+            args.extend(libs.iter().map(PathBuf::as_path));
+            run("cc", &args)
+        }
+        Backend::Bytecode => unreachable!("bytecode is written without external tools"),
+        Backend::Asm => {
+            let object = output.with_extension("tmp.o");
+            let built = run("as", &[Path::new("-o"), &object, &source])
+                && run("ld", &[Path::new("-o"), &output, &object]);
+            let _ = std::fs::remove_file(&object);
+            built
+        }
+    };
+    let _ = std::fs::remove_file(&source);
+    Ok(if built { 0 } else { 1 })
+}
+
+/// Compile each module of a project to an object under `target/.cache`,
+/// reusing those of modules that didn't change, and link them into `output`
+fn build_cached(
+    options: &Options,
+    project: &Project,
+    analysis: &Analysis,
+    output: &Path,
+    verbose: bool,
+) -> bool {
+    let cache = Cache::new(project.target_dir().join(".cache"));
+    if let Err(err) = cache.create_dirs() {
+        eprintln!("{}: couldn't create the cache: {}", "error".red().bold(), err);
+        return false;
+    }
+    // Objects of another build of lune can't be trusted to match this one
+    let exe = std::env::current_exe().and_then(|exe| exe.metadata()?.modified());
+    let built = exe.map_or(0, |time| {
+        let since = time.duration_since(std::time::UNIX_EPOCH);
+        since.map_or(0, |since| since.as_nanos())
+    });
+    let compiler = format!(
+        "lune {} built {}, checks {}",
+        env!("CARGO_PKG_VERSION"),
+        built,
+        !options.release
     );
-    let tokens: Vec<Token> = lexer.scan();
-    let mut parser = Parser::new(tokens.clone());
 
-    println!("tokens: {:?}", tokens);
-    //println!("parsed: {:?}", parser.parse());
+    let modules = cache::modules(analysis);
+    let inits: Vec<String> = modules.iter().map(|module| module.module.clone()).collect();
+    let interfaces: Vec<String> = {
+        let mut interfaces = vec![String::new(); analysis.files.len()];
+        for module in &modules {
+            interfaces[module.file] = cache::hash(module.interface.as_bytes());
+        }
+        interfaces
+    };
+
+    let mut objects = vec![];
+    for module in &modules {
+        let file = &analysis.files[module.file];
+        let name = match module.module.as_str() {
+            "" => file.path.file_stem().map_or("".into(), |stem| stem.to_string_lossy()),
+            name => name.into(),
+        };
+        let entry = module.module.is_empty();
+        let visible = cache::visible(&analysis.files, module.file).into_iter();
+        let record = Record {
+            options: compiler.clone(),
+            tokens: module.tokens.clone(),
+            interface: module.interface.clone(),
+            imports: visible
+                .map(|i| (analysis.files[i].module.clone(), interfaces[i].clone()))
+                .collect(),
+            inits: if entry { inits.clone() } else { vec![] },
+        };
+
+        let source = cache.file(&module.module, "c");
+        let object = cache.file(&module.module, "o");
+        let stale = match cache.load(&module.module) {
+            Some(old) if object.is_file() => old.stale(&record),
+            _ => Some("not cached".into()),
+        };
+        objects.push(object.clone());
+        let Some(reason) = stale else {
+            if verbose {
+                eprintln!("{} {}", "reused".green().bold(), name);
+            }
+            continue;
+        };
+        if verbose {
+            eprintln!("{} {} ({})", "compiling".green().bold(), name, reason);
+        }
 
-    let dumper = ASTDumper {};
-    dumper.dump_ast(parser.parse().unwrap());
+        let unit = Unit {
+            module: &module.module,
+            root: &module.root,
+            main: entry.then_some(&inits[..]),
+        };
+        let mut cgen = CGen::new(&analysis.resolution, &analysis.types, &analysis.consts)
+            .with_files(&analysis.files)
+            .checks(!options.release);
+        let text = cgen.generate_module(&analysis.folded, &unit);
+        if let Err(err) = std::fs::write(&source, text) {
+            eprintln!("{}: couldn't write {}: {}", "error".red().bold(), source.display(), err);
+            return false;
+        }
+        if !run("cc", &[Path::new("-c"), Path::new("-o"), &object, &source]) {
+            return false;
+        }
+        if let Err(err) = cache.store(&module.module, &record) {
+            eprintln!("{}: couldn't write to the cache: {}", "error".red().bold(), err);
+            return false;
+        }
+    }
+
+    let libs: Vec<PathBuf> = project.libs.iter().map(|lib| format!("-l{}", lib).into()).collect();
+    let mut args = vec![Path::new("-o"), output];
+    args.extend(objects.iter().map(PathBuf::as_path));
+    args.extend(libs.iter().map(PathBuf::as_path));
+    if !run("cc", &args) {
+        return false;
+    }
+    if verbose {
+        eprintln!("{} {}", "linked".green().bold(), output.display());
+    }
+    true
+}
+
+/// `lune run`, compile a file to bytecode or load a `.lbc` file and run it
+fn run_bytecode(args: &[String]) -> Result<i32, String> {
+    let mut options = Options::parse(args, true)?;
+    if options.emit != Emit::Nothing {
+        return Err("--emit can't be used with run".into());
+    }
+    if !options.open_project()? {
+        return Ok(1);
+    }
+    let program = if options.path.ends_with(".lbc") {
+        load(&options.path)
+    } else {
+        let analysis = analyze(&options.path, options.loader());
+        analysis.map(|analysis| compile_bytecode(&analysis))
+    };
+    let Some(program) = program else {
+        return Ok(1);
+    };
+
+    let stdout = io::stdout();
+    let mut vm = Vm::new(&program, BufWriter::new(stdout.lock()));
+    if let Err(err) = vm.run() {
+        let location = err.location.map(|location| format!("{}: ", location));
+        eprintln!("{}: {}{}", "error".red().bold(), location.unwrap_or_default(), err.message);
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// `lune doc`, write the docs of every file into the output directory
+fn doc(args: &[String]) -> Result<i32, String> {
+    let mut out = "docs".to_string();
+    let mut search_path = vec![];
+    let mut paths = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            out = args.next().ok_or("expected a directory after -o")?.clone();
+        } else if arg == "-I" {
+            search_path.push(args.next().ok_or("expected a directory after -I")?.into());
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option `{}`", arg));
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        return Err("missing input files".into());
+    }
+
+    let mut analyses = vec![];
+    for path in paths {
+        let Some(analysis) = analyze(path, ModuleLoader::new(&search_path)) else {
+            return Ok(1);
+        };
+        analyses.push((path, analysis));
+    }
+
+    // A file another one imports is a module, whose private items aren't
+    // documented
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let imported: HashSet<PathBuf> = analyses
+        .iter()
+        .flat_map(|(_, analysis)| &analysis.files[1..])
+        .map(|file| canonical(&file.path))
+        .collect();
+    let mut modules = vec![];
+    for (path, analysis) in &analyses {
+        let name = Path::new(path)
+            .file_stem()
+            .map_or(path.to_string(), |stem| stem.to_string_lossy().into());
+        let public_only = imported.contains(&canonical(Path::new(path)));
+        modules.push(ModuleDoc::new(&name, &analysis.ast, &analysis.file_root(), public_only));
+    }
+
+    if let Err(err) = write_docs(&modules, Path::new(&out)) {
+        eprintln!("{}: couldn't write docs to {}: {}", "error".red().bold(), out, err);
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// `lune new`, create a package with a manifest and a hello world program
+fn new(args: &[String]) -> Result<i32, String> {
+    let [path] = args else {
+        return Err("expected the path of the package".into());
+    };
+    let dir = Path::new(path);
+    let name = dir.file_name().map(|name| name.to_string_lossy());
+    let name = name.ok_or("expected the path of the package")?;
+    if !is_identifier(&name) {
+        return Err(format!("package name `{}` isn't an identifier", name));
+    }
+    if dir.exists() {
+        eprintln!("{}: {} already exists", "error".red().bold(), dir.display());
+        return Ok(1);
+    }
+
+    let manifest = Manifest::new(&name);
+    let files = [
+        (dir.join(MANIFEST), manifest.to_string()),
+        (dir.join(&manifest.entry), "print(\"hello world!\")\n".into()),
+        (dir.join(".gitignore"), "/target\n".into()),
+    ];
+    for (path, contents) in files {
+        let written = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, contents));
+        if let Err(err) = written {
+            eprintln!("{}: couldn't write {}: {}", "error".red().bold(), path.display(), err);
+            return Ok(1);
+        }
+    }
+    println!("created package `{}` in {}", name, dir.display());
+    Ok(0)
+}
+
+/// A subcommand taking the arguments after its name, returning the exit
+/// code or a usage error
+type Subcommand = fn(&[String]) -> Result<i32, String>;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    /*
-    let mut printer = ASTPrinter {};
+    let subcommand: Option<Subcommand> = match args.first().map(String::as_str) {
+        Some("doc") => Some(doc),
+        Some("build") => Some(build),
+        Some("run") => Some(run_bytecode),
+        Some("new") => Some(new),
+        _ => None,
+    };
+    let code = if let Some(subcommand) = subcommand {
+        subcommand(&args[1..]).unwrap_or_else(|err| {
+            eprintln!("{}: {}\n\n{}", "error".red().bold(), err, USAGE);
+            2
+        })
+    } else if args.len() == 1 && args[0] == "lsp" {
+        let stdin = io::stdin();
+        let mut server = Server::new(io::stdout());
+        server.run(stdin.lock()).unwrap_or_else(|err| {
+            eprintln!("{}: {}", "error".red().bold(), err);
+            1
+        })
+    } else {
+        match Options::parse(&args, false) {
+            Ok(options) => compile(&options),
+            Err(err) => {
+                eprintln!("{}: {}\n\n{}", "error".red().bold(), err, USAGE);
+                2
+            }
+        }
+    };
 
-    match parser.parse() {
-        Ok(result) => printer.print(result),
-        Err(err) => eprintln!("{}", err)
-    }*/
+    process::exit(code);
 }
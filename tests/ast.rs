@@ -0,0 +1,477 @@
+//! Snapshots of the AST dump formats and S-expression round trips
+
+mod common;
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+use std::collections::BTreeSet;
+
+use lune::backend::ast::*;
+use lune::backend::ast_dumper::{ASTDumper, AstFormat};
+use lune::backend::ast_printer::ASTPrinter;
+use lune::backend::ast_reader::ASTReader;
+use lune::backend::visit::{self, Folder, Visitor, VisitorMut};
+use lune::frontend::modules::ModuleLoader;
+use lune::frontend::analysis::Analysis;
+
+fn dump(source: &str, format: AstFormat) -> String {
+    let analysis = Analysis::new(source);
+    assert!(
+        analysis.diagnostics.is_empty(),
+        "{:?}",
+        analysis.diagnostics
+    );
+
+    let mut out = vec![];
+    ASTDumper::new(format)
+        .dump_ast(&analysis.ast, &mut out)
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn dump_formats() {
+    let source = common::program("syntax.lune");
+    for format in [AstFormat::Tree, AstFormat::Json, AstFormat::SExpr] {
+        let name = format!("ast/syntax.{}", format);
+        common::snapshot(&name, &dump(&source, format));
+    }
+}
+
+#[test]
+fn dump_to_closed_pipe() {
+    // More than a pipe buffer, so the write fails once the reader is gone
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("big.lune");
+    let source: String = (0..5000)
+        .map(|i| format!("var v{}: int = {}\n", i, i))
+        .collect();
+    std::fs::write(&path, source).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lune"))
+        .arg("--emit=ast")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = [0; 16];
+    child.stdout.take().unwrap().read_exact(&mut line).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+/// Names of the kinds of statements, expressions and patterns in `ast`. The
+/// matches are exhaustive so a new variant has to be added here, and to
+/// `syntax.lune` for the round trip to cover it.
+fn kinds(ast: &AST) -> BTreeSet<&'static str> {
+    let mut kinds = BTreeSet::new();
+    for stmt in &ast.stmts {
+        let kind = match &stmt.kind {
+            StmtKind::Expr(_) => "Expr",
+            StmtKind::VarDeclaration(..) => "VarDeclaration",
+            StmtKind::ConstDeclaration(..) => "ConstDeclaration",
+            StmtKind::Assignment(..) => "Assignment",
+            StmtKind::If(_, _, None) => "If",
+            StmtKind::If(_, _, Some(_)) => "IfElse",
+            StmtKind::While(..) => "While",
+            StmtKind::Return(None) => "Return",
+            StmtKind::Return(Some(_)) => "ReturnValue",
+            StmtKind::ProcDeclaration(..) => "ProcDeclaration",
+            StmtKind::StructDeclaration(..) => "StructDeclaration",
+            StmtKind::EnumDeclaration(..) => "EnumDeclaration",
+            StmtKind::Case(_, arms) => {
+                patterns(&mut kinds, arms.iter().map(|arm| &arm.pattern));
+                "Case"
+            }
+        };
+        kinds.insert(kind);
+    }
+    for expr in &ast.exprs {
+        let kind = match &expr.kind {
+            ExprKind::IntLit(_) => "IntLit",
+            ExprKind::StringLit(_) => "StringLit",
+            ExprKind::BoolLit(_) => "BoolLit",
+            ExprKind::Variable(_) => "Variable",
+            ExprKind::Call(..) => "Call",
+            ExprKind::UnaryOp(..) => "UnaryOp",
+            ExprKind::BinOp(..) => "BinOp",
+            ExprKind::StructLit(..) => "StructLit",
+            ExprKind::FieldAccess(..) => "FieldAccess",
+            ExprKind::Case(_, arms) => {
+                patterns(&mut kinds, arms.iter().map(|arm| &arm.pattern));
+                "CaseExpr"
+            }
+            ExprKind::ArrayLit(_) => "ArrayLit",
+            ExprKind::Index(..) => "Index",
+            ExprKind::Slice(_, start, end) => match (start, end) {
+                (None, None) => "Slice",
+                (Some(_), None) => "SliceFrom",
+                (None, Some(_)) => "SliceTo",
+                (Some(_), Some(_)) => "SliceRange",
+            },
+        };
+        kinds.insert(kind);
+    }
+    kinds
+}
+
+fn patterns<'a>(kinds: &mut BTreeSet<&'static str>, patterns: impl Iterator<Item = &'a Pattern>) {
+    for pattern in patterns {
+        kinds.insert(match &pattern.kind {
+            PatternKind::Wildcard => "Wildcard",
+            PatternKind::Int(_) => "IntPattern",
+            PatternKind::Range(..) => "RangePattern",
+            PatternKind::Str(_) => "StrPattern",
+            PatternKind::Variant(_, bindings) if bindings.is_empty() => "VariantPattern",
+            PatternKind::Variant(..) => "BindingPattern",
+        });
+    }
+}
+
+#[test]
+fn syntax_covers_every_node() {
+    let analysis = Analysis::new(&common::program("syntax.lune"));
+    let found = kinds(&analysis.ast);
+    let all = [
+        "Expr",
+        "VarDeclaration",
+        "ConstDeclaration",
+        "Assignment",
+        "If",
+        "IfElse",
+        "While",
+        "Return",
+        "ReturnValue",
+        "ProcDeclaration",
+        "StructDeclaration",
+        "EnumDeclaration",
+        "Case",
+        "IntLit",
+        "StringLit",
+        "BoolLit",
+        "Variable",
+        "Call",
+        "UnaryOp",
+        "BinOp",
+        "StructLit",
+        "FieldAccess",
+        "CaseExpr",
+        "ArrayLit",
+        "Index",
+        "Slice",
+        "SliceFrom",
+        "SliceTo",
+        "SliceRange",
+        "Wildcard",
+        "IntPattern",
+        "RangePattern",
+        "StrPattern",
+        "VariantPattern",
+        "BindingPattern",
+    ];
+    let missing: Vec<&str> = all
+        .into_iter()
+        .filter(|kind| !found.contains(kind))
+        .collect();
+    assert!(missing.is_empty(), "syntax.lune has no {:?}", missing);
+}
+
+fn round_trip(ast: &AST) {
+    let printed = ASTPrinter {}.print(ast);
+    let read = match ASTReader::new(&printed).read() {
+        Ok(read) => read,
+        Err(err) => panic!("{} at {}:\n{}", err.message, err.position, printed),
+    };
+    assert_eq!(ASTPrinter {}.print(&read), printed);
+}
+
+#[test]
+fn sexpr_round_trip() {
+    let analysis = Analysis::new(&common::program("syntax.lune"));
+    round_trip(&analysis.ast);
+    round_trip(&analysis.folded);
+}
+
+#[test]
+fn sexpr_read_errors() {
+    for (source, position) in [
+        ("(var x int", 0),
+        ("(frobnicate 1)", 0),
+        ("(var x int 1) )", 14),
+    ] {
+        let err = ASTReader::new(source).read().unwrap_err();
+        assert_eq!(err.position, position, "{}: {}", source, err.message);
+    }
+}
+
+/// Dumps of a program importing a module, whose spans are relative to the
+/// module's own file
+#[test]
+fn dump_modules() {
+    let main = common::path("programs/modules/main.lune");
+    for format in ["tree", "json", "sexpr"] {
+        let output = common::lune(&[
+            "--emit=ast",
+            &format!("--ast-format={}", format),
+            main.to_str().unwrap(),
+        ]);
+        assert!(output.status.success());
+        let dump = String::from_utf8(output.stdout).unwrap();
+        common::snapshot(&format!("ast/modules.{}", format), &dump);
+    }
+}
+
+/// `--emit=ast-opt` of a program under `tests/programs`, as S-expressions
+fn emit_folded(name: &str) -> std::process::Output {
+    let file = common::path("programs").join(name);
+    common::lune(&[
+        "--emit=ast-opt",
+        "--ast-format=sexpr",
+        file.to_str().unwrap(),
+    ])
+}
+
+/// Identities are dropped but their operand is still evaluated
+#[test]
+fn fold_identities() {
+    let output = emit_folded("fold/identities.lune");
+    assert!(output.status.success());
+    let dump = String::from_utf8(output.stdout).unwrap();
+    common::snapshot("ast/fold_identities.sexpr", &dump);
+
+    let run = common::run_vm("fold/identities.lune");
+    assert_eq!(run.stdout, "5\n5\n5\n5\n5\ntrue\n1\n2\n10\n");
+}
+
+#[test]
+fn fold_division_errors() {
+    let output = emit_folded("fold/divide.lune");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    for expected in [
+        "divide.lune:3:11: attempt to divide by zero",
+        "divide.lune:4:11: attempt to divide `7` by zero",
+        "divide.lune:5:11: attempt to compute `-2147483648 / -1`, which would overflow",
+    ] {
+        assert!(stderr.contains(expected), "{}", stderr);
+    }
+}
+
+/// Stderr of dumping a program under `tests/programs` that fails to load
+fn load_error(name: &str) -> String {
+    let file = common::path("programs").join(name);
+    let output = common::lune(&["--emit=ast", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn import_cycle() {
+    let stderr = load_error("modules/cycle/a.lune");
+    assert!(
+        stderr.contains("cycle/b.lune:1:1: import cycle: a -> b -> a"),
+        "{}",
+        stderr
+    );
+}
+
+/// Items of an imported module that aren't `pub` can't be used
+#[test]
+fn private_item() {
+    let stderr = load_error("modules/private.lune");
+    assert!(
+        stderr.contains("private.lune:2:7: `hidden` is private to module `geo`"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn sexpr_round_trip_keeps_pub_and_imports() {
+    let main = common::path("programs/modules/main.lune");
+    let source = std::fs::read_to_string(&main).unwrap();
+    let analysis = Analysis::with_path(&source, &main, ModuleLoader::new(&[]));
+    assert!(
+        analysis.diagnostics.is_empty(),
+        "{:?}",
+        analysis.diagnostics
+    );
+    round_trip(&analysis.ast);
+
+    let printed = ASTPrinter {}.print(&analysis.ast);
+    let read = ASTReader::new(&printed).read().unwrap();
+    assert_eq!(read.imports.len(), 1);
+    assert_eq!(read.imports[0].path, "shapes/geo");
+    assert_eq!(read.imports[0].name, "geo");
+    let public: Vec<&str> = read
+        .root
+        .iter()
+        .filter(|&&stmt| read.public.contains(&read[stmt].id))
+        .map(|&stmt| match &read[stmt].kind {
+            StmtKind::StructDeclaration(struct_) => struct_.name.value.as_str(),
+            StmtKind::ProcDeclaration(proc) => proc.name.value.as_str(),
+            _ => "",
+        })
+        .collect();
+    assert_eq!(public, ["shapes.geo.Point", "shapes.geo.origin"]);
+}
+
+/// Imported modules are documented without their private items
+#[test]
+fn doc_modules() {
+    let dir = common::temp_dir("doc_modules");
+    let main = common::path("programs/modules/main.lune");
+    let geo = common::path("programs/modules/shapes/geo.lune");
+    let output = common::lune(&[
+        "doc",
+        main.to_str().unwrap(),
+        geo.to_str().unwrap(),
+        "-o",
+        dir.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    for page in ["geo.md", "geo.html", "search-index.json"] {
+        let text = std::fs::read_to_string(dir.join(page)).unwrap();
+        assert!(text.contains("origin"), "{}", page);
+        assert!(!text.contains("hidden"), "{}", page);
+    }
+}
+
+/// Struct and enum types in signatures link to their declaration, qualified
+/// ones to the module they name
+#[test]
+fn doc_links_types() {
+    let dir = common::temp_dir("doc_links_types");
+    let shapes = common::path("programs/doc/shapes.lune");
+    let main = common::path("programs/modules/main.lune");
+    let geo = common::path("programs/modules/shapes/geo.lune");
+    let output = common::lune(&[
+        "doc",
+        shapes.to_str().unwrap(),
+        main.to_str().unwrap(),
+        geo.to_str().unwrap(),
+        "-o",
+        dir.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    for page in ["shapes.md", "shapes.html", "main.md"] {
+        let text = std::fs::read_to_string(dir.join(page)).unwrap();
+        common::snapshot(&format!("doc/{}", page), &text);
+    }
+}
+
+/// Counts names, only overriding `visit_name` so the default walk reaches
+/// them in nested blocks
+struct Names(usize);
+
+impl Visitor<()> for Names {
+    fn visit_name(&mut self, _name: &Name) {
+        self.0 += 1;
+    }
+}
+
+/// Renames `x` to `y` in place
+struct Rename;
+
+impl VisitorMut for Rename {
+    fn visit_name_mut(&mut self, name: &mut Name) {
+        if name.value == "x" {
+            name.value = "y".into();
+        }
+    }
+}
+
+/// Replaces every int literal with a new one holding the next number
+struct Increment;
+
+impl Folder for Increment {
+    fn fold_expr(&mut self, ast: &mut AST, expr: ExprId) -> ExprId {
+        let expr = visit::fold_expr(self, ast, expr);
+        match ast[expr].kind {
+            ExprKind::IntLit(n) => {
+                let span = ast[expr].span;
+                ast.add_expr(ExprKind::IntLit(n + 1), span)
+            }
+            _ => expr,
+        }
+    }
+}
+
+const NESTED: &str = "proc f(x: int): int {
+    if x > 0 {
+        return x + 1
+    }
+    return 2
+}
+var x: int = f(3)
+";
+
+#[test]
+fn visitors_walk_nested_blocks() {
+    let ast = Analysis::new(NESTED).ast;
+    let mut names = Names(0);
+    for &stmt in &ast.root {
+        names.visit_stmt(&ast, stmt);
+    }
+    // f, x, x in the condition, x in the return, x and the call of f
+    assert_eq!(names.0, 6);
+
+    let mut renamed = ast.clone();
+    visit::visit_ast_mut(&mut Rename, &mut renamed);
+    assert_eq!(
+        ASTPrinter {}.print(&renamed),
+        "(proc f ((y int)) int (block (if (> y 0) (block (return (+ y 1)))) (return 2)))\n\
+         (var y int (call f 3))"
+    );
+
+    let folded = visit::fold_ast(&mut Increment, ast);
+    assert_eq!(
+        ASTPrinter {}.print(&folded),
+        "(proc f ((x int)) int (block (if (> x 1) (block (return (+ x 2)))) (return 3)))\n\
+         (var x int (call f 4))"
+    );
+    // Replaced literals are dropped when the arenas are compacted
+    assert_eq!(folded.exprs.len(), 9);
+}
+
+/// Collects the node ids of names
+struct NameIds(Vec<NodeId>);
+
+impl Visitor<()> for NameIds {
+    fn visit_name(&mut self, name: &Name) {
+        self.0.push(name.id);
+    }
+}
+
+#[test]
+fn node_ids_are_unique_and_kept_by_folding() {
+    let analysis = Analysis::new(&common::program("syntax.lune"));
+    let ast = &analysis.ast;
+    let mut names = NameIds(vec![]);
+    for &stmt in &ast.root {
+        names.visit_stmt(ast, stmt);
+    }
+    let ids: Vec<NodeId> = ast
+        .exprs
+        .iter()
+        .map(|expr| expr.id)
+        .chain(ast.stmts.iter().map(|stmt| stmt.id))
+        .chain(names.0)
+        .collect();
+    let unique: BTreeSet<NodeId> = ids.iter().copied().collect();
+    assert_eq!(unique.len(), ids.len());
+    assert!(ids.iter().all(|id| (id.0 as usize) < ast.node_count()));
+
+    // Binary operators only keep the kind of their token
+    assert!(ast
+        .exprs
+        .iter()
+        .any(|expr| matches!(expr.kind, ExprKind::BinOp(_, BinOpKind::Mul, _))));
+
+    // Folding rewrites values, the statements keep their ids
+    let top = |ast: &AST| -> Vec<NodeId> { ast.root.iter().map(|&stmt| ast[stmt].id).collect() };
+    assert_eq!(top(&analysis.folded), top(ast));
+}
@@ -0,0 +1,58 @@
+//! Every program under `tests/programs` prints the same with every backend
+//! as with `lune run`. Backends differ in how they say why a program
+//! stopped, so what they print to stderr isn't compared.
+
+mod common;
+
+use common::{has_tool, path, run_built, run_llvm, run_vm, Run};
+
+/// Names of the programs under `tests/programs`
+fn programs() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(path("programs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".lune"))
+        .collect();
+    names.sort();
+    names
+}
+
+fn assert_same(name: &str, backend: &str, run: Run, vm: &Run) {
+    assert_eq!(
+        (&run.stdout, run.success),
+        (&vm.stdout, vm.success),
+        "{} with {}: {}",
+        name,
+        backend,
+        run.stderr
+    );
+}
+
+#[test]
+fn asm_and_c() {
+    let asm = has_tool("as") && has_tool("ld");
+    let c = has_tool("cc");
+    for name in programs() {
+        let vm = run_vm(&name);
+        if asm {
+            assert_same(&name, "asm", run_built(&name, &["--backend=asm"]), &vm);
+            let optimized = run_built(&name, &["--backend=asm", "-O2"]);
+            assert_same(&name, "asm -O2", optimized, &vm);
+        }
+        if c {
+            assert_same(&name, "C", run_built(&name, &[]), &vm);
+        }
+    }
+}
+
+#[test]
+fn llvm() {
+    if !has_tool("lli") {
+        return;
+    }
+    for name in programs() {
+        let vm = run_vm(&name);
+        assert_same(&name, "LLVM", run_llvm(&name, &[]), &vm);
+        assert_same(&name, "LLVM -O2", run_llvm(&name, &["-O2"]), &vm);
+    }
+}
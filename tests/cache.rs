@@ -0,0 +1,153 @@
+//! Incremental project builds: which modules `lune build -v` compiles again
+//! after files change between builds
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::{has_tool, temp_dir};
+
+const UTIL: &str = "pub const K: int = 2
+pub struct Pair { a: int, b: int }
+pub proc add(p: Pair): int {
+    return p.a + p.b + K
+}
+";
+
+const MAIN: &str = "import util
+var p: util.Pair = util.Pair { a: 1, b: 2 }
+print(util.add(p))
+";
+
+/// A new package with `main.lune` importing `util.lune`
+fn project(name: &str) -> PathBuf {
+    let dir = temp_dir(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_lune"))
+        .args(["new", "app"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let app = dir.join("app");
+    std::fs::write(app.join("src/util.lune"), UTIL).unwrap();
+    std::fs::write(app.join("src/main.lune"), MAIN).unwrap();
+    app
+}
+
+fn edit(app: &Path, file: &str, from: &str, to: &str) {
+    let path = app.join("src").join(file);
+    let source = std::fs::read_to_string(&path).unwrap();
+    assert!(source.contains(from), "{} has no `{}`", file, from);
+    std::fs::write(&path, source.replace(from, to)).unwrap();
+}
+
+/// Build the project and return the modules that were compiled, checking
+/// the others were reused, and what the program prints
+fn build(app: &Path, args: &[&str]) -> (Vec<String>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_lune"))
+        .arg("build")
+        .arg("-v")
+        .args(args)
+        .current_dir(app)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+
+    let mut compiled = vec![];
+    for line in stderr.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["compiling", module, ..] => compiled.push(module.to_string()),
+            ["reused", _] | ["linked", _] => {}
+            _ => panic!("unexpected output `{}`", line),
+        }
+    }
+
+    let run = Command::new(app.join("target/app")).output().unwrap();
+    assert!(run.status.success());
+    (compiled, String::from_utf8(run.stdout).unwrap())
+}
+
+#[test]
+fn dependency_body() {
+    if !has_tool("cc") {
+        return;
+    }
+    let app = project("cache_body");
+    assert_eq!(
+        build(&app, &[]),
+        (vec!["util".into(), "main".into()], "5\n".into())
+    );
+    assert_eq!(build(&app, &[]), (vec![], "5\n".into()));
+
+    // Only the edited module is compiled again
+    edit(&app, "util.lune", "p.a + p.b", "p.a * p.b");
+    assert_eq!(build(&app, &[]), (vec!["util".into()], "4\n".into()));
+    edit(&app, "main.lune", "a: 1", "a: 3");
+    assert_eq!(build(&app, &[]), (vec!["main".into()], "8\n".into()));
+}
+
+#[test]
+fn public_interface() {
+    if !has_tool("cc") {
+        return;
+    }
+    let app = project("cache_interface");
+    build(&app, &[]);
+
+    // Constants are compiled into the modules using them
+    edit(&app, "util.lune", "K: int = 2", "K: int = 3");
+    assert_eq!(
+        build(&app, &[]),
+        (vec!["util".into(), "main".into()], "6\n".into())
+    );
+
+    // So is the layout of structs
+    edit(
+        &app,
+        "util.lune",
+        "{ a: int, b: int }",
+        "{ b: int, a: int }",
+    );
+    assert_eq!(
+        build(&app, &[]),
+        (vec!["util".into(), "main".into()], "6\n".into())
+    );
+}
+
+#[test]
+fn release_toggle() {
+    if !has_tool("cc") {
+        return;
+    }
+    let app = project("cache_release");
+    let all = vec!["util".to_string(), "main".to_string()];
+    build(&app, &[]);
+    assert_eq!(build(&app, &["--release"]).0, all);
+    assert_eq!(build(&app, &["--release"]).0, Vec::<String>::new());
+    assert_eq!(build(&app, &[]).0, all);
+}
+
+#[test]
+fn deleted_cache_entries() {
+    if !has_tool("cc") {
+        return;
+    }
+    let app = project("cache_deleted");
+    build(&app, &[]);
+    let cache = app.join("target/.cache");
+
+    std::fs::remove_file(cache.join("modules/util.o")).unwrap();
+    assert_eq!(build(&app, &[]), (vec!["util".into()], "5\n".into()));
+
+    std::fs::remove_file(cache.join("entry.json")).unwrap();
+    assert_eq!(build(&app, &[]), (vec!["main".into()], "5\n".into()));
+
+    std::fs::remove_dir_all(&cache).unwrap();
+    assert_eq!(
+        build(&app, &[]),
+        (vec!["util".into(), "main".into()], "5\n".into())
+    );
+}
@@ -0,0 +1,34 @@
+//! Executables built through C behave like `lune run`
+
+mod common;
+
+use common::{has_tool, run_built, run_vm};
+
+fn same_as_vm(name: &str) {
+    let vm = run_vm(name);
+    assert_eq!(run_built(name, &[]), vm, "{}", name);
+    // Division stays checked in release builds
+    assert_eq!(run_built(name, &["--release"]), vm, "{} --release", name);
+}
+
+#[test]
+fn ints_wrap_around() {
+    if has_tool("cc") {
+        same_as_vm("overflow.lune");
+    }
+}
+
+#[test]
+fn division_is_checked() {
+    if has_tool("cc") {
+        same_as_vm("divide_by_zero.lune");
+        same_as_vm("divide_overflow.lune");
+    }
+}
+
+#[test]
+fn names_of_the_c_library() {
+    if has_tool("cc") {
+        same_as_vm("names.lune");
+    }
+}
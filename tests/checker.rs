@@ -0,0 +1,192 @@
+//! Errors of the type checker and of evaluating constants before it
+
+use lune::backend::ast::StmtKind;
+use lune::frontend::analysis::Analysis;
+
+/// The diagnostics of checking `source`, each as its message and the first
+/// line of the source text it points at
+fn errors(source: &str) -> Vec<(String, String)> {
+    Analysis::new(source)
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let text = &source[diagnostic.span.start..diagnostic.span.end];
+            let line = text.lines().next().unwrap_or("");
+            (diagnostic.message, line.to_string())
+        })
+        .collect()
+}
+
+fn error(message: &str, at: &str) -> (String, String) {
+    (message.to_string(), at.to_string())
+}
+
+#[test]
+fn case_missing_variant() {
+    let source = "enum Color { Red, Green, Blue }
+proc name(c: Color): str {
+    return case c {
+        Red => \"red\",
+        Blue => \"blue\",
+    }
+}
+";
+    assert_eq!(
+        errors(source),
+        [error(
+            "non-exhaustive patterns: `Green` not covered",
+            "case c {"
+        )]
+    );
+}
+
+#[test]
+fn case_missing_wildcard() {
+    let ints = "proc sign(n: int): int {
+    case n {
+        0 => return 0,
+        1..10 => return 1,
+    }
+    return -1
+}
+";
+    assert_eq!(
+        errors(ints),
+        [error(
+            "non-exhaustive patterns: `-2147483648..-1` not covered",
+            "case n {"
+        )]
+    );
+
+    let strs = "proc greet(name: str) {
+    case name {
+        \"world\" => print(1),
+    }
+}
+";
+    assert_eq!(
+        errors(strs),
+        [error(
+            "non-exhaustive patterns: `_` not covered",
+            "case name {"
+        )]
+    );
+}
+
+#[test]
+fn case_arm_after_wildcard() {
+    let ints = "proc f(n: int) {
+    case n {
+        _ => print(1),
+        0 => print(0),
+    }
+}
+";
+    assert_eq!(errors(ints), [error("unreachable pattern", "0")]);
+
+    let variants = "enum Color { Red, Green }
+proc f(c: Color) {
+    case c {
+        _ => print(1),
+        Red => print(0),
+    }
+}
+";
+    assert_eq!(errors(variants), [error("unreachable pattern", "Red")]);
+}
+
+#[test]
+fn inferred_array_length() {
+    let source = "var xs: [_]int = [1, 2, 3]
+var grid: [_][_]int = [[1, 2], [3, 4], [5, 6]]
+print(len(xs) + len(grid[0]))
+";
+    let analysis = Analysis::new(source);
+    assert!(
+        analysis.diagnostics.is_empty(),
+        "{:?}",
+        analysis.diagnostics
+    );
+    let types: Vec<String> = analysis
+        .ast
+        .root
+        .iter()
+        .filter_map(|&id| match &analysis.ast[id].kind {
+            StmtKind::VarDeclaration(_, type_, _) => Some(type_.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(types, ["[3]int", "[3][2]int"]);
+}
+
+#[test]
+fn mismatched_array_length() {
+    assert_eq!(
+        errors("var xs: [2]int = [1, 2, 3]\n"),
+        [error(
+            "mismatched types: expected `[2]int`, found `[3]int`",
+            "[1, 2, 3]"
+        )]
+    );
+    assert_eq!(
+        errors("var grid: [_][2]int = [[1, 2], [3]]\n"),
+        [error(
+            "mismatched types: expected `[2]int`, found `[1]int`",
+            "[3]"
+        )]
+    );
+    assert_eq!(
+        errors("proc ones(): [2]int {\n    return [1, 1]\n}\nvar ys: [_]int = ones()\n"),
+        [error(
+            "cannot infer the array length, `[_]` needs an array literal to take it from",
+            "ys"
+        )]
+    );
+}
+
+#[test]
+fn non_constant_initializer() {
+    assert_eq!(
+        errors("var n: int = 3\nconst M: int = n + 1\n"),
+        [error("cannot use variable `n` in a constant", "n")]
+    );
+    let source = "proc f(): int {\n    print(1)\n    return 1\n}\nconst A: int = f()\n";
+    assert_eq!(
+        errors(source),
+        [error(
+            "procs called in constants can only use `var`, `if` and `return`",
+            "print(1)"
+        )]
+    );
+}
+
+#[test]
+fn const_cycle() {
+    let source = "const A: int = B + 1\nconst B: int = A * 2\n";
+    let analysis = Analysis::new(source);
+    let [diagnostic] = &analysis.diagnostics[..] else {
+        panic!("{:?}", analysis.diagnostics);
+    };
+    assert_eq!(diagnostic.message, "constant `A` depends on itself");
+    // The use of `A` in the initializer of `B` closes the cycle
+    assert_eq!(diagnostic.span.start, source.rfind('A').unwrap());
+}
+
+#[test]
+fn non_const_array_length() {
+    assert_eq!(
+        errors("var n: int = 2\nvar xs: [n]int = [1, 2]\n"),
+        [error("cannot find constant `n` in this scope", "xs")]
+    );
+    assert_eq!(
+        errors("const S: str = \"a\"\nvar xs: [S]int = [1]\n"),
+        [error(
+            "array length `S` must be an `int`, found `str`",
+            "xs"
+        )]
+    );
+    assert_eq!(
+        errors("const Z: int = 0\nvar xs: [Z]int = [1]\n"),
+        [error("array length `Z` must be positive, it is 0", "xs")]
+    );
+}
@@ -0,0 +1,138 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Path of a file under `tests/`
+pub fn path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(name)
+}
+
+/// Source of a program under `tests/programs`
+pub fn program(name: &str) -> String {
+    let path = path("programs").join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Compare `actual` with the snapshot under `tests/snapshots`. Run the tests
+/// with `UPDATE_SNAPSHOTS=1` to write the snapshots instead.
+pub fn snapshot(name: &str, actual: &str) {
+    let path = path("snapshots").join(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display(),
+            e
+        )
+    });
+    if expected != actual {
+        panic!(
+            "{} changed, run with UPDATE_SNAPSHOTS=1 to accept it\n--- expected\n{}\n--- actual\n{}",
+            path.display(),
+            expected,
+            actual
+        );
+    }
+}
+
+/// Run the `lune` binary
+pub fn lune(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lune"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Whether an external tool can be run, tests needing a missing one are skipped
+pub fn has_tool(tool: &str) -> bool {
+    let found = Command::new(tool).arg("--version").output().is_ok();
+    if !found {
+        eprintln!("skipped: `{}` isn't installed", tool);
+    }
+    found
+}
+
+/// Directory for the files of one test, empty at the start
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// What a program printed and whether it succeeded. Errors are compared
+/// without the `error: ` that `lune run` prefixes them with.
+#[derive(Debug, PartialEq)]
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+impl Run {
+    pub fn new(output: Output) -> Run {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Run {
+            stdout: String::from_utf8_lossy(&output.stdout).into(),
+            stderr: stderr.replace("error: ", ""),
+            success: output.status.success(),
+        }
+    }
+}
+
+/// Run a program under `tests/programs` with `lune run`
+pub fn run_vm(name: &str) -> Run {
+    let path = path("programs").join(name);
+    Run::new(lune(&["run", path.to_str().unwrap()]))
+}
+
+/// Build a program under `tests/programs` with `lune build` and the extra
+/// `args`, and run the executable
+pub fn run_built(name: &str, args: &[&str]) -> Run {
+    let path = path("programs").join(name);
+    let exe = temp_dir(&format!("{}{}", name, args.concat())).join("program");
+    let mut build = vec!["build", "-o", exe.to_str().unwrap()];
+    build.extend(args);
+    build.push(path.to_str().unwrap());
+    let output = lune(&build);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Run::new(Command::new(&exe).output().unwrap())
+}
+
+/// Run a program under `tests/programs` with `lli`, from the LLVM IR of
+/// `--emit=llvm` with the extra `args`
+pub fn run_llvm(name: &str, args: &[&str]) -> Run {
+    let path = path("programs").join(name);
+    let mut emit = vec!["--emit=llvm"];
+    emit.extend(args);
+    emit.push(path.to_str().unwrap());
+    let output = lune(&emit);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let file = temp_dir(&format!("{}{}.llvm", name, args.concat())).join("program.ll");
+    std::fs::write(&file, &output.stdout).unwrap();
+
+    // Opaque pointers are the default from LLVM 15 on
+    let mut lli = Command::new("lli");
+    let version = Command::new("lli").arg("--version").output().unwrap();
+    if String::from_utf8_lossy(&version.stdout).contains("LLVM version 14.") {
+        lli.arg("-opaque-pointers");
+    }
+    Run::new(lli.arg(&file).output().unwrap())
+}
@@ -0,0 +1,80 @@
+//! `--emit=ir` output reads back as the same IR, and the assembly built from
+//! it behaves like `lune run`
+
+mod common;
+
+use common::{has_tool, lune, path, run_built, run_vm, temp_dir};
+
+const PROGRAMS: [&str; 4] = [
+    "syntax.lune",
+    "aggregates.lune",
+    "overflow.lune",
+    "names.lune",
+];
+
+fn emit(file: &str, level: &str) -> String {
+    let mut args = vec!["--emit=ir", file];
+    if !level.is_empty() {
+        args.insert(1, level);
+    }
+    let output = lune(&args);
+    assert!(
+        output.status.success(),
+        "{} {}: {}",
+        file,
+        level,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let dir = temp_dir("ir_round_trip");
+    for name in PROGRAMS {
+        let source = path("programs").join(name);
+        for level in ["", "-O1", "-O2"] {
+            let ir = emit(source.to_str().unwrap(), level);
+            let file = dir.join(name).with_extension("ir");
+            std::fs::write(&file, &ir).unwrap();
+            assert_eq!(emit(file.to_str().unwrap(), ""), ir, "{} {}", name, level);
+        }
+    }
+}
+
+#[test]
+fn constant_out_of_range() {
+    let file = temp_dir("ir_constant").join("big.ir");
+    let ir = "entry @main\n\nproc @main() {\nbb0:\n    %0 = const int 99999999999\n    ret\n}\n";
+    std::fs::write(&file, ir).unwrap();
+    let output = lune(&["--emit=ir", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("big.ir:5:20: constant doesn't fit in an int"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn asm_handles_the_whole_language() {
+    if !has_tool("as") || !has_tool("ld") {
+        return;
+    }
+    for name in ["syntax.lune", "aggregates.lune"] {
+        let vm = run_vm(name);
+        assert_eq!(run_built(name, &["--backend=asm"]), vm, "{}", name);
+        assert_eq!(
+            run_built(name, &["--backend=asm", "-O2"]),
+            vm,
+            "{} -O2",
+            name
+        );
+    }
+
+    // Bounds checks trap without a message
+    let run = run_built("out_of_bounds.lune", &["--backend=asm"]);
+    assert_eq!(run.stdout, "1\n");
+    assert!(!run.success);
+}
@@ -0,0 +1,93 @@
+//! Tokens of the lexer and its errors, on its own and streamed into the
+//! parser
+
+use lune::errors::LexerError;
+use lune::frontend::lexer::lexer::Lexer;
+use lune::frontend::lexer::token::TokenKind;
+use lune::frontend::parser::Parser;
+
+#[test]
+fn parser_pulls_from_lexer() {
+    let source = "proc main() {\n    print(1 + 2)\n}\n";
+    let mut parser = Parser::new(Lexer::new(source));
+    let ast = parser.parse().unwrap();
+    assert_eq!(ast.root.len(), 1);
+    assert!(parser.lexer_error().is_none());
+}
+
+#[test]
+fn parser_reports_lexer_errors() {
+    let source = "proc main() {\n    print(1 $ 2)\n}\n";
+    let mut parser = Parser::new(Lexer::new(source));
+    let err = parser.parse().unwrap_err();
+    let lexer_err = parser.lexer_error().expect("lexer error");
+    assert_eq!(err.message, lexer_err.message);
+    assert_eq!(lexer_err.line, 1);
+    assert_eq!(lexer_err.position, source.find('$').unwrap());
+}
+
+/// The error lexing `source` stops at
+fn error(source: &str) -> LexerError {
+    Lexer::new(source).scan_all().unwrap_err()
+}
+
+#[test]
+fn digit_separators() {
+    let values: Vec<TokenKind> = Lexer::new("1_000 0xf_f 0b1_0")
+        .map(|token| token.unwrap().kind)
+        .collect();
+    assert_eq!(values[0], TokenKind::IntLit(1000));
+    assert_eq!(values[1], TokenKind::IntLit(255));
+    assert_eq!(values[2], TokenKind::IntLit(2));
+
+    for (source, name) in [
+        ("10_", "decimal"),
+        ("1__0", "decimal"),
+        ("0x_ff", "hexadecimal"),
+        ("0b1_", "binary"),
+    ] {
+        let err = error(source);
+        assert_eq!(
+            err.message,
+            format!("`_` must be between digits in {} literal", name),
+            "{}",
+            source
+        );
+        assert_eq!(err.position, 0);
+    }
+}
+
+#[test]
+fn literal_out_of_range() {
+    let source = "var x: int = 340282366920938463463374607431768211456";
+    let err = error(source);
+    assert_eq!(err.message, "integer literal is too large");
+    assert_eq!(err.position, source.find('3').unwrap());
+}
+
+#[test]
+fn nested_block_comments() {
+    let source = "1 #[ outer #[ inner\n]# still a comment ]# 2\n3";
+    let tokens: Vec<_> = Lexer::new(source).map(|token| token.unwrap()).collect();
+    let kinds: Vec<&TokenKind> = tokens.iter().map(|token| &token.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            &TokenKind::IntLit(1),
+            &TokenKind::IntLit(2),
+            &TokenKind::IntLit(3),
+            &TokenKind::Eof
+        ]
+    );
+    // Newlines inside the comment still count
+    assert_eq!(tokens[1].line, 1);
+    assert_eq!(tokens[2].line, 2);
+}
+
+#[test]
+fn unterminated_nested_block_comment() {
+    let source = "1 #[ outer #[ inner ]# never closed\n";
+    let err = error(source);
+    assert_eq!(err.message, "unterminated block comment");
+    assert_eq!(err.position, 2);
+}
@@ -0,0 +1,58 @@
+//! Snapshots of the LLVM IR of `--emit=llvm`, which `llc` has to accept
+
+mod common;
+
+use std::process::Command;
+
+use common::{has_tool, lune, path, snapshot, temp_dir};
+
+const PROGRAMS: [&str; 3] = ["syntax.lune", "aggregates.lune", "optimize.lune"];
+
+fn emit(name: &str, args: &[&str]) -> String {
+    let file = path("programs").join(name);
+    let mut all = vec!["--emit=llvm"];
+    all.extend(args);
+    all.push(file.to_str().unwrap());
+    let output = lune(&all);
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn golden() {
+    for name in PROGRAMS {
+        let stem = name.trim_end_matches(".lune");
+        snapshot(&format!("llvm/{}.ll", stem), &emit(name, &[]));
+        snapshot(&format!("llvm/{}-O2.ll", stem), &emit(name, &["-O2"]));
+    }
+}
+
+#[test]
+fn llc_accepts_it() {
+    if !has_tool("llc") {
+        return;
+    }
+    let version = Command::new("llc").arg("--version").output().unwrap();
+    let llvm_14 = String::from_utf8_lossy(&version.stdout).contains("LLVM version 14.");
+    let dir = temp_dir("llvm_llc");
+    for name in PROGRAMS {
+        let file = dir.join(name).with_extension("ll");
+        std::fs::write(&file, emit(name, &["-O2"])).unwrap();
+        let mut llc = Command::new("llc");
+        if llvm_14 {
+            llc.arg("-opaque-pointers");
+        }
+        let output = llc.arg("-filetype=null").arg(&file).output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Replays recorded sessions through `lune lsp` and checks its replies
+
+use std::io::Cursor;
+
+use lune::json::Json;
+use lune::lsp::rpc::read_message;
+use lune::lsp::server::Server;
+
+const URI: &str = "untitled:test.lune";
+
+/// Frame each message body like a client would
+fn record(messages: &[String]) -> Vec<u8> {
+    let mut input = vec![];
+    for body in messages {
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+    }
+    input
+}
+
+/// Run a session, returning the exit code and the messages the server sent
+fn replay(messages: &[String]) -> (i32, Vec<Json>) {
+    let mut output = vec![];
+    let code = Server::new(&mut output)
+        .run(Cursor::new(record(messages)))
+        .unwrap();
+
+    let mut output = Cursor::new(output);
+    let mut replies = vec![];
+    while let Some(reply) = read_message(&mut output).unwrap() {
+        replies.push(reply.unwrap());
+    }
+    (code, replies)
+}
+
+fn request(id: i64, method: &str, params: &str) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+        id, method, params
+    )
+}
+
+fn notification(method: &str, params: &str) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#,
+        method, params
+    )
+}
+
+fn open(text: &str) -> String {
+    let params = Json::object(vec![(
+        "textDocument",
+        Json::object(vec![
+            ("uri", URI.into()),
+            ("languageId", "lune".into()),
+            ("version", 1i64.into()),
+            ("text", text.into()),
+        ]),
+    )]);
+    notification("textDocument/didOpen", &params.to_string())
+}
+
+fn at(line: i64, character: i64) -> String {
+    format!(
+        r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#,
+        URI, line, character
+    )
+}
+
+fn exit() -> Vec<String> {
+    vec![
+        request(99, "shutdown", "null"),
+        notification("exit", "null"),
+    ]
+}
+
+/// The reply to the request with `id`
+fn reply(replies: &[Json], id: i64) -> &Json {
+    let found = replies
+        .iter()
+        .find(|reply| reply.get("id").and_then(Json::as_i64) == Some(id));
+    found.unwrap_or_else(|| panic!("no reply to request {}", id))
+}
+
+fn diagnostics(replies: &[Json]) -> Vec<&Json> {
+    let published = replies.iter().filter(|reply| {
+        reply.get("method").and_then(Json::as_str) == Some("textDocument/publishDiagnostics")
+    });
+    published
+        .map(|reply| reply.path(&["params", "diagnostics"]).unwrap())
+        .collect()
+}
+
+#[test]
+fn initialize_and_exit() {
+    let mut session = vec![request(1, "initialize", "{}")];
+    session.extend(exit());
+    let (code, replies) = replay(&session);
+
+    assert_eq!(code, 0);
+    let capabilities = reply(&replies, 1)
+        .path(&["result", "capabilities"])
+        .unwrap();
+    assert_eq!(capabilities.get("hoverProvider"), Some(&Json::Bool(true)));
+    assert_eq!(reply(&replies, 99).get("result"), Some(&Json::Null));
+}
+
+#[test]
+fn exit_without_shutdown_fails() {
+    let (code, _) = replay(&[notification("exit", "null")]);
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn malformed_message_is_a_parse_error() {
+    let mut session = vec!["{bad}".to_string(), request(1, "initialize", "{}")];
+    session.extend(exit());
+    let (code, replies) = replay(&session);
+
+    assert_eq!(code, 0);
+    let error = &replies[0];
+    assert_eq!(error.get("id"), Some(&Json::Null));
+    assert_eq!(
+        error.path(&["error", "code"]).and_then(Json::as_i64),
+        Some(-32700)
+    );
+    // Messages after it are still served
+    assert!(reply(&replies, 1).get("result").is_some());
+}
+
+#[test]
+fn unknown_method() {
+    let mut session = vec![request(1, "textDocument/rename", "{}")];
+    session.extend(exit());
+    let (_, replies) = replay(&session);
+
+    let code = reply(&replies, 1)
+        .path(&["error", "code"])
+        .and_then(Json::as_i64);
+    assert_eq!(code, Some(-32601));
+}
+
+#[test]
+fn diagnostics_on_open_and_change() {
+    let change = Json::object(vec![
+        ("textDocument", Json::object(vec![("uri", URI.into())])),
+        (
+            "contentChanges",
+            vec![Json::object(vec![("text", "var x: int = 1\n".into())])].into(),
+        ),
+    ]);
+    let mut session = vec![
+        open("var x: int = true\n"),
+        notification("textDocument/didChange", &change.to_string()),
+    ];
+    session.extend(exit());
+    let (_, replies) = replay(&session);
+
+    let published = diagnostics(&replies);
+    assert_eq!(published.len(), 2);
+    let errors = published[0].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    let start = errors[0].path(&["range", "start"]).unwrap();
+    assert_eq!(start.get("line").and_then(Json::as_i64), Some(0));
+    assert_eq!(published[1].as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn hover_definition_and_symbols() {
+    let source = "proc double(x: int): int {\n    return x * 2\n}\nvar y: int = double(4)\n";
+    let mut session = vec![
+        open(source),
+        request(1, "textDocument/hover", &at(3, 4)),
+        request(2, "textDocument/definition", &at(3, 14)),
+        request(
+            3,
+            "textDocument/documentSymbol",
+            &format!(r#"{{"textDocument":{{"uri":"{}"}}}}"#, URI),
+        ),
+    ];
+    session.extend(exit());
+    let (_, replies) = replay(&session);
+
+    assert!(diagnostics(&replies)[0].as_array().unwrap().is_empty());
+
+    let hover = reply(&replies, 1).path(&["result", "contents", "value"]);
+    let hover = hover.and_then(Json::as_str).unwrap();
+    assert!(hover.contains("y: int"), "{}", hover);
+
+    let definition = reply(&replies, 2).get("result").unwrap();
+    let start = definition.path(&["range", "start"]).unwrap();
+    assert_eq!(start.get("line").and_then(Json::as_i64), Some(0));
+
+    let symbols = reply(&replies, 3).get("result").and_then(Json::as_array);
+    let names: Vec<&str> = symbols
+        .unwrap()
+        .iter()
+        .filter_map(|symbol| symbol.get("name").and_then(Json::as_str))
+        .collect();
+    assert_eq!(names, ["double", "y"]);
+}
+
+#[test]
+fn semantic_tokens() {
+    let mut session = vec![
+        open("proc f(x: int): int {\n    return x\n}\n"),
+        request(
+            1,
+            "textDocument/semanticTokens/full",
+            &format!(r#"{{"textDocument":{{"uri":"{}"}}}}"#, URI),
+        ),
+    ];
+    session.extend(exit());
+    let (_, replies) = replay(&session);
+
+    let data: Vec<i64> = reply(&replies, 1)
+        .path(&["result", "data"])
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|n| n.as_i64().unwrap())
+        .collect();
+    // Line delta, start delta, length, type and modifiers of each token:
+    // `proc` keyword, `f` function, `x` parameter, two `int` types, then
+    // `return` keyword and `x` parameter on the next line
+    #[rustfmt::skip]
+    let expected = [
+        0, 0, 4, 0, 0,
+        0, 5, 1, 2, 0,
+        0, 2, 1, 4, 0,
+        0, 3, 3, 1, 0,
+        0, 6, 3, 1, 0,
+        1, 4, 6, 0, 0,
+        0, 7, 1, 4, 0,
+    ];
+    assert_eq!(data, expected);
+}
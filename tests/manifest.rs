@@ -0,0 +1,55 @@
+//! Errors in `lune.toml` manifests and in the dependencies they name
+
+mod common;
+
+use std::path::Path;
+
+use lune::manifest::{Manifest, Project, MANIFEST};
+
+/// The location and message of the error parsing `source`
+fn error(source: &str) -> (String, String) {
+    let err = Manifest::parse(source, Path::new("lune.toml")).unwrap_err();
+    (err.location, err.message)
+}
+
+#[test]
+fn missing_package() {
+    let source = "[dependencies]\n";
+    assert_eq!(
+        error(source),
+        ("lune.toml:1:1".into(), "missing the [package] table".into())
+    );
+}
+
+#[test]
+fn unknown_key() {
+    let source = "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nauthor = \"me\"\n";
+    assert_eq!(
+        error(source),
+        (
+            "lune.toml:4:1".into(),
+            "unknown key `author` in [package]".into()
+        )
+    );
+}
+
+#[test]
+fn bad_dependency_path() {
+    let dir = common::temp_dir("manifest_bad_dependency");
+    let manifest = "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n\
+                    [dependencies]\nshapes = { path = \"../missing\" }\n";
+    std::fs::write(dir.join(MANIFEST), manifest).unwrap();
+
+    let err = Project::load(&dir)
+        .err()
+        .expect("the dependency is missing");
+    assert_eq!(err.location, dir.join(MANIFEST).display().to_string());
+    assert_eq!(
+        err.message,
+        format!(
+            "dependency `shapes` has no {} in {}",
+            MANIFEST,
+            dir.join("../missing").display()
+        )
+    );
+}
@@ -0,0 +1,92 @@
+//! Snapshots of the IR before and after each optimization pass
+
+mod common;
+
+use common::{lune, path, run_built, run_vm, snapshot};
+
+use lune::backend::ir::opt::{Pass, PassManager, PASSES};
+use lune::backend::ir::parser::IrParser;
+use lune::backend::ir::Module;
+
+/// `--emit=ir` of a file under `tests/programs` with the extra `args`
+fn emit(name: &str, args: &[&str]) -> String {
+    let file = path("programs").join(name);
+    let mut all = vec!["--emit=ir"];
+    all.extend(args);
+    all.push(file.to_str().unwrap());
+    let output = lune(&all);
+    assert!(
+        output.status.success(),
+        "{} {:?}: {}",
+        name,
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn each_pass() {
+    snapshot("opt/optimize.ir", &emit("optimize.lune", &[]));
+    for pass in PASSES {
+        let after = emit("optimize.lune", &[&format!("--passes={}", pass)]);
+        snapshot(&format!("opt/optimize.{}.ir", pass), &after);
+    }
+}
+
+#[test]
+fn levels() {
+    for level in ["-O1", "-O2"] {
+        let after = emit("optimize.lune", &[level]);
+        snapshot(&format!("opt/optimize{}.ir", level), &after);
+    }
+}
+
+/// Inlining `twice` leaves a loop that constprop cuts off from the entry,
+/// which copyprop used to turn into an add reading its own result
+#[test]
+fn loop_cut_off_by_inlining() {
+    snapshot("opt/inline_loop-O2.ir", &emit("inline_loop.lune", &["-O2"]));
+    snapshot(
+        "opt/unreachable_loop.copyprop.ir",
+        &emit("unreachable_loop.ir", &["--passes=copyprop"]),
+    );
+    if common::has_tool("as") && common::has_tool("ld") {
+        assert_eq!(
+            run_built("inline_loop.lune", &["--backend=asm", "-O2"]),
+            run_vm("inline_loop.lune")
+        );
+    }
+}
+
+/// Forgets the first instruction of every proc
+struct Forget;
+
+impl Pass for Forget {
+    fn name(&self) -> &'static str {
+        "forget"
+    }
+
+    fn run(&mut self, module: &mut Module) -> bool {
+        for proc in &mut module.procs {
+            proc.blocks[0].insts.remove(0);
+        }
+        true
+    }
+}
+
+#[test]
+fn broken_ir_is_an_error() {
+    let ir =
+        "entry @main\n\nproc @main() {\nbb0:\n    %0 = const int 1\n    print int %0\n    ret\n}\n";
+    let mut module = IrParser::new(ir).unwrap().parse().unwrap();
+    let mut manager = PassManager::new();
+    manager.add(Box::new(Forget));
+    let err = manager.run(&mut module).unwrap_err();
+    assert_eq!(err.proc.as_deref(), Some("main"));
+    assert!(
+        err.message.starts_with("pass `forget` produced invalid IR"),
+        "{}",
+        err.message
+    );
+}
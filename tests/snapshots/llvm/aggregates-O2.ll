@@ -0,0 +1,464 @@
+declare i32 @printf(ptr, ...)
+declare i32 @puts(ptr)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @llvm.trap() cold noreturn nounwind
+declare void @llvm.memmove.p0.p0.i64(ptr, ptr, i64, i1)
+
+@lune.int = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@lune.true = private unnamed_addr constant [5 x i8] c"true\00"
+@lune.false = private unnamed_addr constant [6 x i8] c"false\00"
+@lune.error = private unnamed_addr constant [11 x i8] c"error: %s\0A\00"
+@lune.div_zero = private unnamed_addr constant [26 x i8] c"attempt to divide by zero\00"
+@lune.div_overflow = private unnamed_addr constant [32 x i8] c"attempt to divide with overflow\00"
+@lune.bounds = private unnamed_addr constant [20 x i8] c"index out of bounds\00"
+
+define internal void @lune_print_int(i32 %value) {
+  %1 = call i32 (ptr, ...) @printf(ptr @lune.int, i32 %value)
+  ret void
+}
+
+define internal void @lune_print_str(ptr %value) {
+  %1 = call i32 @puts(ptr %value)
+  ret void
+}
+
+define internal void @lune_print_bool(i1 %value) {
+  %1 = select i1 %value, ptr @lune.true, ptr @lune.false
+  %2 = call i32 @puts(ptr %1)
+  ret void
+}
+
+define internal i1 @lune_str_eq(ptr %a, ptr %b) {
+  %1 = call i32 @strcmp(ptr %a, ptr %b)
+  %2 = icmp eq i32 %1, 0
+  ret i1 %2
+}
+
+define internal void @lune_fail(ptr %message) cold noreturn {
+  %1 = call i32 @fflush(ptr null)
+  %2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @lune.error, ptr %message)
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @lune_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %by_zero, label %nonzero
+by_zero:
+  call void @lune_fail(ptr @lune.div_zero)
+  unreachable
+nonzero:
+  %min = icmp eq i32 %a, -2147483648
+  %minus_one = icmp eq i32 %b, -1
+  %overflow = and i1 %min, %minus_one
+  br i1 %overflow, label %overflows, label %ok
+overflows:
+  call void @lune_fail(ptr @lune.div_overflow)
+  unreachable
+ok:
+  %1 = sdiv i32 %a, %b
+  ret i32 %1
+}
+
+@lune_a = internal global [16 x i8] zeroinitializer, align 8
+@lune_b = internal global [16 x i8] zeroinitializer, align 8
+@lune_line = internal global [32 x i8] zeroinitializer, align 8
+@lune_shapes = internal global [120 x i8] zeroinitializer, align 8
+@lune_i = internal global i32 0
+@lune_xs = internal global [32 x i8] zeroinitializer, align 8
+@lune_ys = internal global [16 x i8] zeroinitializer, align 8
+@lune_zs = internal global [16 x i8] zeroinitializer, align 8
+@lune_points = internal global [32 x i8] zeroinitializer, align 8
+
+define internal void @lune_mirror(ptr %r0, ptr %r1) {
+bb0:
+  %r2 = load i32, ptr %r1
+  %r3 = sub i32 0, %r2
+  store i32 %r3, ptr %r1
+  call void @llvm.memmove.p0.p0.i64(ptr %r0, ptr %r1, i64 16, i1 false)
+  ret void
+}
+
+define internal i32 @lune_length(ptr %r0) {
+bb0:
+  %r1 = getelementptr inbounds i8, ptr %r0, i64 16
+  %r2 = load i32, ptr %r1
+  %r3 = load i32, ptr %r0
+  %r4 = sub i32 %r2, %r3
+  %r5 = getelementptr inbounds i8, ptr %r0, i64 16
+  %r6 = getelementptr inbounds i8, ptr %r5, i64 8
+  %r7 = load i32, ptr %r6
+  %r8 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r9 = load i32, ptr %r8
+  %r10 = sub i32 %r7, %r9
+  %r11 = add i32 %r4, %r10
+  ret i32 %r11
+}
+
+define internal i32 @lune_area(ptr %r0) {
+bb0:
+  %r14 = alloca [16 x i8], align 8
+  %r1 = load i32, ptr %r0
+  %r3 = icmp eq i32 %r1, 0
+  br i1 %r3, label %bb6, label %bb1
+bb1:
+  %r5 = icmp eq i32 %r1, 1
+  br i1 %r5, label %bb2, label %bb3
+bb2:
+  %r6 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r7 = load i32, ptr %r6
+  %r9 = mul i32 3, %r7
+  %r10 = mul i32 %r9, %r7
+  br label %bb6
+bb3:
+  %r12 = icmp eq i32 %r1, 2
+  br i1 %r12, label %bb4, label %bb5
+bb4:
+  %r13 = getelementptr inbounds i8, ptr %r0, i64 8
+  call void @llvm.memmove.p0.p0.i64(ptr %r14, ptr %r13, i64 16, i1 false)
+  %r15 = getelementptr inbounds i8, ptr %r0, i64 24
+  %r16 = load i32, ptr %r15
+  %r17 = getelementptr inbounds i8, ptr %r0, i64 32
+  %r18 = load i32, ptr %r17
+  %r19 = mul i32 %r16, %r18
+  %r20 = load i32, ptr %r14
+  %r21 = add i32 %r19, %r20
+  br label %bb6
+bb5:
+  unreachable
+bb6:
+  %r22 = phi i32 [ %r10, %bb2 ], [ %r21, %bb4 ], [ 0, %bb0 ]
+  ret i32 %r22
+}
+
+define internal i32 @lune_sum(ptr %r0) {
+bb0:
+  br label %bb1
+bb1:
+  %r2 = phi i32 [ 0, %bb0 ], [ %r16, %bb4 ]
+  %r3 = phi i32 [ 0, %bb0 ], [ %r14, %bb4 ]
+  %r4 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r5 = load i32, ptr %r4
+  %r6 = icmp slt i32 %r2, %r5
+  br i1 %r6, label %bb2, label %bb5
+bb2:
+  %r7 = load ptr, ptr %r0
+  %r8 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r9 = load i32, ptr %r8
+  %r10 = icmp sge i32 %r2, 0
+  br i1 %r10, label %bb3, label %bb6
+bb3:
+  %r11 = icmp slt i32 %r2, %r9
+  br i1 %r11, label %bb4, label %bb6
+bb4:
+  %r12.i = sext i32 %r2 to i64
+  %r12 = getelementptr inbounds [8 x i8], ptr %r7, i64 %r12.i
+  %r13 = load i32, ptr %r12
+  %r14 = add i32 %r3, %r13
+  %r16 = add i32 %r2, 1
+  br label %bb1
+bb5:
+  ret i32 %r3
+bb6:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+}
+
+define internal void @lune_fill(ptr %r0, i32 %r1) {
+bb0:
+  %r2 = alloca [32 x i8], align 8
+  store i32 %r1, ptr %r2
+  %r3 = getelementptr inbounds i8, ptr %r2, i64 8
+  store i32 %r1, ptr %r3
+  %r4 = getelementptr inbounds i8, ptr %r2, i64 16
+  store i32 %r1, ptr %r4
+  %r5 = getelementptr inbounds i8, ptr %r2, i64 24
+  store i32 %r1, ptr %r5
+  %r7 = add i32 %r1, 1
+  %r8.i = sext i32 1 to i64
+  %r8 = getelementptr inbounds [8 x i8], ptr %r2, i64 %r8.i
+  store i32 %r7, ptr %r8
+  call void @llvm.memmove.p0.p0.i64(ptr %r0, ptr %r2, i64 32, i1 false)
+  ret void
+}
+
+define internal void @lune_main() {
+bb0:
+  %r0 = alloca [16 x i8], align 8
+  %r11 = alloca [16 x i8], align 8
+  %r13 = alloca [16 x i8], align 8
+  %r19 = alloca [32 x i8], align 8
+  %r21 = alloca [16 x i8], align 8
+  %r28 = alloca [32 x i8], align 8
+  %r52 = alloca [120 x i8], align 8
+  %r54 = alloca [40 x i8], align 8
+  %r55 = alloca [40 x i8], align 8
+  %r58 = alloca [40 x i8], align 8
+  %r59 = alloca [16 x i8], align 8
+  %r75 = alloca [40 x i8], align 8
+  %r85 = alloca [40 x i8], align 8
+  %r87 = alloca [32 x i8], align 8
+  %r89 = alloca [32 x i8], align 8
+  %r98 = alloca [16 x i8], align 8
+  %r116 = alloca [16 x i8], align 8
+  %r134 = alloca [16 x i8], align 8
+  %r155 = alloca [16 x i8], align 8
+  %r169 = alloca [32 x i8], align 8
+  store i32 1, ptr %r0
+  %r3 = getelementptr inbounds i8, ptr %r0, i64 8
+  store i32 2, ptr %r3
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_a, ptr %r0, i64 16, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_b, ptr @lune_a, i64 16, i1 false)
+  store i32 10, ptr @lune_b
+  %r10 = load i32, ptr @lune_a
+  call void @lune_print_int(i32 %r10)
+  call void @llvm.memmove.p0.p0.i64(ptr %r13, ptr @lune_b, i64 16, i1 false)
+  %r14 = load i32, ptr %r13
+  %r15 = sub i32 0, %r14
+  store i32 %r15, ptr %r13
+  call void @llvm.memmove.p0.p0.i64(ptr %r11, ptr %r13, i64 16, i1 false)
+  %r16 = load i32, ptr %r11
+  call void @lune_print_int(i32 %r16)
+  %r18 = load i32, ptr @lune_b
+  call void @lune_print_int(i32 %r18)
+  call void @llvm.memmove.p0.p0.i64(ptr %r19, ptr @lune_a, i64 16, i1 false)
+  store i32 4, ptr %r21
+  %r24 = getelementptr inbounds i8, ptr %r21, i64 8
+  store i32 6, ptr %r24
+  %r25 = getelementptr inbounds i8, ptr %r19, i64 16
+  call void @llvm.memmove.p0.p0.i64(ptr %r25, ptr %r21, i64 16, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_line, ptr %r19, i64 32, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr %r28, ptr @lune_line, i64 32, i1 false)
+  %r29 = getelementptr inbounds i8, ptr %r28, i64 16
+  %r30 = load i32, ptr %r29
+  %r31 = load i32, ptr %r28
+  %r32 = sub i32 %r30, %r31
+  %r33 = getelementptr inbounds i8, ptr %r28, i64 16
+  %r34 = getelementptr inbounds i8, ptr %r33, i64 8
+  %r35 = load i32, ptr %r34
+  %r36 = getelementptr inbounds i8, ptr %r28, i64 8
+  %r37 = load i32, ptr %r36
+  %r38 = sub i32 %r35, %r37
+  %r39 = add i32 %r32, %r38
+  call void @lune_print_int(i32 %r39)
+  %r41 = getelementptr inbounds i8, ptr @lune_line, i64 16
+  %r42 = getelementptr inbounds i8, ptr %r41, i64 8
+  %r44 = getelementptr inbounds i8, ptr @lune_line, i64 16
+  %r45 = getelementptr inbounds i8, ptr %r44, i64 8
+  %r46 = load i32, ptr %r45
+  %r47 = add i32 %r46, 10
+  store i32 %r47, ptr %r42
+  %r49 = getelementptr inbounds i8, ptr @lune_line, i64 16
+  %r50 = getelementptr inbounds i8, ptr %r49, i64 8
+  %r51 = load i32, ptr %r50
+  call void @lune_print_int(i32 %r51)
+  store i32 0, ptr %r54
+  call void @llvm.memmove.p0.p0.i64(ptr %r52, ptr %r54, i64 40, i1 false)
+  store i32 1, ptr %r55
+  %r56 = getelementptr inbounds i8, ptr %r55, i64 8
+  store i32 2, ptr %r56
+  %r57 = getelementptr inbounds i8, ptr %r52, i64 40
+  call void @llvm.memmove.p0.p0.i64(ptr %r57, ptr %r55, i64 40, i1 false)
+  store i32 2, ptr %r58
+  store i32 5, ptr %r59
+  %r61 = getelementptr inbounds i8, ptr %r59, i64 8
+  store i32 0, ptr %r61
+  %r62 = getelementptr inbounds i8, ptr %r58, i64 8
+  call void @llvm.memmove.p0.p0.i64(ptr %r62, ptr %r59, i64 16, i1 false)
+  %r63 = getelementptr inbounds i8, ptr %r58, i64 24
+  store i32 2, ptr %r63
+  %r65 = getelementptr inbounds i8, ptr %r58, i64 32
+  store i32 3, ptr %r65
+  %r66 = getelementptr inbounds i8, ptr %r52, i64 80
+  call void @llvm.memmove.p0.p0.i64(ptr %r66, ptr %r58, i64 40, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_shapes, ptr %r52, i64 120, i1 false)
+  store i32 0, ptr @lune_i
+  br label %bb1
+bb1:
+  %r68 = load i32, ptr @lune_i
+  %r69 = icmp slt i32 %r68, 3
+  br i1 %r69, label %bb2, label %bb5
+bb2:
+  %r71 = load i32, ptr @lune_i
+  %r72 = icmp sge i32 %r71, 0
+  br i1 %r72, label %bb3, label %bb22
+bb3:
+  %r73 = icmp slt i32 %r71, 3
+  br i1 %r73, label %bb4, label %bb22
+bb4:
+  %r74.i = sext i32 %r71 to i64
+  %r74 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r74.i
+  call void @llvm.memmove.p0.p0.i64(ptr %r75, ptr %r74, i64 40, i1 false)
+  %r76 = call i32 @lune_area(ptr %r75)
+  call void @lune_print_int(i32 %r76)
+  %r77 = load i32, ptr @lune_i
+  %r78 = add i32 %r77, 1
+  store i32 %r78, ptr @lune_i
+  br label %bb1
+bb5:
+  %r81.i = sext i32 1 to i64
+  %r81 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r81.i
+  %r82.i = sext i32 0 to i64
+  %r82 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r82.i
+  call void @llvm.memmove.p0.p0.i64(ptr %r82, ptr %r81, i64 40, i1 false)
+  %r84.i = sext i32 0 to i64
+  %r84 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r84.i
+  call void @llvm.memmove.p0.p0.i64(ptr %r85, ptr %r84, i64 40, i1 false)
+  %r86 = call i32 @lune_area(ptr %r85)
+  call void @lune_print_int(i32 %r86)
+  store i32 7, ptr %r89
+  %r90 = getelementptr inbounds i8, ptr %r89, i64 8
+  store i32 7, ptr %r90
+  %r91 = getelementptr inbounds i8, ptr %r89, i64 16
+  store i32 7, ptr %r91
+  %r92 = getelementptr inbounds i8, ptr %r89, i64 24
+  store i32 7, ptr %r92
+  %r94.i = sext i32 1 to i64
+  %r94 = getelementptr inbounds [8 x i8], ptr %r89, i64 %r94.i
+  store i32 8, ptr %r94
+  call void @llvm.memmove.p0.p0.i64(ptr %r87, ptr %r89, i64 32, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_xs, ptr %r87, i64 32, i1 false)
+  %r97.i = sext i32 0 to i64
+  %r97 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r97.i
+  %r99 = getelementptr inbounds i8, ptr %r98, i64 8
+  store ptr %r97, ptr %r98
+  store i32 4, ptr %r99
+  br label %bb6
+bb6:
+  %r100 = phi i32 [ 0, %bb5 ], [ %r113, %bb9 ]
+  %r101 = phi i32 [ 0, %bb5 ], [ %r112, %bb9 ]
+  %r102 = getelementptr inbounds i8, ptr %r98, i64 8
+  %r103 = load i32, ptr %r102
+  %r104 = icmp slt i32 %r100, %r103
+  br i1 %r104, label %bb7, label %bb11
+bb7:
+  %r105 = load ptr, ptr %r98
+  %r106 = getelementptr inbounds i8, ptr %r98, i64 8
+  %r107 = load i32, ptr %r106
+  %r108 = icmp sge i32 %r100, 0
+  br i1 %r108, label %bb8, label %bb10
+bb8:
+  %r109 = icmp slt i32 %r100, %r107
+  br i1 %r109, label %bb9, label %bb10
+bb9:
+  %r110.i = sext i32 %r100 to i64
+  %r110 = getelementptr inbounds [8 x i8], ptr %r105, i64 %r110.i
+  %r111 = load i32, ptr %r110
+  %r112 = add i32 %r101, %r111
+  %r113 = add i32 %r100, 1
+  br label %bb6
+bb10:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+bb11:
+  call void @lune_print_int(i32 %r101)
+  %r115.i = sext i32 1 to i64
+  %r115 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r115.i
+  %r117 = getelementptr inbounds i8, ptr %r116, i64 8
+  store ptr %r115, ptr %r116
+  store i32 2, ptr %r117
+  br label %bb12
+bb12:
+  %r118 = phi i32 [ 0, %bb11 ], [ %r131, %bb15 ]
+  %r119 = phi i32 [ 0, %bb11 ], [ %r130, %bb15 ]
+  %r120 = getelementptr inbounds i8, ptr %r116, i64 8
+  %r121 = load i32, ptr %r120
+  %r122 = icmp slt i32 %r118, %r121
+  br i1 %r122, label %bb13, label %bb17
+bb13:
+  %r123 = load ptr, ptr %r116
+  %r124 = getelementptr inbounds i8, ptr %r116, i64 8
+  %r125 = load i32, ptr %r124
+  %r126 = icmp sge i32 %r118, 0
+  br i1 %r126, label %bb14, label %bb16
+bb14:
+  %r127 = icmp slt i32 %r118, %r125
+  br i1 %r127, label %bb15, label %bb16
+bb15:
+  %r128.i = sext i32 %r118 to i64
+  %r128 = getelementptr inbounds [8 x i8], ptr %r123, i64 %r128.i
+  %r129 = load i32, ptr %r128
+  %r130 = add i32 %r119, %r129
+  %r131 = add i32 %r118, 1
+  br label %bb12
+bb16:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+bb17:
+  call void @lune_print_int(i32 %r119)
+  %r133.i = sext i32 2 to i64
+  %r133 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r133.i
+  %r135 = getelementptr inbounds i8, ptr %r134, i64 8
+  store ptr %r133, ptr %r134
+  store i32 2, ptr %r135
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_ys, ptr %r134, i64 16, i1 false)
+  %r138 = load ptr, ptr @lune_ys
+  %r139 = getelementptr inbounds i8, ptr @lune_ys, i64 8
+  %r140 = load i32, ptr %r139
+  %r142 = icmp slt i32 0, %r140
+  br i1 %r142, label %bb18, label %bb22
+bb18:
+  %r143.i = sext i32 0 to i64
+  %r143 = getelementptr inbounds [8 x i8], ptr %r138, i64 %r143.i
+  store i32 100, ptr %r143
+  %r145.i = sext i32 2 to i64
+  %r145 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r145.i
+  %r146 = load i32, ptr %r145
+  call void @lune_print_int(i32 %r146)
+  %r148 = load ptr, ptr @lune_ys
+  %r149 = getelementptr inbounds i8, ptr @lune_ys, i64 8
+  %r150 = load i32, ptr %r149
+  %r151 = icmp sle i32 1, %r150
+  br i1 %r151, label %bb19, label %bb22
+bb19:
+  %r152 = icmp sle i32 %r150, %r150
+  br i1 %r152, label %bb20, label %bb22
+bb20:
+  %r153.i = sext i32 1 to i64
+  %r153 = getelementptr inbounds [8 x i8], ptr %r148, i64 %r153.i
+  %r154 = sub i32 %r150, 1
+  %r156 = getelementptr inbounds i8, ptr %r155, i64 8
+  store ptr %r153, ptr %r155
+  store i32 %r154, ptr %r156
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_zs, ptr %r155, i64 16, i1 false)
+  %r159 = getelementptr inbounds i8, ptr @lune_zs, i64 8
+  %r160 = load i32, ptr %r159
+  %r162 = load ptr, ptr @lune_zs
+  %r163 = getelementptr inbounds i8, ptr @lune_zs, i64 8
+  %r164 = load i32, ptr %r163
+  %r165 = icmp slt i32 0, %r164
+  br i1 %r165, label %bb21, label %bb22
+bb21:
+  %r166.i = sext i32 0 to i64
+  %r166 = getelementptr inbounds [8 x i8], ptr %r162, i64 %r166.i
+  %r167 = load i32, ptr %r166
+  %r168 = add i32 %r160, %r167
+  call void @lune_print_int(i32 %r168)
+  call void @llvm.memmove.p0.p0.i64(ptr %r169, ptr @lune_a, i64 16, i1 false)
+  %r172 = getelementptr inbounds i8, ptr %r169, i64 16
+  call void @llvm.memmove.p0.p0.i64(ptr %r172, ptr @lune_b, i64 16, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_points, ptr %r169, i64 32, i1 false)
+  %r175.i = sext i32 1 to i64
+  %r175 = getelementptr inbounds [16 x i8], ptr @lune_points, i64 %r175.i
+  %r176 = getelementptr inbounds i8, ptr %r175, i64 8
+  store i32 42, ptr %r176
+  %r179.i = sext i32 1 to i64
+  %r179 = getelementptr inbounds [16 x i8], ptr @lune_points, i64 %r179.i
+  %r180 = getelementptr inbounds i8, ptr %r179, i64 8
+  %r181 = load i32, ptr %r180
+  call void @lune_print_int(i32 %r181)
+  %r183 = getelementptr inbounds i8, ptr @lune_b, i64 8
+  %r184 = load i32, ptr %r183
+  call void @lune_print_int(i32 %r184)
+  ret void
+bb22:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+}
+
+define i32 @main() {
+  call void @lune_main()
+  ret i32 0
+}
@@ -0,0 +1,474 @@
+declare i32 @printf(ptr, ...)
+declare i32 @puts(ptr)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @llvm.trap() cold noreturn nounwind
+declare void @llvm.memmove.p0.p0.i64(ptr, ptr, i64, i1)
+
+@lune.int = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@lune.true = private unnamed_addr constant [5 x i8] c"true\00"
+@lune.false = private unnamed_addr constant [6 x i8] c"false\00"
+@lune.error = private unnamed_addr constant [11 x i8] c"error: %s\0A\00"
+@lune.div_zero = private unnamed_addr constant [26 x i8] c"attempt to divide by zero\00"
+@lune.div_overflow = private unnamed_addr constant [32 x i8] c"attempt to divide with overflow\00"
+@lune.bounds = private unnamed_addr constant [20 x i8] c"index out of bounds\00"
+
+define internal void @lune_print_int(i32 %value) {
+  %1 = call i32 (ptr, ...) @printf(ptr @lune.int, i32 %value)
+  ret void
+}
+
+define internal void @lune_print_str(ptr %value) {
+  %1 = call i32 @puts(ptr %value)
+  ret void
+}
+
+define internal void @lune_print_bool(i1 %value) {
+  %1 = select i1 %value, ptr @lune.true, ptr @lune.false
+  %2 = call i32 @puts(ptr %1)
+  ret void
+}
+
+define internal i1 @lune_str_eq(ptr %a, ptr %b) {
+  %1 = call i32 @strcmp(ptr %a, ptr %b)
+  %2 = icmp eq i32 %1, 0
+  ret i1 %2
+}
+
+define internal void @lune_fail(ptr %message) cold noreturn {
+  %1 = call i32 @fflush(ptr null)
+  %2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @lune.error, ptr %message)
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @lune_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %by_zero, label %nonzero
+by_zero:
+  call void @lune_fail(ptr @lune.div_zero)
+  unreachable
+nonzero:
+  %min = icmp eq i32 %a, -2147483648
+  %minus_one = icmp eq i32 %b, -1
+  %overflow = and i1 %min, %minus_one
+  br i1 %overflow, label %overflows, label %ok
+overflows:
+  call void @lune_fail(ptr @lune.div_overflow)
+  unreachable
+ok:
+  %1 = sdiv i32 %a, %b
+  ret i32 %1
+}
+
+@lune_a = internal global [16 x i8] zeroinitializer, align 8
+@lune_b = internal global [16 x i8] zeroinitializer, align 8
+@lune_line = internal global [32 x i8] zeroinitializer, align 8
+@lune_shapes = internal global [120 x i8] zeroinitializer, align 8
+@lune_i = internal global i32 0
+@lune_xs = internal global [32 x i8] zeroinitializer, align 8
+@lune_ys = internal global [16 x i8] zeroinitializer, align 8
+@lune_zs = internal global [16 x i8] zeroinitializer, align 8
+@lune_points = internal global [32 x i8] zeroinitializer, align 8
+
+define internal void @lune_mirror(ptr %r0, ptr %r1) {
+bb0:
+  %r2 = load i32, ptr %r1
+  %r3 = sub i32 0, %r2
+  store i32 %r3, ptr %r1
+  call void @llvm.memmove.p0.p0.i64(ptr %r0, ptr %r1, i64 16, i1 false)
+  ret void
+}
+
+define internal i32 @lune_length(ptr %r0) {
+bb0:
+  %r1 = getelementptr inbounds i8, ptr %r0, i64 16
+  %r2 = load i32, ptr %r1
+  %r3 = load i32, ptr %r0
+  %r4 = sub i32 %r2, %r3
+  %r5 = getelementptr inbounds i8, ptr %r0, i64 16
+  %r6 = getelementptr inbounds i8, ptr %r5, i64 8
+  %r7 = load i32, ptr %r6
+  %r8 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r9 = load i32, ptr %r8
+  %r10 = sub i32 %r7, %r9
+  %r11 = add i32 %r4, %r10
+  ret i32 %r11
+}
+
+define internal i32 @lune_area(ptr %r0) {
+bb0:
+  %r15 = alloca [16 x i8], align 8
+  %r1 = load i32, ptr %r0
+  %r3 = icmp eq i32 %r1, 0
+  br i1 %r3, label %bb1, label %bb2
+bb1:
+  br label %bb7
+bb2:
+  %r6 = icmp eq i32 %r1, 1
+  br i1 %r6, label %bb3, label %bb4
+bb3:
+  %r7 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r8 = load i32, ptr %r7
+  %r10 = mul i32 3, %r8
+  %r11 = mul i32 %r10, %r8
+  br label %bb7
+bb4:
+  %r13 = icmp eq i32 %r1, 2
+  br i1 %r13, label %bb5, label %bb6
+bb5:
+  %r14 = getelementptr inbounds i8, ptr %r0, i64 8
+  call void @llvm.memmove.p0.p0.i64(ptr %r15, ptr %r14, i64 16, i1 false)
+  %r16 = getelementptr inbounds i8, ptr %r0, i64 24
+  %r17 = load i32, ptr %r16
+  %r18 = getelementptr inbounds i8, ptr %r0, i64 32
+  %r19 = load i32, ptr %r18
+  %r20 = mul i32 %r17, %r19
+  %r21 = load i32, ptr %r15
+  %r22 = add i32 %r20, %r21
+  br label %bb7
+bb6:
+  unreachable
+bb7:
+  %r23 = phi i32 [ 0, %bb1 ], [ %r11, %bb3 ], [ %r22, %bb5 ]
+  ret i32 %r23
+}
+
+define internal i32 @lune_sum(ptr %r0) {
+bb0:
+  br label %bb1
+bb1:
+  %r3 = phi i32 [ 0, %bb0 ], [ %r19, %bb4 ]
+  %r4 = phi i32 [ 0, %bb0 ], [ %r17, %bb4 ]
+  %r5 = load ptr, ptr %r0
+  %r6 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r7 = load i32, ptr %r6
+  %r8 = icmp slt i32 %r3, %r7
+  br i1 %r8, label %bb2, label %bb5
+bb2:
+  %r9 = load ptr, ptr %r0
+  %r10 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r11 = load i32, ptr %r10
+  %r13 = icmp sge i32 %r3, 0
+  br i1 %r13, label %bb3, label %bb6
+bb3:
+  %r14 = icmp slt i32 %r3, %r11
+  br i1 %r14, label %bb4, label %bb6
+bb4:
+  %r15.i = sext i32 %r3 to i64
+  %r15 = getelementptr inbounds [8 x i8], ptr %r9, i64 %r15.i
+  %r16 = load i32, ptr %r15
+  %r17 = add i32 %r4, %r16
+  %r19 = add i32 %r3, 1
+  br label %bb1
+bb5:
+  ret i32 %r4
+bb6:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+}
+
+define internal void @lune_fill(ptr %r0, i32 %r1) {
+bb0:
+  %r2 = alloca [32 x i8], align 8
+  store i32 %r1, ptr %r2
+  %r3 = getelementptr inbounds i8, ptr %r2, i64 8
+  store i32 %r1, ptr %r3
+  %r4 = getelementptr inbounds i8, ptr %r2, i64 16
+  store i32 %r1, ptr %r4
+  %r5 = getelementptr inbounds i8, ptr %r2, i64 24
+  store i32 %r1, ptr %r5
+  %r9 = add i32 %r1, 1
+  %r11 = icmp sge i32 1, 0
+  br i1 %r11, label %bb1, label %bb3
+bb1:
+  %r12 = icmp slt i32 1, 4
+  br i1 %r12, label %bb2, label %bb3
+bb2:
+  %r13.i = sext i32 1 to i64
+  %r13 = getelementptr inbounds [8 x i8], ptr %r2, i64 %r13.i
+  store i32 %r9, ptr %r13
+  call void @llvm.memmove.p0.p0.i64(ptr %r0, ptr %r2, i64 32, i1 false)
+  ret void
+bb3:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+}
+
+define internal void @lune_main() {
+bb0:
+  %r0 = alloca [16 x i8], align 8
+  %r11 = alloca [16 x i8], align 8
+  %r13 = alloca [16 x i8], align 8
+  %r17 = alloca [32 x i8], align 8
+  %r19 = alloca [16 x i8], align 8
+  %r26 = alloca [32 x i8], align 8
+  %r41 = alloca [120 x i8], align 8
+  %r43 = alloca [40 x i8], align 8
+  %r45 = alloca [40 x i8], align 8
+  %r50 = alloca [40 x i8], align 8
+  %r51 = alloca [16 x i8], align 8
+  %r73 = alloca [40 x i8], align 8
+  %r99 = alloca [40 x i8], align 8
+  %r101 = alloca [32 x i8], align 8
+  %r112 = alloca [16 x i8], align 8
+  %r125 = alloca [16 x i8], align 8
+  %r137 = alloca [16 x i8], align 8
+  %r169 = alloca [16 x i8], align 8
+  %r187 = alloca [32 x i8], align 8
+  store i32 1, ptr %r0
+  %r3 = getelementptr inbounds i8, ptr %r0, i64 8
+  store i32 2, ptr %r3
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_a, ptr %r0, i64 16, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_b, ptr @lune_a, i64 16, i1 false)
+  store i32 10, ptr @lune_b
+  %r10 = load i32, ptr @lune_a
+  call void @lune_print_int(i32 %r10)
+  call void @llvm.memmove.p0.p0.i64(ptr %r13, ptr @lune_b, i64 16, i1 false)
+  call void @lune_mirror(ptr %r11, ptr %r13)
+  %r14 = load i32, ptr %r11
+  call void @lune_print_int(i32 %r14)
+  %r16 = load i32, ptr @lune_b
+  call void @lune_print_int(i32 %r16)
+  call void @llvm.memmove.p0.p0.i64(ptr %r17, ptr @lune_a, i64 16, i1 false)
+  store i32 4, ptr %r19
+  %r22 = getelementptr inbounds i8, ptr %r19, i64 8
+  store i32 6, ptr %r22
+  %r23 = getelementptr inbounds i8, ptr %r17, i64 16
+  call void @llvm.memmove.p0.p0.i64(ptr %r23, ptr %r19, i64 16, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_line, ptr %r17, i64 32, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr %r26, ptr @lune_line, i64 32, i1 false)
+  %r27 = call i32 @lune_length(ptr %r26)
+  call void @lune_print_int(i32 %r27)
+  %r29 = getelementptr inbounds i8, ptr @lune_line, i64 16
+  %r30 = getelementptr inbounds i8, ptr %r29, i64 8
+  %r32 = getelementptr inbounds i8, ptr @lune_line, i64 16
+  %r33 = getelementptr inbounds i8, ptr %r32, i64 8
+  %r34 = load i32, ptr %r33
+  %r36 = add i32 %r34, 10
+  store i32 %r36, ptr %r30
+  %r38 = getelementptr inbounds i8, ptr @lune_line, i64 16
+  %r39 = getelementptr inbounds i8, ptr %r38, i64 8
+  %r40 = load i32, ptr %r39
+  call void @lune_print_int(i32 %r40)
+  store i32 0, ptr %r43
+  call void @llvm.memmove.p0.p0.i64(ptr %r41, ptr %r43, i64 40, i1 false)
+  store i32 1, ptr %r45
+  %r47 = getelementptr inbounds i8, ptr %r45, i64 8
+  store i32 2, ptr %r47
+  %r48 = getelementptr inbounds i8, ptr %r41, i64 40
+  call void @llvm.memmove.p0.p0.i64(ptr %r48, ptr %r45, i64 40, i1 false)
+  store i32 2, ptr %r50
+  store i32 5, ptr %r51
+  %r54 = getelementptr inbounds i8, ptr %r51, i64 8
+  store i32 0, ptr %r54
+  %r55 = getelementptr inbounds i8, ptr %r50, i64 8
+  call void @llvm.memmove.p0.p0.i64(ptr %r55, ptr %r51, i64 16, i1 false)
+  %r57 = getelementptr inbounds i8, ptr %r50, i64 24
+  store i32 2, ptr %r57
+  %r59 = getelementptr inbounds i8, ptr %r50, i64 32
+  store i32 3, ptr %r59
+  %r60 = getelementptr inbounds i8, ptr %r41, i64 80
+  call void @llvm.memmove.p0.p0.i64(ptr %r60, ptr %r50, i64 40, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_shapes, ptr %r41, i64 120, i1 false)
+  store i32 0, ptr @lune_i
+  br label %bb1
+bb1:
+  %r63 = load i32, ptr @lune_i
+  %r65 = icmp slt i32 %r63, 3
+  br i1 %r65, label %bb2, label %bb5
+bb2:
+  %r68 = load i32, ptr @lune_i
+  %r70 = icmp sge i32 %r68, 0
+  br i1 %r70, label %bb3, label %bb34
+bb3:
+  %r71 = icmp slt i32 %r68, 3
+  br i1 %r71, label %bb4, label %bb34
+bb4:
+  %r72.i = sext i32 %r68 to i64
+  %r72 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r72.i
+  call void @llvm.memmove.p0.p0.i64(ptr %r73, ptr %r72, i64 40, i1 false)
+  %r74 = call i32 @lune_area(ptr %r73)
+  call void @lune_print_int(i32 %r74)
+  %r75 = load i32, ptr @lune_i
+  %r77 = add i32 %r75, 1
+  store i32 %r77, ptr @lune_i
+  br label %bb1
+bb5:
+  %r85 = icmp sge i32 1, 0
+  br i1 %r85, label %bb6, label %bb34
+bb6:
+  %r86 = icmp slt i32 1, 3
+  br i1 %r86, label %bb7, label %bb34
+bb7:
+  %r87.i = sext i32 1 to i64
+  %r87 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r87.i
+  %r89 = icmp sge i32 0, 0
+  br i1 %r89, label %bb8, label %bb34
+bb8:
+  %r90 = icmp slt i32 0, 3
+  br i1 %r90, label %bb9, label %bb34
+bb9:
+  %r91.i = sext i32 0 to i64
+  %r91 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r91.i
+  call void @llvm.memmove.p0.p0.i64(ptr %r91, ptr %r87, i64 40, i1 false)
+  %r96 = icmp sge i32 0, 0
+  br i1 %r96, label %bb10, label %bb34
+bb10:
+  %r97 = icmp slt i32 0, 3
+  br i1 %r97, label %bb11, label %bb34
+bb11:
+  %r98.i = sext i32 0 to i64
+  %r98 = getelementptr inbounds [40 x i8], ptr @lune_shapes, i64 %r98.i
+  call void @llvm.memmove.p0.p0.i64(ptr %r99, ptr %r98, i64 40, i1 false)
+  %r100 = call i32 @lune_area(ptr %r99)
+  call void @lune_print_int(i32 %r100)
+  call void @lune_fill(ptr %r101, i32 7)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_xs, ptr %r101, i64 32, i1 false)
+  %r107 = icmp sle i32 0, 0
+  br i1 %r107, label %bb12, label %bb34
+bb12:
+  %r108 = icmp sle i32 0, 4
+  br i1 %r108, label %bb13, label %bb34
+bb13:
+  %r109 = icmp sle i32 4, 4
+  br i1 %r109, label %bb14, label %bb34
+bb14:
+  %r110.i = sext i32 0 to i64
+  %r110 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r110.i
+  %r111 = sub i32 4, 0
+  %r113 = getelementptr inbounds i8, ptr %r112, i64 8
+  store ptr %r110, ptr %r112
+  store i32 %r111, ptr %r113
+  %r114 = call i32 @lune_sum(ptr %r112)
+  call void @lune_print_int(i32 %r114)
+  %r120 = icmp sle i32 0, 1
+  br i1 %r120, label %bb15, label %bb34
+bb15:
+  %r121 = icmp sle i32 1, 3
+  br i1 %r121, label %bb16, label %bb34
+bb16:
+  %r122 = icmp sle i32 3, 4
+  br i1 %r122, label %bb17, label %bb34
+bb17:
+  %r123.i = sext i32 1 to i64
+  %r123 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r123.i
+  %r124 = sub i32 3, 1
+  %r126 = getelementptr inbounds i8, ptr %r125, i64 8
+  store ptr %r123, ptr %r125
+  store i32 %r124, ptr %r126
+  %r127 = call i32 @lune_sum(ptr %r125)
+  call void @lune_print_int(i32 %r127)
+  %r132 = icmp sle i32 0, 2
+  br i1 %r132, label %bb18, label %bb34
+bb18:
+  %r133 = icmp sle i32 2, 4
+  br i1 %r133, label %bb19, label %bb34
+bb19:
+  %r134 = icmp sle i32 4, 4
+  br i1 %r134, label %bb20, label %bb34
+bb20:
+  %r135.i = sext i32 2 to i64
+  %r135 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r135.i
+  %r136 = sub i32 4, 2
+  %r138 = getelementptr inbounds i8, ptr %r137, i64 8
+  store ptr %r135, ptr %r137
+  store i32 %r136, ptr %r138
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_ys, ptr %r137, i64 16, i1 false)
+  %r141 = load ptr, ptr @lune_ys
+  %r142 = getelementptr inbounds i8, ptr @lune_ys, i64 8
+  %r143 = load i32, ptr %r142
+  %r147 = icmp sge i32 0, 0
+  br i1 %r147, label %bb21, label %bb34
+bb21:
+  %r148 = icmp slt i32 0, %r143
+  br i1 %r148, label %bb22, label %bb34
+bb22:
+  %r149.i = sext i32 0 to i64
+  %r149 = getelementptr inbounds [8 x i8], ptr %r141, i64 %r149.i
+  store i32 100, ptr %r149
+  %r154 = icmp sge i32 2, 0
+  br i1 %r154, label %bb23, label %bb34
+bb23:
+  %r155 = icmp slt i32 2, 4
+  br i1 %r155, label %bb24, label %bb34
+bb24:
+  %r156.i = sext i32 2 to i64
+  %r156 = getelementptr inbounds [8 x i8], ptr @lune_xs, i64 %r156.i
+  %r157 = load i32, ptr %r156
+  call void @lune_print_int(i32 %r157)
+  %r159 = load ptr, ptr @lune_ys
+  %r160 = getelementptr inbounds i8, ptr @lune_ys, i64 8
+  %r161 = load i32, ptr %r160
+  %r164 = icmp sle i32 0, 1
+  br i1 %r164, label %bb25, label %bb34
+bb25:
+  %r165 = icmp sle i32 1, %r161
+  br i1 %r165, label %bb26, label %bb34
+bb26:
+  %r166 = icmp sle i32 %r161, %r161
+  br i1 %r166, label %bb27, label %bb34
+bb27:
+  %r167.i = sext i32 1 to i64
+  %r167 = getelementptr inbounds [8 x i8], ptr %r159, i64 %r167.i
+  %r168 = sub i32 %r161, 1
+  %r170 = getelementptr inbounds i8, ptr %r169, i64 8
+  store ptr %r167, ptr %r169
+  store i32 %r168, ptr %r170
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_zs, ptr %r169, i64 16, i1 false)
+  %r173 = load ptr, ptr @lune_zs
+  %r174 = getelementptr inbounds i8, ptr @lune_zs, i64 8
+  %r175 = load i32, ptr %r174
+  %r177 = load ptr, ptr @lune_zs
+  %r178 = getelementptr inbounds i8, ptr @lune_zs, i64 8
+  %r179 = load i32, ptr %r178
+  %r182 = icmp sge i32 0, 0
+  br i1 %r182, label %bb28, label %bb34
+bb28:
+  %r183 = icmp slt i32 0, %r179
+  br i1 %r183, label %bb29, label %bb34
+bb29:
+  %r184.i = sext i32 0 to i64
+  %r184 = getelementptr inbounds [8 x i8], ptr %r177, i64 %r184.i
+  %r185 = load i32, ptr %r184
+  %r186 = add i32 %r175, %r185
+  call void @lune_print_int(i32 %r186)
+  call void @llvm.memmove.p0.p0.i64(ptr %r187, ptr @lune_a, i64 16, i1 false)
+  %r190 = getelementptr inbounds i8, ptr %r187, i64 16
+  call void @llvm.memmove.p0.p0.i64(ptr %r190, ptr @lune_b, i64 16, i1 false)
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_points, ptr %r187, i64 32, i1 false)
+  %r196 = icmp sge i32 1, 0
+  br i1 %r196, label %bb30, label %bb34
+bb30:
+  %r197 = icmp slt i32 1, 2
+  br i1 %r197, label %bb31, label %bb34
+bb31:
+  %r198.i = sext i32 1 to i64
+  %r198 = getelementptr inbounds [16 x i8], ptr @lune_points, i64 %r198.i
+  %r199 = getelementptr inbounds i8, ptr %r198, i64 8
+  store i32 42, ptr %r199
+  %r205 = icmp sge i32 1, 0
+  br i1 %r205, label %bb32, label %bb34
+bb32:
+  %r206 = icmp slt i32 1, 2
+  br i1 %r206, label %bb33, label %bb34
+bb33:
+  %r207.i = sext i32 1 to i64
+  %r207 = getelementptr inbounds [16 x i8], ptr @lune_points, i64 %r207.i
+  %r208 = getelementptr inbounds i8, ptr %r207, i64 8
+  %r209 = load i32, ptr %r208
+  call void @lune_print_int(i32 %r209)
+  %r211 = getelementptr inbounds i8, ptr @lune_b, i64 8
+  %r212 = load i32, ptr %r211
+  call void @lune_print_int(i32 %r212)
+  ret void
+bb34:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+}
+
+define i32 @main() {
+  call void @lune_main()
+  ret i32 0
+}
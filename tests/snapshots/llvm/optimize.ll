@@ -0,0 +1,122 @@
+declare i32 @printf(ptr, ...)
+declare i32 @puts(ptr)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @llvm.trap() cold noreturn nounwind
+declare void @llvm.memmove.p0.p0.i64(ptr, ptr, i64, i1)
+
+@lune.int = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@lune.true = private unnamed_addr constant [5 x i8] c"true\00"
+@lune.false = private unnamed_addr constant [6 x i8] c"false\00"
+@lune.error = private unnamed_addr constant [11 x i8] c"error: %s\0A\00"
+@lune.div_zero = private unnamed_addr constant [26 x i8] c"attempt to divide by zero\00"
+@lune.div_overflow = private unnamed_addr constant [32 x i8] c"attempt to divide with overflow\00"
+@lune.bounds = private unnamed_addr constant [20 x i8] c"index out of bounds\00"
+
+define internal void @lune_print_int(i32 %value) {
+  %1 = call i32 (ptr, ...) @printf(ptr @lune.int, i32 %value)
+  ret void
+}
+
+define internal void @lune_print_str(ptr %value) {
+  %1 = call i32 @puts(ptr %value)
+  ret void
+}
+
+define internal void @lune_print_bool(i1 %value) {
+  %1 = select i1 %value, ptr @lune.true, ptr @lune.false
+  %2 = call i32 @puts(ptr %1)
+  ret void
+}
+
+define internal i1 @lune_str_eq(ptr %a, ptr %b) {
+  %1 = call i32 @strcmp(ptr %a, ptr %b)
+  %2 = icmp eq i32 %1, 0
+  ret i1 %2
+}
+
+define internal void @lune_fail(ptr %message) cold noreturn {
+  %1 = call i32 @fflush(ptr null)
+  %2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @lune.error, ptr %message)
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @lune_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %by_zero, label %nonzero
+by_zero:
+  call void @lune_fail(ptr @lune.div_zero)
+  unreachable
+nonzero:
+  %min = icmp eq i32 %a, -2147483648
+  %minus_one = icmp eq i32 %b, -1
+  %overflow = and i1 %min, %minus_one
+  br i1 %overflow, label %overflows, label %ok
+overflows:
+  call void @lune_fail(ptr @lune.div_overflow)
+  unreachable
+ok:
+  %1 = sdiv i32 %a, %b
+  ret i32 %1
+}
+
+@lune_unused = internal global i32 0
+
+@.str.0 = private unnamed_addr constant [3 x i8] c"on\00"
+@.str.1 = private unnamed_addr constant [4 x i8] c"off\00"
+
+define internal i32 @lune_twice(i32 %r0) {
+bb0:
+  %r3 = icmp sgt i32 %r0, 3
+  br i1 %r3, label %bb1, label %bb2
+bb1:
+  %r5 = mul i32 %r0, 2
+  ret i32 %r5
+bb2:
+  br label %bb3
+bb3:
+  %r6 = phi i32 [ 0, %bb2 ], [ %r9, %bb4 ]
+  %r7 = icmp slt i32 %r6, %r0
+  br i1 %r7, label %bb4, label %bb5
+bb4:
+  %r9 = add i32 %r6, 1
+  br label %bb3
+bb5:
+  ret i32 %r6
+}
+
+define internal i32 @lune_square__sum(i32 %r0, i32 %r1) {
+bb0:
+  %r2 = mul i32 %r0, %r1
+  %r3 = mul i32 %r0, %r1
+  %r4 = add i32 %r2, %r3
+  ret i32 %r4
+}
+
+define internal void @lune_main() {
+bb0:
+  store i32 3, ptr @lune_unused
+  %r2 = call i32 @lune_twice(i32 2)
+  %r4 = call i32 @lune_twice(i32 10)
+  %r5 = add i32 %r2, %r4
+  call void @lune_print_int(i32 %r5)
+  %r8 = call i32 @lune_square__sum(i32 3, i32 4)
+  call void @lune_print_int(i32 %r8)
+  %r11 = icmp sgt i32 1, 0
+  br i1 %r11, label %bb1, label %bb2
+bb1:
+  call void @lune_print_str(ptr @.str.0)
+  br label %bb3
+bb2:
+  call void @lune_print_str(ptr @.str.1)
+  br label %bb3
+bb3:
+  ret void
+}
+
+define i32 @main() {
+  call void @lune_main()
+  ret i32 0
+}
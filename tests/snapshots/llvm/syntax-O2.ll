@@ -0,0 +1,393 @@
+declare i32 @printf(ptr, ...)
+declare i32 @puts(ptr)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @llvm.trap() cold noreturn nounwind
+declare void @llvm.memmove.p0.p0.i64(ptr, ptr, i64, i1)
+
+@lune.int = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@lune.true = private unnamed_addr constant [5 x i8] c"true\00"
+@lune.false = private unnamed_addr constant [6 x i8] c"false\00"
+@lune.error = private unnamed_addr constant [11 x i8] c"error: %s\0A\00"
+@lune.div_zero = private unnamed_addr constant [26 x i8] c"attempt to divide by zero\00"
+@lune.div_overflow = private unnamed_addr constant [32 x i8] c"attempt to divide with overflow\00"
+@lune.bounds = private unnamed_addr constant [20 x i8] c"index out of bounds\00"
+
+define internal void @lune_print_int(i32 %value) {
+  %1 = call i32 (ptr, ...) @printf(ptr @lune.int, i32 %value)
+  ret void
+}
+
+define internal void @lune_print_str(ptr %value) {
+  %1 = call i32 @puts(ptr %value)
+  ret void
+}
+
+define internal void @lune_print_bool(i1 %value) {
+  %1 = select i1 %value, ptr @lune.true, ptr @lune.false
+  %2 = call i32 @puts(ptr %1)
+  ret void
+}
+
+define internal i1 @lune_str_eq(ptr %a, ptr %b) {
+  %1 = call i32 @strcmp(ptr %a, ptr %b)
+  %2 = icmp eq i32 %1, 0
+  ret i1 %2
+}
+
+define internal void @lune_fail(ptr %message) cold noreturn {
+  %1 = call i32 @fflush(ptr null)
+  %2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @lune.error, ptr %message)
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @lune_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %by_zero, label %nonzero
+by_zero:
+  call void @lune_fail(ptr @lune.div_zero)
+  unreachable
+nonzero:
+  %min = icmp eq i32 %a, -2147483648
+  %minus_one = icmp eq i32 %b, -1
+  %overflow = and i1 %min, %minus_one
+  br i1 %overflow, label %overflows, label %ok
+overflows:
+  call void @lune_fail(ptr @lune.div_overflow)
+  unreachable
+ok:
+  %1 = sdiv i32 %a, %b
+  ret i32 %1
+}
+
+@lune_p = internal global [16 x i8] zeroinitializer, align 8
+@lune_grid = internal global [24 x i8] zeroinitializer, align 8
+@lune_all = internal global [16 x i8] zeroinitializer, align 8
+@lune_tail = internal global [16 x i8] zeroinitializer, align 8
+@lune_head = internal global [16 x i8] zeroinitializer, align 8
+@lune_mid = internal global [16 x i8] zeroinitializer, align 8
+@lune_gr_uf6__udf_e = internal global i32 0
+@lune_done = internal global i1 false
+
+@.str.0 = private unnamed_addr constant [10 x i8] c"minus one\00"
+@.str.1 = private unnamed_addr constant [5 x i8] c"zero\00"
+@.str.2 = private unnamed_addr constant [6 x i8] c"small\00"
+@.str.3 = private unnamed_addr constant [6 x i8] c"limit\00"
+@.str.4 = private unnamed_addr constant [4 x i8] c"big\00"
+@.str.5 = private unnamed_addr constant [6 x i8] c"world\00"
+@.str.6 = private unnamed_addr constant [12 x i8] c"hello world\00"
+
+define internal i32 @lune_area(ptr %r0) {
+bb0:
+  %r1 = load i32, ptr %r0
+  %r3 = icmp eq i32 %r1, 0
+  br i1 %r3, label %bb6, label %bb1
+bb1:
+  %r5 = icmp eq i32 %r1, 4
+  br i1 %r5, label %bb2, label %bb3
+bb2:
+  %r6 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r7 = load i32, ptr %r6
+  %r8 = mul i32 %r7, %r7
+  br label %bb6
+bb3:
+  %r10 = icmp eq i32 %r1, 5
+  br i1 %r10, label %bb4, label %bb5
+bb4:
+  %r11 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r12 = load i32, ptr %r11
+  %r13 = getelementptr inbounds i8, ptr %r0, i64 16
+  %r14 = load i32, ptr %r13
+  %r15 = mul i32 %r12, %r14
+  br label %bb6
+bb5:
+  unreachable
+bb6:
+  %r16 = phi i32 [ %r8, %bb2 ], [ %r15, %bb4 ], [ 0, %bb0 ]
+  ret i32 %r16
+}
+
+define internal ptr @lune_describe(i32 %r0) {
+bb0:
+  %r2 = icmp eq i32 %r0, -1
+  br i1 %r2, label %bb1, label %bb2
+bb1:
+  ret ptr @.str.0
+bb2:
+  %r5 = icmp eq i32 %r0, 0
+  br i1 %r5, label %bb3, label %bb4
+bb3:
+  ret ptr @.str.1
+bb4:
+  %r8 = icmp sge i32 %r0, 1
+  br i1 %r8, label %bb5, label %bb7
+bb5:
+  %r10 = icmp sle i32 %r0, 7
+  br i1 %r10, label %bb6, label %bb7
+bb6:
+  ret ptr @.str.2
+bb7:
+  %r13 = icmp eq i32 %r0, 8
+  br i1 %r13, label %bb8, label %bb9
+bb8:
+  ret ptr @.str.3
+bb9:
+  ret ptr @.str.4
+}
+
+define internal void @lune_greet(ptr %r0) {
+bb0:
+  %r2 = call i1 @lune_str_eq(ptr %r0, ptr @.str.5)
+  br i1 %r2, label %bb1, label %bb2
+bb1:
+  call void @lune_print_str(ptr @.str.6)
+  br label %bb3
+bb2:
+  call void @lune_print_str(ptr %r0)
+  br label %bb3
+bb3:
+  ret void
+}
+
+define internal void @lune_main() {
+bb0:
+  %r0 = alloca [16 x i8], align 8
+  %r16 = alloca [24 x i8], align 8
+  %r31 = alloca [16 x i8], align 8
+  %r36 = alloca [16 x i8], align 8
+  %r41 = alloca [16 x i8], align 8
+  %r46 = alloca [16 x i8], align 8
+  %r106 = alloca [24 x i8], align 8
+  %r122 = alloca [24 x i8], align 8
+  %r139 = alloca [24 x i8], align 8
+  store i32 1, ptr %r0
+  %r3 = getelementptr inbounds i8, ptr %r0, i64 8
+  store i32 -2, ptr %r3
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_p, ptr %r0, i64 16, i1 false)
+  %r7 = load i32, ptr @lune_p
+  %r9 = add i32 %r7, 3
+  store i32 %r9, ptr @lune_p
+  %r11 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r13 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r14 = load i32, ptr %r13
+  %r15 = sub i32 %r14, 1
+  store i32 %r15, ptr %r11
+  store i32 1, ptr %r16
+  %r18 = getelementptr inbounds i8, ptr %r16, i64 8
+  store i32 2, ptr %r18
+  %r19 = getelementptr inbounds i8, ptr %r16, i64 16
+  store i32 3, ptr %r19
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_grid, ptr %r16, i64 24, i1 false)
+  %r25.i = sext i32 2 to i64
+  %r25 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r25.i
+  %r26 = load i32, ptr %r25
+  %r27 = call i32 @lune_div(i32 %r26, i32 2)
+  %r28.i = sext i32 0 to i64
+  %r28 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r28.i
+  store i32 %r27, ptr %r28
+  %r30.i = sext i32 0 to i64
+  %r30 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r30.i
+  %r32 = getelementptr inbounds i8, ptr %r31, i64 8
+  store ptr %r30, ptr %r31
+  store i32 3, ptr %r32
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_all, ptr %r31, i64 16, i1 false)
+  %r35.i = sext i32 1 to i64
+  %r35 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r35.i
+  %r37 = getelementptr inbounds i8, ptr %r36, i64 8
+  store ptr %r35, ptr %r36
+  store i32 2, ptr %r37
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_tail, ptr %r36, i64 16, i1 false)
+  %r40.i = sext i32 0 to i64
+  %r40 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r40.i
+  %r42 = getelementptr inbounds i8, ptr %r41, i64 8
+  store ptr %r40, ptr %r41
+  store i32 2, ptr %r42
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_head, ptr %r41, i64 16, i1 false)
+  %r45.i = sext i32 1 to i64
+  %r45 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r45.i
+  %r47 = getelementptr inbounds i8, ptr %r46, i64 8
+  store ptr %r45, ptr %r46
+  store i32 1, ptr %r47
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_mid, ptr %r46, i64 16, i1 false)
+  %r50 = getelementptr inbounds i8, ptr @lune_all, i64 8
+  %r51 = load i32, ptr %r50
+  %r53 = getelementptr inbounds i8, ptr @lune_tail, i64 8
+  %r54 = load i32, ptr %r53
+  %r55 = add i32 %r51, %r54
+  %r57 = getelementptr inbounds i8, ptr @lune_head, i64 8
+  %r58 = load i32, ptr %r57
+  %r59 = add i32 %r55, %r58
+  %r61 = getelementptr inbounds i8, ptr @lune_mid, i64 8
+  %r62 = load i32, ptr %r61
+  %r63 = add i32 %r59, %r62
+  store i32 %r63, ptr @lune_gr_uf6__udf_e
+  %r65 = load i32, ptr @lune_p
+  %r67 = icmp eq i32 %r65, 4
+  %r68 = xor i1 %r67, true
+  %r70 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r71 = load i32, ptr %r70
+  %r72 = icmp sgt i32 %r71, 0
+  %r73 = icmp ne i1 %r68, %r72
+  store i1 %r73, ptr @lune_done
+  %r75 = load i32, ptr @lune_p
+  %r76 = icmp slt i32 %r75, 3
+  br i1 %r76, label %bb1, label %bb2
+bb1:
+  %r78 = load i32, ptr @lune_p
+  call void @lune_print_int(i32 %r78)
+  br label %bb15
+bb2:
+  %r80 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r81 = load i32, ptr %r80
+  %r82 = icmp sle i32 %r81, 0
+  br i1 %r82, label %bb3, label %bb4
+bb3:
+  %r84 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r85 = load i32, ptr %r84
+  call void @lune_print_int(i32 %r85)
+  br label %bb15
+bb4:
+  %r86 = load i32, ptr @lune_gr_uf6__udf_e
+  %r88 = icmp eq i32 %r86, -1
+  br i1 %r88, label %bb5, label %bb6
+bb5:
+  br label %bb14
+bb6:
+  %r90 = icmp eq i32 %r86, 0
+  br i1 %r90, label %bb7, label %bb8
+bb7:
+  br label %bb14
+bb8:
+  %r92 = icmp sge i32 %r86, 1
+  br i1 %r92, label %bb9, label %bb11
+bb9:
+  %r94 = icmp sle i32 %r86, 7
+  br i1 %r94, label %bb10, label %bb11
+bb10:
+  br label %bb14
+bb11:
+  %r97 = icmp eq i32 %r86, 8
+  br i1 %r97, label %bb12, label %bb13
+bb12:
+  br label %bb14
+bb13:
+  br label %bb14
+bb14:
+  %r100 = phi ptr [ @.str.0, %bb5 ], [ @.str.1, %bb7 ], [ @.str.2, %bb10 ], [ @.str.3, %bb12 ], [ @.str.4, %bb13 ]
+  call void @lune_print_str(ptr %r100)
+  br label %bb15
+bb15:
+  %r101 = load i32, ptr @lune_gr_uf6__udf_e
+  %r102 = icmp sge i32 %r101, 0
+  br i1 %r102, label %bb16, label %bb17
+bb16:
+  %r103 = load i32, ptr @lune_gr_uf6__udf_e
+  %r104 = sub i32 %r103, 1
+  store i32 %r104, ptr @lune_gr_uf6__udf_e
+  br label %bb15
+bb17:
+  store i32 5, ptr %r106
+  %r107 = getelementptr inbounds i8, ptr %r106, i64 8
+  store i32 2, ptr %r107
+  %r108 = getelementptr inbounds i8, ptr %r106, i64 16
+  store i32 3, ptr %r108
+  %r109 = load i32, ptr %r106
+  %r110 = icmp eq i32 %r109, 0
+  br i1 %r110, label %bb23, label %bb18
+bb18:
+  %r111 = icmp eq i32 %r109, 4
+  br i1 %r111, label %bb19, label %bb20
+bb19:
+  %r112 = getelementptr inbounds i8, ptr %r106, i64 8
+  %r113 = load i32, ptr %r112
+  %r114 = mul i32 %r113, %r113
+  br label %bb23
+bb20:
+  %r115 = icmp eq i32 %r109, 5
+  br i1 %r115, label %bb21, label %bb22
+bb21:
+  %r116 = getelementptr inbounds i8, ptr %r106, i64 8
+  %r117 = load i32, ptr %r116
+  %r118 = getelementptr inbounds i8, ptr %r106, i64 16
+  %r119 = load i32, ptr %r118
+  %r120 = mul i32 %r117, %r119
+  br label %bb23
+bb22:
+  unreachable
+bb23:
+  %r121 = phi i32 [ %r114, %bb19 ], [ %r120, %bb21 ], [ 0, %bb17 ]
+  store i32 4, ptr %r122
+  %r124 = getelementptr inbounds i8, ptr %r122, i64 8
+  store i32 8, ptr %r124
+  %r125 = load i32, ptr %r122
+  %r126 = icmp eq i32 %r125, 0
+  br i1 %r126, label %bb29, label %bb24
+bb24:
+  %r127 = icmp eq i32 %r125, 4
+  br i1 %r127, label %bb25, label %bb26
+bb25:
+  %r128 = getelementptr inbounds i8, ptr %r122, i64 8
+  %r129 = load i32, ptr %r128
+  %r130 = mul i32 %r129, %r129
+  br label %bb29
+bb26:
+  %r131 = icmp eq i32 %r125, 5
+  br i1 %r131, label %bb27, label %bb28
+bb27:
+  %r132 = getelementptr inbounds i8, ptr %r122, i64 8
+  %r133 = load i32, ptr %r132
+  %r134 = getelementptr inbounds i8, ptr %r122, i64 16
+  %r135 = load i32, ptr %r134
+  %r136 = mul i32 %r133, %r135
+  br label %bb29
+bb28:
+  unreachable
+bb29:
+  %r137 = phi i32 [ %r130, %bb25 ], [ %r136, %bb27 ], [ 0, %bb23 ]
+  %r138 = add i32 %r121, %r137
+  store i32 0, ptr %r139
+  %r140 = load i32, ptr %r139
+  %r141 = icmp eq i32 %r140, 0
+  br i1 %r141, label %bb35, label %bb30
+bb30:
+  %r142 = icmp eq i32 %r140, 4
+  br i1 %r142, label %bb31, label %bb32
+bb31:
+  %r143 = getelementptr inbounds i8, ptr %r139, i64 8
+  %r144 = load i32, ptr %r143
+  %r145 = mul i32 %r144, %r144
+  br label %bb35
+bb32:
+  %r146 = icmp eq i32 %r140, 5
+  br i1 %r146, label %bb33, label %bb34
+bb33:
+  %r147 = getelementptr inbounds i8, ptr %r139, i64 8
+  %r148 = load i32, ptr %r147
+  %r149 = getelementptr inbounds i8, ptr %r139, i64 16
+  %r150 = load i32, ptr %r149
+  %r151 = mul i32 %r148, %r150
+  br label %bb35
+bb34:
+  unreachable
+bb35:
+  %r152 = phi i32 [ %r145, %bb31 ], [ %r151, %bb33 ], [ 0, %bb29 ]
+  %r153 = sub i32 %r138, %r152
+  call void @lune_print_int(i32 %r153)
+  call void @lune_print_str(ptr @.str.6)
+  %r155 = load i1, ptr @lune_done
+  call void @lune_print_bool(i1 %r155)
+  %r156 = load i1, ptr @lune_done
+  br i1 %r156, label %bb36, label %bb37
+bb36:
+  call void @lune_print_bool(i1 true)
+  br label %bb37
+bb37:
+  ret void
+}
+
+define i32 @main() {
+  call void @lune_main()
+  ret i32 0
+}
@@ -0,0 +1,367 @@
+declare i32 @printf(ptr, ...)
+declare i32 @puts(ptr)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @llvm.trap() cold noreturn nounwind
+declare void @llvm.memmove.p0.p0.i64(ptr, ptr, i64, i1)
+
+@lune.int = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@lune.true = private unnamed_addr constant [5 x i8] c"true\00"
+@lune.false = private unnamed_addr constant [6 x i8] c"false\00"
+@lune.error = private unnamed_addr constant [11 x i8] c"error: %s\0A\00"
+@lune.div_zero = private unnamed_addr constant [26 x i8] c"attempt to divide by zero\00"
+@lune.div_overflow = private unnamed_addr constant [32 x i8] c"attempt to divide with overflow\00"
+@lune.bounds = private unnamed_addr constant [20 x i8] c"index out of bounds\00"
+
+define internal void @lune_print_int(i32 %value) {
+  %1 = call i32 (ptr, ...) @printf(ptr @lune.int, i32 %value)
+  ret void
+}
+
+define internal void @lune_print_str(ptr %value) {
+  %1 = call i32 @puts(ptr %value)
+  ret void
+}
+
+define internal void @lune_print_bool(i1 %value) {
+  %1 = select i1 %value, ptr @lune.true, ptr @lune.false
+  %2 = call i32 @puts(ptr %1)
+  ret void
+}
+
+define internal i1 @lune_str_eq(ptr %a, ptr %b) {
+  %1 = call i32 @strcmp(ptr %a, ptr %b)
+  %2 = icmp eq i32 %1, 0
+  ret i1 %2
+}
+
+define internal void @lune_fail(ptr %message) cold noreturn {
+  %1 = call i32 @fflush(ptr null)
+  %2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @lune.error, ptr %message)
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @lune_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %by_zero, label %nonzero
+by_zero:
+  call void @lune_fail(ptr @lune.div_zero)
+  unreachable
+nonzero:
+  %min = icmp eq i32 %a, -2147483648
+  %minus_one = icmp eq i32 %b, -1
+  %overflow = and i1 %min, %minus_one
+  br i1 %overflow, label %overflows, label %ok
+overflows:
+  call void @lune_fail(ptr @lune.div_overflow)
+  unreachable
+ok:
+  %1 = sdiv i32 %a, %b
+  ret i32 %1
+}
+
+@lune_p = internal global [16 x i8] zeroinitializer, align 8
+@lune_grid = internal global [24 x i8] zeroinitializer, align 8
+@lune_all = internal global [16 x i8] zeroinitializer, align 8
+@lune_tail = internal global [16 x i8] zeroinitializer, align 8
+@lune_head = internal global [16 x i8] zeroinitializer, align 8
+@lune_mid = internal global [16 x i8] zeroinitializer, align 8
+@lune_gr_uf6__udf_e = internal global i32 0
+@lune_done = internal global i1 false
+
+@.str.0 = private unnamed_addr constant [10 x i8] c"minus one\00"
+@.str.1 = private unnamed_addr constant [5 x i8] c"zero\00"
+@.str.2 = private unnamed_addr constant [6 x i8] c"small\00"
+@.str.3 = private unnamed_addr constant [6 x i8] c"limit\00"
+@.str.4 = private unnamed_addr constant [4 x i8] c"big\00"
+@.str.5 = private unnamed_addr constant [6 x i8] c"world\00"
+@.str.6 = private unnamed_addr constant [12 x i8] c"hello world\00"
+
+define internal i32 @lune_area(ptr %r0) {
+bb0:
+  %r1 = load i32, ptr %r0
+  %r3 = icmp eq i32 %r1, 0
+  br i1 %r3, label %bb1, label %bb2
+bb1:
+  br label %bb7
+bb2:
+  %r6 = icmp eq i32 %r1, 4
+  br i1 %r6, label %bb3, label %bb4
+bb3:
+  %r7 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r8 = load i32, ptr %r7
+  %r9 = mul i32 %r8, %r8
+  br label %bb7
+bb4:
+  %r11 = icmp eq i32 %r1, 5
+  br i1 %r11, label %bb5, label %bb6
+bb5:
+  %r12 = getelementptr inbounds i8, ptr %r0, i64 8
+  %r13 = load i32, ptr %r12
+  %r14 = getelementptr inbounds i8, ptr %r0, i64 16
+  %r15 = load i32, ptr %r14
+  %r16 = mul i32 %r13, %r15
+  br label %bb7
+bb6:
+  unreachable
+bb7:
+  %r17 = phi i32 [ 0, %bb1 ], [ %r9, %bb3 ], [ %r16, %bb5 ]
+  ret i32 %r17
+}
+
+define internal ptr @lune_describe(i32 %r0) {
+bb0:
+  %r2 = icmp eq i32 %r0, -1
+  br i1 %r2, label %bb1, label %bb2
+bb1:
+  ret ptr @.str.0
+bb2:
+  %r5 = icmp eq i32 %r0, 0
+  br i1 %r5, label %bb3, label %bb4
+bb3:
+  ret ptr @.str.1
+bb4:
+  %r8 = icmp sge i32 %r0, 1
+  br i1 %r8, label %bb5, label %bb7
+bb5:
+  %r10 = icmp sle i32 %r0, 7
+  br i1 %r10, label %bb6, label %bb7
+bb6:
+  ret ptr @.str.2
+bb7:
+  %r13 = icmp eq i32 %r0, 8
+  br i1 %r13, label %bb8, label %bb9
+bb8:
+  ret ptr @.str.3
+bb9:
+  br label %bb10
+bb10:
+  br label %bb11
+bb11:
+  ret ptr @.str.4
+}
+
+define internal void @lune_greet(ptr %r0) {
+bb0:
+  %r2 = call i1 @lune_str_eq(ptr %r0, ptr @.str.5)
+  br i1 %r2, label %bb1, label %bb2
+bb1:
+  call void @lune_print_str(ptr @.str.6)
+  br label %bb4
+bb2:
+  br label %bb3
+bb3:
+  call void @lune_print_str(ptr %r0)
+  br label %bb4
+bb4:
+  ret void
+}
+
+define internal void @lune_main() {
+bb0:
+  %r0 = alloca [16 x i8], align 8
+  %r17 = alloca [24 x i8], align 8
+  %r49 = alloca [16 x i8], align 8
+  %r61 = alloca [16 x i8], align 8
+  %r73 = alloca [16 x i8], align 8
+  %r86 = alloca [16 x i8], align 8
+  %r142 = alloca [24 x i8], align 8
+  %r149 = alloca [24 x i8], align 8
+  %r155 = alloca [24 x i8], align 8
+  store i32 1, ptr %r0
+  %r3 = getelementptr inbounds i8, ptr %r0, i64 8
+  store i32 -2, ptr %r3
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_p, ptr %r0, i64 16, i1 false)
+  %r7 = load i32, ptr @lune_p
+  %r9 = add i32 %r7, 3
+  store i32 %r9, ptr @lune_p
+  %r11 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r13 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r14 = load i32, ptr %r13
+  %r16 = sub i32 %r14, 1
+  store i32 %r16, ptr %r11
+  store i32 1, ptr %r17
+  %r20 = getelementptr inbounds i8, ptr %r17, i64 8
+  store i32 2, ptr %r20
+  %r22 = getelementptr inbounds i8, ptr %r17, i64 16
+  store i32 3, ptr %r22
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_grid, ptr %r17, i64 24, i1 false)
+  %r31 = icmp sge i32 2, 0
+  br i1 %r31, label %bb1, label %bb28
+bb1:
+  %r32 = icmp slt i32 2, 3
+  br i1 %r32, label %bb2, label %bb28
+bb2:
+  %r33.i = sext i32 2 to i64
+  %r33 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r33.i
+  %r34 = load i32, ptr %r33
+  %r36 = call i32 @lune_div(i32 %r34, i32 2)
+  %r38 = icmp sge i32 0, 0
+  br i1 %r38, label %bb3, label %bb28
+bb3:
+  %r39 = icmp slt i32 0, 3
+  br i1 %r39, label %bb4, label %bb28
+bb4:
+  %r40.i = sext i32 0 to i64
+  %r40 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r40.i
+  store i32 %r36, ptr %r40
+  %r44 = icmp sle i32 0, 0
+  br i1 %r44, label %bb5, label %bb28
+bb5:
+  %r45 = icmp sle i32 0, 3
+  br i1 %r45, label %bb6, label %bb28
+bb6:
+  %r46 = icmp sle i32 3, 3
+  br i1 %r46, label %bb7, label %bb28
+bb7:
+  %r47.i = sext i32 0 to i64
+  %r47 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r47.i
+  %r48 = sub i32 3, 0
+  %r50 = getelementptr inbounds i8, ptr %r49, i64 8
+  store ptr %r47, ptr %r49
+  store i32 %r48, ptr %r50
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_all, ptr %r49, i64 16, i1 false)
+  %r56 = icmp sle i32 0, 1
+  br i1 %r56, label %bb8, label %bb28
+bb8:
+  %r57 = icmp sle i32 1, 3
+  br i1 %r57, label %bb9, label %bb28
+bb9:
+  %r58 = icmp sle i32 3, 3
+  br i1 %r58, label %bb10, label %bb28
+bb10:
+  %r59.i = sext i32 1 to i64
+  %r59 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r59.i
+  %r60 = sub i32 3, 1
+  %r62 = getelementptr inbounds i8, ptr %r61, i64 8
+  store ptr %r59, ptr %r61
+  store i32 %r60, ptr %r62
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_tail, ptr %r61, i64 16, i1 false)
+  %r68 = icmp sle i32 0, 0
+  br i1 %r68, label %bb11, label %bb28
+bb11:
+  %r69 = icmp sle i32 0, 2
+  br i1 %r69, label %bb12, label %bb28
+bb12:
+  %r70 = icmp sle i32 2, 3
+  br i1 %r70, label %bb13, label %bb28
+bb13:
+  %r71.i = sext i32 0 to i64
+  %r71 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r71.i
+  %r72 = sub i32 2, 0
+  %r74 = getelementptr inbounds i8, ptr %r73, i64 8
+  store ptr %r71, ptr %r73
+  store i32 %r72, ptr %r74
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_head, ptr %r73, i64 16, i1 false)
+  %r81 = icmp sle i32 0, 1
+  br i1 %r81, label %bb14, label %bb28
+bb14:
+  %r82 = icmp sle i32 1, 2
+  br i1 %r82, label %bb15, label %bb28
+bb15:
+  %r83 = icmp sle i32 2, 3
+  br i1 %r83, label %bb16, label %bb28
+bb16:
+  %r84.i = sext i32 1 to i64
+  %r84 = getelementptr inbounds [8 x i8], ptr @lune_grid, i64 %r84.i
+  %r85 = sub i32 2, 1
+  %r87 = getelementptr inbounds i8, ptr %r86, i64 8
+  store ptr %r84, ptr %r86
+  store i32 %r85, ptr %r87
+  call void @llvm.memmove.p0.p0.i64(ptr @lune_mid, ptr %r86, i64 16, i1 false)
+  %r90 = load ptr, ptr @lune_all
+  %r91 = getelementptr inbounds i8, ptr @lune_all, i64 8
+  %r92 = load i32, ptr %r91
+  %r94 = load ptr, ptr @lune_tail
+  %r95 = getelementptr inbounds i8, ptr @lune_tail, i64 8
+  %r96 = load i32, ptr %r95
+  %r97 = add i32 %r92, %r96
+  %r99 = load ptr, ptr @lune_head
+  %r100 = getelementptr inbounds i8, ptr @lune_head, i64 8
+  %r101 = load i32, ptr %r100
+  %r102 = add i32 %r97, %r101
+  %r104 = load ptr, ptr @lune_mid
+  %r105 = getelementptr inbounds i8, ptr @lune_mid, i64 8
+  %r106 = load i32, ptr %r105
+  %r107 = add i32 %r102, %r106
+  store i32 %r107, ptr @lune_gr_uf6__udf_e
+  %r109 = load i32, ptr @lune_p
+  %r111 = icmp eq i32 %r109, 4
+  %r112 = xor i1 %r111, true
+  %r114 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r115 = load i32, ptr %r114
+  %r117 = icmp sgt i32 %r115, 0
+  %r118 = icmp ne i1 %r112, %r117
+  store i1 %r118, ptr @lune_done
+  %r120 = load i32, ptr @lune_p
+  %r122 = icmp slt i32 %r120, 3
+  br i1 %r122, label %bb17, label %bb18
+bb17:
+  %r124 = load i32, ptr @lune_p
+  call void @lune_print_int(i32 %r124)
+  br label %bb22
+bb18:
+  %r126 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r127 = load i32, ptr %r126
+  %r129 = icmp sle i32 %r127, 0
+  br i1 %r129, label %bb19, label %bb20
+bb19:
+  %r131 = getelementptr inbounds i8, ptr @lune_p, i64 8
+  %r132 = load i32, ptr %r131
+  call void @lune_print_int(i32 %r132)
+  br label %bb21
+bb20:
+  %r133 = load i32, ptr @lune_gr_uf6__udf_e
+  %r134 = call ptr @lune_describe(i32 %r133)
+  call void @lune_print_str(ptr %r134)
+  br label %bb21
+bb21:
+  br label %bb22
+bb22:
+  br label %bb23
+bb23:
+  %r135 = load i32, ptr @lune_gr_uf6__udf_e
+  %r137 = icmp sge i32 %r135, 0
+  br i1 %r137, label %bb24, label %bb25
+bb24:
+  %r138 = load i32, ptr @lune_gr_uf6__udf_e
+  %r140 = sub i32 %r138, 1
+  store i32 %r140, ptr @lune_gr_uf6__udf_e
+  br label %bb23
+bb25:
+  store i32 5, ptr %r142
+  %r144 = getelementptr inbounds i8, ptr %r142, i64 8
+  store i32 2, ptr %r144
+  %r146 = getelementptr inbounds i8, ptr %r142, i64 16
+  store i32 3, ptr %r146
+  %r147 = call i32 @lune_area(ptr %r142)
+  store i32 4, ptr %r149
+  %r151 = getelementptr inbounds i8, ptr %r149, i64 8
+  store i32 8, ptr %r151
+  %r152 = call i32 @lune_area(ptr %r149)
+  %r153 = add i32 %r147, %r152
+  store i32 0, ptr %r155
+  %r156 = call i32 @lune_area(ptr %r155)
+  %r157 = sub i32 %r153, %r156
+  call void @lune_print_int(i32 %r157)
+  call void @lune_greet(ptr @.str.5)
+  %r159 = load i1, ptr @lune_done
+  call void @lune_print_bool(i1 %r159)
+  %r160 = load i1, ptr @lune_done
+  br i1 %r160, label %bb26, label %bb27
+bb26:
+  call void @lune_print_bool(i1 true)
+  br label %bb27
+bb27:
+  ret void
+bb28:
+  call void @lune_fail(ptr @lune.bounds)
+  unreachable
+}
+
+define i32 @main() {
+  call void @lune_main()
+  ret i32 0
+}
@@ -0,0 +1,1758 @@
+(module
+  (import "env" "print" (func $print (param i32 i32)))
+
+  (data (i32.const 0) "true\00false\00\0a")
+
+  ;; Print the int and a newline, formatting it backwards from the end of
+  ;; the scratch space
+  (func $lune_print_int (param $value i32)
+    (local $n i64) (local $at i32)
+    (i32.store8 (i32.const 23) (i32.const 10))
+    (local.set $at (i32.const 23))
+    (local.set $n (i64.extend_i32_s (local.get $value)))
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (local.set $n (i64.sub (i64.const 0) (local.get $n)))))
+    (loop $digits
+      (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+      (i32.store8 (local.get $at)
+        (i32.add (i32.wrap_i64 (i64.rem_u (local.get $n) (i64.const 10))) (i32.const 48)))
+      (local.set $n (i64.div_u (local.get $n) (i64.const 10)))
+      (br_if $digits (i64.ne (local.get $n) (i64.const 0))))
+    (if (i32.lt_s (local.get $value) (i32.const 0))
+      (then
+        (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+        (i32.store8 (local.get $at) (i32.const 45))))
+    (call $print (local.get $at) (i32.sub (i32.const 24) (local.get $at))))
+
+  ;; Print the string at $s and a newline
+  (func $lune_print_str (param $s i32)
+    (local $len i32)
+    (block $done
+      (loop $scan
+        (br_if $done (i32.eqz (i32.load8_u (i32.add (local.get $s) (local.get $len)))))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (br $scan)))
+    (call $print (local.get $s) (local.get $len))
+    (call $print (i32.const 11) (i32.const 1)))
+
+  (func $lune_print_bool (param $b i32)
+    (call $lune_print_str (select (i32.const 0) (i32.const 5) (local.get $b))))
+
+  ;; Whether the strings at $a and $b are equal
+  (func $lune_str_eq (param $a i32) (param $b i32) (result i32)
+    (local $c i32)
+    (loop $next
+      (local.set $c (i32.load8_u (local.get $a)))
+      (if (i32.ne (local.get $c) (i32.load8_u (local.get $b)))
+        (then (return (i32.const 0))))
+      (if (i32.eqz (local.get $c))
+        (then (return (i32.const 1))))
+      (local.set $a (i32.add (local.get $a) (i32.const 1)))
+      (local.set $b (i32.add (local.get $b) (i32.const 1)))
+      (br $next))
+    (unreachable))
+
+  (memory (export "memory") 17)
+
+  (global $lune_i (mut i32) (i32.const 0))
+  (global $lune_a i32 (i32.const 24))
+  (global $lune_b i32 (i32.const 40))
+  (global $lune_line i32 (i32.const 56))
+  (global $lune_shapes i32 (i32.const 88))
+  (global $lune_xs i32 (i32.const 208))
+  (global $lune_ys i32 (i32.const 240))
+  (global $lune_zs i32 (i32.const 256))
+  (global $lune_points i32 (i32.const 272))
+  (global $lune_sp (mut i32) (i32.const 1114112))
+
+  (func $lune_mirror (param $r0 i32) (param $r1 i32)
+    (local $block i32) (local $r2 i32) (local $r3 i32)
+    loop $dispatch
+      block $bb0
+        local.get $block
+        br_table $bb0
+      end ;; bb0
+      local.get $r1
+      i32.load
+      local.set $r2
+      i32.const 0
+      local.get $r2
+      i32.sub
+      local.set $r3
+      local.get $r1
+      local.get $r3
+      i32.store
+      local.get $r0
+      local.get $r1
+      i32.const 16
+      memory.copy
+      return
+    end)
+
+  (func $lune_length (param $r0 i32) (result i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32)
+    loop $dispatch
+      block $bb0
+        local.get $block
+        br_table $bb0
+      end ;; bb0
+      local.get $r0
+      i32.const 16
+      i32.add
+      local.set $r1
+      local.get $r1
+      i32.load
+      local.set $r2
+      local.get $r0
+      i32.load
+      local.set $r3
+      local.get $r2
+      local.get $r3
+      i32.sub
+      local.set $r4
+      local.get $r0
+      i32.const 16
+      i32.add
+      local.set $r5
+      local.get $r5
+      i32.const 8
+      i32.add
+      local.set $r6
+      local.get $r6
+      i32.load
+      local.set $r7
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r8
+      local.get $r8
+      i32.load
+      local.set $r9
+      local.get $r7
+      local.get $r9
+      i32.sub
+      local.set $r10
+      local.get $r4
+      local.get $r10
+      i32.add
+      local.set $r11
+      local.get $r11
+      return
+    end
+    unreachable)
+
+  (func $lune_area (param $r0 i32) (result i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $r14 i32) (local $r15 i32) (local $r16 i32) (local $r17 i32) (local $r18 i32) (local $r19 i32) (local $r20 i32) (local $r21 i32) (local $r22 i32) (local $r23 i32) (local $frame i32)
+    global.get $lune_sp
+    i32.const 16
+    i32.sub
+    local.tee $frame
+    global.set $lune_sp
+    loop $dispatch
+      block $bb7
+      block $bb6
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5 $bb6 $bb7
+      end ;; bb0
+      local.get $r0
+      i32.load
+      local.set $r1
+      i32.const 0
+      local.set $r2
+      local.get $r1
+      local.get $r2
+      i32.eq
+      local.set $r3
+      local.get $r3
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      i32.const 0
+      local.set $r4
+      local.get $r4
+      local.set $r23
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      i32.const 1
+      local.set $r5
+      local.get $r1
+      local.get $r5
+      i32.eq
+      local.set $r6
+      local.get $r6
+      if
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end ;; bb3
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r7
+      local.get $r7
+      i32.load
+      local.set $r8
+      i32.const 3
+      local.set $r9
+      local.get $r9
+      local.get $r8
+      i32.mul
+      local.set $r10
+      local.get $r10
+      local.get $r8
+      i32.mul
+      local.set $r11
+      local.get $r11
+      local.set $r23
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end ;; bb4
+      i32.const 2
+      local.set $r12
+      local.get $r1
+      local.get $r12
+      i32.eq
+      local.set $r13
+      local.get $r13
+      if
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r14
+      local.get $frame
+      i32.const 0
+      i32.add
+      local.set $r15
+      local.get $r15
+      local.get $r14
+      i32.const 16
+      memory.copy
+      local.get $r0
+      i32.const 24
+      i32.add
+      local.set $r16
+      local.get $r16
+      i32.load
+      local.set $r17
+      local.get $r0
+      i32.const 32
+      i32.add
+      local.set $r18
+      local.get $r18
+      i32.load
+      local.set $r19
+      local.get $r17
+      local.get $r19
+      i32.mul
+      local.set $r20
+      local.get $r15
+      i32.load
+      local.set $r21
+      local.get $r20
+      local.get $r21
+      i32.add
+      local.set $r22
+      local.get $r22
+      local.set $r23
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end ;; bb6
+      unreachable
+      end ;; bb7
+      local.get $r23
+      local.get $frame
+      i32.const 16
+      i32.add
+      global.set $lune_sp
+      return
+    end
+    unreachable)
+
+  (func $lune_sum (param $r0 i32) (result i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $r14 i32) (local $r15 i32) (local $r16 i32) (local $r17 i32) (local $r18 i32) (local $r19 i32)
+    loop $dispatch
+      block $bb6
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5 $bb6
+      end ;; bb0
+      i32.const 0
+      local.set $r1
+      i32.const 0
+      local.set $r2
+      local.get $r2
+      local.get $r1
+      local.set $r4
+      local.set $r3
+      end ;; bb1
+      local.get $r0
+      i32.load
+      local.set $r5
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r6
+      local.get $r6
+      i32.load
+      local.set $r7
+      local.get $r3
+      local.get $r7
+      i32.lt_s
+      local.set $r8
+      local.get $r8
+      if
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      local.get $r0
+      i32.load
+      local.set $r9
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r10
+      local.get $r10
+      i32.load
+      local.set $r11
+      i32.const 0
+      local.set $r12
+      local.get $r3
+      local.get $r12
+      i32.ge_s
+      local.set $r13
+      local.get $r13
+      if
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end ;; bb3
+      local.get $r3
+      local.get $r11
+      i32.lt_s
+      local.set $r14
+      local.get $r14
+      if
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end ;; bb4
+      local.get $r9
+      local.get $r3
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r15
+      local.get $r15
+      i32.load
+      local.set $r16
+      local.get $r4
+      local.get $r16
+      i32.add
+      local.set $r17
+      i32.const 1
+      local.set $r18
+      local.get $r3
+      local.get $r18
+      i32.add
+      local.set $r19
+      local.get $r19
+      local.get $r17
+      local.set $r4
+      local.set $r3
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      local.get $r4
+      return
+      end ;; bb6
+      unreachable
+    end
+    unreachable)
+
+  (func $lune_fill (param $r0 i32) (param $r1 i32)
+    (local $block i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $frame i32)
+    global.get $lune_sp
+    i32.const 32
+    i32.sub
+    local.tee $frame
+    global.set $lune_sp
+    loop $dispatch
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3
+      end ;; bb0
+      local.get $frame
+      i32.const 0
+      i32.add
+      local.set $r2
+      local.get $r2
+      local.get $r1
+      i32.store
+      local.get $r2
+      i32.const 8
+      i32.add
+      local.set $r3
+      local.get $r3
+      local.get $r1
+      i32.store
+      local.get $r2
+      i32.const 16
+      i32.add
+      local.set $r4
+      local.get $r4
+      local.get $r1
+      i32.store
+      local.get $r2
+      i32.const 24
+      i32.add
+      local.set $r5
+      local.get $r5
+      local.get $r1
+      i32.store
+      i32.const 4
+      local.set $r6
+      i32.const 1
+      local.set $r7
+      i32.const 1
+      local.set $r8
+      local.get $r1
+      local.get $r8
+      i32.add
+      local.set $r9
+      i32.const 0
+      local.set $r10
+      local.get $r7
+      local.get $r10
+      i32.ge_s
+      local.set $r11
+      local.get $r11
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      local.get $r7
+      local.get $r6
+      i32.lt_s
+      local.set $r12
+      local.get $r12
+      if
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      local.get $r2
+      local.get $r7
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r13
+      local.get $r13
+      local.get $r9
+      i32.store
+      local.get $r0
+      local.get $r2
+      i32.const 32
+      memory.copy
+      local.get $frame
+      i32.const 32
+      i32.add
+      global.set $lune_sp
+      return
+      end ;; bb3
+      unreachable
+    end)
+
+  (func $lune_main
+    (local $block i32) (local $r0 i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $r14 i32) (local $r15 i32) (local $r16 i32) (local $r17 i32) (local $r18 i32) (local $r19 i32) (local $r20 i32) (local $r21 i32) (local $r22 i32) (local $r23 i32) (local $r24 i32) (local $r25 i32) (local $r26 i32) (local $r27 i32) (local $r28 i32) (local $r29 i32) (local $r30 i32) (local $r31 i32) (local $r32 i32) (local $r33 i32) (local $r34 i32) (local $r35 i32) (local $r36 i32) (local $r37 i32) (local $r38 i32) (local $r39 i32) (local $r40 i32) (local $r41 i32) (local $r42 i32) (local $r43 i32) (local $r44 i32) (local $r45 i32) (local $r46 i32) (local $r47 i32) (local $r48 i32) (local $r49 i32) (local $r50 i32) (local $r51 i32) (local $r52 i32) (local $r53 i32) (local $r54 i32) (local $r55 i32) (local $r56 i32) (local $r57 i32) (local $r58 i32) (local $r59 i32) (local $r60 i32) (local $r61 i32) (local $r62 i32) (local $r63 i32) (local $r64 i32) (local $r65 i32) (local $r66 i32) (local $r67 i32) (local $r68 i32) (local $r69 i32) (local $r70 i32) (local $r71 i32) (local $r72 i32) (local $r73 i32) (local $r74 i32) (local $r75 i32) (local $r76 i32) (local $r77 i32) (local $r78 i32) (local $r79 i32) (local $r80 i32) (local $r81 i32) (local $r82 i32) (local $r83 i32) (local $r84 i32) (local $r85 i32) (local $r86 i32) (local $r87 i32) (local $r88 i32) (local $r89 i32) (local $r90 i32) (local $r91 i32) (local $r92 i32) (local $r93 i32) (local $r94 i32) (local $r95 i32) (local $r96 i32) (local $r97 i32) (local $r98 i32) (local $r99 i32) (local $r100 i32) (local $r101 i32) (local $r102 i32) (local $r103 i32) (local $r104 i32) (local $r105 i32) (local $r106 i32) (local $r107 i32) (local $r108 i32) (local $r109 i32) (local $r110 i32) (local $r111 i32) (local $r112 i32) (local $r113 i32) (local $r114 i32) (local $r115 i32) (local $r116 i32) (local $r117 i32) (local $r118 i32) (local $r119 i32) (local $r120 i32) (local $r121 i32) (local $r122 i32) (local $r123 i32) (local $r124 i32) (local $r125 i32) (local $r126 i32) (local $r127 i32) (local $r128 i32) (local $r129 i32) (local $r130 i32) (local $r131 i32) (local $r132 i32) (local $r133 i32) (local $r134 i32) (local $r135 i32) (local $r136 i32) (local $r137 i32) (local $r138 i32) (local $r139 i32) (local $r140 i32) (local $r141 i32) (local $r142 i32) (local $r143 i32) (local $r144 i32) (local $r145 i32) (local $r146 i32) (local $r147 i32) (local $r148 i32) (local $r149 i32) (local $r150 i32) (local $r151 i32) (local $r152 i32) (local $r153 i32) (local $r154 i32) (local $r155 i32) (local $r156 i32) (local $r157 i32) (local $r158 i32) (local $r159 i32) (local $r160 i32) (local $r161 i32) (local $r162 i32) (local $r163 i32) (local $r164 i32) (local $r165 i32) (local $r166 i32) (local $r167 i32) (local $r168 i32) (local $r169 i32) (local $r170 i32) (local $r171 i32) (local $r172 i32) (local $r173 i32) (local $r174 i32) (local $r175 i32) (local $r176 i32) (local $r177 i32) (local $r178 i32) (local $r179 i32) (local $r180 i32) (local $r181 i32) (local $r182 i32) (local $r183 i32) (local $r184 i32) (local $r185 i32) (local $r186 i32) (local $r187 i32) (local $r188 i32) (local $r189 i32) (local $r190 i32) (local $r191 i32) (local $r192 i32) (local $r193 i32) (local $r194 i32) (local $r195 i32) (local $r196 i32) (local $r197 i32) (local $r198 i32) (local $r199 i32) (local $r200 i32) (local $r201 i32) (local $r202 i32) (local $r203 i32) (local $r204 i32) (local $r205 i32) (local $r206 i32) (local $r207 i32) (local $r208 i32) (local $r209 i32) (local $r210 i32) (local $r211 i32) (local $r212 i32) (local $frame i32)
+    global.get $lune_sp
+    i32.const 592
+    i32.sub
+    local.tee $frame
+    global.set $lune_sp
+    loop $dispatch
+      block $bb34
+      block $bb33
+      block $bb32
+      block $bb31
+      block $bb30
+      block $bb29
+      block $bb28
+      block $bb27
+      block $bb26
+      block $bb25
+      block $bb24
+      block $bb23
+      block $bb22
+      block $bb21
+      block $bb20
+      block $bb19
+      block $bb18
+      block $bb17
+      block $bb16
+      block $bb15
+      block $bb14
+      block $bb13
+      block $bb12
+      block $bb11
+      block $bb10
+      block $bb9
+      block $bb8
+      block $bb7
+      block $bb6
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5 $bb6 $bb7 $bb8 $bb9 $bb10 $bb11 $bb12 $bb13 $bb14 $bb15 $bb16 $bb17 $bb18 $bb19 $bb20 $bb21 $bb22 $bb23 $bb24 $bb25 $bb26 $bb27 $bb28 $bb29 $bb30 $bb31 $bb32 $bb33 $bb34
+      end ;; bb0
+      local.get $frame
+      i32.const 0
+      i32.add
+      local.set $r0
+      i32.const 1
+      local.set $r1
+      local.get $r0
+      local.get $r1
+      i32.store
+      i32.const 2
+      local.set $r2
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r3
+      local.get $r3
+      local.get $r2
+      i32.store
+      global.get $lune_a
+      local.set $r4
+      local.get $r4
+      local.get $r0
+      i32.const 16
+      memory.copy
+      global.get $lune_a
+      local.set $r5
+      global.get $lune_b
+      local.set $r6
+      local.get $r6
+      local.get $r5
+      i32.const 16
+      memory.copy
+      global.get $lune_b
+      local.set $r7
+      i32.const 10
+      local.set $r8
+      local.get $r7
+      local.get $r8
+      i32.store
+      global.get $lune_a
+      local.set $r9
+      local.get $r9
+      i32.load
+      local.set $r10
+      local.get $r10
+      call $lune_print_int
+      local.get $frame
+      i32.const 16
+      i32.add
+      local.set $r11
+      global.get $lune_b
+      local.set $r12
+      local.get $frame
+      i32.const 32
+      i32.add
+      local.set $r13
+      local.get $r13
+      local.get $r12
+      i32.const 16
+      memory.copy
+      local.get $r11
+      local.get $r13
+      call $lune_mirror
+      local.get $r11
+      i32.load
+      local.set $r14
+      local.get $r14
+      call $lune_print_int
+      global.get $lune_b
+      local.set $r15
+      local.get $r15
+      i32.load
+      local.set $r16
+      local.get $r16
+      call $lune_print_int
+      local.get $frame
+      i32.const 48
+      i32.add
+      local.set $r17
+      global.get $lune_a
+      local.set $r18
+      local.get $r17
+      local.get $r18
+      i32.const 16
+      memory.copy
+      local.get $frame
+      i32.const 80
+      i32.add
+      local.set $r19
+      i32.const 4
+      local.set $r20
+      local.get $r19
+      local.get $r20
+      i32.store
+      i32.const 6
+      local.set $r21
+      local.get $r19
+      i32.const 8
+      i32.add
+      local.set $r22
+      local.get $r22
+      local.get $r21
+      i32.store
+      local.get $r17
+      i32.const 16
+      i32.add
+      local.set $r23
+      local.get $r23
+      local.get $r19
+      i32.const 16
+      memory.copy
+      global.get $lune_line
+      local.set $r24
+      local.get $r24
+      local.get $r17
+      i32.const 32
+      memory.copy
+      global.get $lune_line
+      local.set $r25
+      local.get $frame
+      i32.const 96
+      i32.add
+      local.set $r26
+      local.get $r26
+      local.get $r25
+      i32.const 32
+      memory.copy
+      local.get $r26
+      call $lune_length
+      local.set $r27
+      local.get $r27
+      call $lune_print_int
+      global.get $lune_line
+      local.set $r28
+      local.get $r28
+      i32.const 16
+      i32.add
+      local.set $r29
+      local.get $r29
+      i32.const 8
+      i32.add
+      local.set $r30
+      global.get $lune_line
+      local.set $r31
+      local.get $r31
+      i32.const 16
+      i32.add
+      local.set $r32
+      local.get $r32
+      i32.const 8
+      i32.add
+      local.set $r33
+      local.get $r33
+      i32.load
+      local.set $r34
+      i32.const 10
+      local.set $r35
+      local.get $r34
+      local.get $r35
+      i32.add
+      local.set $r36
+      local.get $r30
+      local.get $r36
+      i32.store
+      global.get $lune_line
+      local.set $r37
+      local.get $r37
+      i32.const 16
+      i32.add
+      local.set $r38
+      local.get $r38
+      i32.const 8
+      i32.add
+      local.set $r39
+      local.get $r39
+      i32.load
+      local.set $r40
+      local.get $r40
+      call $lune_print_int
+      local.get $frame
+      i32.const 128
+      i32.add
+      local.set $r41
+      i32.const 0
+      local.set $r42
+      local.get $frame
+      i32.const 248
+      i32.add
+      local.set $r43
+      local.get $r43
+      local.get $r42
+      i32.store
+      local.get $r41
+      local.get $r43
+      i32.const 40
+      memory.copy
+      i32.const 1
+      local.set $r44
+      local.get $frame
+      i32.const 288
+      i32.add
+      local.set $r45
+      local.get $r45
+      local.get $r44
+      i32.store
+      i32.const 2
+      local.set $r46
+      local.get $r45
+      i32.const 8
+      i32.add
+      local.set $r47
+      local.get $r47
+      local.get $r46
+      i32.store
+      local.get $r41
+      i32.const 40
+      i32.add
+      local.set $r48
+      local.get $r48
+      local.get $r45
+      i32.const 40
+      memory.copy
+      i32.const 2
+      local.set $r49
+      local.get $frame
+      i32.const 328
+      i32.add
+      local.set $r50
+      local.get $r50
+      local.get $r49
+      i32.store
+      local.get $frame
+      i32.const 368
+      i32.add
+      local.set $r51
+      i32.const 5
+      local.set $r52
+      local.get $r51
+      local.get $r52
+      i32.store
+      i32.const 0
+      local.set $r53
+      local.get $r51
+      i32.const 8
+      i32.add
+      local.set $r54
+      local.get $r54
+      local.get $r53
+      i32.store
+      local.get $r50
+      i32.const 8
+      i32.add
+      local.set $r55
+      local.get $r55
+      local.get $r51
+      i32.const 16
+      memory.copy
+      i32.const 2
+      local.set $r56
+      local.get $r50
+      i32.const 24
+      i32.add
+      local.set $r57
+      local.get $r57
+      local.get $r56
+      i32.store
+      i32.const 3
+      local.set $r58
+      local.get $r50
+      i32.const 32
+      i32.add
+      local.set $r59
+      local.get $r59
+      local.get $r58
+      i32.store
+      local.get $r41
+      i32.const 80
+      i32.add
+      local.set $r60
+      local.get $r60
+      local.get $r50
+      i32.const 40
+      memory.copy
+      global.get $lune_shapes
+      local.set $r61
+      local.get $r61
+      local.get $r41
+      i32.const 120
+      memory.copy
+      i32.const 0
+      local.set $r62
+      local.get $r62
+      global.set $lune_i
+      end ;; bb1
+      global.get $lune_i
+      local.set $r63
+      i32.const 3
+      local.set $r64
+      local.get $r63
+      local.get $r64
+      i32.lt_s
+      local.set $r65
+      local.get $r65
+      if
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      global.get $lune_shapes
+      local.set $r66
+      i32.const 3
+      local.set $r67
+      global.get $lune_i
+      local.set $r68
+      i32.const 0
+      local.set $r69
+      local.get $r68
+      local.get $r69
+      i32.ge_s
+      local.set $r70
+      local.get $r70
+      if
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb3
+      local.get $r68
+      local.get $r67
+      i32.lt_s
+      local.set $r71
+      local.get $r71
+      if
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb4
+      local.get $r66
+      local.get $r68
+      i32.const 40
+      i32.mul
+      i32.add
+      local.set $r72
+      local.get $frame
+      i32.const 384
+      i32.add
+      local.set $r73
+      local.get $r73
+      local.get $r72
+      i32.const 40
+      memory.copy
+      local.get $r73
+      call $lune_area
+      local.set $r74
+      local.get $r74
+      call $lune_print_int
+      global.get $lune_i
+      local.set $r75
+      i32.const 1
+      local.set $r76
+      local.get $r75
+      local.get $r76
+      i32.add
+      local.set $r77
+      local.get $r77
+      global.set $lune_i
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      global.get $lune_shapes
+      local.set $r78
+      i32.const 3
+      local.set $r79
+      i32.const 0
+      local.set $r80
+      global.get $lune_shapes
+      local.set $r81
+      i32.const 3
+      local.set $r82
+      i32.const 1
+      local.set $r83
+      i32.const 0
+      local.set $r84
+      local.get $r83
+      local.get $r84
+      i32.ge_s
+      local.set $r85
+      local.get $r85
+      if
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb6
+      local.get $r83
+      local.get $r82
+      i32.lt_s
+      local.set $r86
+      local.get $r86
+      if
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb7
+      local.get $r81
+      local.get $r83
+      i32.const 40
+      i32.mul
+      i32.add
+      local.set $r87
+      i32.const 0
+      local.set $r88
+      local.get $r80
+      local.get $r88
+      i32.ge_s
+      local.set $r89
+      local.get $r89
+      if
+      i32.const 8
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb8
+      local.get $r80
+      local.get $r79
+      i32.lt_s
+      local.set $r90
+      local.get $r90
+      if
+      i32.const 9
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb9
+      local.get $r78
+      local.get $r80
+      i32.const 40
+      i32.mul
+      i32.add
+      local.set $r91
+      local.get $r91
+      local.get $r87
+      i32.const 40
+      memory.copy
+      global.get $lune_shapes
+      local.set $r92
+      i32.const 3
+      local.set $r93
+      i32.const 0
+      local.set $r94
+      i32.const 0
+      local.set $r95
+      local.get $r94
+      local.get $r95
+      i32.ge_s
+      local.set $r96
+      local.get $r96
+      if
+      i32.const 10
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb10
+      local.get $r94
+      local.get $r93
+      i32.lt_s
+      local.set $r97
+      local.get $r97
+      if
+      i32.const 11
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb11
+      local.get $r92
+      local.get $r94
+      i32.const 40
+      i32.mul
+      i32.add
+      local.set $r98
+      local.get $frame
+      i32.const 424
+      i32.add
+      local.set $r99
+      local.get $r99
+      local.get $r98
+      i32.const 40
+      memory.copy
+      local.get $r99
+      call $lune_area
+      local.set $r100
+      local.get $r100
+      call $lune_print_int
+      local.get $frame
+      i32.const 464
+      i32.add
+      local.set $r101
+      i32.const 7
+      local.set $r102
+      local.get $r101
+      local.get $r102
+      call $lune_fill
+      global.get $lune_xs
+      local.set $r103
+      local.get $r103
+      local.get $r101
+      i32.const 32
+      memory.copy
+      global.get $lune_xs
+      local.set $r104
+      i32.const 4
+      local.set $r105
+      i32.const 0
+      local.set $r106
+      local.get $r106
+      local.get $r106
+      i32.le_s
+      local.set $r107
+      local.get $r107
+      if
+      i32.const 12
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb12
+      local.get $r106
+      local.get $r105
+      i32.le_s
+      local.set $r108
+      local.get $r108
+      if
+      i32.const 13
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb13
+      local.get $r105
+      local.get $r105
+      i32.le_s
+      local.set $r109
+      local.get $r109
+      if
+      i32.const 14
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb14
+      local.get $r104
+      local.get $r106
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r110
+      local.get $r105
+      local.get $r106
+      i32.sub
+      local.set $r111
+      local.get $frame
+      i32.const 496
+      i32.add
+      local.set $r112
+      local.get $r112
+      i32.const 8
+      i32.add
+      local.set $r113
+      local.get $r112
+      local.get $r110
+      i32.store
+      local.get $r113
+      local.get $r111
+      i32.store
+      local.get $r112
+      call $lune_sum
+      local.set $r114
+      local.get $r114
+      call $lune_print_int
+      global.get $lune_xs
+      local.set $r115
+      i32.const 4
+      local.set $r116
+      i32.const 0
+      local.set $r117
+      i32.const 1
+      local.set $r118
+      i32.const 3
+      local.set $r119
+      local.get $r117
+      local.get $r118
+      i32.le_s
+      local.set $r120
+      local.get $r120
+      if
+      i32.const 15
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb15
+      local.get $r118
+      local.get $r119
+      i32.le_s
+      local.set $r121
+      local.get $r121
+      if
+      i32.const 16
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb16
+      local.get $r119
+      local.get $r116
+      i32.le_s
+      local.set $r122
+      local.get $r122
+      if
+      i32.const 17
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb17
+      local.get $r115
+      local.get $r118
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r123
+      local.get $r119
+      local.get $r118
+      i32.sub
+      local.set $r124
+      local.get $frame
+      i32.const 512
+      i32.add
+      local.set $r125
+      local.get $r125
+      i32.const 8
+      i32.add
+      local.set $r126
+      local.get $r125
+      local.get $r123
+      i32.store
+      local.get $r126
+      local.get $r124
+      i32.store
+      local.get $r125
+      call $lune_sum
+      local.set $r127
+      local.get $r127
+      call $lune_print_int
+      global.get $lune_xs
+      local.set $r128
+      i32.const 4
+      local.set $r129
+      i32.const 0
+      local.set $r130
+      i32.const 2
+      local.set $r131
+      local.get $r130
+      local.get $r131
+      i32.le_s
+      local.set $r132
+      local.get $r132
+      if
+      i32.const 18
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb18
+      local.get $r131
+      local.get $r129
+      i32.le_s
+      local.set $r133
+      local.get $r133
+      if
+      i32.const 19
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb19
+      local.get $r129
+      local.get $r129
+      i32.le_s
+      local.set $r134
+      local.get $r134
+      if
+      i32.const 20
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb20
+      local.get $r128
+      local.get $r131
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r135
+      local.get $r129
+      local.get $r131
+      i32.sub
+      local.set $r136
+      local.get $frame
+      i32.const 528
+      i32.add
+      local.set $r137
+      local.get $r137
+      i32.const 8
+      i32.add
+      local.set $r138
+      local.get $r137
+      local.get $r135
+      i32.store
+      local.get $r138
+      local.get $r136
+      i32.store
+      global.get $lune_ys
+      local.set $r139
+      local.get $r139
+      local.get $r137
+      i32.const 16
+      memory.copy
+      global.get $lune_ys
+      local.set $r140
+      local.get $r140
+      i32.load
+      local.set $r141
+      local.get $r140
+      i32.const 8
+      i32.add
+      local.set $r142
+      local.get $r142
+      i32.load
+      local.set $r143
+      i32.const 0
+      local.set $r144
+      i32.const 100
+      local.set $r145
+      i32.const 0
+      local.set $r146
+      local.get $r144
+      local.get $r146
+      i32.ge_s
+      local.set $r147
+      local.get $r147
+      if
+      i32.const 21
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb21
+      local.get $r144
+      local.get $r143
+      i32.lt_s
+      local.set $r148
+      local.get $r148
+      if
+      i32.const 22
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb22
+      local.get $r141
+      local.get $r144
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r149
+      local.get $r149
+      local.get $r145
+      i32.store
+      global.get $lune_xs
+      local.set $r150
+      i32.const 4
+      local.set $r151
+      i32.const 2
+      local.set $r152
+      i32.const 0
+      local.set $r153
+      local.get $r152
+      local.get $r153
+      i32.ge_s
+      local.set $r154
+      local.get $r154
+      if
+      i32.const 23
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb23
+      local.get $r152
+      local.get $r151
+      i32.lt_s
+      local.set $r155
+      local.get $r155
+      if
+      i32.const 24
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb24
+      local.get $r150
+      local.get $r152
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r156
+      local.get $r156
+      i32.load
+      local.set $r157
+      local.get $r157
+      call $lune_print_int
+      global.get $lune_ys
+      local.set $r158
+      local.get $r158
+      i32.load
+      local.set $r159
+      local.get $r158
+      i32.const 8
+      i32.add
+      local.set $r160
+      local.get $r160
+      i32.load
+      local.set $r161
+      i32.const 0
+      local.set $r162
+      i32.const 1
+      local.set $r163
+      local.get $r162
+      local.get $r163
+      i32.le_s
+      local.set $r164
+      local.get $r164
+      if
+      i32.const 25
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb25
+      local.get $r163
+      local.get $r161
+      i32.le_s
+      local.set $r165
+      local.get $r165
+      if
+      i32.const 26
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb26
+      local.get $r161
+      local.get $r161
+      i32.le_s
+      local.set $r166
+      local.get $r166
+      if
+      i32.const 27
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb27
+      local.get $r159
+      local.get $r163
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r167
+      local.get $r161
+      local.get $r163
+      i32.sub
+      local.set $r168
+      local.get $frame
+      i32.const 544
+      i32.add
+      local.set $r169
+      local.get $r169
+      i32.const 8
+      i32.add
+      local.set $r170
+      local.get $r169
+      local.get $r167
+      i32.store
+      local.get $r170
+      local.get $r168
+      i32.store
+      global.get $lune_zs
+      local.set $r171
+      local.get $r171
+      local.get $r169
+      i32.const 16
+      memory.copy
+      global.get $lune_zs
+      local.set $r172
+      local.get $r172
+      i32.load
+      local.set $r173
+      local.get $r172
+      i32.const 8
+      i32.add
+      local.set $r174
+      local.get $r174
+      i32.load
+      local.set $r175
+      global.get $lune_zs
+      local.set $r176
+      local.get $r176
+      i32.load
+      local.set $r177
+      local.get $r176
+      i32.const 8
+      i32.add
+      local.set $r178
+      local.get $r178
+      i32.load
+      local.set $r179
+      i32.const 0
+      local.set $r180
+      i32.const 0
+      local.set $r181
+      local.get $r180
+      local.get $r181
+      i32.ge_s
+      local.set $r182
+      local.get $r182
+      if
+      i32.const 28
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb28
+      local.get $r180
+      local.get $r179
+      i32.lt_s
+      local.set $r183
+      local.get $r183
+      if
+      i32.const 29
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb29
+      local.get $r177
+      local.get $r180
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r184
+      local.get $r184
+      i32.load
+      local.set $r185
+      local.get $r175
+      local.get $r185
+      i32.add
+      local.set $r186
+      local.get $r186
+      call $lune_print_int
+      local.get $frame
+      i32.const 560
+      i32.add
+      local.set $r187
+      global.get $lune_a
+      local.set $r188
+      local.get $r187
+      local.get $r188
+      i32.const 16
+      memory.copy
+      global.get $lune_b
+      local.set $r189
+      local.get $r187
+      i32.const 16
+      i32.add
+      local.set $r190
+      local.get $r190
+      local.get $r189
+      i32.const 16
+      memory.copy
+      global.get $lune_points
+      local.set $r191
+      local.get $r191
+      local.get $r187
+      i32.const 32
+      memory.copy
+      global.get $lune_points
+      local.set $r192
+      i32.const 2
+      local.set $r193
+      i32.const 1
+      local.set $r194
+      i32.const 0
+      local.set $r195
+      local.get $r194
+      local.get $r195
+      i32.ge_s
+      local.set $r196
+      local.get $r196
+      if
+      i32.const 30
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb30
+      local.get $r194
+      local.get $r193
+      i32.lt_s
+      local.set $r197
+      local.get $r197
+      if
+      i32.const 31
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb31
+      local.get $r192
+      local.get $r194
+      i32.const 16
+      i32.mul
+      i32.add
+      local.set $r198
+      local.get $r198
+      i32.const 8
+      i32.add
+      local.set $r199
+      i32.const 42
+      local.set $r200
+      local.get $r199
+      local.get $r200
+      i32.store
+      global.get $lune_points
+      local.set $r201
+      i32.const 2
+      local.set $r202
+      i32.const 1
+      local.set $r203
+      i32.const 0
+      local.set $r204
+      local.get $r203
+      local.get $r204
+      i32.ge_s
+      local.set $r205
+      local.get $r205
+      if
+      i32.const 32
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb32
+      local.get $r203
+      local.get $r202
+      i32.lt_s
+      local.set $r206
+      local.get $r206
+      if
+      i32.const 33
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb33
+      local.get $r201
+      local.get $r203
+      i32.const 16
+      i32.mul
+      i32.add
+      local.set $r207
+      local.get $r207
+      i32.const 8
+      i32.add
+      local.set $r208
+      local.get $r208
+      i32.load
+      local.set $r209
+      local.get $r209
+      call $lune_print_int
+      global.get $lune_b
+      local.set $r210
+      local.get $r210
+      i32.const 8
+      i32.add
+      local.set $r211
+      local.get $r211
+      i32.load
+      local.set $r212
+      local.get $r212
+      call $lune_print_int
+      local.get $frame
+      i32.const 592
+      i32.add
+      global.set $lune_sp
+      return
+      end ;; bb34
+      unreachable
+    end)
+
+  (func (export "main")
+    call $lune_main)
+)
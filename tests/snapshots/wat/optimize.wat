@@ -0,0 +1,234 @@
+(module
+  (import "env" "print" (func $print (param i32 i32)))
+
+  (data (i32.const 0) "true\00false\00\0a")
+
+  ;; Print the int and a newline, formatting it backwards from the end of
+  ;; the scratch space
+  (func $lune_print_int (param $value i32)
+    (local $n i64) (local $at i32)
+    (i32.store8 (i32.const 23) (i32.const 10))
+    (local.set $at (i32.const 23))
+    (local.set $n (i64.extend_i32_s (local.get $value)))
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (local.set $n (i64.sub (i64.const 0) (local.get $n)))))
+    (loop $digits
+      (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+      (i32.store8 (local.get $at)
+        (i32.add (i32.wrap_i64 (i64.rem_u (local.get $n) (i64.const 10))) (i32.const 48)))
+      (local.set $n (i64.div_u (local.get $n) (i64.const 10)))
+      (br_if $digits (i64.ne (local.get $n) (i64.const 0))))
+    (if (i32.lt_s (local.get $value) (i32.const 0))
+      (then
+        (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+        (i32.store8 (local.get $at) (i32.const 45))))
+    (call $print (local.get $at) (i32.sub (i32.const 24) (local.get $at))))
+
+  ;; Print the string at $s and a newline
+  (func $lune_print_str (param $s i32)
+    (local $len i32)
+    (block $done
+      (loop $scan
+        (br_if $done (i32.eqz (i32.load8_u (i32.add (local.get $s) (local.get $len)))))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (br $scan)))
+    (call $print (local.get $s) (local.get $len))
+    (call $print (i32.const 11) (i32.const 1)))
+
+  (func $lune_print_bool (param $b i32)
+    (call $lune_print_str (select (i32.const 0) (i32.const 5) (local.get $b))))
+
+  ;; Whether the strings at $a and $b are equal
+  (func $lune_str_eq (param $a i32) (param $b i32) (result i32)
+    (local $c i32)
+    (loop $next
+      (local.set $c (i32.load8_u (local.get $a)))
+      (if (i32.ne (local.get $c) (i32.load8_u (local.get $b)))
+        (then (return (i32.const 0))))
+      (if (i32.eqz (local.get $c))
+        (then (return (i32.const 1))))
+      (local.set $a (i32.add (local.get $a) (i32.const 1)))
+      (local.set $b (i32.add (local.get $b) (i32.const 1)))
+      (br $next))
+    (unreachable))
+
+  (memory (export "memory") 1)
+  (data (i32.const 24) "on\00")
+  (data (i32.const 27) "off\00")
+
+  (global $lune_unused (mut i32) (i32.const 0))
+
+  (func $lune_twice (param $r0 i32) (result i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32)
+    loop $dispatch
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5
+      end ;; bb0
+      i32.const 0
+      local.set $r1
+      i32.const 3
+      local.set $r2
+      local.get $r0
+      local.get $r2
+      i32.gt_s
+      local.set $r3
+      local.get $r3
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      i32.const 2
+      local.set $r4
+      local.get $r0
+      local.get $r4
+      i32.mul
+      local.set $r5
+      local.get $r5
+      return
+      end ;; bb2
+      local.get $r1
+      local.set $r6
+      end ;; bb3
+      local.get $r6
+      local.get $r0
+      i32.lt_s
+      local.set $r7
+      local.get $r7
+      if
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end ;; bb4
+      i32.const 1
+      local.set $r8
+      local.get $r6
+      local.get $r8
+      i32.add
+      local.set $r9
+      local.get $r9
+      local.set $r6
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      local.get $r6
+      return
+    end
+    unreachable)
+
+  (func $lune_square__sum (param $r0 i32) (param $r1 i32) (result i32)
+    (local $block i32) (local $r2 i32) (local $r3 i32) (local $r4 i32)
+    loop $dispatch
+      block $bb0
+        local.get $block
+        br_table $bb0
+      end ;; bb0
+      local.get $r0
+      local.get $r1
+      i32.mul
+      local.set $r2
+      local.get $r0
+      local.get $r1
+      i32.mul
+      local.set $r3
+      local.get $r2
+      local.get $r3
+      i32.add
+      local.set $r4
+      local.get $r4
+      return
+    end
+    unreachable)
+
+  (func $lune_main
+    (local $block i32) (local $r0 i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32)
+    loop $dispatch
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3
+      end ;; bb0
+      i32.const 3
+      local.set $r0
+      local.get $r0
+      global.set $lune_unused
+      i32.const 2
+      local.set $r1
+      local.get $r1
+      call $lune_twice
+      local.set $r2
+      i32.const 10
+      local.set $r3
+      local.get $r3
+      call $lune_twice
+      local.set $r4
+      local.get $r2
+      local.get $r4
+      i32.add
+      local.set $r5
+      local.get $r5
+      call $lune_print_int
+      i32.const 3
+      local.set $r6
+      i32.const 4
+      local.set $r7
+      local.get $r6
+      local.get $r7
+      call $lune_square__sum
+      local.set $r8
+      local.get $r8
+      call $lune_print_int
+      i32.const 1
+      local.set $r9
+      i32.const 0
+      local.set $r10
+      local.get $r9
+      local.get $r10
+      i32.gt_s
+      local.set $r11
+      local.get $r11
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      i32.const 24
+      local.set $r12
+      local.get $r12
+      call $lune_print_str
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      i32.const 27
+      local.set $r13
+      local.get $r13
+      call $lune_print_str
+      end ;; bb3
+      return
+    end)
+
+  (func (export "main")
+    call $lune_main)
+)
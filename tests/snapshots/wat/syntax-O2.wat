@@ -0,0 +1,1276 @@
+(module
+  (import "env" "print" (func $print (param i32 i32)))
+
+  (data (i32.const 0) "true\00false\00\0a")
+
+  ;; Print the int and a newline, formatting it backwards from the end of
+  ;; the scratch space
+  (func $lune_print_int (param $value i32)
+    (local $n i64) (local $at i32)
+    (i32.store8 (i32.const 23) (i32.const 10))
+    (local.set $at (i32.const 23))
+    (local.set $n (i64.extend_i32_s (local.get $value)))
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (local.set $n (i64.sub (i64.const 0) (local.get $n)))))
+    (loop $digits
+      (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+      (i32.store8 (local.get $at)
+        (i32.add (i32.wrap_i64 (i64.rem_u (local.get $n) (i64.const 10))) (i32.const 48)))
+      (local.set $n (i64.div_u (local.get $n) (i64.const 10)))
+      (br_if $digits (i64.ne (local.get $n) (i64.const 0))))
+    (if (i32.lt_s (local.get $value) (i32.const 0))
+      (then
+        (local.set $at (i32.sub (local.get $at) (i32.const 1)))
+        (i32.store8 (local.get $at) (i32.const 45))))
+    (call $print (local.get $at) (i32.sub (i32.const 24) (local.get $at))))
+
+  ;; Print the string at $s and a newline
+  (func $lune_print_str (param $s i32)
+    (local $len i32)
+    (block $done
+      (loop $scan
+        (br_if $done (i32.eqz (i32.load8_u (i32.add (local.get $s) (local.get $len)))))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (br $scan)))
+    (call $print (local.get $s) (local.get $len))
+    (call $print (i32.const 11) (i32.const 1)))
+
+  (func $lune_print_bool (param $b i32)
+    (call $lune_print_str (select (i32.const 0) (i32.const 5) (local.get $b))))
+
+  ;; Whether the strings at $a and $b are equal
+  (func $lune_str_eq (param $a i32) (param $b i32) (result i32)
+    (local $c i32)
+    (loop $next
+      (local.set $c (i32.load8_u (local.get $a)))
+      (if (i32.ne (local.get $c) (i32.load8_u (local.get $b)))
+        (then (return (i32.const 0))))
+      (if (i32.eqz (local.get $c))
+        (then (return (i32.const 1))))
+      (local.set $a (i32.add (local.get $a) (i32.const 1)))
+      (local.set $b (i32.add (local.get $b) (i32.const 1)))
+      (br $next))
+    (unreachable))
+
+  (memory (export "memory") 17)
+  (data (i32.const 24) "minus one\00")
+  (data (i32.const 34) "zero\00")
+  (data (i32.const 39) "small\00")
+  (data (i32.const 45) "limit\00")
+  (data (i32.const 51) "big\00")
+  (data (i32.const 55) "world\00")
+  (data (i32.const 61) "hello world\00")
+
+  (global $lune_gr_uf6__udf_e (mut i32) (i32.const 0))
+  (global $lune_done (mut i32) (i32.const 0))
+  (global $lune_p i32 (i32.const 80))
+  (global $lune_grid i32 (i32.const 96))
+  (global $lune_all i32 (i32.const 120))
+  (global $lune_tail i32 (i32.const 136))
+  (global $lune_head i32 (i32.const 152))
+  (global $lune_mid i32 (i32.const 168))
+  (global $lune_sp (mut i32) (i32.const 1114112))
+
+  (func $lune_area (param $r0 i32) (result i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $r14 i32) (local $r15 i32) (local $r16 i32)
+    loop $dispatch
+      block $bb6
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5 $bb6
+      end ;; bb0
+      local.get $r0
+      i32.load
+      local.set $r1
+      i32.const 0
+      local.set $r2
+      local.get $r1
+      local.get $r2
+      i32.eq
+      local.set $r3
+      local.get $r3
+      if
+      local.get $r2
+      local.set $r16
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end
+      end ;; bb1
+      i32.const 4
+      local.set $r4
+      local.get $r1
+      local.get $r4
+      i32.eq
+      local.set $r5
+      local.get $r5
+      if
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r6
+      local.get $r6
+      i32.load
+      local.set $r7
+      local.get $r7
+      local.get $r7
+      i32.mul
+      local.set $r8
+      local.get $r8
+      local.set $r16
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end ;; bb3
+      i32.const 5
+      local.set $r9
+      local.get $r1
+      local.get $r9
+      i32.eq
+      local.set $r10
+      local.get $r10
+      if
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end ;; bb4
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r11
+      local.get $r11
+      i32.load
+      local.set $r12
+      local.get $r0
+      i32.const 16
+      i32.add
+      local.set $r13
+      local.get $r13
+      i32.load
+      local.set $r14
+      local.get $r12
+      local.get $r14
+      i32.mul
+      local.set $r15
+      local.get $r15
+      local.set $r16
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      unreachable
+      end ;; bb6
+      local.get $r16
+      return
+    end
+    unreachable)
+
+  (func $lune_describe (param $r0 i32) (result i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $r14 i32) (local $r15 i32)
+    loop $dispatch
+      block $bb9
+      block $bb8
+      block $bb7
+      block $bb6
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5 $bb6 $bb7 $bb8 $bb9
+      end ;; bb0
+      i32.const -1
+      local.set $r1
+      local.get $r0
+      local.get $r1
+      i32.eq
+      local.set $r2
+      local.get $r2
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      i32.const 24
+      local.set $r3
+      local.get $r3
+      return
+      end ;; bb2
+      i32.const 0
+      local.set $r4
+      local.get $r0
+      local.get $r4
+      i32.eq
+      local.set $r5
+      local.get $r5
+      if
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end ;; bb3
+      i32.const 34
+      local.set $r6
+      local.get $r6
+      return
+      end ;; bb4
+      i32.const 1
+      local.set $r7
+      local.get $r0
+      local.get $r7
+      i32.ge_s
+      local.set $r8
+      local.get $r8
+      if
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      i32.const 7
+      local.set $r9
+      local.get $r0
+      local.get $r9
+      i32.le_s
+      local.set $r10
+      local.get $r10
+      if
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end ;; bb6
+      i32.const 39
+      local.set $r11
+      local.get $r11
+      return
+      end ;; bb7
+      i32.const 8
+      local.set $r12
+      local.get $r0
+      local.get $r12
+      i32.eq
+      local.set $r13
+      local.get $r13
+      if
+      i32.const 8
+      local.set $block
+      br $dispatch
+      end
+      i32.const 9
+      local.set $block
+      br $dispatch
+      end ;; bb8
+      i32.const 45
+      local.set $r14
+      local.get $r14
+      return
+      end ;; bb9
+      i32.const 51
+      local.set $r15
+      local.get $r15
+      return
+    end
+    unreachable)
+
+  (func $lune_greet (param $r0 i32)
+    (local $block i32) (local $r1 i32) (local $r2 i32) (local $r3 i32)
+    loop $dispatch
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3
+      end ;; bb0
+      i32.const 55
+      local.set $r1
+      local.get $r0
+      local.get $r1
+      call $lune_str_eq
+      local.set $r2
+      local.get $r2
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      i32.const 61
+      local.set $r3
+      local.get $r3
+      call $lune_print_str
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      local.get $r0
+      call $lune_print_str
+      end ;; bb3
+      return
+    end)
+
+  (func $lune_main
+    (local $block i32) (local $r0 i32) (local $r1 i32) (local $r2 i32) (local $r3 i32) (local $r4 i32) (local $r5 i32) (local $r6 i32) (local $r7 i32) (local $r8 i32) (local $r9 i32) (local $r10 i32) (local $r11 i32) (local $r12 i32) (local $r13 i32) (local $r14 i32) (local $r15 i32) (local $r16 i32) (local $r17 i32) (local $r18 i32) (local $r19 i32) (local $r20 i32) (local $r21 i32) (local $r22 i32) (local $r23 i32) (local $r24 i32) (local $r25 i32) (local $r26 i32) (local $r27 i32) (local $r28 i32) (local $r29 i32) (local $r30 i32) (local $r31 i32) (local $r32 i32) (local $r33 i32) (local $r34 i32) (local $r35 i32) (local $r36 i32) (local $r37 i32) (local $r38 i32) (local $r39 i32) (local $r40 i32) (local $r41 i32) (local $r42 i32) (local $r43 i32) (local $r44 i32) (local $r45 i32) (local $r46 i32) (local $r47 i32) (local $r48 i32) (local $r49 i32) (local $r50 i32) (local $r51 i32) (local $r52 i32) (local $r53 i32) (local $r54 i32) (local $r55 i32) (local $r56 i32) (local $r57 i32) (local $r58 i32) (local $r59 i32) (local $r60 i32) (local $r61 i32) (local $r62 i32) (local $r63 i32) (local $r64 i32) (local $r65 i32) (local $r66 i32) (local $r67 i32) (local $r68 i32) (local $r69 i32) (local $r70 i32) (local $r71 i32) (local $r72 i32) (local $r73 i32) (local $r74 i32) (local $r75 i32) (local $r76 i32) (local $r77 i32) (local $r78 i32) (local $r79 i32) (local $r80 i32) (local $r81 i32) (local $r82 i32) (local $r83 i32) (local $r84 i32) (local $r85 i32) (local $r86 i32) (local $r87 i32) (local $r88 i32) (local $r89 i32) (local $r90 i32) (local $r91 i32) (local $r92 i32) (local $r93 i32) (local $r94 i32) (local $r95 i32) (local $r96 i32) (local $r97 i32) (local $r98 i32) (local $r99 i32) (local $r100 i32) (local $r101 i32) (local $r102 i32) (local $r103 i32) (local $r104 i32) (local $r105 i32) (local $r106 i32) (local $r107 i32) (local $r108 i32) (local $r109 i32) (local $r110 i32) (local $r111 i32) (local $r112 i32) (local $r113 i32) (local $r114 i32) (local $r115 i32) (local $r116 i32) (local $r117 i32) (local $r118 i32) (local $r119 i32) (local $r120 i32) (local $r121 i32) (local $r122 i32) (local $r123 i32) (local $r124 i32) (local $r125 i32) (local $r126 i32) (local $r127 i32) (local $r128 i32) (local $r129 i32) (local $r130 i32) (local $r131 i32) (local $r132 i32) (local $r133 i32) (local $r134 i32) (local $r135 i32) (local $r136 i32) (local $r137 i32) (local $r138 i32) (local $r139 i32) (local $r140 i32) (local $r141 i32) (local $r142 i32) (local $r143 i32) (local $r144 i32) (local $r145 i32) (local $r146 i32) (local $r147 i32) (local $r148 i32) (local $r149 i32) (local $r150 i32) (local $r151 i32) (local $r152 i32) (local $r153 i32) (local $r154 i32) (local $r155 i32) (local $r156 i32) (local $frame i32)
+    global.get $lune_sp
+    i32.const 176
+    i32.sub
+    local.tee $frame
+    global.set $lune_sp
+    loop $dispatch
+      block $bb37
+      block $bb36
+      block $bb35
+      block $bb34
+      block $bb33
+      block $bb32
+      block $bb31
+      block $bb30
+      block $bb29
+      block $bb28
+      block $bb27
+      block $bb26
+      block $bb25
+      block $bb24
+      block $bb23
+      block $bb22
+      block $bb21
+      block $bb20
+      block $bb19
+      block $bb18
+      block $bb17
+      block $bb16
+      block $bb15
+      block $bb14
+      block $bb13
+      block $bb12
+      block $bb11
+      block $bb10
+      block $bb9
+      block $bb8
+      block $bb7
+      block $bb6
+      block $bb5
+      block $bb4
+      block $bb3
+      block $bb2
+      block $bb1
+      block $bb0
+        local.get $block
+        br_table $bb0 $bb1 $bb2 $bb3 $bb4 $bb5 $bb6 $bb7 $bb8 $bb9 $bb10 $bb11 $bb12 $bb13 $bb14 $bb15 $bb16 $bb17 $bb18 $bb19 $bb20 $bb21 $bb22 $bb23 $bb24 $bb25 $bb26 $bb27 $bb28 $bb29 $bb30 $bb31 $bb32 $bb33 $bb34 $bb35 $bb36 $bb37
+      end ;; bb0
+      local.get $frame
+      i32.const 0
+      i32.add
+      local.set $r0
+      i32.const 1
+      local.set $r1
+      local.get $r0
+      local.get $r1
+      i32.store
+      i32.const -2
+      local.set $r2
+      local.get $r0
+      i32.const 8
+      i32.add
+      local.set $r3
+      local.get $r3
+      local.get $r2
+      i32.store
+      global.get $lune_p
+      local.set $r4
+      local.get $r4
+      local.get $r0
+      i32.const 16
+      memory.copy
+      global.get $lune_p
+      local.set $r5
+      global.get $lune_p
+      local.set $r6
+      local.get $r6
+      i32.load
+      local.set $r7
+      i32.const 3
+      local.set $r8
+      local.get $r7
+      local.get $r8
+      i32.add
+      local.set $r9
+      local.get $r5
+      local.get $r9
+      i32.store
+      global.get $lune_p
+      local.set $r10
+      local.get $r10
+      i32.const 8
+      i32.add
+      local.set $r11
+      global.get $lune_p
+      local.set $r12
+      local.get $r12
+      i32.const 8
+      i32.add
+      local.set $r13
+      local.get $r13
+      i32.load
+      local.set $r14
+      local.get $r14
+      local.get $r1
+      i32.sub
+      local.set $r15
+      local.get $r11
+      local.get $r15
+      i32.store
+      local.get $frame
+      i32.const 16
+      i32.add
+      local.set $r16
+      local.get $r16
+      local.get $r1
+      i32.store
+      i32.const 2
+      local.set $r17
+      local.get $r16
+      i32.const 8
+      i32.add
+      local.set $r18
+      local.get $r18
+      local.get $r17
+      i32.store
+      local.get $r16
+      i32.const 16
+      i32.add
+      local.set $r19
+      local.get $r19
+      local.get $r8
+      i32.store
+      global.get $lune_grid
+      local.set $r20
+      local.get $r20
+      local.get $r16
+      i32.const 24
+      memory.copy
+      global.get $lune_grid
+      local.set $r21
+      i32.const 0
+      local.set $r22
+      global.get $lune_grid
+      local.set $r23
+      i32.const 1
+      local.set $r24
+      local.get $r23
+      local.get $r17
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r25
+      local.get $r25
+      i32.load
+      local.set $r26
+      local.get $r26
+      local.get $r17
+      i32.div_s
+      local.set $r27
+      local.get $r21
+      local.get $r22
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r28
+      local.get $r28
+      local.get $r27
+      i32.store
+      global.get $lune_grid
+      local.set $r29
+      local.get $r29
+      local.get $r22
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r30
+      local.get $frame
+      i32.const 40
+      i32.add
+      local.set $r31
+      local.get $r31
+      i32.const 8
+      i32.add
+      local.set $r32
+      local.get $r31
+      local.get $r30
+      i32.store
+      local.get $r32
+      local.get $r8
+      i32.store
+      global.get $lune_all
+      local.set $r33
+      local.get $r33
+      local.get $r31
+      i32.const 16
+      memory.copy
+      global.get $lune_grid
+      local.set $r34
+      local.get $r34
+      local.get $r1
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r35
+      local.get $frame
+      i32.const 56
+      i32.add
+      local.set $r36
+      local.get $r36
+      i32.const 8
+      i32.add
+      local.set $r37
+      local.get $r36
+      local.get $r35
+      i32.store
+      local.get $r37
+      local.get $r17
+      i32.store
+      global.get $lune_tail
+      local.set $r38
+      local.get $r38
+      local.get $r36
+      i32.const 16
+      memory.copy
+      global.get $lune_grid
+      local.set $r39
+      local.get $r39
+      local.get $r22
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r40
+      local.get $frame
+      i32.const 72
+      i32.add
+      local.set $r41
+      local.get $r41
+      i32.const 8
+      i32.add
+      local.set $r42
+      local.get $r41
+      local.get $r40
+      i32.store
+      local.get $r42
+      local.get $r17
+      i32.store
+      global.get $lune_head
+      local.set $r43
+      local.get $r43
+      local.get $r41
+      i32.const 16
+      memory.copy
+      global.get $lune_grid
+      local.set $r44
+      local.get $r44
+      local.get $r1
+      i32.const 8
+      i32.mul
+      i32.add
+      local.set $r45
+      local.get $frame
+      i32.const 88
+      i32.add
+      local.set $r46
+      local.get $r46
+      i32.const 8
+      i32.add
+      local.set $r47
+      local.get $r46
+      local.get $r45
+      i32.store
+      local.get $r47
+      local.get $r1
+      i32.store
+      global.get $lune_mid
+      local.set $r48
+      local.get $r48
+      local.get $r46
+      i32.const 16
+      memory.copy
+      global.get $lune_all
+      local.set $r49
+      local.get $r49
+      i32.const 8
+      i32.add
+      local.set $r50
+      local.get $r50
+      i32.load
+      local.set $r51
+      global.get $lune_tail
+      local.set $r52
+      local.get $r52
+      i32.const 8
+      i32.add
+      local.set $r53
+      local.get $r53
+      i32.load
+      local.set $r54
+      local.get $r51
+      local.get $r54
+      i32.add
+      local.set $r55
+      global.get $lune_head
+      local.set $r56
+      local.get $r56
+      i32.const 8
+      i32.add
+      local.set $r57
+      local.get $r57
+      i32.load
+      local.set $r58
+      local.get $r55
+      local.get $r58
+      i32.add
+      local.set $r59
+      global.get $lune_mid
+      local.set $r60
+      local.get $r60
+      i32.const 8
+      i32.add
+      local.set $r61
+      local.get $r61
+      i32.load
+      local.set $r62
+      local.get $r59
+      local.get $r62
+      i32.add
+      local.set $r63
+      local.get $r63
+      global.set $lune_gr_uf6__udf_e
+      global.get $lune_p
+      local.set $r64
+      local.get $r64
+      i32.load
+      local.set $r65
+      i32.const 4
+      local.set $r66
+      local.get $r65
+      local.get $r66
+      i32.eq
+      local.set $r67
+      local.get $r67
+      i32.eqz
+      local.set $r68
+      global.get $lune_p
+      local.set $r69
+      local.get $r69
+      i32.const 8
+      i32.add
+      local.set $r70
+      local.get $r70
+      i32.load
+      local.set $r71
+      local.get $r71
+      local.get $r22
+      i32.gt_s
+      local.set $r72
+      local.get $r68
+      local.get $r72
+      i32.ne
+      local.set $r73
+      local.get $r73
+      global.set $lune_done
+      global.get $lune_p
+      local.set $r74
+      local.get $r74
+      i32.load
+      local.set $r75
+      local.get $r75
+      local.get $r8
+      i32.lt_s
+      local.set $r76
+      local.get $r76
+      if
+      i32.const 1
+      local.set $block
+      br $dispatch
+      end
+      i32.const 2
+      local.set $block
+      br $dispatch
+      end ;; bb1
+      global.get $lune_p
+      local.set $r77
+      local.get $r77
+      i32.load
+      local.set $r78
+      local.get $r78
+      call $lune_print_int
+      i32.const 15
+      local.set $block
+      br $dispatch
+      end ;; bb2
+      global.get $lune_p
+      local.set $r79
+      local.get $r79
+      i32.const 8
+      i32.add
+      local.set $r80
+      local.get $r80
+      i32.load
+      local.set $r81
+      local.get $r81
+      local.get $r22
+      i32.le_s
+      local.set $r82
+      local.get $r82
+      if
+      i32.const 3
+      local.set $block
+      br $dispatch
+      end
+      i32.const 4
+      local.set $block
+      br $dispatch
+      end ;; bb3
+      global.get $lune_p
+      local.set $r83
+      local.get $r83
+      i32.const 8
+      i32.add
+      local.set $r84
+      local.get $r84
+      i32.load
+      local.set $r85
+      local.get $r85
+      call $lune_print_int
+      i32.const 15
+      local.set $block
+      br $dispatch
+      end ;; bb4
+      global.get $lune_gr_uf6__udf_e
+      local.set $r86
+      i32.const -1
+      local.set $r87
+      local.get $r86
+      local.get $r87
+      i32.eq
+      local.set $r88
+      local.get $r88
+      if
+      i32.const 5
+      local.set $block
+      br $dispatch
+      end
+      i32.const 6
+      local.set $block
+      br $dispatch
+      end ;; bb5
+      i32.const 24
+      local.set $r89
+      local.get $r89
+      local.set $r100
+      i32.const 14
+      local.set $block
+      br $dispatch
+      end ;; bb6
+      local.get $r86
+      local.get $r22
+      i32.eq
+      local.set $r90
+      local.get $r90
+      if
+      i32.const 7
+      local.set $block
+      br $dispatch
+      end
+      i32.const 8
+      local.set $block
+      br $dispatch
+      end ;; bb7
+      i32.const 34
+      local.set $r91
+      local.get $r91
+      local.set $r100
+      i32.const 14
+      local.set $block
+      br $dispatch
+      end ;; bb8
+      local.get $r86
+      local.get $r1
+      i32.ge_s
+      local.set $r92
+      local.get $r92
+      if
+      i32.const 9
+      local.set $block
+      br $dispatch
+      end
+      i32.const 11
+      local.set $block
+      br $dispatch
+      end ;; bb9
+      i32.const 7
+      local.set $r93
+      local.get $r86
+      local.get $r93
+      i32.le_s
+      local.set $r94
+      local.get $r94
+      if
+      i32.const 10
+      local.set $block
+      br $dispatch
+      end
+      i32.const 11
+      local.set $block
+      br $dispatch
+      end ;; bb10
+      i32.const 39
+      local.set $r95
+      local.get $r95
+      local.set $r100
+      i32.const 14
+      local.set $block
+      br $dispatch
+      end ;; bb11
+      i32.const 8
+      local.set $r96
+      local.get $r86
+      local.get $r96
+      i32.eq
+      local.set $r97
+      local.get $r97
+      if
+      i32.const 12
+      local.set $block
+      br $dispatch
+      end
+      i32.const 13
+      local.set $block
+      br $dispatch
+      end ;; bb12
+      i32.const 45
+      local.set $r98
+      local.get $r98
+      local.set $r100
+      i32.const 14
+      local.set $block
+      br $dispatch
+      end ;; bb13
+      i32.const 51
+      local.set $r99
+      local.get $r99
+      local.set $r100
+      end ;; bb14
+      local.get $r100
+      call $lune_print_str
+      end ;; bb15
+      global.get $lune_gr_uf6__udf_e
+      local.set $r101
+      local.get $r101
+      local.get $r22
+      i32.ge_s
+      local.set $r102
+      local.get $r102
+      if
+      i32.const 16
+      local.set $block
+      br $dispatch
+      end
+      i32.const 17
+      local.set $block
+      br $dispatch
+      end ;; bb16
+      global.get $lune_gr_uf6__udf_e
+      local.set $r103
+      local.get $r103
+      local.get $r1
+      i32.sub
+      local.set $r104
+      local.get $r104
+      global.set $lune_gr_uf6__udf_e
+      i32.const 15
+      local.set $block
+      br $dispatch
+      end ;; bb17
+      i32.const 5
+      local.set $r105
+      local.get $frame
+      i32.const 104
+      i32.add
+      local.set $r106
+      local.get $r106
+      local.get $r105
+      i32.store
+      local.get $r106
+      i32.const 8
+      i32.add
+      local.set $r107
+      local.get $r107
+      local.get $r17
+      i32.store
+      local.get $r106
+      i32.const 16
+      i32.add
+      local.set $r108
+      local.get $r108
+      local.get $r8
+      i32.store
+      local.get $r106
+      i32.load
+      local.set $r109
+      local.get $r109
+      local.get $r22
+      i32.eq
+      local.set $r110
+      local.get $r110
+      if
+      local.get $r22
+      local.set $r121
+      i32.const 23
+      local.set $block
+      br $dispatch
+      end
+      end ;; bb18
+      local.get $r109
+      local.get $r66
+      i32.eq
+      local.set $r111
+      local.get $r111
+      if
+      i32.const 19
+      local.set $block
+      br $dispatch
+      end
+      i32.const 20
+      local.set $block
+      br $dispatch
+      end ;; bb19
+      local.get $r106
+      i32.const 8
+      i32.add
+      local.set $r112
+      local.get $r112
+      i32.load
+      local.set $r113
+      local.get $r113
+      local.get $r113
+      i32.mul
+      local.set $r114
+      local.get $r114
+      local.set $r121
+      i32.const 23
+      local.set $block
+      br $dispatch
+      end ;; bb20
+      local.get $r109
+      local.get $r105
+      i32.eq
+      local.set $r115
+      local.get $r115
+      if
+      i32.const 21
+      local.set $block
+      br $dispatch
+      end
+      i32.const 22
+      local.set $block
+      br $dispatch
+      end ;; bb21
+      local.get $r106
+      i32.const 8
+      i32.add
+      local.set $r116
+      local.get $r116
+      i32.load
+      local.set $r117
+      local.get $r106
+      i32.const 16
+      i32.add
+      local.set $r118
+      local.get $r118
+      i32.load
+      local.set $r119
+      local.get $r117
+      local.get $r119
+      i32.mul
+      local.set $r120
+      local.get $r120
+      local.set $r121
+      i32.const 23
+      local.set $block
+      br $dispatch
+      end ;; bb22
+      unreachable
+      end ;; bb23
+      local.get $frame
+      i32.const 128
+      i32.add
+      local.set $r122
+      local.get $r122
+      local.get $r66
+      i32.store
+      i32.const 8
+      local.set $r123
+      local.get $r122
+      i32.const 8
+      i32.add
+      local.set $r124
+      local.get $r124
+      local.get $r123
+      i32.store
+      local.get $r122
+      i32.load
+      local.set $r125
+      local.get $r125
+      local.get $r22
+      i32.eq
+      local.set $r126
+      local.get $r126
+      if
+      local.get $r22
+      local.set $r137
+      i32.const 29
+      local.set $block
+      br $dispatch
+      end
+      end ;; bb24
+      local.get $r125
+      local.get $r66
+      i32.eq
+      local.set $r127
+      local.get $r127
+      if
+      i32.const 25
+      local.set $block
+      br $dispatch
+      end
+      i32.const 26
+      local.set $block
+      br $dispatch
+      end ;; bb25
+      local.get $r122
+      i32.const 8
+      i32.add
+      local.set $r128
+      local.get $r128
+      i32.load
+      local.set $r129
+      local.get $r129
+      local.get $r129
+      i32.mul
+      local.set $r130
+      local.get $r130
+      local.set $r137
+      i32.const 29
+      local.set $block
+      br $dispatch
+      end ;; bb26
+      local.get $r125
+      local.get $r105
+      i32.eq
+      local.set $r131
+      local.get $r131
+      if
+      i32.const 27
+      local.set $block
+      br $dispatch
+      end
+      i32.const 28
+      local.set $block
+      br $dispatch
+      end ;; bb27
+      local.get $r122
+      i32.const 8
+      i32.add
+      local.set $r132
+      local.get $r132
+      i32.load
+      local.set $r133
+      local.get $r122
+      i32.const 16
+      i32.add
+      local.set $r134
+      local.get $r134
+      i32.load
+      local.set $r135
+      local.get $r133
+      local.get $r135
+      i32.mul
+      local.set $r136
+      local.get $r136
+      local.set $r137
+      i32.const 29
+      local.set $block
+      br $dispatch
+      end ;; bb28
+      unreachable
+      end ;; bb29
+      local.get $r121
+      local.get $r137
+      i32.add
+      local.set $r138
+      local.get $frame
+      i32.const 152
+      i32.add
+      local.set $r139
+      local.get $r139
+      local.get $r22
+      i32.store
+      local.get $r139
+      i32.load
+      local.set $r140
+      local.get $r140
+      local.get $r22
+      i32.eq
+      local.set $r141
+      local.get $r141
+      if
+      local.get $r22
+      local.set $r152
+      i32.const 35
+      local.set $block
+      br $dispatch
+      end
+      end ;; bb30
+      local.get $r140
+      local.get $r66
+      i32.eq
+      local.set $r142
+      local.get $r142
+      if
+      i32.const 31
+      local.set $block
+      br $dispatch
+      end
+      i32.const 32
+      local.set $block
+      br $dispatch
+      end ;; bb31
+      local.get $r139
+      i32.const 8
+      i32.add
+      local.set $r143
+      local.get $r143
+      i32.load
+      local.set $r144
+      local.get $r144
+      local.get $r144
+      i32.mul
+      local.set $r145
+      local.get $r145
+      local.set $r152
+      i32.const 35
+      local.set $block
+      br $dispatch
+      end ;; bb32
+      local.get $r140
+      local.get $r105
+      i32.eq
+      local.set $r146
+      local.get $r146
+      if
+      i32.const 33
+      local.set $block
+      br $dispatch
+      end
+      i32.const 34
+      local.set $block
+      br $dispatch
+      end ;; bb33
+      local.get $r139
+      i32.const 8
+      i32.add
+      local.set $r147
+      local.get $r147
+      i32.load
+      local.set $r148
+      local.get $r139
+      i32.const 16
+      i32.add
+      local.set $r149
+      local.get $r149
+      i32.load
+      local.set $r150
+      local.get $r148
+      local.get $r150
+      i32.mul
+      local.set $r151
+      local.get $r151
+      local.set $r152
+      i32.const 35
+      local.set $block
+      br $dispatch
+      end ;; bb34
+      unreachable
+      end ;; bb35
+      local.get $r138
+      local.get $r152
+      i32.sub
+      local.set $r153
+      local.get $r153
+      call $lune_print_int
+      i32.const 61
+      local.set $r154
+      local.get $r154
+      call $lune_print_str
+      global.get $lune_done
+      local.set $r155
+      local.get $r155
+      call $lune_print_bool
+      global.get $lune_done
+      local.set $r156
+      local.get $r156
+      if
+      i32.const 36
+      local.set $block
+      br $dispatch
+      end
+      i32.const 37
+      local.set $block
+      br $dispatch
+      end ;; bb36
+      local.get $r24
+      call $lune_print_bool
+      end ;; bb37
+      local.get $frame
+      i32.const 176
+      i32.add
+      global.set $lune_sp
+      return
+    end)
+
+  (func (export "main")
+    call $lune_main)
+)
@@ -0,0 +1,100 @@
+//! Snapshots of the WebAssembly text of `--emit=wat`, and runs of it when
+//! `wat2wasm` and `node` are installed
+
+mod common;
+
+use std::process::Command;
+
+use common::{has_tool, lune, path, run_vm, snapshot, temp_dir, Run};
+
+const PROGRAMS: [&str; 3] = ["syntax.lune", "aggregates.lune", "optimize.lune"];
+
+/// Runs a `.wasm` module with `node`, providing `env.print`
+const HOST: &str = r#"const fs = require("fs");
+const bytes = fs.readFileSync(process.argv[2]);
+let memory;
+const env = {
+    print: (at, length) => fs.writeSync(1, new Uint8Array(memory.buffer, at, length)),
+};
+WebAssembly.instantiate(bytes, { env }).then(({ instance }) => {
+    memory = instance.exports.memory;
+    try {
+        instance.exports.main();
+    } catch (err) {
+        console.error(err.message);
+        process.exitCode = 1;
+    }
+});
+"#;
+
+fn emit(name: &str, args: &[&str]) -> String {
+    let file = path("programs").join(name);
+    let mut all = vec!["--emit=wat"];
+    all.extend(args);
+    all.push(file.to_str().unwrap());
+    let output = lune(&all);
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn golden() {
+    for name in PROGRAMS {
+        let stem = name.trim_end_matches(".lune");
+        snapshot(&format!("wat/{}.wat", stem), &emit(name, &[]));
+        snapshot(&format!("wat/{}-O2.wat", stem), &emit(name, &["-O2"]));
+    }
+}
+
+/// Every program prints what `lune run` prints
+#[test]
+fn run() {
+    if !has_tool("wat2wasm") || !has_tool("node") {
+        return;
+    }
+    let dir = temp_dir("wat_run");
+    let host = dir.join("host.js");
+    std::fs::write(&host, HOST).unwrap();
+
+    let mut names: Vec<String> = std::fs::read_dir(path("programs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".lune"))
+        .collect();
+    names.sort();
+    for name in names {
+        let vm = run_vm(&name);
+        for level in ["-O0", "-O2"] {
+            let wat = dir.join(format!("{}{}.wat", name.trim_end_matches(".lune"), level));
+            let wasm = wat.with_extension("wasm");
+            std::fs::write(&wat, emit(&name, &[level])).unwrap();
+            let output = Command::new("wat2wasm")
+                .arg(&wat)
+                .arg("-o")
+                .arg(&wasm)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "{}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            let run = Run::new(Command::new("node").arg(&host).arg(&wasm).output().unwrap());
+            assert_eq!(
+                (&run.stdout, run.success),
+                (&vm.stdout, vm.success),
+                "{} {}: {}",
+                name,
+                level,
+                run.stderr
+            );
+        }
+    }
+}